@@ -0,0 +1,59 @@
+//! Synthetic data for the benchmarks: value series shaped like the scraped feeds, with
+//! deterministic values so every run writes the same files
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Europe::Vienna;
+use std::collections::HashMap;
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use scraping_service::storage::Storage;
+
+pub const SCRAPER: &str = "bench";
+pub const FOLDER: &str = "bench/synthetic";
+
+/// Local day every benchmark writes
+pub fn day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()
+}
+
+/// Start of `day()` in the default partition timezone
+pub fn day_start() -> DateTime<Utc> {
+    Vienna.from_local_datetime(&day().and_hms_opt(0, 0, 0).unwrap()).unwrap().with_timezone(&Utc)
+}
+
+/// One day of intervals of `resolution_minutes` with `columns` values each. `revision` shifts
+/// every value, so each revision changes every interval.
+pub fn values_day(resolution_minutes: i64, columns: usize, revision: u64) -> Vec<ScraperData> {
+    let count = 24 * 60 / resolution_minutes;
+    (0..count).map(|i| {
+        let start = day_start() + Duration::minutes(resolution_minutes * i);
+        let values: HashMap<String, f64> = (0..columns).map(|c| {
+            let shape = (i as f64 / count as f64 * std::f64::consts::TAU).sin() * 100.0;
+            (format!("value_{}", c), shape + c as f64 + revision as f64 * 0.25)
+        }).collect();
+        ScraperData {
+            delivery_from: start,
+            delivery_to: start + Duration::minutes(resolution_minutes),
+            payload: ScraperPayload::Values(values.into_iter().collect()),
+        }
+    }).collect()
+}
+
+/// `len` intervals of a day from `offset` on, like a single live scrape
+pub fn values_window(resolution_minutes: i64, columns: usize, revision: u64, offset: usize, len: usize) -> Vec<ScraperData> {
+    values_day(resolution_minutes, columns, revision).into_iter().skip(offset).take(len).collect()
+}
+
+/// Write a partition holding `revisions` versions of every interval of a day, as left behind
+/// by that many scrapes that each revised the whole day
+pub async fn partition_with_revisions(base_path: &str, resolution_minutes: i64, columns: usize, revisions: u64) {
+    let storage = Storage::new(base_path, None);
+    for revision in 0..revisions {
+        storage.save_if_new(SCRAPER, Some(FOLDER), &values_day(resolution_minutes, columns, revision), None).await.unwrap();
+    }
+}
+
+/// Path of the partition of `day()`
+pub fn partition_path(base_path: &str) -> String {
+    format!("{}/{}/year=2025/month=06/day=01/data.parquet", base_path, FOLDER)
+}
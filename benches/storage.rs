@@ -0,0 +1,197 @@
+//! Cost of the rewrite-on-every-change storage design: `cargo bench --bench storage`
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use tokio::runtime::Runtime;
+
+use scraping_service::query::{self, Query};
+use scraping_service::schema;
+use scraping_service::storage::{BufferConfig, Storage};
+
+use common::{FOLDER, SCRAPER};
+
+/// 1 day of 5-minute data
+const RESOLUTION_MINUTES: i64 = 5;
+const COLUMNS: usize = 4;
+/// Stored versions per interval: a fresh partition up to a year of daily revisions
+const REVISIONS: [u64; 4] = [1, 30, 90, 365];
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+}
+
+/// Partitions with a growing history, prepared once and copied for every iteration that writes
+fn prepared(rt: &Runtime) -> Vec<(u64, tempfile::TempDir)> {
+    REVISIONS.iter().map(|&revisions| {
+        let dir = tempfile::tempdir().unwrap();
+        rt.block_on(common::partition_with_revisions(dir.path().to_str().unwrap(), RESOLUTION_MINUTES, COLUMNS, revisions));
+        (revisions, dir)
+    }).collect()
+}
+
+/// A copy of a prepared data directory to write into
+fn copy(source: &tempfile::TempDir) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let from = common::partition_path(source.path().to_str().unwrap());
+    let to = common::partition_path(dir.path().to_str().unwrap());
+    std::fs::create_dir_all(std::path::Path::new(&to).parent().unwrap()).unwrap();
+    std::fs::copy(from, to).unwrap();
+    dir
+}
+
+fn process_partition(c: &mut Criterion) {
+    let rt = runtime();
+    let partitions = prepared(&rt);
+    let mut group = c.benchmark_group("process_partition");
+    group.sample_size(10);
+
+    for (revisions, source) in &partitions {
+        // A scrape revising the whole day rewrites the partition with one more version per interval
+        group.bench_with_input(BenchmarkId::new("revise_day", revisions), revisions, |b, &revisions| {
+            let data = common::values_day(RESOLUTION_MINUTES, COLUMNS, revisions);
+            b.iter_batched(
+                || copy(source),
+                |dir| {
+                    let storage = Storage::new(dir.path().to_str().unwrap(), None);
+                    rt.block_on(storage.save_if_new(SCRAPER, Some(FOLDER), &data, None)).unwrap();
+                    dir
+                },
+                BatchSize::PerIteration,
+            );
+        });
+
+        // A live scrape of the last hour with one changed interval still rewrites the whole file
+        group.bench_with_input(BenchmarkId::new("revise_one_interval", revisions), revisions, |b, &revisions| {
+            let mut data = common::values_window(RESOLUTION_MINUTES, COLUMNS, revisions - 1, 276, 12);
+            data.truncate(11);
+            data.extend(common::values_window(RESOLUTION_MINUTES, COLUMNS, revisions, 287, 1));
+            b.iter_batched(
+                || copy(source),
+                |dir| {
+                    let storage = Storage::new(dir.path().to_str().unwrap(), None);
+                    rt.block_on(storage.save_if_new(SCRAPER, Some(FOLDER), &data, None)).unwrap();
+                    dir
+                },
+                BatchSize::PerIteration,
+            );
+        });
+
+        // An unchanged re-scrape reads the partition to find nothing new...
+        group.bench_with_input(BenchmarkId::new("unchanged_uncached", revisions), revisions, |b, &revisions| {
+            let data = common::values_day(RESOLUTION_MINUTES, COLUMNS, revisions - 1);
+            let base_path = source.path().to_str().unwrap();
+            b.iter(|| {
+                let storage = Storage::new(base_path, None);
+                rt.block_on(storage.save_if_new(SCRAPER, Some(FOLDER), &data, None)).unwrap()
+            });
+        });
+
+        // ...unless the in-memory cache still holds its latest state
+        group.bench_with_input(BenchmarkId::new("unchanged_cached", revisions), revisions, |b, &revisions| {
+            let data = common::values_day(RESOLUTION_MINUTES, COLUMNS, revisions - 1);
+            let storage = Storage::new(source.path().to_str().unwrap(), None);
+            rt.block_on(storage.save_if_new(SCRAPER, Some(FOLDER), &data, None)).unwrap();
+            b.iter(|| rt.block_on(storage.save_if_new(SCRAPER, Some(FOLDER), &data, None)).unwrap());
+        });
+    }
+    group.finish();
+}
+
+/// A day of scrapes, one per 5-minute interval that each revise the last hour, written directly
+/// or buffered and flushed once
+fn buffered_writes(c: &mut Criterion) {
+    let rt = runtime();
+    let scrapes: Vec<_> = (0..288u64)
+        .map(|i| common::values_window(RESOLUTION_MINUTES, COLUMNS, i, (i as usize).saturating_sub(11), 12))
+        .collect();
+    let mut group = c.benchmark_group("buffered_writes");
+    group.sample_size(10);
+
+    group.bench_function("direct", |b| {
+        b.iter_batched(
+            || tempfile::tempdir().unwrap(),
+            |dir| {
+                let storage = Storage::new(dir.path().to_str().unwrap(), None);
+                for data in &scrapes {
+                    rt.block_on(storage.save_if_new(SCRAPER, Some(FOLDER), data, None)).unwrap();
+                }
+                dir
+            },
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.bench_function("buffered", |b| {
+        b.iter_batched(
+            || tempfile::tempdir().unwrap(),
+            |dir| {
+                let storage = Storage::new(dir.path().to_str().unwrap(), None)
+                    .with_buffer(BufferConfig { flush_interval_ms: u64::MAX, max_rows: usize::MAX });
+                for data in &scrapes {
+                    rt.block_on(storage.save_if_new(SCRAPER, Some(FOLDER), data, None)).unwrap();
+                }
+                rt.block_on(storage.flush()).unwrap();
+                dir
+            },
+            BatchSize::PerIteration,
+        );
+    });
+    group.finish();
+}
+
+/// Reading the latest value per interval has to deduplicate every stored version
+fn read_path(c: &mut Criterion) {
+    let rt = runtime();
+    let partitions = prepared(&rt);
+    let mut group = c.benchmark_group("read_path");
+    group.sample_size(20);
+
+    for (revisions, source) in &partitions {
+        let base_path = source.path().to_str().unwrap();
+        group.bench_with_input(BenchmarkId::new("latest", revisions), base_path, |b, base_path| {
+            let query = Query::new(base_path);
+            b.iter(|| query.latest(FOLDER, common::day(), common::day(), None).unwrap());
+        });
+
+        // Deduplication alone, on rows already in memory
+        let batches = query::read_batches(std::path::Path::new(&common::partition_path(base_path))).unwrap();
+        group.bench_with_input(BenchmarkId::new("dedup", revisions), &batches, |b, batches| {
+            b.iter(|| query::latest_values(query::read_value_rows(batches).unwrap()));
+        });
+    }
+    group.finish();
+}
+
+/// Read a partition and write it back in one go, as the migrate and repartition tools do. The
+/// storage has no compaction step; this is the floor of what compacting a partition would cost.
+fn rewrite(c: &mut Criterion) {
+    let rt = runtime();
+    let partitions = prepared(&rt);
+    let mut group = c.benchmark_group("rewrite");
+    group.sample_size(10);
+
+    for (revisions, source) in &partitions {
+        let path = common::partition_path(source.path().to_str().unwrap());
+        group.bench_with_input(BenchmarkId::new("full_partition", revisions), &path, |b, path| {
+            let out = tempfile::tempdir().unwrap();
+            let target = out.path().join("data.parquet");
+            b.iter(|| {
+                let partition = schema::read_partition(std::path::Path::new(path)).unwrap();
+                let batch_schema = partition.batches[0].schema();
+                let mut writer = ArrowWriter::try_new(File::create(&target).unwrap(), batch_schema, None).unwrap();
+                writer.append_key_value_metadata(schema::version_metadata());
+                for batch in &partition.batches {
+                    writer.write(batch).unwrap();
+                }
+                writer.close().unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, process_partition, buffered_writes, read_path, rewrite);
+criterion_main!(benches);
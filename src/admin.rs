@@ -0,0 +1,308 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::dashboard::{self, CoverageSource, Overview, RemoteCoverage, ScraperOverview};
+use crate::history::{self, RunLedger, RunRecord};
+use crate::metrics::{self, ScraperMetrics};
+use crate::storage::Storage;
+use crate::uploader::Uploader;
+
+/// Authenticated HTTP API for operators
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AdminConfig {
+    #[serde(default = "default_bind")]
+    pub bind: String,
+    /// Bearer token required on every request, overridden by the ADMIN_TOKEN env var
+    pub token: Option<String>,
+}
+
+fn default_bind() -> String {
+    "127.0.0.1:8080".to_string()
+}
+
+impl AdminConfig {
+    pub fn get_token(&self) -> Option<String> {
+        env::var("ADMIN_TOKEN").ok().or_else(|| self.token.clone())
+    }
+}
+
+/// A running scraper, as seen by the admin API
+#[async_trait]
+pub trait ScrapeTrigger: Send + Sync {
+    /// Scrape a window now, the scraper's regular window if none is given.
+    /// None if the scrape was skipped, e.g. because another instance holds the lease.
+    async fn scrape_now(&self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Option<RunRecord>;
+
+    /// `closed`, `open` or `half_open`, None without a circuit breaker
+    fn circuit_state(&self) -> Option<&'static str>;
+
+    /// Whether this instance may scrape, always true without locking
+    fn holds_lease(&self) -> bool;
+}
+
+pub struct AdminState {
+    token: String,
+    scrapers: BTreeMap<String, Arc<dyn ScrapeTrigger>>,
+    coverage: BTreeMap<String, CoverageSource>,
+    storage: Arc<Storage>,
+    uploader: Option<Arc<Uploader>>,
+    remote_coverage: Option<RemoteCoverage>,
+    ledger: Arc<RunLedger>,
+    /// Passed to the backfill binary, None for the default tenant
+    tenant: Option<String>,
+}
+
+impl AdminState {
+    pub fn new(config: &AdminConfig, storage: Arc<Storage>, uploader: Option<Arc<Uploader>>, ledger: Arc<RunLedger>) -> Result<Self> {
+        let token = config.get_token().filter(|t| !t.is_empty())
+            .context("The admin API requires admin.token or ADMIN_TOKEN")?;
+        Ok(Self {
+            token,
+            scrapers: BTreeMap::new(),
+            coverage: BTreeMap::new(),
+            storage,
+            remote_coverage: uploader.clone().map(RemoteCoverage::new),
+            uploader,
+            ledger,
+            tenant: None,
+        })
+    }
+
+    /// Serve the scrapers of a tenant other than the default one
+    pub fn with_tenant(mut self, tenant: Option<String>) -> Self {
+        self.tenant = tenant;
+        self
+    }
+
+    pub fn with_scraper(mut self, name: &str, trigger: Arc<dyn ScrapeTrigger>) -> Self {
+        self.scrapers.insert(name.to_string(), trigger);
+        self
+    }
+
+    /// Show the stored days of a scraper on the dashboard
+    pub fn with_coverage(mut self, name: &str, source: CoverageSource) -> Self {
+        self.coverage.insert(name.to_string(), source);
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ScraperStatus {
+    name: String,
+    holds_lease: bool,
+    circuit: Option<&'static str>,
+    last_run: Option<RunRecord>,
+    metrics: Option<ScraperMetrics>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ScrapeRequest {
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashboardParams {
+    days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct FlushResponse {
+    rows_written: usize,
+    files_uploaded: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct BackfillRequest {
+    /// Scraper name or `all`
+    scraper: String,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    #[serde(default)]
+    skip_existing: bool,
+    #[serde(default)]
+    resume: bool,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct BackfillResponse {
+    pid: Option<u32>,
+    log: String,
+}
+
+/// Errors are returned as 500 with the error chain as body
+struct AdminError(anyhow::Error);
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", self.0)).into_response()
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for AdminError {
+    fn from(e: E) -> Self {
+        AdminError(e.into())
+    }
+}
+
+pub fn router(state: Arc<AdminState>) -> Router {
+    Router::new()
+        .route("/scrapers", get(list_scrapers))
+        .route("/scrapers/:name/scrape", post(scrape))
+        .route("/flush", post(flush))
+        .route("/backfill", post(backfill))
+        .route("/dashboard/data", get(dashboard_data))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        // The page itself holds no data and asks for the token in the browser
+        .route("/dashboard", get(|| async { Html(dashboard::PAGE) }))
+        .with_state(state)
+}
+
+/// Serve the admin API until the process exits
+pub async fn serve(bind: &str, state: Arc<AdminState>) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(bind).await
+        .with_context(|| format!("Failed to bind the admin API to {}", bind))?;
+    info!("Admin API listening on {}", bind);
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+async fn authenticate(State(state): State<Arc<AdminState>>, request: Request, next: Next) -> Response {
+    let token = request.headers().get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), state.token.as_bytes()) => next.run(request).await,
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn list_scrapers(State(state): State<Arc<AdminState>>) -> Json<Vec<ScraperStatus>> {
+    let metrics = metrics::global().snapshot();
+    Json(state.scrapers.iter().map(|(name, trigger)| ScraperStatus {
+        name: name.clone(),
+        holds_lease: trigger.holds_lease(),
+        circuit: trigger.circuit_state(),
+        last_run: state.ledger.last_run(name),
+        metrics: metrics.get(name).cloned(),
+    }).collect())
+}
+
+async fn scrape(State(state): State<Arc<AdminState>>, Path(name): Path<String>, request: Option<Json<ScrapeRequest>>) -> Response {
+    let Some(trigger) = state.scrapers.get(&name) else {
+        return (StatusCode::NOT_FOUND, format!("Unknown scraper {}", name)).into_response();
+    };
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    info!("Admin API triggered a scrape of {}", name);
+    match trigger.scrape_now(request.start, request.end).await {
+        Some(run) => Json(run).into_response(),
+        None => (StatusCode::CONFLICT, "Skipped: another instance holds the lease or the circuit is open").into_response(),
+    }
+}
+
+/// Runs of the last 7 days, coverage of the last `days` days (default 90)
+async fn dashboard_data(State(state): State<Arc<AdminState>>, Query(params): Query<DashboardParams>) -> Result<Json<Overview>, AdminError> {
+    let days = params.days.unwrap_or(90).clamp(1, 366);
+    let today = Utc::now().date_naive();
+    let runs = history::read_runs(state.ledger.base_path(), today - Duration::days(7), today)?;
+    let upload_queue = match &state.uploader {
+        Some(uploader) => Some(uploader.pending_count().await),
+        None => None,
+    };
+
+    let mut scrapers = Vec::new();
+    for name in state.scrapers.keys() {
+        let scraper_runs: Vec<&RunRecord> = runs.iter().filter(|r| &r.scraper == name).collect();
+        let coverage = match state.coverage.get(name) {
+            Some(source) => dashboard::coverage(state.storage.base_path(), source, days, state.remote_coverage.as_ref()).await?,
+            None => Vec::new(),
+        };
+        scrapers.push(ScraperOverview {
+            name: name.clone(),
+            last_run: state.ledger.last_run(name).or_else(|| scraper_runs.last().map(|r| (*r).clone())),
+            runs: scraper_runs.len(),
+            success_rate: dashboard::success_rate(&scraper_runs),
+            coverage,
+        });
+    }
+
+    Ok(Json(Overview { generated_at: Utc::now(), upload_queue, scrapers }))
+}
+
+/// Write buffered data and upload all pending files, ignoring upload windows
+async fn flush(State(state): State<Arc<AdminState>>) -> Result<Json<FlushResponse>, AdminError> {
+    let rows_written = state.storage.flush().await?;
+    let files_uploaded = match &state.uploader {
+        Some(uploader) => uploader.upload_pending(true).await,
+        None => 0,
+    };
+    info!("Admin API flush: {} rows written, {} files uploaded", rows_written, files_uploaded);
+    Ok(Json(FlushResponse { rows_written, files_uploaded }))
+}
+
+/// Run the backfill binary next to this one in the background, its output goes to `logs/`
+async fn backfill(State(state): State<Arc<AdminState>>, Json(request): Json<BackfillRequest>) -> Result<Response, AdminError> {
+    if request.scraper != "all" && !state.scrapers.contains_key(&request.scraper) {
+        return Ok((StatusCode::NOT_FOUND, format!("Unknown scraper {}", request.scraper)).into_response());
+    }
+    if request.end_date < request.start_date {
+        return Ok((StatusCode::BAD_REQUEST, "end_date is before start_date").into_response());
+    }
+
+    let exe = env::current_exe()?.with_file_name(format!("backfill{}", env::consts::EXE_SUFFIX));
+    std::fs::create_dir_all("logs")?;
+    let log = PathBuf::from("logs").join(format!("backfill-{}-{}.log", request.scraper, Utc::now().format("%Y%m%d%H%M%S")));
+    let log_file = std::fs::File::create(&log)?;
+
+    let mut command = tokio::process::Command::new(&exe);
+    command
+        .arg(&request.scraper)
+        .arg(request.start_date.to_string())
+        .arg(request.end_date.to_string())
+        .stdout(log_file.try_clone()?)
+        .stderr(log_file);
+    if request.skip_existing {
+        command.arg("--skip-existing");
+    }
+    if request.resume {
+        command.arg("--resume");
+    }
+    if request.dry_run {
+        command.arg("--dry-run");
+    }
+    if let Some(tenant) = &state.tenant {
+        command.arg("--tenant").arg(tenant);
+    }
+
+    let mut child = command.spawn().with_context(|| format!("Failed to start {:?}", exe))?;
+    let pid = child.id();
+    info!("Admin API started a backfill of {} from {} to {} (pid {:?})", request.scraper, request.start_date, request.end_date, pid);
+    let scraper = request.scraper.clone();
+    tokio::spawn(async move {
+        match child.wait().await {
+            Ok(status) => info!("Backfill of {} finished: {}", scraper, status),
+            Err(e) => tracing::error!("Backfill of {} failed: {:?}", scraper, e),
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(BackfillResponse { pid, log: log.to_string_lossy().to_string() })).into_response())
+}
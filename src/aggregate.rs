@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Offset, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use crate::completeness;
+use crate::values::Value;
+
+/// Aggregates are stored in `agg/<window>/<data folder>`
+pub const AGG_DIR: &str = "agg";
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregationWindow {
+    /// Local hours, so the two 02:00 hours of a DST switch stay apart
+    Hourly,
+    /// Local days, 23 or 25 hours long on DST transition days
+    Daily,
+}
+
+impl AggregationWindow {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AggregationWindow::Hourly => "hourly",
+            AggregationWindow::Daily => "daily",
+        }
+    }
+
+    /// Data folder of the aggregates of `folder`
+    pub fn folder(&self, folder: &str) -> String {
+        format!("{}/{}/{}", AGG_DIR, self.name(), folder)
+    }
+
+    /// The window an interval starting at `start` belongs to
+    fn bounds(&self, start: DateTime<Utc>, tz: Tz) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+        let local = start.with_timezone(&tz);
+        match self {
+            AggregationWindow::Hourly => {
+                let offset = local.offset().fix().local_minus_utc() as i64;
+                let local_seconds = start.timestamp() + offset;
+                let window_start = DateTime::from_timestamp(local_seconds - local_seconds.rem_euclid(3600) - offset, 0)
+                    .context("Timestamp out of range")?;
+                Ok((window_start, window_start + Duration::hours(1)))
+            }
+            AggregationWindow::Daily => completeness::day_bounds(local.date_naive(), tz),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregateFunction {
+    Mean,
+    Sum,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AggregateFunction::Mean => "mean",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+        }
+    }
+}
+
+/// A derived series of a scraper, e.g. the hourly means of quarter-hourly prices
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AggregationConfig {
+    pub window: AggregationWindow,
+    #[serde(default = "default_functions")]
+    pub functions: Vec<AggregateFunction>,
+    /// Columns to aggregate, all numeric columns if not set
+    pub columns: Option<Vec<String>>,
+}
+
+fn default_functions() -> Vec<AggregateFunction> {
+    vec![AggregateFunction::Mean]
+}
+
+/// Running statistics of one column in one window
+#[derive(Default)]
+struct Accumulator {
+    count: usize,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Accumulator {
+    fn add(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn get(&self, function: AggregateFunction) -> f64 {
+        match function {
+            AggregateFunction::Mean => self.sum / self.count as f64,
+            AggregateFunction::Sum => self.sum,
+            AggregateFunction::Min => self.min,
+            AggregateFunction::Max => self.max,
+        }
+    }
+}
+
+/// Aggregate the latest values of the intervals of one partition, keyed by interval start.
+/// Every window gets a `<column>_<function>` value per numeric column and function, and
+/// `intervals`, the number of source intervals, so incomplete windows can be told apart.
+/// Categorical columns are skipped.
+pub fn aggregate<'a, V>(config: &AggregationConfig, intervals: impl IntoIterator<Item = (DateTime<Utc>, V)>, tz: Tz) -> Result<Vec<ScraperData>>
+where
+    V: IntoIterator<Item = (&'a String, &'a Value)>,
+{
+    let mut windows: BTreeMap<(DateTime<Utc>, DateTime<Utc>), (usize, BTreeMap<String, Accumulator>)> = BTreeMap::new();
+
+    for (start, values) in intervals {
+        let (count, columns) = windows.entry(config.window.bounds(start, tz)?).or_default();
+        *count += 1;
+        for (column, value) in values {
+            if config.columns.as_ref().is_some_and(|c| !c.contains(column)) {
+                continue;
+            }
+            if let Some(value) = value.as_f64() {
+                columns.entry(column.clone()).or_default().add(value);
+            }
+        }
+    }
+
+    Ok(windows.into_iter().map(|((delivery_from, delivery_to), (count, columns))| {
+        let mut values = HashMap::new();
+        values.insert("intervals".to_string(), count as f64);
+        for (column, accumulator) in &columns {
+            for function in &config.functions {
+                values.insert(format!("{}_{}", column, function.name()), accumulator.get(*function));
+            }
+        }
+        ScraperData { delivery_from, delivery_to, payload: ScraperPayload::Values(values) }
+    }).collect())
+}
@@ -0,0 +1,218 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use tracing::{error, warn};
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use crate::config::ScraperConfig;
+use crate::metrics;
+use crate::query::{Query, QueryResult, ValueRow};
+
+/// Column set to 1 on records with an anomalous value and to 0 on the other checked records
+/// when `action` is `flag`
+pub const ANOMALY_COLUMN: &str = "anomaly";
+
+/// Flag values far outside the usual range of their time of day, e.g. a glitch of 1e9 MW
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnomalyConfig {
+    /// Values more than this many standard deviations from the mean of their time of day are anomalous
+    #[serde(default = "default_z_threshold")]
+    pub z_threshold: f64,
+    /// Days of accepted values the mean and standard deviation are computed over
+    #[serde(default = "default_window_days")]
+    pub window_days: i64,
+    /// Values of a time of day are only checked once this many earlier values are known
+    #[serde(default = "default_min_samples")]
+    pub min_samples: usize,
+    /// Lower bound of the standard deviation, so an almost constant series doesn't flag every small change
+    #[serde(default)]
+    pub min_stddev: f64,
+    #[serde(default)]
+    pub action: AnomalyAction,
+    /// Log an `ALERT` for every scrape with anomalies
+    #[serde(default = "default_alert")]
+    pub alert: bool,
+}
+
+fn default_z_threshold() -> f64 {
+    6.0
+}
+
+fn default_window_days() -> i64 {
+    28
+}
+
+fn default_min_samples() -> usize {
+    7
+}
+
+fn default_alert() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnomalyAction {
+    /// Store the record with `anomaly` = 1
+    #[default]
+    Flag,
+    /// Store the record in `rejected/` like a validation failure
+    Reject,
+}
+
+/// A value outside the usual range of its time of day
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub column: String,
+    pub value: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub z: f64,
+}
+
+pub struct AnomalyResult {
+    /// Records to store, flagged ones included with the `flag` action
+    pub accepted: Vec<ScraperData>,
+    /// Anomalous records with the `reject` action
+    pub rejected: Vec<ScraperData>,
+    pub anomalies: usize,
+}
+
+/// Rolling mean and standard deviation per column and local time of day
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    tz: Tz,
+    /// Accepted values per column and minute of the local day, by interval start. Keyed by
+    /// start so re-scrapes of the same interval replace their value instead of adding to it.
+    history: Mutex<HashMap<(String, u32), BTreeMap<DateTime<Utc>, f64>>>,
+}
+
+impl AnomalyDetector {
+    pub fn new(config: AnomalyConfig, tz: Tz) -> Self {
+        Self { config, tz, history: Mutex::new(HashMap::new()) }
+    }
+
+    /// Detector of a scraper seeded with its values stored in the window before `until`, so the
+    /// checks don't start from scratch. None if the scraper has no `anomaly` config.
+    pub fn for_scraper(config: &ScraperConfig, base_path: &str, until: DateTime<Utc>) -> Result<Option<Self>> {
+        let Some(anomaly_config) = &config.anomaly else {
+            return Ok(None);
+        };
+        let tz = config.partition_timezone()?;
+        let detector = AnomalyDetector::new(anomaly_config.clone(), tz);
+        let until = until.with_timezone(&tz).date_naive();
+        let query = Query::new(base_path).with_partitioning(config.data_folder(), config.partition_granularity, tz);
+        match query.latest(config.data_folder(), until - Duration::days(anomaly_config.window_days), until, None) {
+            Ok(QueryResult::Values(rows)) => detector.warm_up(&rows),
+            Ok(QueryResult::Bids(_)) => {}
+            Err(e) => warn!("Failed to load the anomaly statistics of {}: {:?}", config.scraper_config.name, e),
+        }
+        Ok(Some(detector))
+    }
+
+    fn slot(&self, start: DateTime<Utc>) -> u32 {
+        let local = start.with_timezone(&self.tz);
+        local.hour() * 60 + local.minute()
+    }
+
+    /// Seed the statistics with stored values, e.g. the last `window_days` on startup
+    pub fn warm_up(&self, rows: &[ValueRow]) {
+        let mut history = self.history.lock().unwrap();
+        for row in rows {
+            // Flagged records never entered the statistics
+            if row.values.get(ANOMALY_COLUMN).and_then(|v| v.as_f64()) == Some(1.0) {
+                continue;
+            }
+            for (column, value) in &row.values {
+                if let Some(value) = value.as_f64() {
+                    history.entry((column.clone(), self.slot(row.start))).or_default().insert(row.start, value);
+                }
+            }
+        }
+    }
+
+    /// Anomalies of one value against the other values of its time of day in the window
+    fn check_value(&self, values: &BTreeMap<DateTime<Utc>, f64>, start: DateTime<Utc>, column: &str, value: f64) -> Option<Anomaly> {
+        let window_start = start - Duration::days(self.config.window_days);
+        let samples: Vec<f64> = values.range(window_start..start).map(|(_, v)| *v).collect();
+        if samples.len() < self.config.min_samples.max(2) {
+            return None;
+        }
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let stddev = variance.sqrt().max(self.config.min_stddev);
+        if stddev <= 0.0 {
+            return None;
+        }
+        let z = (value - mean) / stddev;
+        (!value.is_finite() || z.abs() > self.config.z_threshold).then(|| Anomaly { column: column.to_string(), value, mean, stddev, z })
+    }
+
+    /// Check every value of the records, flag or reject anomalous records and add the others to
+    /// the statistics
+    pub fn check(&self, scraper_name: &str, mut data: Vec<ScraperData>) -> AnomalyResult {
+        data.sort_by_key(|item| item.delivery_from);
+        let mut history = self.history.lock().unwrap();
+        let mut accepted = Vec::with_capacity(data.len());
+        let mut rejected = Vec::new();
+        let mut anomalies = 0;
+
+        for mut item in data {
+            let start = item.delivery_from;
+            let ScraperPayload::Values(values) = &mut item.payload else {
+                accepted.push(item);
+                continue;
+            };
+            let found: Vec<Anomaly> = values.iter()
+                .filter(|(column, _)| column.as_str() != ANOMALY_COLUMN)
+                .filter_map(|(column, &value)| {
+                    let slot_values = history.get(&(column.clone(), self.slot(start)))?;
+                    self.check_value(slot_values, start, column, value)
+                })
+                .collect();
+
+            if found.is_empty() {
+                for (column, value) in values.iter() {
+                    let slot_values = history.entry((column.clone(), self.slot(start))).or_default();
+                    slot_values.insert(start, *value);
+                    // Keep one window before the newest value, older values can't be compared against anymore
+                    let cutoff = slot_values.keys().next_back().copied().unwrap_or(start) - Duration::days(self.config.window_days);
+                    *slot_values = slot_values.split_off(&cutoff);
+                }
+                // Clears the flag of an earlier anomalous version of the interval
+                if self.config.action == AnomalyAction::Flag {
+                    values.insert(ANOMALY_COLUMN.to_string(), 0.0);
+                }
+                accepted.push(item);
+                continue;
+            }
+
+            for anomaly in &found {
+                warn!(
+                    "[{}] Anomalous value at {}: {} = {} (mean {:.3}, stddev {:.3}, z {:.1})",
+                    scraper_name, start, anomaly.column, anomaly.value, anomaly.mean, anomaly.stddev, anomaly.z
+                );
+            }
+            anomalies += 1;
+            match self.config.action {
+                AnomalyAction::Flag => {
+                    values.insert(ANOMALY_COLUMN.to_string(), 1.0);
+                    accepted.push(item);
+                }
+                AnomalyAction::Reject => rejected.push(item),
+            }
+        }
+
+        if anomalies > 0 {
+            metrics::global().update(scraper_name, |m| m.anomalies += anomalies as u64);
+            if self.config.alert {
+                error!("ALERT: {} anomalous records of {} ({:?})", anomalies, scraper_name, self.config.action);
+            }
+        }
+        AnomalyResult { accepted, rejected, anomalies }
+    }
+}
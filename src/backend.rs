@@ -0,0 +1,33 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ve_energy_scrapers::models::scraper_data::ScraperData;
+
+use crate::storage::Storage;
+
+/// Somewhere scraped data is stored. Parquet `Storage` is the primary backend,
+/// others (e.g. Postgres) receive the same data per scraper.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Store live data, returns the number of rows written
+    async fn save_if_new(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData]) -> Result<usize>;
+
+    /// Store backfilled data, which has no scraped_at
+    async fn save_backfill(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData]) -> Result<usize>;
+}
+
+#[async_trait]
+impl StorageBackend for Storage {
+    fn name(&self) -> &str {
+        "parquet"
+    }
+
+    async fn save_if_new(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData]) -> Result<usize> {
+        Storage::save_if_new(self, name, subfolder, data, None).await
+    }
+
+    async fn save_backfill(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData]) -> Result<usize> {
+        Storage::save_backfill(self, name, subfolder, data, None).await
+    }
+}
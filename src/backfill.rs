@@ -0,0 +1,49 @@
+use chrono::{Duration, NaiveDate};
+use futures::stream::{self, Stream, StreamExt};
+use std::future::Future;
+use tokio::task::{JoinError, JoinHandle};
+
+/// Group consecutive days into chunks that fit into one backfill window.
+/// Without a window every day is its own chunk.
+pub fn build_chunks(days: &[NaiveDate], window_hours: Option<i64>) -> Vec<Vec<NaiveDate>> {
+    // A chunk of n days is requested with one day of padding on each side, i.e. n + 1 days
+    let days_per_chunk = window_hours
+        .map(|hours| (hours / 24 - 1).max(1) as usize)
+        .unwrap_or(1);
+
+    let mut chunks: Vec<Vec<NaiveDate>> = Vec::new();
+    for &date in days {
+        match chunks.last_mut() {
+            Some(chunk) if chunk.len() < days_per_chunk && *chunk.last().unwrap() + Duration::days(1) == date => {
+                chunk.push(date);
+            }
+            _ => chunks.push(vec![date]),
+        }
+    }
+    chunks
+}
+
+/// Aborts a spawned task when dropped, so a failed backfill doesn't keep scraping in the background
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawn the task of every item, at most `concurrency` at a time, and yield their results in the
+/// order of the items, each with the value `task` returned next to its future. Tasks keep running
+/// while earlier results are handled; dropping the stream aborts the ones still running.
+pub fn spawn_ordered<I, M, F>(items: I, concurrency: usize, mut task: impl FnMut(I::Item) -> (M, F)) -> impl Stream<Item = (M, Result<F::Output, JoinError>)>
+where
+    I: IntoIterator,
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    stream::iter(items).map(move |item| {
+        let (meta, future) = task(item);
+        let mut handle = AbortOnDrop(tokio::spawn(future));
+        async move { (meta, (&mut handle.0).await) }
+    }).buffered(concurrency)
+}
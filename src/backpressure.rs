@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::disk;
+use crate::uploader::Uploader;
+
+/// Slow down scraping while uploads fall behind or the disk fills up
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BackpressureConfig {
+    /// Throttle once this many files wait for upload
+    pub max_pending_files: Option<usize>,
+    /// Throttle once the disk holding the data directory is fuller than this, in percent
+    pub max_disk_usage_percent: Option<f64>,
+    /// Scrape delays of non-critical scrapers are multiplied by this while throttled
+    #[serde(default = "default_slowdown_factor")]
+    pub slowdown_factor: u32,
+    #[serde(default = "default_check_interval_secs")]
+    pub check_interval_secs: u64,
+}
+
+fn default_slowdown_factor() -> u32 {
+    4
+}
+
+fn default_check_interval_secs() -> u64 {
+    30
+}
+
+/// Throttling ends once both measures drop below this share of their threshold,
+/// so scrapers don't flap around the threshold
+const RESUME_RATIO: f64 = 0.8;
+
+pub struct Backpressure {
+    config: BackpressureConfig,
+    base_path: PathBuf,
+    uploader: Option<Arc<Uploader>>,
+    throttled: AtomicBool,
+}
+
+impl Backpressure {
+    pub fn new(config: BackpressureConfig, base_path: &str, uploader: Option<Arc<Uploader>>) -> Self {
+        Self {
+            config,
+            base_path: PathBuf::from(base_path),
+            uploader,
+            throttled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    /// Delay before the next scrape of a scraper, longer while throttled unless it is critical
+    pub fn delay(&self, delay: Duration, critical: bool) -> Duration {
+        if critical || !self.is_throttled() {
+            return delay;
+        }
+        delay * self.config.slowdown_factor.max(1)
+    }
+
+    /// Wait until throttling ends, e.g. before the next request of a backfill
+    pub async fn wait(&self) {
+        while self.is_throttled() {
+            sleep(Duration::from_secs(self.config.check_interval_secs.max(1))).await;
+        }
+    }
+
+    /// Re-check the thresholds every `check_interval_secs` until the process exits
+    pub async fn run(&self) {
+        loop {
+            self.check().await;
+            sleep(Duration::from_secs(self.config.check_interval_secs.max(1))).await;
+        }
+    }
+
+    async fn check(&self) {
+        let pending = match &self.uploader {
+            Some(uploader) => uploader.pending_count().await,
+            None => 0,
+        };
+        let disk_percent = match self.config.max_disk_usage_percent {
+            Some(_) => match disk::usage(&self.base_path) {
+                Ok(usage) => usage.used_percent(),
+                Err(e) => {
+                    warn!("Backpressure can't check the disk usage: {:?}", e);
+                    0.0
+                }
+            },
+            None => 0.0,
+        };
+
+        // Ratio of each measure to its threshold, above 1 means over the threshold
+        let pending_ratio = self.config.max_pending_files
+            .filter(|max| *max > 0)
+            .map(|max| pending as f64 / max as f64)
+            .unwrap_or(0.0);
+        let disk_ratio = self.config.max_disk_usage_percent
+            .filter(|max| *max > 0.0)
+            .map(|max| disk_percent / max)
+            .unwrap_or(0.0);
+        let ratio = pending_ratio.max(disk_ratio);
+
+        let throttled = self.is_throttled();
+        if !throttled && ratio >= 1.0 {
+            warn!("Throttling non-critical scrapers: {} files waiting for upload, disk {:.1}% full", pending, disk_percent);
+            self.throttled.store(true, Ordering::Relaxed);
+        } else if throttled && ratio < RESUME_RATIO {
+            info!("Resuming regular scraping: {} files waiting for upload, disk {:.1}% full", pending, disk_percent);
+            self.throttled.store(false, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDate};
+use std::env;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use scraping_service::{config, logging, storage, uploader};
+use config::{load_config, ScraperConfig};
+use storage::Storage;
+use uploader::Uploader;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--tenant" => tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 3 {
+        eprintln!("Usage: {} <scraper_name|all> <start_date> <end_date> [--tenant <name>]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json, or 'all' for every scraper with aggregations");
+        eprintln!("  start_date: Start date in YYYY-MM-DD format");
+        eprintln!("  end_date: End date in YYYY-MM-DD format");
+        eprintln!("  --tenant: Tenant of the scrapers (default: the top-level scrapers)");
+        eprintln!("\nRecomputes the configured aggregations from the stored partitions into agg/ of the data directory.");
+        eprintln!("\nExample: {} apg_imb_15min 2025-01-01 2025-01-31", args[0]);
+        std::process::exit(1);
+    }
+
+    let start_date = NaiveDate::parse_from_str(&positional[1], "%Y-%m-%d")
+        .context("Failed to parse start_date. Use YYYY-MM-DD format")?;
+    let end_date = NaiveDate::parse_from_str(&positional[2], "%Y-%m-%d")
+        .context("Failed to parse end_date. Use YYYY-MM-DD format")?;
+
+    let config = load_config("config.json").context("Failed to load config.json")?
+        .tenant(tenant.as_deref())?;
+    let scrapers: Vec<&ScraperConfig> = config.scrapers.iter()
+        .filter(|s| !s.aggregations.is_empty())
+        .filter(|s| positional[0] == "all" || s.scraper_config.name == positional[0])
+        .collect();
+    if scrapers.is_empty() {
+        anyhow::bail!("No scraper named '{}' with aggregations in config.json", positional[0]);
+    }
+
+    let s3_uploader = Uploader::from_config(&config).await?.map(Arc::new);
+
+    let mut storage = Storage::new(config.base_path(), s3_uploader.as_ref().map(|u| u.get_pending_files_handle()))
+        .with_parquet_config(config.parquet.clone());
+    for scraper in &scrapers {
+        storage = storage.with_partition_timezone(scraper.data_folder(), scraper.partition_timezone()?)
+            .with_partition_granularity(scraper.data_folder(), scraper.partition_granularity)
+            .with_aggregations(scraper.data_folder(), scraper.aggregations.clone());
+    }
+
+    let mut rows_written = 0;
+    for scraper in &scrapers {
+        let mut date = start_date;
+        while date <= end_date {
+            rows_written += storage.aggregate_day(scraper.data_folder(), date).await
+                .with_context(|| format!("Failed to aggregate {} on {}", scraper.scraper_config.name, date))?;
+            date += Duration::days(1);
+        }
+        info!("Aggregated {} from {} to {}", scraper.scraper_config.name, start_date, end_date);
+    }
+
+    if let Some(uploader) = &s3_uploader {
+        let pending: Vec<String> = uploader.get_pending_files_handle().lock().await.drain().collect();
+        info!("Uploading {} changed files", pending.len());
+        for file_path in pending {
+            if let Err(e) = uploader.upload_file(&file_path).await {
+                error!("Failed to upload {}: {:?}", file_path, e);
+            }
+        }
+    }
+
+    println!("\n✓ {} aggregate rows written", rows_written);
+    Ok(())
+}
@@ -1,15 +1,36 @@
 use anyhow::{Context, Result};
-use chrono::{NaiveDate, Duration};
+use chrono::{DateTime, NaiveDate, NaiveTime, Duration, Utc};
+use chrono_tz::Tz;
+use futures::StreamExt;
 use std::env;
+use std::pin::pin;
 use std::sync::Arc;
-use tracing::{info, error};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Instant};
+use tracing::{info, error, info_span, warn, Instrument};
 use indicatif::{ProgressBar, ProgressStyle};
 
-use scraping_service::{config, storage, scraper_factory, uploader};
-use config::load_config;
+use scraping_service::{anomaly, backend, backfill, backpressure, calendar, checkpoint, completeness, conflict, config, derived, history, notify, partition, postgres, provenance, query, rate_limit, raw_archive, storage, scraper_factory, uploader, validation, logging};
+use anomaly::AnomalyDetector;
+use backend::StorageBackend;
+use backfill::build_chunks;
+use backpressure::Backpressure;
+use calendar::CalendarConfig;
+use checkpoint::Checkpoint;
+use config::{load_config, ScraperConfig};
+use history::{RunLedger, RunRecord};
+use notify::Notifier;
+use partition::Granularity;
+use postgres::PostgresSink;
+use provenance::Provenance;
+use raw_archive::RawResponse;
+use query::Query;
+use rate_limit::{RateLimiter, RateLimiters};
 use storage::Storage;
 use uploader::Uploader;
+use validation::Validator;
+
+const CHECKPOINT_FILE: &str = "backfill_checkpoint.json";
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -17,69 +38,109 @@ async fn main() -> Result<()> {
     #[cfg(debug_assertions)]
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_filter(tracing_subscriber::EnvFilter::try_from_default_env()
-                    .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")))
-        )
-        .init();
-
     let args: Vec<String> = env::args().collect();
-    
-    if args.len() < 4 {
-        eprintln!("Usage: {} <scraper_name> <start_date> <end_date>", args[0]);
-        eprintln!("  scraper_name: Name of the scraper from config.json");
+
+    let mut positional = Vec::new();
+    let mut concurrency: usize = 1;
+    let mut min_interval_ms: Option<u64> = None;
+    let mut resume = false;
+    let mut dry_run = false;
+    let mut skip_existing = false;
+    let mut min_rows: Option<usize> = None;
+    let mut tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--concurrency" => {
+                let value = iter.next().context("--concurrency requires a value")?;
+                concurrency = value.parse().context("Invalid --concurrency value")?;
+            }
+            "--min-interval-ms" => {
+                let value = iter.next().context("--min-interval-ms requires a value")?;
+                min_interval_ms = Some(value.parse().context("Invalid --min-interval-ms value")?);
+            }
+            "--resume" => resume = true,
+            "--dry-run" => dry_run = true,
+            "--skip-existing" => skip_existing = true,
+            "--min-rows" => {
+                let value = iter.next().context("--min-rows requires a value")?;
+                min_rows = Some(value.parse().context("Invalid --min-rows value")?);
+            }
+            "--tenant" => tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 3 || concurrency == 0 {
+        eprintln!("Usage: {} <scraper_name|all> <start_date> <end_date> [--concurrency N] [--min-interval-ms M] [--resume] [--skip-existing [--min-rows N]] [--dry-run] [--tenant <name>]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json, or 'all' for all scrapers");
         eprintln!("  start_date: Start date in YYYY-MM-DD format");
         eprintln!("  end_date: End date in YYYY-MM-DD format");
-        eprintln!("\nExample: {} apg_at_cz_exchange 2025-01-01 2025-01-31", args[0]);
+        eprintln!("  --concurrency: Number of days scraped in parallel (default: 1)");
+        eprintln!("  --min-interval-ms: Minimum time between two API requests (default: task_generator_delay_ms of the scraper)");
+        eprintln!("  --resume: Skip days completed by a previous run, as recorded in {}", CHECKPOINT_FILE);
+        eprintln!("  --skip-existing: Skip days whose partition already exists locally or in S3");
+        eprintln!("  --min-rows: With --skip-existing, only skip partitions with at least this many intervals");
+        eprintln!("  --dry-run: Print which days would be scraped without calling any API or writing data");
+        eprintln!("  --tenant: Backfill a scraper of this tenant, into its data directory and bucket (default: the top-level scrapers)");
+        eprintln!("\nExample: {} apg_at_cz_exchange 2025-01-01 2025-01-31 --concurrency 4", args[0]);
+        eprintln!("Example: {} all 2025-01-01 2025-01-31 --resume", args[0]);
+        eprintln!("Example: {} all 2025-01-01 2025-01-31 --skip-existing --min-rows 96 --dry-run", args[0]);
         std::process::exit(1);
     }
 
-    let scraper_name = &args[1];
-    let start_date_str = &args[2];
-    let end_date_str = &args[3];
+    let scraper_filter = &positional[0];
+    let start_date_str = &positional[1];
+    let end_date_str = &positional[2];
 
     // Parse dates
     let start_date = NaiveDate::parse_from_str(start_date_str, "%Y-%m-%d")
         .context("Failed to parse start_date. Use YYYY-MM-DD format")?;
-    
+
     let end_date = NaiveDate::parse_from_str(end_date_str, "%Y-%m-%d")
         .context("Failed to parse end_date. Use YYYY-MM-DD format")?;
 
     // Calculate total days
     let total_days = (end_date - start_date).num_days() + 1;
-    
+
     if total_days <= 0 {
         eprintln!("Error: end_date must be equal to or after start_date");
         std::process::exit(1);
     }
 
-    info!("Starting backfill for {} from {} to {} ({} days)", 
-        scraper_name, start_date, end_date, total_days);
-
     // Load config
-    let config = load_config("config.json").context("Failed to load config.json")?;
-    
-    // Find the scraper config
-    let scraper_config = config.scrapers.iter()
-        .find(|s| s.scraper_config.name == *scraper_name)
-        .context(format!("Scraper '{}' not found in config.json", scraper_name))?;
+    let config = load_config("config.json").context("Failed to load config.json")?
+        .tenant(tenant.as_deref())?;
+    let _log_guard = logging::init(None, config.telemetry.as_ref())?;
+
+    info!("Starting backfill for {} from {} to {} ({} days)",
+        scraper_filter, start_date, end_date, total_days);
+
+    // Find the scraper configs
+    let scrapers_to_backfill: Vec<ScraperConfig> = if scraper_filter == "all" {
+        config.scrapers.clone()
+    } else {
+        let scraper_config = config.scrapers.iter()
+            .find(|s| s.scraper_config.name == *scraper_filter)
+            .context(format!("Scraper '{}' not found in config.json", scraper_filter))?;
+        vec![scraper_config.clone()]
+    };
 
     // Set up uploader if S3 is configured
     let mut dirty_files_handle = None;
     let mut uploader_handle = None;
-    
-    if let Some(bucket) = config.get_s3_bucket() {
-        info!("S3 bucket configured: {}, setting up uploader", bucket);
-        let uploader = Uploader::new(
-            bucket,
-            config.get_s3_region(),
-            config.get_s3_endpoint(),
-            config.get_s3_prefix(),
-        ).await?;
+    let mut s3_uploader = None;
+
+    if let Some(mut uploader) = Uploader::from_config(&config).await? {
+        info!("S3 bucket configured, setting up uploader");
+        if let Some(notify_config) = &config.notify {
+            uploader = uploader.with_notifier(Arc::new(Notifier::new(notify_config, &config.scrapers, config.base_path())?));
+        }
+        let uploader = Arc::new(uploader);
         dirty_files_handle = Some(uploader.get_pending_files_handle());
-        
+        s3_uploader = Some(uploader.clone());
+
         let handle = tokio::spawn(async move {
             uploader.run().await;
         });
@@ -89,83 +150,460 @@ async fn main() -> Result<()> {
     }
 
     // Create storage with uploader support
-    let storage = Arc::new(Storage::new("data", dirty_files_handle));
+    let mut storage = Storage::new(config.base_path(), dirty_files_handle).with_parquet_config(config.parquet.clone());
+    // Partitions deleted locally, e.g. by the disk guard, are merged with S3 instead of replaced
+    if let Some(uploader) = &s3_uploader {
+        storage = storage.with_hydration(uploader.clone());
+    }
+    for scraper in &scrapers_to_backfill {
+        storage = storage.with_scraper(scraper)?;
+    }
+    for derived in derived::from_config(&config.derived)? {
+        let tz = derived.config.partition_timezone()?;
+        storage = storage.with_derived(derived, tz);
+    }
+    let delta_log = config.delta_log(config.base_path()).map(|delta_log| match &s3_uploader {
+        Some(uploader) => Arc::new(delta_log.with_uploader(uploader.clone())),
+        None => Arc::new(delta_log),
+    });
+    if let Some(delta_log) = &delta_log {
+        storage = storage.with_delta(delta_log.clone());
+    }
+    let storage = Arc::new(storage);
+
+    let postgres = match &config.postgres {
+        Some(pg_config) if !dry_run && scrapers_to_backfill.iter().any(|s| s.postgres) => {
+            Some(Arc::new(PostgresSink::connect(pg_config).await.context("Failed to set up Postgres")?))
+        }
+        _ => None,
+    };
+
+    let rate_limiters = RateLimiters::for_backfill(config.rate_limits.as_ref());
+
+    // Backfills pause entirely while throttled, based on this process's upload queue
+    let backpressure = config.backpressure.clone().filter(|_| !dry_run).map(|bp_config| {
+        let backpressure = Arc::new(Backpressure::new(bp_config, config.base_path(), s3_uploader.clone()));
+        let monitor = backpressure.clone();
+        tokio::spawn(async move {
+            monitor.run().await;
+        });
+        backpressure
+    });
+
+    let mut checkpoint = Checkpoint::load(CHECKPOINT_FILE).context("Failed to load backfill checkpoint")?;
+    let query = config.query(config.base_path())?;
+    let ledger = RunLedger::new(&config.history_dir());
+
+    for scraper_config in &scrapers_to_backfill {
+        let name = &scraper_config.scraper_config.name;
+        println!("\n=== Backfilling {} ===", name);
+
+        if !resume && !dry_run {
+            checkpoint.reset(name)?;
+        }
+
+        let mut days: Vec<NaiveDate> = (0..total_days)
+            .map(|i| start_date + Duration::days(i))
+            .filter(|date| !resume || !checkpoint.is_completed(name, *date))
+            .collect();
+
+        if skip_existing {
+            let partition_tz = scraper_config.partition_timezone()?;
+            let calendar = config.calendar(scraper_config)?;
+            let mut missing = Vec::new();
+            for date in days {
+                let (complete, status) = existing_status(&query, s3_uploader.as_deref(), scraper_config.data_folder(), date, min_rows, partition_tz, &calendar, dry_run).await?;
+                if dry_run || !complete {
+                    println!("  {} - {}", date, status);
+                }
+                if !complete {
+                    missing.push(date);
+                }
+            }
+            days = missing;
+        }
+
+        if days.is_empty() {
+            println!("✓ All {} days already completed", total_days);
+            continue;
+        }
+
+        if dry_run {
+            let chunks = build_chunks(&days, scraper_config.backfill_window_hours);
+            println!("Would scrape {} days in {} chunks:", days.len(), chunks.len());
+            for chunk in &chunks {
+                println!("  {}", chunk_label(chunk));
+            }
+            continue;
+        }
+
+        let rate_limiter = rate_limiters.for_scraper(scraper_config);
+        let mut sinks: Vec<Arc<dyn StorageBackend>> = Vec::new();
+        if scraper_config.postgres {
+            sinks.push(postgres.clone().context("postgres is enabled for this scraper but no postgres section is configured")?);
+        }
+
+        if let Err(e) = backfill_scraper(scraper_config, &days, concurrency, min_interval_ms, rate_limiter, &storage, &sinks, &mut checkpoint, &ledger, backpressure.clone()).await {
+            error!("Backfill of {} failed: {:?}", name, e);
+        }
+        if let Err(e) = ledger.flush() {
+            error!("Failed to write run history: {:?}", e);
+        }
+    }
+
+    if let Some(delta_log) = &delta_log {
+        match delta_log.commit().await {
+            Ok(committed) => info!("Committed {} partitions to Delta tables", committed),
+            Err(e) => error!("Failed to commit Delta tables: {:?}", e),
+        }
+    }
+
+    // Wait for uploader to process remaining files
+    if uploader_handle.is_some() && !dry_run {
+        info!("Waiting for S3 uploads to complete...");
+        info!("The uploader processes files every 60 seconds.");
+        // Wait at least 90 seconds to ensure one full upload cycle completes
+        tokio::time::sleep(tokio::time::Duration::from_secs(90)).await;
+
+        if let Some(uploader) = &s3_uploader {
+            let remaining = uploader.pending_count().await;
+            if remaining > 0 {
+                warn!("{} files are still waiting for upload (upload window or bandwidth limit) and only exist locally", remaining);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn backfill_scraper(
+    scraper_config: &ScraperConfig,
+    days: &[NaiveDate],
+    concurrency: usize,
+    min_interval_ms: Option<u64>,
+    rate_limiter: RateLimiter,
+    storage: &Storage,
+    sinks: &[Arc<dyn StorageBackend>],
+    checkpoint: &mut Checkpoint,
+    ledger: &RunLedger,
+    backpressure: Option<Arc<Backpressure>>,
+) -> Result<()> {
+    let name = &scraper_config.scraper_config.name;
+    let transforms = scraper_config.transforms()?;
+    let validator = Validator::for_scraper(scraper_config, storage.base_path())?;
+    // Seeded with the stored window before the first day, like the service on startup
+    let first_day = days.iter().min().map(|day| day.and_time(NaiveTime::MIN).and_utc()).unwrap_or_else(Utc::now);
+    let anomaly = AnomalyDetector::for_scraper(scraper_config, storage.base_path(), first_day)?;
 
     // Create scraper
-    let scraper = scraper_factory::create_scraper(&scraper_config.scraper_config)?;
-    
+    let scraper = Arc::new(scraper_factory::create_scraper(&scraper_config.scraper_config, scraper_config.http.as_ref()).await?);
+
     // Create progress bar with known length
-    let pb = ProgressBar::new(total_days as u64);
+    let pb = ProgressBar::new(days.len() as u64);
     pb.set_style(
         ProgressStyle::default_bar()
             .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} days ({eta})\n{msg}")
             .unwrap()
             .progress_chars("#>-")
     );
-    
+
+    // Requests are spaced by min_interval across all tasks so parallel days don't hammer the API
+    let min_interval = std::time::Duration::from_millis(
+        min_interval_ms.unwrap_or(scraper_config.scraper_config.task_generator_delay_ms as u64)
+    );
+    let next_request = Arc::new(Mutex::new(Instant::now()));
+
+    info!("Scraping {} days with concurrency {} and {}ms between requests", days.len(), concurrency, min_interval.as_millis());
+
+    let chunks = build_chunks(days, scraper_config.backfill_window_hours);
+    let mut requests = Vec::with_capacity(chunks.len());
+    for chunk in &chunks {
+        requests.push((chunk, request_windows(chunk, scraper_config)?));
+    }
+
+    // At most `concurrency` chunks are scraped at a time, and their results are saved in day order
+    // so the outcome doesn't depend on which request finished first
+    let mut results = pin!(backfill::spawn_ordered(requests, concurrency, |(chunk, windows)| {
+        let (chunk_start, chunk_end) = (windows[0].0, windows[windows.len() - 1].1);
+        let run_id = history::new_run_id();
+        let span = info_span!("scrape", scraper = %name, run_id = %run_id, source = "backfill",
+            window_start = %chunk_start, window_end = %chunk_end);
+
+        let scraper = scraper.clone();
+        let next_request = next_request.clone();
+        let rate_limiter = rate_limiter.clone();
+        let backpressure = backpressure.clone();
+
+        let task = async move {
+            let started_at = Utc::now();
+            let timer = Instant::now();
+            let result = async {
+                let mut data = Vec::new();
+                for (window_start, window_end) in windows {
+                    if let Some(backpressure) = &backpressure {
+                        backpressure.wait().await;
+                    }
+                    {
+                        let mut next = next_request.lock().await;
+                        if *next > Instant::now() {
+                            sleep_until(*next).await;
+                        }
+                        *next = Instant::now() + min_interval;
+                    }
+                    rate_limiter.acquire().await;
+                    let fetch = info_span!("fetch", otel.kind = "client", window_start = %window_start, window_end = %window_end);
+                    data.extend(scraper.scrape_data(window_start, window_end).instrument(fetch).await?);
+                }
+                Ok::<_, anyhow::Error>(data)
+            }.await;
+            (started_at, timer.elapsed(), result)
+        }.instrument(span.clone());
+        ((chunk, chunk_start, chunk_end, run_id, span), task)
+    }));
+
     let mut total_records = 0;
     let mut days_with_data = 0;
-    let mut current_date = start_date;
-    
-    // Process each day
-    for _ in 0..total_days {
-        // Use same approach as main service: query a window around the target date
-        // This ensures we get all data for the day even with timezone variations
-        let target_datetime = current_date.and_hms_opt(12, 0, 0)
-            .context("Invalid time")?
-            .and_utc();
-        let day_start = target_datetime - Duration::days(1); // Day before
-        let day_end = target_datetime + Duration::days(1);   // Day after
-        
+
+    // Returning early drops the stream, which aborts the chunks still being scraped
+    while let Some(((chunk, window_start, window_end, run_id, span), joined)) = results.next().await {
+        let current_date = chunk_label(chunk);
         pb.set_message(format!("Processing {}", current_date));
-        
-        // Perform the scrape for this day
-        match scraper.scrape_data(day_start, day_end).await {
-            Ok(data) => {
-                if !data.is_empty() {
-                    info!("Scraped {} records for {}", data.len(), current_date);
-                    match storage.save_backfill(
-                        &scraper_config.scraper_config.name,
-                        scraper_config.sub_data_folder.as_deref(),
-                        &data
-                    ).await {
-                        Ok(saved) => {
-                            if saved {
-                                total_records += data.len();
-                                days_with_data += 1;
-                            } else {
-                                pb.println(format!("  {} - {} records (already exists)", current_date, data.len()));
+
+        let mut completed = false;
+
+        let (started_at, duration, result) = joined?;
+        async {
+            let mut run = RunRecord {
+                run_id: Some(run_id),
+                scraper: name.clone(),
+                source: "backfill".to_string(),
+                window_start,
+                window_end,
+                started_at,
+                duration_ms: duration.as_millis() as u64,
+                records_fetched: 0,
+                records_written: 0,
+                error: None,
+            };
+
+            match result {
+                Ok(data) => {
+                    run.records_fetched = data.len() as u64;
+                    let endpoint = scraper_config.scraper_config.values.get("url").and_then(|v| v.as_str()).map(String::from);
+                    let provenance = Provenance::new(name, endpoint, window_start, window_end, started_at, &data);
+                    if scraper_config.raw_archive && !data.is_empty() {
+                        if let Err(e) = storage.archive_raw(name, scraper_config.sub_data_folder.as_deref(), &RawResponse::new(provenance.clone(), &data)).await {
+                            error!("Failed to archive raw response for {}: {:?}", current_date, e);
+                        }
+                    }
+                    // With the error policy nothing of a conflicting response is stored and the day is retried on resume
+                    let data = match conflict::resolve(name, scraper_config.conflict_policy, data) {
+                        Ok(resolved) => {
+                            if !resolved.versions.is_empty() {
+                                if let Err(e) = storage.save_conflicts(name, scraper_config.sub_data_folder.as_deref(), &resolved.versions, Some(&provenance)).await {
+                                    error!("Failed to save conflicting values for {}: {:?}", current_date, e);
+                                }
                             }
+                            resolved.data
                         }
                         Err(e) => {
-                            pb.println(format!("⚠ Failed to save data for {}: {:?}", current_date, e));
-                            error!("Failed to save data for {}: {:?}", current_date, e);
+                            pb.println(format!("⚠ Conflicting response for {}: {:?}", current_date, e));
+                            error!("Conflicting response for {}: {:?}", current_date, e);
+                            run.error = Some(format!("conflict: {:#}", e));
+                            Vec::new()
+                        }
+                    };
+                    let data = transforms.apply(data);
+                    let data = match &validator {
+                        Some(validator) => {
+                            let result = validator.validate(name, data);
+                            if !result.rejected.is_empty() {
+                                pb.println(format!("  {} - {} records failed validation", current_date, result.rejected.len()));
+                                if validator.rules().quarantine {
+                                    if let Err(e) = storage.save_rejected(
+                                        name,
+                                        scraper_config.sub_data_folder.as_deref(),
+                                        &result.rejected,
+                                        Some(&provenance)
+                                    ).await {
+                                        error!("Failed to save rejected data for {}: {:?}", current_date, e);
+                                    }
+                                }
+                            }
+                            result.accepted
                         }
+                        None => data,
+                    };
+                    let data = match &anomaly {
+                        Some(detector) => {
+                            let result = detector.check(name, data);
+                            if !result.rejected.is_empty() {
+                                if let Err(e) = storage.save_rejected(
+                                    name,
+                                    scraper_config.sub_data_folder.as_deref(),
+                                    &result.rejected,
+                                    Some(&provenance)
+                                ).await {
+                                    error!("Failed to save anomalous data for {}: {:?}", current_date, e);
+                                }
+                            }
+                            result.accepted
+                        }
+                        None => data,
+                    };
+
+                    if !data.is_empty() {
+                        info!("Scraped {} records for {}", data.len(), current_date);
+                        match storage.save_backfill(
+                            name,
+                            scraper_config.sub_data_folder.as_deref(),
+                            &data,
+                            Some(&provenance)
+                        ).instrument(info_span!("store", backend = "parquet")).await {
+                            Ok(saved) => {
+                                completed = true;
+                                run.records_written = saved as u64;
+                                if saved > 0 {
+                                    total_records += data.len();
+                                    days_with_data += chunk.len();
+                                } else {
+                                    pb.println(format!("  {} - {} records (already exists)", current_date, data.len()));
+                                }
+                            }
+                            Err(e) => {
+                                pb.println(format!("⚠ Failed to save data for {}: {:?}", current_date, e));
+                                error!("Failed to save data for {}: {:?}", current_date, e);
+                                run.error = Some(format!("save: {:#}", e));
+                            }
+                        }
+
+                        for sink in sinks {
+                            if let Err(e) = sink.save_backfill(name, scraper_config.sub_data_folder.as_deref(), &data).await {
+                                pb.println(format!("⚠ Failed to save data for {} to {}: {:?}", current_date, sink.name(), e));
+                                error!("Failed to save data for {} to {}: {:?}", current_date, sink.name(), e);
+                                run.error.get_or_insert(format!("{}: {:#}", sink.name(), e));
+                                // Retried on resume, Parquet ignores the data it already has
+                                completed = false;
+                            }
+                        }
+                    } else if run.error.is_none() {
+                        completed = true;
+                        pb.println(format!("  {} - No data returned", current_date));
                     }
-                } else {
-                    pb.println(format!("  {} - No data returned", current_date));
+                }
+                Err(e) => {
+                    pb.println(format!("⚠ Failed to scrape {}: {:?}", current_date, e));
+                    error!("Failed to scrape {}: {:?}", current_date, e);
+                    run.error = Some(format!("scrape: {:#}", e));
                 }
             }
-            Err(e) => {
-                pb.println(format!("⚠ Failed to scrape {}: {:?}", current_date, e));
-                error!("Failed to scrape {}: {:?}", current_date, e);
+            ledger.record(run);
+        }.instrument(span).await;
+
+        // Failed days are not recorded, so a resumed run retries them
+        if completed {
+            for date in chunk {
+                checkpoint.complete(name, *date)?;
             }
         }
-        
-        pb.inc(1);
-        current_date = current_date + Duration::days(1);
+
+        pb.inc(chunk.len() as u64);
     }
-    
-    pb.finish_with_message(format!("✓ Completed: {} records from {} days with data", 
+
+    pb.finish_with_message(format!("✓ Completed: {} records from {} days with data",
         total_records, days_with_data));
 
-    // Wait for uploader to process remaining files
-    if uploader_handle.is_some() {
-        info!("Waiting for S3 uploads to complete...");
-        info!("The uploader processes files every 60 seconds.");
-        // Wait at least 90 seconds to ensure one full upload cycle completes
-        tokio::time::sleep(tokio::time::Duration::from_secs(90)).await;
+    Ok(())
+}
+
+/// Time ranges to request for a chunk of days, split so no request exceeds the backfill window
+fn request_windows(chunk: &[NaiveDate], scraper_config: &ScraperConfig) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>> {
+    let first = chunk.first().context("Empty chunk")?;
+    let last = chunk.last().context("Empty chunk")?;
+
+    // Use same approach as main service: query the lookback/lookahead window around noon of the target dates
+    // This ensures we get all data for the days even with timezone variations
+    let range_start = first.and_hms_opt(12, 0, 0).context("Invalid time")?.and_utc() - scraper_config.lookback();
+    let range_end = last.and_hms_opt(12, 0, 0).context("Invalid time")?.and_utc() + scraper_config.lookahead();
+
+    let window = match scraper_config.backfill_window_hours {
+        Some(hours) if hours > 0 => Duration::hours(hours),
+        _ => return Ok(vec![(range_start, range_end)]),
+    };
+
+    let mut windows = Vec::new();
+    let mut window_start = range_start;
+    while window_start < range_end {
+        let window_end = (window_start + window).min(range_end);
+        windows.push((window_start, window_end));
+        window_start = window_end;
     }
+    Ok(windows)
+}
 
-    Ok(())
+/// Whether a day is already stored, locally or in S3, and a description for the output.
+/// With `min_rows`, a partition only counts as complete with at least that many intervals, scaled to
+/// the length of DST transition days (e.g. 92 or 100 instead of 96 quarter-hours); partitions only
+/// in S3 are downloaded to count them, except in a dry run.
+async fn existing_status(
+    query: &Query,
+    uploader: Option<&Uploader>,
+    folder: &str,
+    date: NaiveDate,
+    min_rows: Option<usize>,
+    tz: Tz,
+    calendar: &CalendarConfig,
+    dry_run: bool,
+) -> Result<(bool, String)> {
+    if calendar.is_closed(date) {
+        return Ok((true, "closed by the calendar, no data expected".to_string()));
+    }
+    let path = query.partition_path(folder, date).to_string_lossy().to_string();
+    // Month and year partitions hold other days too, so the day's intervals are always counted
+    let whole_day = query.granularity(folder) == Granularity::Day;
+
+    let mut source = "locally";
+    if !std::path::Path::new(&path).exists() {
+        let in_s3 = match uploader {
+            Some(uploader) => uploader.exists(&path).await?,
+            None => false,
+        };
+        if !in_s3 {
+            return Ok((false, "missing".to_string()));
+        }
+        if min_rows.is_none() && whole_day {
+            return Ok((true, "exists in S3".to_string()));
+        }
+        if dry_run {
+            return Ok((false, "exists in S3, intervals not counted in a dry run".to_string()));
+        }
+        if let Some(uploader) = uploader {
+            uploader.download(&path).await?;
+        }
+        source = "in S3";
+    }
+
+    let count = query.interval_count(folder, date)?.unwrap_or(0);
+    if count == 0 && !whole_day {
+        return Ok((false, format!("no intervals of the day {}", source)));
+    }
+    // Intervals in maintenance windows aren't expected either
+    let (day_start, day_end) = completeness::day_bounds(date, tz)?;
+    let day_minutes = (day_end - day_start).num_minutes();
+    let open_minutes = day_minutes - calendar.maintenance_minutes(day_start, day_end);
+    let min_rows = min_rows
+        .map(|min| completeness::scale_to_day(min, date, tz).map(|rows| (rows as i64 * open_minutes / day_minutes) as usize))
+        .transpose()?;
+    match min_rows {
+        Some(min) if count < min => Ok((false, format!("incomplete {}, {} of {} intervals", source, count, min))),
+        _ => Ok((true, format!("exists {} with {} intervals", source, count))),
+    }
+}
+
+fn chunk_label(chunk: &[NaiveDate]) -> String {
+    match (chunk.first(), chunk.last()) {
+        (Some(first), Some(last)) if first != last => format!("{} to {}", first, last),
+        (Some(first), _) => first.to_string(),
+        _ => String::new(),
+    }
 }
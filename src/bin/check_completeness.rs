@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDate};
+use std::env;
+
+use scraping_service::{completeness, config};
+use config::{load_config, ScraperConfig};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut interval_minutes: Option<i64> = None;
+    let mut dst_only = false;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--interval-minutes" => {
+                let value = iter.next().context("--interval-minutes requires a value")?;
+                interval_minutes = Some(value.parse().context("Invalid --interval-minutes value")?);
+            }
+            "--dst-only" => dst_only = true,
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 3 {
+        eprintln!("Usage: {} <scraper_name|all> <start_date> <end_date> [--interval-minutes N] [--dst-only]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json, or 'all' for all scrapers");
+        eprintln!("  start_date: Start date in YYYY-MM-DD format");
+        eprintln!("  end_date: End date in YYYY-MM-DD format");
+        eprintln!("  --interval-minutes: Interval length (default: validation.expected_interval_minutes of the scraper)");
+        eprintln!("  --dst-only: Only check days with a DST transition");
+        eprintln!("\nChecks every stored day against the expected intervals of its local day, e.g. 92, 96 or 100 quarter-hours.");
+        eprintln!("Holidays, closed weekdays and maintenance windows of the scraper's calendar aren't reported as missing.");
+        eprintln!("\nExample: {} apg_imb_15min 2025-03-01 2025-03-31", args[0]);
+        std::process::exit(1);
+    }
+
+    let start_date = NaiveDate::parse_from_str(&positional[1], "%Y-%m-%d")
+        .context("Failed to parse start_date. Use YYYY-MM-DD format")?;
+    let end_date = NaiveDate::parse_from_str(&positional[2], "%Y-%m-%d")
+        .context("Failed to parse end_date. Use YYYY-MM-DD format")?;
+
+    let config = load_config("config.json").context("Failed to load config.json")?;
+    let scrapers: Vec<&ScraperConfig> = config.scrapers.iter()
+        .filter(|s| positional[0] == "all" || s.scraper_config.name == positional[0])
+        .collect();
+    if scrapers.is_empty() {
+        eprintln!("Error: No matching scrapers found for '{}'", positional[0]);
+        std::process::exit(1);
+    }
+
+    let query = config.query("data")?;
+    let mut incomplete_days = 0;
+
+    for scraper in scrapers {
+        let name = &scraper.scraper_config.name;
+        let interval = match interval_minutes.or(scraper.validation.as_ref().and_then(|v| v.expected_interval_minutes)) {
+            Some(minutes) => minutes,
+            None => {
+                println!("{}: skipped, set validation.expected_interval_minutes or pass --interval-minutes", name);
+                continue;
+            }
+        };
+        let tz = scraper.partition_timezone()?;
+        let calendar = config.calendar(scraper)?;
+
+        println!("=== {} ({} minute intervals, {}) ===", name, interval, tz);
+
+        let mut date = start_date;
+        while date <= end_date {
+            let result = completeness::check_partition(&query, scraper.data_folder(), date, tz, interval, &calendar)?;
+            if !dst_only || result.dst_transition {
+                let dst = if result.dst_transition { " (DST)" } else { "" };
+                if result.closed {
+                    println!("- {}{} - closed, {} intervals stored", date, dst, result.present);
+                } else if result.is_complete() {
+                    let excused = if result.excused > 0 { format!(", {} in maintenance", result.excused) } else { String::new() };
+                    println!("✓ {}{} - {}/{} intervals{}", date, dst, result.present, result.expected, excused);
+                } else {
+                    incomplete_days += 1;
+                    println!("✗ {}{} - {}/{} intervals", date, dst, result.present, result.expected);
+                    for (gap_start, gap_end) in &result.gaps {
+                        println!("    missing {} to {}", gap_start, gap_end);
+                    }
+                }
+            }
+            date += Duration::days(1);
+        }
+    }
+
+    if incomplete_days > 0 {
+        println!("\n{} incomplete days", incomplete_days);
+        std::process::exit(1);
+    }
+    println!("\n✓ All checked days complete");
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDate};
+use std::env;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use scraping_service::{config, derived, logging, storage, uploader};
+use config::load_config;
+use storage::Storage;
+use uploader::Uploader;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--tenant" => tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 3 {
+        eprintln!("Usage: {} <name|all> <start_date> <end_date> [--tenant <name>]", args[0]);
+        eprintln!("  name: Name of a derived series from config.json, or 'all' for every derived series");
+        eprintln!("  start_date: Start date in YYYY-MM-DD format");
+        eprintln!("  end_date: End date in YYYY-MM-DD format");
+        eprintln!("  --tenant: Tenant of the derived series (default: the top-level series)");
+        eprintln!("\nRecomputes derived series from the stored data of their inputs.");
+        eprintln!("\nExample: {} apg_net_position 2025-01-01 2025-01-31", args[0]);
+        std::process::exit(1);
+    }
+
+    let start_date = NaiveDate::parse_from_str(&positional[1], "%Y-%m-%d")
+        .context("Failed to parse start_date. Use YYYY-MM-DD format")?;
+    let end_date = NaiveDate::parse_from_str(&positional[2], "%Y-%m-%d")
+        .context("Failed to parse end_date. Use YYYY-MM-DD format")?;
+
+    let config = load_config("config.json").context("Failed to load config.json")?
+        .tenant(tenant.as_deref())?;
+    let series: Vec<String> = config.derived.iter()
+        .filter(|d| positional[0] == "all" || d.name == positional[0])
+        .map(|d| d.name.clone())
+        .collect();
+    if series.is_empty() {
+        anyhow::bail!("No derived series named '{}' in config.json", positional[0]);
+    }
+
+    let s3_uploader = Uploader::from_config(&config).await?.map(Arc::new);
+
+    let mut storage = Storage::new(config.base_path(), s3_uploader.as_ref().map(|u| u.get_pending_files_handle()))
+        .with_parquet_config(config.parquet.clone());
+    for derived in derived::from_config(&config.derived)? {
+        let tz = derived.config.partition_timezone()?;
+        storage = storage.with_derived(derived, tz);
+    }
+
+    let mut rows_written = 0;
+    for name in &series {
+        let mut date = start_date;
+        while date <= end_date {
+            rows_written += storage.derive_day(name, date).await
+                .with_context(|| format!("Failed to derive {} on {}", name, date))?;
+            date += Duration::days(1);
+        }
+        info!("Derived {} from {} to {}", name, start_date, end_date);
+    }
+
+    if let Some(uploader) = &s3_uploader {
+        let pending: Vec<String> = uploader.get_pending_files_handle().lock().await.drain().collect();
+        info!("Uploading {} changed files", pending.len());
+        for file_path in pending {
+            if let Err(e) = uploader.upload_file(&file_path).await {
+                error!("Failed to upload {}: {:?}", file_path, e);
+            }
+        }
+    }
+
+    println!("\n✓ {} derived rows written", rows_written);
+    Ok(())
+}
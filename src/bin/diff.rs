@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::env;
+use std::io;
+
+use scraping_service::config;
+use config::load_config;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        eprintln!("Usage: {} <scraper_name> <date> [end_date]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json");
+        eprintln!("  date: Date in YYYY-MM-DD format");
+        eprintln!("  end_date: Optional end date in YYYY-MM-DD format to report a range of days");
+        eprintln!("\nPrints every value that changed between scrapes as CSV to stdout");
+        eprintln!("\nExample: {} apg_imb_price_15min 2025-01-14", args[0]);
+        std::process::exit(1);
+    }
+
+    let scraper_name = &args[1];
+
+    let start_date = NaiveDate::parse_from_str(&args[2], "%Y-%m-%d")
+        .context("Failed to parse date. Use YYYY-MM-DD format")?;
+    let end_date = match args.get(3) {
+        Some(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .context("Failed to parse end_date. Use YYYY-MM-DD format")?,
+        None => start_date,
+    };
+
+    let config = load_config("config.json").context("Failed to load config.json")?;
+
+    let scraper_config = config.scrapers.iter()
+        .find(|s| s.scraper_config.name == *scraper_name)
+        .context(format!("Scraper '{}' not found in config.json", scraper_name))?;
+
+    let query = config.query("data")?;
+    let revisions = query.revisions(scraper_config.data_folder(), start_date, end_date)?;
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    writer.write_record(["start", "end", "series", "old_value", "new_value", "previous_scraped_at", "changed_at"])?;
+
+    for revision in &revisions {
+        writer.write_record([
+            revision.start.to_rfc3339(),
+            revision.end.to_rfc3339(),
+            revision.series.clone(),
+            revision.old_value.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+            revision.new_value.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+            revision.previous_scraped_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            revision.changed_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        ])?;
+    }
+
+    writer.flush()?;
+    eprintln!("{} revisions found", revisions.len());
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use scraping_service::{config, export, partition, query};
+use config::load_config;
+use export::ExportFormat;
+use query::QueryResult;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut format = None;
+    let mut timezone = chrono_tz::UTC;
+    let mut as_of = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = iter.next().context("--format requires a value")?;
+                format = Some(ExportFormat::parse(value)?);
+            }
+            "--timezone" => {
+                let value = iter.next().context("--timezone requires a value")?;
+                timezone = partition::parse_timezone(value)?;
+            }
+            "--as-of" => {
+                let value = iter.next().context("--as-of requires a value")?;
+                as_of = Some(query::parse_timestamp(value)?);
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 4 {
+        eprintln!("Usage: {} <scraper_name> <start_date> <end_date> <output_file> [--format csv|json|parquet] [--timezone <tz>] [--as-of <timestamp>]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json");
+        eprintln!("  start_date: Start date in YYYY-MM-DD format");
+        eprintln!("  end_date: End date in YYYY-MM-DD format");
+        eprintln!("  output_file: File to write, the format is derived from the extension unless --format is given");
+        eprintln!("  --timezone: Timezone for timestamps, e.g. Europe/Vienna (default: UTC)");
+        eprintln!("  --as-of: Export the data as it was stored at this time (RFC 3339 or YYYY-MM-DD HH:MM:SS in UTC)");
+        eprintln!("\nExample: {} apg_imb_15min 2025-01-01 2025-01-31 imb.csv --timezone Europe/Vienna", args[0]);
+        std::process::exit(1);
+    }
+
+    let scraper_name = &positional[0];
+    let output_file = &positional[3];
+
+    let start_date = NaiveDate::parse_from_str(&positional[1], "%Y-%m-%d")
+        .context("Failed to parse start_date. Use YYYY-MM-DD format")?;
+    let end_date = NaiveDate::parse_from_str(&positional[2], "%Y-%m-%d")
+        .context("Failed to parse end_date. Use YYYY-MM-DD format")?;
+
+    let format = match format {
+        Some(f) => f,
+        None => {
+            let extension = Path::new(output_file)
+                .extension()
+                .and_then(|e| e.to_str())
+                .context("Cannot derive format from output file, use --format")?;
+            ExportFormat::parse(extension)?
+        }
+    };
+
+    let config = load_config("config.json").context("Failed to load config.json")?;
+
+    let scraper_config = config.scrapers.iter()
+        .find(|s| s.scraper_config.name == *scraper_name)
+        .context(format!("Scraper '{}' not found in config.json", scraper_name))?;
+
+    let query = config.query("data")?;
+    let result = query.latest(scraper_config.data_folder(), start_date, end_date, as_of)?;
+
+    let row_count = match &result {
+        QueryResult::Values(rows) => rows.len(),
+        QueryResult::Bids(rows) => rows.len(),
+    };
+
+    match format {
+        ExportFormat::Csv => export::write_csv(&result, BufWriter::new(File::create(output_file)?), timezone)?,
+        ExportFormat::Json => export::write_json(&result, BufWriter::new(File::create(output_file)?), timezone)?,
+        ExportFormat::Parquet => export::write_parquet(&result, output_file, timezone)?,
+    }
+
+    println!("✓ Exported {} rows to {}", row_count, output_file);
+    Ok(())
+}
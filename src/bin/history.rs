@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::env;
+use std::io;
+
+use scraping_service::history;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut scraper = None;
+    let mut errors_only = false;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--scraper" => {
+                scraper = Some(iter.next().context("--scraper requires a value")?.clone());
+            }
+            "--errors" => errors_only = true,
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.is_empty() {
+        eprintln!("Usage: {} <start_date> [end_date] [--scraper <name>] [--errors]", args[0]);
+        eprintln!("  start_date: Date in YYYY-MM-DD format (UTC)");
+        eprintln!("  end_date: Optional end date in YYYY-MM-DD format to report a range of days");
+        eprintln!("  --scraper: Only show runs of this scraper");
+        eprintln!("  --errors: Only show failed runs");
+        eprintln!("\nPrints every recorded scrape attempt as CSV to stdout");
+        eprintln!("\nExample: {} 2025-01-14 --scraper apg_imb_price_15min", args[0]);
+        std::process::exit(1);
+    }
+
+    let start_date = NaiveDate::parse_from_str(&positional[0], "%Y-%m-%d")
+        .context("Failed to parse start_date. Use YYYY-MM-DD format")?;
+    let end_date = match positional.get(1) {
+        Some(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .context("Failed to parse end_date. Use YYYY-MM-DD format")?,
+        None => start_date,
+    };
+
+    let runs: Vec<_> = history::read_runs(history::HISTORY_DIR, start_date, end_date)?
+        .into_iter()
+        .filter(|r| scraper.as_ref().map(|s| r.scraper == *s).unwrap_or(true))
+        .filter(|r| !errors_only || r.error.is_some())
+        .collect();
+
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    writer.write_record(["started_at", "scraper", "source", "window_start", "window_end", "duration_ms", "records_fetched", "records_written", "error", "run_id"])?;
+
+    for run in &runs {
+        writer.write_record([
+            run.started_at.to_rfc3339(),
+            run.scraper.clone(),
+            run.source.clone(),
+            run.window_start.to_rfc3339(),
+            run.window_end.to_rfc3339(),
+            run.duration_ms.to_string(),
+            run.records_fetched.to_string(),
+            run.records_written.to_string(),
+            run.error.clone().unwrap_or_default(),
+            run.run_id.clone().unwrap_or_default(),
+        ])?;
+    }
+
+    writer.flush()?;
+    eprintln!("{} runs found", runs.len());
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use scraping_service::{anomaly, config, conflict, derived, import, logging, provenance, storage, uploader, validation};
+use anomaly::AnomalyDetector;
+use config::load_config;
+use import::ImportMapping;
+use provenance::Provenance;
+use storage::Storage;
+use uploader::Uploader;
+use validation::Validator;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut mapping_path = None;
+    let mut dry_run = false;
+    let mut tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--mapping" => mapping_path = Some(iter.next().context("--mapping requires a value")?.clone()),
+            "--dry-run" => dry_run = true,
+            "--tenant" => tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 2 {
+        eprintln!("Usage: {} <scraper_name> <file|directory>... [--mapping <mapping.json>] [--dry-run] [--tenant <name>]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json whose series the files belong to");
+        eprintln!("  file|directory: CSV or Parquet files, directories are searched for .csv and .parquet files");
+        eprintln!("  --mapping: Column mapping as JSON (default: the scraper's \"import\" section)");
+        eprintln!("  --dry-run: Only read the files and print which partitions would change");
+        eprintln!("  --tenant: Tenant of the scraper (default: the top-level scrapers)");
+        eprintln!("\nIngests historical dumps through validation, deduplication and storage like a backfill.");
+        eprintln!("\nExample: {} apg_imb_15min vendor/imbalance_2015_2024/ --mapping vendor/mapping.json", args[0]);
+        std::process::exit(1);
+    }
+
+    let config = load_config("config.json").context("Failed to load config.json")?
+        .tenant(tenant.as_deref())?;
+    let scraper = config.scrapers.iter()
+        .find(|s| s.scraper_config.name == positional[0])
+        .with_context(|| format!("Scraper '{}' not found in config.json", positional[0]))?;
+    let name = &scraper.scraper_config.name;
+    let mapping: ImportMapping = match &mapping_path {
+        Some(path) => serde_json::from_str(&std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?)
+            .with_context(|| format!("Invalid import mapping {}", path))?,
+        None => scraper.import.clone().with_context(|| format!("{} has no import mapping, pass --mapping", name))?,
+    };
+
+    let mut files = Vec::new();
+    for path in &positional[1..] {
+        files.extend(import::find_files(Path::new(path))?);
+    }
+    info!("Importing {} files into {}", files.len(), scraper.data_folder());
+
+    let s3_uploader = if dry_run { None } else { Uploader::from_config(&config).await?.map(Arc::new) };
+
+    let mut storage = Storage::new(config.base_path(), s3_uploader.as_ref().map(|u| u.get_pending_files_handle()))
+        .with_parquet_config(config.parquet.clone())
+        .with_scraper(scraper)?;
+    if let Some(uploader) = &s3_uploader {
+        storage = storage.with_hydration(uploader.clone());
+    }
+    for derived in derived::from_config(&config.derived)? {
+        let tz = derived.config.partition_timezone()?;
+        storage = storage.with_derived(derived, tz);
+    }
+    let delta_log = config.delta_log(config.base_path()).map(|delta_log| match &s3_uploader {
+        Some(uploader) => Arc::new(delta_log.with_uploader(uploader.clone())),
+        None => Arc::new(delta_log),
+    });
+    if let Some(delta_log) = &delta_log {
+        storage = storage.with_delta(delta_log.clone());
+    }
+    let transforms = scraper.transforms()?;
+    let validator = Validator::for_scraper(scraper, config.base_path())?;
+
+    let mut records = 0;
+    let mut rows_written = 0;
+    let mut skipped_values = 0;
+
+    for file in &files {
+        let imported = import::read_file(file, &mapping).with_context(|| format!("Failed to import {:?}", file))?;
+        skipped_values += imported.skipped_values;
+        records += imported.data.len();
+        let Some((start, end)) = import::time_range(&imported.data) else {
+            warn!("{:?} has no rows with values", file);
+            continue;
+        };
+        println!("{} - {} rows, {} to {}", file.display(), imported.rows, start, end);
+        let anomaly = AnomalyDetector::for_scraper(scraper, config.base_path(), start)?;
+
+        for chunk in import::chunks(imported.data) {
+            let provenance = Provenance::new(name, Some(format!("file://{}", file.display())), start, end, Utc::now(), &chunk);
+            let resolved = conflict::resolve(name, scraper.conflict_policy, chunk)
+                .with_context(|| format!("Failed to import {:?}", file))?;
+            if !resolved.versions.is_empty() && !dry_run {
+                storage.save_conflicts(name, scraper.sub_data_folder.as_deref(), &resolved.versions, Some(&provenance)).await?;
+            }
+            let data = transforms.apply(resolved.data);
+            let data = match &validator {
+                Some(validator) => {
+                    let result = validator.validate(name, data);
+                    if validator.rules().quarantine && !result.rejected.is_empty() && !dry_run {
+                        storage.save_rejected(name, scraper.sub_data_folder.as_deref(), &result.rejected, Some(&provenance)).await?;
+                    }
+                    result.accepted
+                }
+                None => data,
+            };
+            let data = match &anomaly {
+                Some(detector) => {
+                    let result = detector.check(name, data);
+                    if !result.rejected.is_empty() && !dry_run {
+                        storage.save_rejected(name, scraper.sub_data_folder.as_deref(), &result.rejected, Some(&provenance)).await?;
+                    }
+                    result.accepted
+                }
+                None => data,
+            };
+            if data.is_empty() {
+                continue;
+            }
+            if dry_run {
+                for planned in storage.plan(scraper.data_folder(), &data)? {
+                    println!("    {} - {} new, {} changed", planned.file_path, planned.new_rows, planned.changed_rows);
+                }
+            } else {
+                rows_written += storage.save_backfill(name, scraper.sub_data_folder.as_deref(), &data, Some(&provenance)).await?;
+            }
+        }
+    }
+
+    if skipped_values > 0 {
+        warn!("Skipped {} empty or non-numeric values", skipped_values);
+    }
+
+    if let Some(uploader) = &s3_uploader {
+        let pending: Vec<String> = uploader.get_pending_files_handle().lock().await.drain().collect();
+        info!("Uploading {} changed files", pending.len());
+        for file_path in pending {
+            if let Err(e) = uploader.upload_file(&file_path).await {
+                error!("Failed to upload {}: {:?}", file_path, e);
+            }
+        }
+    }
+
+    if let Some(delta_log) = &delta_log {
+        if let Err(e) = delta_log.commit().await {
+            error!("Failed to commit Delta tables: {:?}", e);
+        }
+    }
+
+    if dry_run {
+        println!("\n{} records in {} files", records, files.len());
+    } else {
+        println!("\n✓ Imported {} records of {} files into {}, {} rows written", records, files.len(), name, rows_written);
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+
+use scraping_service::{config, logging, parquet_config, partition, partition_manifest, schema, uploader};
+use config::load_config;
+use parquet_config::ParquetConfig;
+use schema::CURRENT_SCHEMA_VERSION;
+use uploader::Uploader;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut dry_run = false;
+    let mut tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--tenant" => tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            "--help" | "-h" => {
+                eprintln!("Usage: {} [folder] [--dry-run] [--tenant <name>]", args[0]);
+                eprintln!("  folder: Data folder below the data directory to migrate, e.g. apg_imb_15min (default: everything)");
+                eprintln!("  --dry-run: Only list the partitions that would be migrated");
+                eprintln!("  --tenant: Migrate the data directory and bucket of this tenant (default: data/)");
+                eprintln!("\nUpgrades all partitions to schema version {}", CURRENT_SCHEMA_VERSION);
+                std::process::exit(1);
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let config = load_config("config.json").context("Failed to load config.json")?
+        .tenant(tenant.as_deref())?;
+
+    let root = match positional.first() {
+        Some(folder) => Path::new(config.base_path()).join(folder),
+        None => PathBuf::from(config.base_path()),
+    };
+
+    let uploader = if dry_run { None } else { Uploader::from_config(&config).await? };
+
+    for migration in schema::MIGRATIONS {
+        info!("Migration {} -> {}: {}", migration.from, migration.from + 1, migration.description);
+    }
+
+    let mut files = Vec::new();
+    find_partitions(&root, &mut files)?;
+    files.sort();
+    info!("Found {} partitions in {:?}", files.len(), root);
+
+    let mut migrated = 0;
+    let mut manifests = 0;
+    let mut failed = 0;
+
+    for path in &files {
+        let partition = match schema::read_partition(path) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Failed to read {:?}: {:?}", path, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let file_path = path.to_string_lossy().to_string();
+        if !partition.is_outdated() {
+            // Partitions written before manifests were introduced only need their manifest
+            if Path::new(&partition_manifest::manifest_path(&file_path)).exists() {
+                continue;
+            }
+            if dry_run {
+                println!("{} (no manifest)", path.display());
+                manifests += 1;
+                continue;
+            }
+            match partition_manifest::write(&file_path) {
+                Ok(manifest) => {
+                    manifests += 1;
+                    if let Some(uploader) = &uploader {
+                        if let Err(e) = uploader.upload_file(&manifest).await {
+                            error!("Failed to upload {}: {:?}", manifest, e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to write the manifest of {:?}: {:?}", path, e);
+                    failed += 1;
+                }
+            }
+            continue;
+        }
+
+        if dry_run {
+            println!("{} (version {})", path.display(), partition.stored_version);
+            migrated += 1;
+            continue;
+        }
+
+        if let Err(e) = write_partition(path, &partition.batches, &config.parquet) {
+            error!("Failed to migrate {:?}: {:?}", path, e);
+            failed += 1;
+            continue;
+        }
+        info!("Migrated {:?} from version {}", path, partition.stored_version);
+        migrated += 1;
+
+        let mut uploads = vec![file_path.clone()];
+        match partition_manifest::write(&file_path) {
+            Ok(manifest) => {
+                manifests += 1;
+                uploads.push(manifest);
+            }
+            Err(e) => error!("Failed to write the manifest of {:?}: {:?}", path, e),
+        }
+        if let Some(uploader) = &uploader {
+            for file in &uploads {
+                if let Err(e) = uploader.upload_file(file).await {
+                    error!("Failed to upload {}: {:?}", file, e);
+                }
+            }
+        }
+    }
+
+    if dry_run {
+        println!("\n{} of {} partitions need migration, {} more need a manifest", migrated, files.len(), manifests);
+    } else {
+        println!("\n✓ Migrated {} of {} partitions, wrote {} manifests, {} failed", migrated, files.len(), manifests, failed);
+    }
+
+    Ok(())
+}
+
+fn find_partitions(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_partitions(&path, files)?;
+        } else if path.file_name().is_some_and(|n| n == partition::DATA_FILE) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Rewrite a partition atomically with the current schema version
+fn write_partition(path: &Path, batches: &[RecordBatch], parquet_config: &ParquetConfig) -> Result<()> {
+    let first = match batches.first() {
+        Some(b) => b,
+        None => return Ok(()),
+    };
+
+    let tmp_path = path.with_extension("parquet.tmp");
+    let file = File::create(&tmp_path)?;
+    let props = parquet_config.writer_properties(&[])?;
+    let mut writer = ArrowWriter::try_new(file, first.schema(), Some(props))?;
+    writer.append_key_value_metadata(schema::version_metadata());
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.close()?;
+
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
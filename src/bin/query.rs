@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use datafusion::arrow::datatypes::DataType;
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::ListingOptions;
+use datafusion::prelude::SessionContext;
+use object_store::aws::AmazonS3Builder;
+use url::Url;
+
+use scraping_service::{config, logging, partition};
+use config::load_config;
+use partition::Granularity;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+    let use_s3 = args.iter().any(|a| a == "--s3");
+    let sql = args.iter().skip(1).find(|a| !a.starts_with("--"));
+
+    let sql = match sql {
+        Some(sql) => sql.clone(),
+        None => {
+            eprintln!("Usage: {} [--s3] \"<sql>\"", args[0]);
+            eprintln!("  --s3: Query the data in the configured S3 bucket instead of the local data directory");
+            eprintln!("  sql: SQL statement. Every scraper from config.json is registered as a table named");
+            eprintln!("       after the scraper, with year, month and day as partition columns");
+            eprintln!("\nExample: {} \"SELECT * FROM apg_imb_15min WHERE year = 2025 AND month = 1 ORDER BY start\"", args[0]);
+            std::process::exit(1);
+        }
+    };
+
+    let config = load_config("config.json").context("Failed to load config.json")?;
+    let ctx = SessionContext::new();
+
+    let base_url = if use_s3 {
+        let bucket = config.get_s3_bucket().context("S3 bucket not configured")?;
+
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&bucket)
+            .with_region(config.get_s3_region().unwrap_or_else(|| "eu-central".to_string()));
+
+        if let Some(endpoint) = config.get_s3_endpoint() {
+            builder = builder.with_endpoint(endpoint);
+        }
+
+        // Same credential precedence as the uploader: S3_* env vars before AWS_* env vars
+        if let (Ok(access), Ok(secret)) = (env::var("S3_ACCESS_KEY"), env::var("S3_SECRET_KEY")) {
+            builder = builder
+                .with_access_key_id(access)
+                .with_secret_access_key(secret);
+        }
+
+        let bucket_url = Url::parse(&format!("s3://{}", bucket))?;
+        ctx.runtime_env().register_object_store(&bucket_url, Arc::new(builder.build()?));
+
+        format!("s3://{}/{}", bucket, config.get_s3_prefix())
+    } else {
+        "data/".to_string()
+    };
+
+    for scraper_config in &config.scrapers {
+        let name = &scraper_config.scraper_config.name;
+        let folder = scraper_config.data_folder();
+
+        if !use_s3 && !Path::new(&base_url).join(folder).exists() {
+            info!("No local data for {}, skipping", name);
+            continue;
+        }
+
+        let table_path = format!("{}{}/", base_url, folder);
+        // Month and year partitions have fewer partition columns
+        let partition_cols = match scraper_config.partition_granularity {
+            Granularity::Day => vec!["year", "month", "day"],
+            Granularity::Month => vec!["year", "month"],
+            Granularity::Year => vec!["year"],
+        };
+        let options = ListingOptions::new(Arc::new(ParquetFormat::default()))
+            // Only the current file of a partition, not the versions kept for Delta tables
+            .with_file_extension(partition::DATA_FILE)
+            .with_table_partition_cols(partition_cols.into_iter().map(|c| (c.to_string(), DataType::Int32)).collect());
+
+        if let Err(e) = ctx.register_listing_table(name.as_str(), &table_path, options, None, None).await {
+            warn!("Failed to register table {} at {}: {:?}", name, table_path, e);
+        }
+    }
+
+    let df = ctx.sql(&sql).await?;
+    df.show().await?;
+
+    Ok(())
+}
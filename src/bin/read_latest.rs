@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::env;
+use std::io;
+
+use scraping_service::{config, export, query};
+use config::load_config;
+use query::QueryResult;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut as_of = None;
+    let mut published_before = None;
+    let mut gate_closure = false;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--as-of" => {
+                let value = iter.next().context("--as-of requires a value")?;
+                as_of = Some(query::parse_timestamp(value)?);
+            }
+            "--published-before" => {
+                let value = iter.next().context("--published-before requires a value")?;
+                published_before = Some(query::parse_timestamp(value)?);
+            }
+            "--gate-closure" => gate_closure = true,
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 3 {
+        eprintln!("Usage: {} <scraper_name> <start_date> <end_date> [--as-of <timestamp> | --published-before <timestamp> | --gate-closure]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json");
+        eprintln!("  start_date: Start date in YYYY-MM-DD format");
+        eprintln!("  end_date: End date in YYYY-MM-DD format");
+        eprintln!("  --as-of: Show the data as it was stored at this time (RFC 3339 or YYYY-MM-DD HH:MM:SS in UTC)");
+        eprintln!("  --published-before: Latest forecast vintage published at or before this time");
+        eprintln!("  --gate-closure: Latest forecast vintage published before the gate closure of each interval");
+        eprintln!("\nPrints the latest value per interval as CSV to stdout");
+        eprintln!("\nExample: {} apg_imb_15min 2025-01-01 2025-01-31", args[0]);
+        eprintln!("Example: {} apg_imb_price_15min 2025-01-14 2025-01-14 --as-of \"2025-01-14 13:00:00\"", args[0]);
+        eprintln!("Example: {} entsoe_load_forecast 2025-01-14 2025-01-14 --gate-closure", args[0]);
+        std::process::exit(1);
+    }
+
+    let scraper_name = &positional[0];
+
+    let start_date = NaiveDate::parse_from_str(&positional[1], "%Y-%m-%d")
+        .context("Failed to parse start_date. Use YYYY-MM-DD format")?;
+    let end_date = NaiveDate::parse_from_str(&positional[2], "%Y-%m-%d")
+        .context("Failed to parse end_date. Use YYYY-MM-DD format")?;
+
+    let config = load_config("config.json").context("Failed to load config.json")?;
+
+    let scraper_config = config.scrapers.iter()
+        .find(|s| s.scraper_config.name == *scraper_name)
+        .context(format!("Scraper '{}' not found in config.json", scraper_name))?;
+
+    let query = config.query("data")?;
+    let folder = scraper_config.data_folder();
+    let result = if gate_closure {
+        let gate_closure = scraper_config.forecast.as_ref()
+            .and_then(|f| f.gate_closure)
+            .with_context(|| format!("{} has no forecast gate_closure configured", scraper_name))?;
+        let tz = scraper_config.partition_timezone()?;
+        QueryResult::Values(query.forecast_at_gate_closure(folder, start_date, end_date, gate_closure, tz)?)
+    } else if let Some(at) = published_before {
+        QueryResult::Values(query.forecast_as_of(folder, start_date, end_date, at)?)
+    } else {
+        query.latest(folder, start_date, end_date, as_of)?
+    };
+
+    export::write_csv(&result, io::stdout(), chrono_tz::UTC)?;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::path::Path;
+
+use scraping_service::{config, fixtures, logging, query};
+use config::load_config;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 5 {
+        eprintln!("Usage: {} <scraper_name> <start> <end> <output.json>", args[0]);
+        eprintln!("  scraper_name: Name of a scraper from config.json");
+        eprintln!("  start, end: Window to scrape, RFC 3339 or 'YYYY-MM-DD HH:MM:SS' in UTC");
+        eprintln!("  output: Fixture file to write");
+        eprintln!("\nScrapes the window from the real API and stores the parsed records as a fixture for the mock scraper.");
+        eprintln!("\nExample: {} apg_imb_15min '2025-03-30 00:00:00' '2025-03-31 00:00:00' tests/fixtures/apg_imb_15min/dst_spring.json", args[0]);
+        std::process::exit(1);
+    }
+
+    let start = query::parse_timestamp(&args[2])?;
+    let end = query::parse_timestamp(&args[3])?;
+
+    let config = load_config("config.json").context("Failed to load config.json")?;
+    let scraper_config = config.scrapers.iter()
+        .find(|s| s.scraper_config.name == args[1])
+        .with_context(|| format!("No scraper named '{}' in config.json", args[1]))?;
+
+    let response = fixtures::record(scraper_config, start, end, Path::new(&args[4])).await?;
+    println!("Recorded {} records to {}", response.records.len(), args[4]);
+    Ok(())
+}
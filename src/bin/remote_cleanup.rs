@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use chrono::Duration;
+use std::collections::BTreeMap;
+use std::env;
+use tracing::{error, info};
+
+use scraping_service::{config, logging, remote_cleanup, uploader};
+use config::{load_config, AppConfig};
+use remote_cleanup::{Reason, RemoteCleanup};
+use uploader::Uploader;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut delete = false;
+    let mut unknown_folders = false;
+    let mut versions = false;
+    let mut min_age_hours = 24;
+    let mut only_tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--delete" => delete = true,
+            "--unknown-folders" => unknown_folders = true,
+            "--versions" => versions = true,
+            "--min-age-hours" => {
+                min_age_hours = iter.next().context("--min-age-hours requires a value")?
+                    .parse().context("--min-age-hours must be a number of hours")?;
+            }
+            "--tenant" => only_tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            _ => {
+                eprintln!("Usage: {} [--delete] [--unknown-folders] [--versions] [--min-age-hours <hours>] [--tenant <name>]", args[0]);
+                eprintln!("  --delete: Delete the selected objects, by default they are only listed");
+                eprintln!("  --unknown-folders: Also select partitions of folders no longer in config.json");
+                eprintln!("  --versions: Also select noncurrent versions, for buckets with versioning");
+                eprintln!("  --min-age-hours: Keep objects modified more recently (default: 24)");
+                eprintln!("  --tenant: Only clean the bucket of this tenant (default: every tenant)");
+                eprintln!("\nLists leftover objects below the prefix of every tenant's S3 bucket: temporary files,");
+                eprintln!("partitions of a replaced granularity and Delta part files no version references.");
+                eprintln!("Partition files that still exist locally are never deleted.");
+                eprintln!("\nExample: {} --delete", args[0]);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let config = load_config("config.json").context("Failed to load config.json")?;
+    let mut cleaned = 0;
+    let tenants = match &only_tenant {
+        Some(name) => vec![config.tenant(Some(name))?],
+        None => config.tenants()?,
+    };
+    for tenant in tenants {
+        let Some(uploader) = Uploader::from_config(&tenant).await? else {
+            info!("No S3 bucket configured for {}, skipping", tenant.base_path());
+            continue;
+        };
+        clean(&tenant, &uploader, Duration::hours(min_age_hours), unknown_folders, versions, delete).await?;
+        cleaned += 1;
+    }
+    if cleaned == 0 {
+        anyhow::bail!("No S3 bucket configured");
+    }
+    Ok(())
+}
+
+/// Select and optionally delete the leftover objects of one tenant's bucket
+async fn clean(config: &AppConfig, uploader: &Uploader, min_age: Duration, unknown_folders: bool, versions: bool, delete: bool) -> Result<()> {
+    info!("Listing {} below prefix '{}'", config.get_s3_bucket().unwrap_or_default(), config.get_s3_prefix());
+    let mut objects = uploader.list_objects().await?;
+    let listed = objects.len();
+    if versions {
+        objects.extend(uploader.list_noncurrent_versions().await?);
+    }
+
+    let mut cleanup = RemoteCleanup::new(config, config.base_path(), min_age)?;
+    if unknown_folders {
+        cleanup = cleanup.with_unknown_folders();
+    }
+    let selected = cleanup.select(objects);
+
+    let mut totals: BTreeMap<Reason, (usize, i64)> = BTreeMap::new();
+    for (object, reason) in &selected {
+        let total = totals.entry(*reason).or_default();
+        total.0 += 1;
+        total.1 += object.size;
+        match &object.version_id {
+            Some(version) => println!("{} (version {}) - {}", object.path, version, reason.describe()),
+            None => println!("{} - {}", object.path, reason.describe()),
+        }
+    }
+
+    println!("\n{}: {} objects listed", config.base_path(), listed);
+    for (reason, (count, bytes)) in &totals {
+        println!("  {}: {} objects, {} bytes", reason.describe(), count, bytes);
+    }
+
+    if !delete {
+        println!("\n{} objects would be deleted, run with --delete to delete them", selected.len());
+        return Ok(());
+    }
+
+    let mut deleted = 0;
+    let mut failed = 0;
+    for (object, _) in &selected {
+        match uploader.delete_object(object).await {
+            Ok(()) => deleted += 1,
+            Err(e) => {
+                error!("Failed to delete {}: {:?}", object.path, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("\n✓ Deleted {} objects, {} failed", deleted, failed);
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{error, info};
+
+use arrow::array::{new_null_array, Array, BooleanArray, TimestampMicrosecondArray};
+use arrow::compute::{concat_batches, filter_record_batch, lexsort_to_indices, take_record_batch, SortColumn};
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+
+use scraping_service::{config, forecast, logging, parquet_config, partition, partition_manifest, schema, uploader};
+use config::load_config;
+use parquet_config::ParquetConfig;
+use uploader::Uploader;
+
+/// Old partitions are moved here instead of being deleted
+const BACKUP_DIR: &str = "repartition_backup";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut dry_run = false;
+    let mut tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--tenant" => tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.is_empty() {
+        eprintln!("Usage: {} <scraper_name> [--dry-run] [--tenant <name>]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json");
+        eprintln!("  --dry-run: Only print how rows would move between partitions");
+        eprintln!("  --tenant: Tenant of the scraper (default: the top-level scrapers)");
+        eprintln!("\nRe-partitions the scraper's data by the local day of its partition_timezone into partitions of its partition_granularity.");
+        eprintln!("Stop the service first. The old partitions are kept in {}/ as a backup.", BACKUP_DIR);
+        eprintln!("\nExample: {} nordpool_no1_prices", args[0]);
+        std::process::exit(1);
+    }
+
+    let config = load_config("config.json").context("Failed to load config.json")?
+        .tenant(tenant.as_deref())?;
+    let scraper = config.scrapers.iter()
+        .find(|s| s.scraper_config.name == positional[0])
+        .with_context(|| format!("Scraper '{}' not found in config.json", positional[0]))?;
+    let tz = scraper.partition_timezone()?;
+    let granularity = scraper.partition_granularity;
+    let folder = Path::new(config.base_path()).join(scraper.data_folder());
+
+    let mut files = Vec::new();
+    find_partitions(&folder, &mut files)?;
+    files.sort();
+    info!("Re-partitioning {} partitions of {:?} by {} into {:?} partitions", files.len(), folder, tz, granularity);
+
+    // Rows of every target partition by its first day, and how many of them come from another partition
+    let mut targets: BTreeMap<NaiveDate, Vec<RecordBatch>> = BTreeMap::new();
+    let mut moved = 0;
+    let mut total = 0;
+
+    for path in &files {
+        let source = partition::date_range(path).map(|(granularity, first_day, _)| (granularity, first_day));
+        for batch in schema::read_partition(path).with_context(|| format!("Failed to read {:?}", path))?.batches {
+            for (date, rows) in split_by_day(&batch, tz)? {
+                let target = granularity.start(date);
+                total += rows.num_rows();
+                if source != Some((granularity, target)) {
+                    moved += rows.num_rows();
+                }
+                targets.entry(target).or_default().push(rows);
+            }
+        }
+    }
+
+    if dry_run {
+        for (date, batches) in &targets {
+            println!("{} - {} rows", granularity.dir(*date), batches.iter().map(|b| b.num_rows()).sum::<usize>());
+        }
+        println!("\n{} of {} rows would move, {} partitions before, {} after", moved, total, files.len(), targets.len());
+        return Ok(());
+    }
+
+    if moved == 0 {
+        println!("✓ Already partitioned by {} into {:?} partitions, nothing to do", tz, granularity);
+        return Ok(());
+    }
+
+    // Write the new layout next to the old one and swap the folders once everything is written
+    let staging = PathBuf::from(format!("{}.repartition", folder.display()));
+    if staging.exists() {
+        bail!("{:?} exists, remove the leftover of a previous run first", staging);
+    }
+    let mut written = Vec::new();
+    for (date, batches) in &targets {
+        let path = staging.join(granularity.dir(*date)).join(partition::DATA_FILE);
+        write_partition(&path, batches, &config.parquet)
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        written.push(folder.join(path.strip_prefix(&staging)?));
+    }
+
+    // Tenants keep their backups apart, like their data
+    let backup_dir = match &config.tenant {
+        Some(name) => format!("{}-{}", BACKUP_DIR, name),
+        None => BACKUP_DIR.to_string(),
+    };
+    let backup = Path::new(&backup_dir).join(scraper.data_folder()).join(Utc::now().format("%Y%m%d%H%M%S").to_string());
+    if let Some(parent) = backup.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(&folder, &backup)?;
+    std::fs::rename(&staging, &folder)?;
+    info!("Moved {} of {} rows, old partitions kept in {:?}", moved, total, backup);
+
+    let mut uploads = Vec::new();
+    for path in &written {
+        let file_path = path.to_string_lossy().to_string();
+        uploads.push(partition_manifest::write(&file_path).with_context(|| format!("Failed to write the manifest of {:?}", path))?);
+        uploads.push(file_path);
+    }
+
+    if let Some(uploader) = Uploader::from_config(&config).await? {
+        for file in &uploads {
+            if let Err(e) = uploader.upload_file(file).await {
+                error!("Failed to upload {}: {:?}", file, e);
+            }
+        }
+    }
+
+    println!("\n✓ Re-partitioned {} into {} partitions, {} rows moved", scraper.scraper_config.name, written.len(), moved);
+    Ok(())
+}
+
+fn find_partitions(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_partitions(&path, files)?;
+        } else if path.file_name().is_some_and(|n| n == partition::DATA_FILE) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Split the rows of a batch by the local day of their start
+fn split_by_day(batch: &RecordBatch, tz: Tz) -> Result<Vec<(NaiveDate, RecordBatch)>> {
+    let start = batch.column(batch.schema().index_of("start")?)
+        .as_any()
+        .downcast_ref::<TimestampMicrosecondArray>()
+        .context("start is not a timestamp")?;
+
+    let mut dates = Vec::with_capacity(start.len());
+    for i in 0..start.len() {
+        let start = DateTime::<Utc>::from_timestamp_micros(start.value(i)).context("Timestamp out of range")?;
+        dates.push(start.with_timezone(&tz).date_naive());
+    }
+
+    let mut unique = dates.clone();
+    unique.sort();
+    unique.dedup();
+
+    let mut result = Vec::new();
+    for date in unique {
+        let mask = BooleanArray::from(dates.iter().map(|d| *d == date).collect::<Vec<_>>());
+        result.push((date, filter_record_batch(batch, &mask)?));
+    }
+    Ok(result)
+}
+
+/// Union of the columns of all batches. Value columns are sorted by name like `Storage` writes them.
+fn merged_schema(batches: &[RecordBatch]) -> Result<SchemaRef> {
+    let merged = Schema::try_merge(batches.iter().map(|b| b.schema().as_ref().clone()))?;
+    if merged.index_of("bid_type").is_ok() {
+        return Ok(Arc::new(merged));
+    }
+
+    let mut fields: Vec<Field> = merged.fields().iter().map(|f| f.as_ref().clone()).collect();
+    let key_columns = ["start", "end", "scraped_at", forecast::PUBLICATION_COLUMN];
+    let mut values: Vec<Field> = fields.iter().filter(|f| !key_columns.contains(&f.name().as_str())).cloned().collect();
+    values.sort_by(|a, b| a.name().cmp(b.name()));
+    fields.retain(|f| key_columns.contains(&f.name().as_str()));
+    fields.extend(values.into_iter().map(|f| f.with_nullable(true)));
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+/// Bring a batch to the merged schema, with nulls for the columns it doesn't have
+fn align(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    let columns = schema.fields().iter()
+        .map(|field| match batch.column_by_name(field.name()) {
+            Some(column) => column.clone(),
+            None => new_null_array(field.data_type(), batch.num_rows()),
+        })
+        .collect();
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Write the rows of one day, sorted by start, end and scraped_at
+fn write_partition(path: &Path, batches: &[RecordBatch], parquet_config: &ParquetConfig) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let merged = merged_schema(batches)?;
+    let aligned = batches.iter().map(|b| align(b, &merged)).collect::<Result<Vec<_>>>()?;
+    let batch = concat_batches(&merged, &aligned)?;
+
+    let sort_columns: Vec<SortColumn> = ["start", "end", "scraped_at"].iter()
+        .filter_map(|name| batch.column_by_name(name))
+        .map(|values| SortColumn { values: values.clone(), options: None })
+        .collect();
+    let batch = take_record_batch(&batch, &lexsort_to_indices(&sort_columns, None)?)?;
+
+    let file = File::create(path)?;
+    let props = parquet_config.writer_properties(&[])?;
+    let mut writer = ArrowWriter::try_new(file, merged, Some(props))?;
+    writer.append_key_value_metadata(schema::version_metadata());
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use std::env;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use scraping_service::{anomaly, config, conflict, derived, logging, raw_archive, storage, uploader, validation};
+use anomaly::AnomalyDetector;
+use config::load_config;
+use storage::Storage;
+use uploader::Uploader;
+use validation::Validator;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut dry_run = false;
+    let mut tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--tenant" => tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 3 {
+        eprintln!("Usage: {} <scraper_name> <start_date> <end_date> [--dry-run] [--tenant <name>]", args[0]);
+        eprintln!("  scraper_name: Name of the scraper from config.json");
+        eprintln!("  start_date: Start date in YYYY-MM-DD format");
+        eprintln!("  end_date: End date in YYYY-MM-DD format");
+        eprintln!("  --dry-run: Only list the archived responses");
+        eprintln!("  --tenant: Tenant of the scraper (default: the top-level scrapers)");
+        eprintln!("\nReplays the responses archived in the raw/ folder of the data directory through validation and storage, oldest first.");
+        eprintln!("Requires \"raw_archive\": true on the scraper while the responses were scraped.");
+        eprintln!("\nExample: {} apg_imb_15min 2025-01-01 2025-01-31", args[0]);
+        std::process::exit(1);
+    }
+
+    let start_date = NaiveDate::parse_from_str(&positional[1], "%Y-%m-%d")
+        .context("Failed to parse start_date. Use YYYY-MM-DD format")?;
+    let end_date = NaiveDate::parse_from_str(&positional[2], "%Y-%m-%d")
+        .context("Failed to parse end_date. Use YYYY-MM-DD format")?;
+
+    let config = load_config("config.json").context("Failed to load config.json")?
+        .tenant(tenant.as_deref())?;
+    let scraper = config.scrapers.iter()
+        .find(|s| s.scraper_config.name == positional[0])
+        .with_context(|| format!("Scraper '{}' not found in config.json", positional[0]))?;
+    let name = &scraper.scraper_config.name;
+
+    let s3_uploader = if dry_run { None } else { Uploader::from_config(&config).await?.map(Arc::new) };
+
+    let mut storage = Storage::new(config.base_path(), s3_uploader.as_ref().map(|u| u.get_pending_files_handle()))
+        .with_parquet_config(config.parquet.clone())
+        .with_scraper(scraper)?;
+    if let Some(uploader) = &s3_uploader {
+        storage = storage.with_hydration(uploader.clone());
+    }
+    for derived in derived::from_config(&config.derived)? {
+        let tz = derived.config.partition_timezone()?;
+        storage = storage.with_derived(derived, tz);
+    }
+    let delta_log = config.delta_log(config.base_path()).map(|delta_log| match &s3_uploader {
+        Some(uploader) => Arc::new(delta_log.with_uploader(uploader.clone())),
+        None => Arc::new(delta_log),
+    });
+    if let Some(delta_log) = &delta_log {
+        storage = storage.with_delta(delta_log.clone());
+    }
+    let transforms = scraper.transforms()?;
+    let validator = Validator::for_scraper(scraper, config.base_path())?;
+    let anomaly = AnomalyDetector::for_scraper(scraper, config.base_path(), start_date.and_time(NaiveTime::MIN).and_utc())?;
+
+    let mut responses = 0;
+    let mut rows_written = 0;
+    let mut skipped_bids = 0;
+
+    let mut date = start_date;
+    while date <= end_date {
+        for path in raw_archive::list_day(config.base_path(), scraper.data_folder(), date)? {
+            let response = raw_archive::read(&path)?;
+            responses += 1;
+            if dry_run {
+                println!("{} - {} records fetched at {}", path.display(), response.records.len(), response.provenance.fetched_at);
+                continue;
+            }
+
+            let (data, bids) = response.to_scraper_data();
+            skipped_bids += bids;
+            let resolved = conflict::resolve(name, scraper.conflict_policy, data)
+                .with_context(|| format!("Failed to reprocess {}", path.display()))?;
+            if !resolved.versions.is_empty() {
+                storage.save_conflicts(name, scraper.sub_data_folder.as_deref(), &resolved.versions, Some(&response.provenance)).await?;
+            }
+            let data = transforms.apply(resolved.data);
+            let data = match &validator {
+                Some(validator) => {
+                    let result = validator.validate(name, data);
+                    if validator.rules().quarantine && !result.rejected.is_empty() {
+                        storage.save_rejected(name, scraper.sub_data_folder.as_deref(), &result.rejected, Some(&response.provenance)).await?;
+                    }
+                    result.accepted
+                }
+                None => data,
+            };
+            let data = match &anomaly {
+                Some(detector) => {
+                    let result = detector.check(name, data);
+                    if !result.rejected.is_empty() {
+                        storage.save_rejected(name, scraper.sub_data_folder.as_deref(), &result.rejected, Some(&response.provenance)).await?;
+                    }
+                    result.accepted
+                }
+                None => data,
+            };
+            if !data.is_empty() {
+                // Stored as of the original fetch, so the revision history matches the live service
+                rows_written += storage.save_as_of(name, scraper.sub_data_folder.as_deref(), &data, response.provenance.fetched_at, Some(&response.provenance)).await?;
+            }
+        }
+        date += Duration::days(1);
+    }
+
+    if skipped_bids > 0 {
+        warn!("Skipped {} bid records, bids can't be reprocessed", skipped_bids);
+    }
+
+    if let Some(uploader) = &s3_uploader {
+        let pending: Vec<String> = uploader.get_pending_files_handle().lock().await.drain().collect();
+        info!("Uploading {} changed files", pending.len());
+        for file_path in pending {
+            if let Err(e) = uploader.upload_file(&file_path).await {
+                error!("Failed to upload {}: {:?}", file_path, e);
+            }
+        }
+    }
+
+    if let Some(delta_log) = &delta_log {
+        if let Err(e) = delta_log.commit().await {
+            error!("Failed to commit Delta tables: {:?}", e);
+        }
+    }
+
+    if dry_run {
+        println!("\n{} archived responses", responses);
+    } else {
+        println!("\n✓ Reprocessed {} responses of {}, {} rows written", responses, name, rows_written);
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use anyhow::{bail, Context, Result};
+use std::env;
+
+use scraping_service::{config, metadata};
+use config::load_config;
+use metadata::ScraperMetadata;
+
+fn main() -> Result<()> {
+    // Load .env file in debug builds only
+    #[cfg(debug_assertions)]
+    dotenvy::dotenv().ok();
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut json = false;
+
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let usage = || {
+        eprintln!("Usage: {} list [--json]", args[0]);
+        eprintln!("       {} describe <scraper_name> [--json]", args[0]);
+        eprintln!("  list: One line per configured scraper with its source, resolution, folder and last data point");
+        eprintln!("  describe: Every resolved parameter of a scraper");
+        eprintln!("  --json: Print the metadata as JSON");
+        eprintln!("\nExample: {} describe apg_imb_15min", args[0]);
+        std::process::exit(1);
+    };
+
+    let config = load_config("config.json").context("Failed to load config.json")?;
+    let query = config.query("data")?;
+
+    let describe = |name: &str| -> Result<ScraperMetadata> {
+        let scraper = config.scrapers.iter()
+            .find(|s| s.scraper_config.name == name)
+            .with_context(|| format!("Scraper '{}' not found in config.json", name))?;
+        scraper.metadata("data", config.retention_days)?.with_last_data_point(&query, scraper)
+    };
+
+    match positional.first().map(String::as_str) {
+        Some("list") => {
+            let scrapers = config.scrapers.iter()
+                .map(|s| describe(&s.scraper_config.name))
+                .collect::<Result<Vec<_>>>()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&scrapers)?);
+                return Ok(());
+            }
+            println!("{:<28} {:<8} {:>10}  {:<40} last data point", "name", "source", "resolution", "folder");
+            for s in &scrapers {
+                println!(
+                    "{:<28} {:<8} {:>10}  {:<40} {}",
+                    s.name,
+                    s.source.map(|source| format!("{:?}", source).to_lowercase()).unwrap_or_else(|| "-".to_string()),
+                    s.resolution_minutes.map(|m| format!("{} min", m)).unwrap_or_else(|| "-".to_string()),
+                    s.data_folder,
+                    s.last_data_point.map(|t| t.to_rfc3339()).unwrap_or_else(|| "-".to_string()),
+                );
+            }
+        }
+        Some("describe") => {
+            let Some(name) = positional.get(1) else {
+                usage();
+                return Ok(());
+            };
+            let s = describe(name)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&s)?);
+                return Ok(());
+            }
+            let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+            println!("name:                  {}", s.name);
+            println!("source:                {}", or_dash(s.source.map(|source| format!("{:?}", source).to_lowercase())));
+            println!("url:                   {}", or_dash(s.url.clone()));
+            println!("url template:          {}", or_dash(s.url_template.clone()));
+            println!("resolution:            {}", or_dash(s.resolution_minutes.map(|m| format!("{} min", m))));
+            println!("value columns:         {}", if s.value_columns.is_empty() { "-".to_string() } else { s.value_columns.join(", ") });
+            println!("schedule:              every {} ms with {} workers, now - {}h to now + {}h",
+                s.schedule.delay_ms, s.schedule.workers, s.schedule.lookback_hours, s.schedule.lookahead_hours);
+            println!("revisions:             {}", or_dash(s.schedule.revision_window_days.map(|days| format!(
+                "last {} days every {}h", days, s.schedule.revision_interval_hours.unwrap_or(24)))));
+            println!("catch-up:              {} days", s.schedule.catch_up_days);
+            println!("requests per minute:   {}", or_dash(s.schedule.requests_per_minute.map(|r| r.to_string())));
+            println!("partition path:        {}", s.partition_path);
+            println!("partition timezone:    {}", s.partition_timezone);
+            println!("retention:             {}", match s.retention.days {
+                Some(days) => format!("{} days ({:?})", days, s.retention.mode).to_lowercase(),
+                None => "keep forever".to_string(),
+            });
+            println!("last data point:       {}", or_dash(s.last_data_point.map(|t| t.to_rfc3339())));
+        }
+        Some(other) => bail!("Unknown command '{}', expected list or describe", other),
+        None => usage(),
+    }
+    Ok(())
+}
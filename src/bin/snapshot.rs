@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::env;
+use std::path::Path;
+
+use scraping_service::{config, logging, snapshot};
+use config::{load_config, DEFAULT_BASE_PATH};
+use snapshot::SnapshotFilter;
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} create <archive> [--scraper <name>]... [--start <date>] [--end <date>] [--tenant <name>]", program);
+    eprintln!("       {} restore <archive> [--overwrite] [--tenant <name>]", program);
+    eprintln!("  create: Writes data/ to a .tar.gz with a manifest of SHA-256 checksums");
+    eprintln!("    --scraper: Only the data of this scraper from config.json, may be repeated");
+    eprintln!("    --start, --end: Only partitions of these days (YYYY-MM-DD, inclusive)");
+    eprintln!("  restore: Verifies the checksums of a snapshot and unpacks it into data/");
+    eprintln!("    --overwrite: Replace existing files instead of keeping them");
+    eprintln!("  --tenant: Use the data directory and scrapers of this tenant instead of data/");
+    eprintln!("\nExample: {} create snapshot.tar.gz --scraper apg_imb_15min --start 2025-01-01", program);
+    std::process::exit(1);
+}
+
+fn main() -> Result<()> {
+    let _log_guard = logging::init(None, None)?;
+
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional = Vec::new();
+    let mut scrapers = Vec::new();
+    let mut start = None;
+    let mut end = None;
+    let mut overwrite = false;
+    let mut tenant: Option<String> = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--scraper" => scrapers.push(iter.next().context("--scraper requires a value")?.clone()),
+            "--start" => {
+                let value = iter.next().context("--start requires a value")?;
+                start = Some(NaiveDate::parse_from_str(value, "%Y-%m-%d").context("Failed to parse --start. Use YYYY-MM-DD format")?);
+            }
+            "--end" => {
+                let value = iter.next().context("--end requires a value")?;
+                end = Some(NaiveDate::parse_from_str(value, "%Y-%m-%d").context("Failed to parse --end. Use YYYY-MM-DD format")?);
+            }
+            "--overwrite" => overwrite = true,
+            "--tenant" => tenant = Some(iter.next().context("--tenant requires a value")?.clone()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() < 2 {
+        usage(&args[0]);
+    }
+    let archive = Path::new(&positional[1]);
+
+    // config.json is only needed to look up scrapers or a tenant
+    let config = if tenant.is_some() || !scrapers.is_empty() {
+        Some(load_config("config.json").context("Failed to load config.json")?.tenant(tenant.as_deref())?)
+    } else {
+        None
+    };
+    let base_path = config.as_ref().map(|c| c.base_path()).unwrap_or(DEFAULT_BASE_PATH);
+
+    match positional[0].as_str() {
+        "create" => {
+            let mut filter = SnapshotFilter { prefixes: Vec::new(), start, end };
+            if let Some(config) = config.as_ref().filter(|_| !scrapers.is_empty()) {
+                for name in &scrapers {
+                    let scraper = config.scrapers.iter()
+                        .find(|s| s.scraper_config.name == *name)
+                        .context(format!("Scraper '{}' not found in config.json", name))?;
+                    filter.prefixes.extend(snapshot::scraper_prefixes(scraper.data_folder()));
+                }
+            }
+            let manifest = snapshot::create(base_path, archive, &filter)?;
+            let bytes: u64 = manifest.files.iter().map(|f| f.size).sum();
+            println!("\n✓ {} files ({} MB) written to {}", manifest.files.len(), bytes / (1024 * 1024), archive.display());
+        }
+        "restore" => {
+            let summary = snapshot::restore(archive, base_path, overwrite)?;
+            println!("\n✓ {} files restored, {} existing files kept", summary.restored, summary.skipped);
+        }
+        _ => usage(&args[0]),
+    }
+    Ok(())
+}
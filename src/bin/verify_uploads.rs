@@ -1,12 +1,13 @@
 use anyhow::{Context, Result};
-use chrono::{NaiveDate, Duration, Datelike};
+use chrono::NaiveDate;
 use std::env;
+use std::path::Path;
 use tracing::info;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 use indicatif::{ProgressBar, ProgressStyle};
 
-use scraping_service::config;
+use scraping_service::{config, logging, partition, partition_manifest, uploader};
 use config::load_config;
+use partition_manifest::PartitionManifest;
 
 use aws_config;
 use aws_sdk_s3::Client;
@@ -17,21 +18,28 @@ async fn main() -> Result<()> {
     #[cfg(debug_assertions)]
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_filter(tracing_subscriber::EnvFilter::try_from_default_env()
-                    .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")))
-        )
-        .init();
+    let _log_guard = logging::init(None, None)?;
 
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let check_manifests = args.iter().any(|a| a == "--manifests");
+    args.retain(|a| a != "--manifests");
+    let tenant = match args.iter().position(|a| a == "--tenant") {
+        Some(i) if i + 1 < args.len() => {
+            args.remove(i);
+            Some(args.remove(i))
+        }
+        Some(_) => anyhow::bail!("--tenant requires a value"),
+        None => None,
+    };
     
     if args.len() < 4 {
-        eprintln!("Usage: {} <scraper_name|all> <start_date> <end_date>", args[0]);
+        eprintln!("Usage: {} <scraper_name|all> <start_date> <end_date> [--manifests] [--tenant <name>]", args[0]);
         eprintln!("  scraper_name: Name of the scraper from config.json, or 'all' for all scrapers");
         eprintln!("  start_date: Start date in YYYY-MM-DD format");
         eprintln!("  end_date: End date in YYYY-MM-DD format");
+        eprintln!("  --manifests: Also download each partition's _manifest.json, report its rows and intervals and");
+        eprintln!("               whether the uploaded file matches the local one");
+        eprintln!("  --tenant: Verify the scrapers and bucket of this tenant (default: the top-level scrapers)");
         eprintln!("\nExample: {} apg_imb_15min 2025-01-01 2026-01-05", args[0]);
         eprintln!("Example: {} all 2025-01-01 2026-01-05", args[0]);
         std::process::exit(1);
@@ -56,7 +64,8 @@ async fn main() -> Result<()> {
     }
 
     // Load config
-    let config = load_config("config.json").context("Failed to load config.json")?;
+    let config = load_config("config.json").context("Failed to load config.json")?
+        .tenant(tenant.as_deref())?;
     
     let bucket = config.get_s3_bucket().context("No S3 bucket configured")?;
     let prefix = config.get_s3_prefix();
@@ -116,6 +125,15 @@ async fn main() -> Result<()> {
     }
     
     let client = Client::from_conf(s3_config_builder.build());
+
+    // Every destination has to hold the file: the primary bucket and each replica
+    let mut destinations = vec![("primary".to_string(), client, bucket.clone(), prefix.clone())];
+    for replica in &config.replicas {
+        info!("Also checking replica {} (bucket: {})", replica.name, replica.bucket);
+        let client = uploader::s3_client(replica.region.clone(), replica.endpoint.clone()).await;
+        let replica_prefix = replica.prefix.clone().unwrap_or_else(|| prefix.clone());
+        destinations.push((replica.name.clone(), client, replica.bucket.clone(), replica_prefix));
+    }
     
     // Check each scraper
     for scraper_config in &scrapers_to_check {
@@ -128,67 +146,118 @@ async fn main() -> Result<()> {
             scraper_config.scraper_config.name.clone()
         };
         
-        // The S3 key is: prefix + base_folder + /year=.../month=.../day=.../data.parquet
+        // The S3 key is: prefix + base_folder + /year=.../month=.../day=.../data.parquet, without the
+        // day or month directory for month and year partitions.
         // This matches how the uploader constructs keys from local files
+        let granularity = scraper_config.partition_granularity;
+        let partitions = granularity.partitions(start_date, end_date);
         
         // Create progress bar
-        let pb = ProgressBar::new(total_days as u64);
+        let pb = ProgressBar::new(partitions.len() as u64);
         pb.set_style(
             ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} days\n{msg}")
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} partitions\n{msg}")
                 .unwrap()
                 .progress_chars("#>-")
         );
         
         let mut missing_dates = Vec::new();
-        let mut current_date = start_date;
+        // Partitions without manifest, and uploaded partitions differing from the local file
+        let mut without_manifest = Vec::new();
+        let mut outdated = Vec::new();
+        let (mut rows, mut intervals) = (0, 0);
         
-        for _ in 0..total_days {
-            let year = current_date.year();
-            let month = current_date.month();
-            let day = current_date.day();
-            
-            // Construct S3 key: prefix + base_folder + partition path
-            let s3_key = format!("{}{}/year={}/month={:02}/day={:02}/data.parquet", 
-                prefix, base_folder, year, month, day);
-            
-            info!("Checking S3 key: {}", s3_key);
+        for &current_date in &partitions {
             pb.set_message(format!("Checking {}", current_date));
-            
-            // Check if file exists in S3
-            match client
-                .head_object()
-                .bucket(&bucket)
-                .key(&s3_key)
-                .send()
-                .await
-            {
-                Ok(_) => {
-                    info!("Found: {}", s3_key);
-                    // File exists
-                }
-                Err(e) => {
-                    info!("Not found: {} - Error: {:?}", s3_key, e);
-                    missing_dates.push(current_date);
-                    pb.println(format!("  ⚠ Missing: {}", current_date));
+
+            for (name, client, bucket, prefix) in &destinations {
+                // Construct S3 key: prefix + base_folder + partition path
+                let s3_key = format!("{}{}/{}/{}",
+                    prefix, base_folder, granularity.dir(current_date), partition::DATA_FILE);
+
+                info!("Checking S3 key: {} ({})", s3_key, name);
+
+                // Check if file exists in S3
+                match client
+                    .head_object()
+                    .bucket(bucket)
+                    .key(&s3_key)
+                    .send()
+                    .await
+                {
+                    Ok(_) => {
+                        info!("Found: {}", s3_key);
+                        if !check_manifests {
+                            continue;
+                        }
+                        let label = if destinations.len() > 1 { format!("{} ({})", current_date, name) } else { current_date.to_string() };
+                        let manifest_key = format!("{}{}/{}/{}",
+                            prefix, base_folder, granularity.dir(current_date), partition_manifest::MANIFEST_FILE);
+                        let manifest: PartitionManifest = match client.get_object().bucket(bucket).key(&manifest_key).send().await {
+                            Ok(output) => {
+                                let bytes = output.body.collect().await?.into_bytes();
+                                serde_json::from_slice(&bytes).with_context(|| format!("Invalid manifest {}", manifest_key))?
+                            }
+                            Err(e) => {
+                                info!("No manifest: {} - Error: {:?}", manifest_key, e);
+                                without_manifest.push(label);
+                                continue;
+                            }
+                        };
+                        rows += manifest.rows;
+                        intervals += manifest.intervals;
+                        // The local copy is compared by checksum, without downloading the uploaded file
+                        let local = granularity.path(&format!("{}/{}", config.base_path(), base_folder), current_date);
+                        if Path::new(&local).exists() && partition_manifest::checksum(Path::new(&local))? != manifest.sha256 {
+                            pb.println(format!("  ⚠ Outdated: {} in {}", current_date, name));
+                            outdated.push(label);
+                        }
+                    }
+                    Err(e) => {
+                        info!("Not found: {} - Error: {:?}", s3_key, e);
+                        if destinations.len() > 1 {
+                            missing_dates.push(format!("{} ({})", current_date, name));
+                        } else {
+                            missing_dates.push(current_date.to_string());
+                        }
+                        pb.println(format!("  ⚠ Missing: {} in {}", current_date, name));
+                    }
                 }
             }
             
             pb.inc(1);
-            current_date = current_date + Duration::days(1);
         }
         
         pb.finish_and_clear();
         
         // Print summary for this scraper
         if missing_dates.is_empty() {
-            println!("✓ All {} days present in S3", total_days);
+            println!("✓ All {} partitions present in S3", partitions.len());
         } else {
-            println!("⚠ Missing {} of {} days:", missing_dates.len(), total_days);
+            if destinations.len() > 1 {
+                println!("⚠ Missing {} partitions across {} destinations:", missing_dates.len(), destinations.len());
+            } else {
+                println!("⚠ Missing {} of {} partitions:", missing_dates.len(), partitions.len());
+            }
             for date in &missing_dates {
                 println!("  - {}", date);
             }
         }
+        if check_manifests {
+            println!("  {} rows, {} intervals in the uploaded manifests", rows, intervals);
+            if !without_manifest.is_empty() {
+                println!("⚠ {} partitions have no manifest, run migrate to write them:", without_manifest.len());
+                for date in &without_manifest {
+                    println!("  - {}", date);
+                }
+            }
+            if !outdated.is_empty() {
+                println!("⚠ {} uploaded partitions differ from the local file:", outdated.len());
+                for date in &outdated {
+                    println!("  - {}", date);
+                }
+            }
+        }
     }
     
     Ok(())
@@ -0,0 +1,83 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// Days and periods a feed legitimately publishes nothing, e.g. market holidays and announced
+/// maintenance of the source. Missing data in them isn't a gap.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct CalendarConfig {
+    /// Local days of the partition timezone without data
+    #[serde(default)]
+    pub holidays: Vec<NaiveDate>,
+    /// Weekdays without data, e.g. `["Sat", "Sun"]` for a feed of exchange trading days
+    #[serde(default)]
+    pub closed_weekdays: Vec<Weekday>,
+    #[serde(default)]
+    pub maintenance: Vec<MaintenanceWindow>,
+}
+
+/// Announced maintenance of the source, in UTC
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MaintenanceWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+impl CalendarConfig {
+    /// Add the days and windows of another calendar, e.g. a shared holiday calendar
+    pub fn merge(&mut self, other: &CalendarConfig) {
+        self.holidays.extend(&other.holidays);
+        self.closed_weekdays.extend(&other.closed_weekdays);
+        self.maintenance.extend(other.maintenance.iter().cloned());
+    }
+
+    /// Whether no data is expected on a whole local day
+    pub fn is_closed(&self, date: NaiveDate) -> bool {
+        self.holidays.contains(&date) || self.closed_weekdays.contains(&date.weekday())
+    }
+
+    /// The maintenance window `at` falls into
+    pub fn maintenance_at(&self, at: DateTime<Utc>) -> Option<&MaintenanceWindow> {
+        self.maintenance.iter().find(|w| w.start <= at && at < w.end)
+    }
+
+    /// Whether missing data of an interval is expected: its local day is closed, or it overlaps
+    /// a maintenance window
+    pub fn is_expected_gap(&self, start: DateTime<Utc>, end: DateTime<Utc>, tz: Tz) -> bool {
+        self.is_closed(start.with_timezone(&tz).date_naive())
+            || self.maintenance.iter().any(|w| w.start < end && start < w.end)
+    }
+
+    /// Minutes of `start..end` covered by maintenance windows, overlapping windows counted once
+    pub fn maintenance_minutes(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> i64 {
+        let mut windows: Vec<(DateTime<Utc>, DateTime<Utc>)> = self.maintenance.iter()
+            .map(|w| (w.start.max(start), w.end.min(end)))
+            .filter(|(s, e)| s < e)
+            .collect();
+        windows.sort();
+
+        let mut minutes = 0;
+        let mut covered_until = start;
+        for (s, e) in windows {
+            let s = s.max(covered_until);
+            if s < e {
+                minutes += (e - s).num_minutes();
+                covered_until = e;
+            }
+        }
+        minutes
+    }
+
+    /// Whether every interval of `start..end` is an expected gap, checked in steps of `step`
+    pub fn covers(&self, start: DateTime<Utc>, end: DateTime<Utc>, tz: Tz, step: chrono::Duration) -> bool {
+        let mut at = start;
+        while at < end {
+            if !self.is_expected_gap(at, (at + step).min(end), tz) {
+                return false;
+            }
+            at += step;
+        }
+        true
+    }
+}
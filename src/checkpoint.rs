@@ -0,0 +1,50 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// Days completed by a backfill, so an interrupted run can be resumed
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Checkpoint {
+    #[serde(skip)]
+    path: String,
+    scrapers: HashMap<String, BTreeSet<NaiveDate>>,
+}
+
+impl Checkpoint {
+    /// Load the checkpoint file, or start an empty checkpoint if it doesn't exist
+    pub fn load(path: &str) -> Result<Self> {
+        let mut checkpoint = if Path::new(path).exists() {
+            let content = std::fs::read_to_string(path)?;
+            serde_json::from_str::<Checkpoint>(&content)?
+        } else {
+            Checkpoint::default()
+        };
+        checkpoint.path = path.to_string();
+        Ok(checkpoint)
+    }
+
+    pub fn is_completed(&self, scraper: &str, date: NaiveDate) -> bool {
+        self.scrapers.get(scraper).map(|days| days.contains(&date)).unwrap_or(false)
+    }
+
+    /// Forget all completed days of a scraper
+    pub fn reset(&mut self, scraper: &str) -> Result<()> {
+        self.scrapers.remove(scraper);
+        self.save()
+    }
+
+    /// Mark a day as completed and persist the checkpoint
+    pub fn complete(&mut self, scraper: &str, date: NaiveDate) -> Result<()> {
+        self.scrapers.entry(scraper.to_string()).or_default().insert(date);
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let tmp_path = format!("{}.tmp", self.path);
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+use crate::metrics;
+
+/// Stop calling an API after repeated failures, then probe it with single requests until it recovers
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the circuit
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long an open circuit blocks requests before the next probe
+    #[serde(default = "default_cool_down_ms")]
+    pub cool_down_ms: u64,
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_cool_down_ms() -> u64 {
+    5 * 60 * 1000
+}
+
+/// Rough cause of a scrape error, from its message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorClass {
+    /// Connection refused, DNS, timeouts
+    Network,
+    /// HTTP 429
+    RateLimited,
+    /// HTTP 5xx
+    Server,
+    /// Other HTTP 4xx, usually a configuration problem
+    Client,
+    /// The response couldn't be parsed
+    Parse,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        // Status codes must be whole words so timestamps and ids don't match
+        let words: Vec<&str> = message.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+        let has_code = |codes: &[&str]| codes.iter().any(|c| words.contains(c));
+        let has_text = |texts: &[&str]| texts.iter().any(|t| message.contains(t));
+
+        if has_code(&["429"]) || has_text(&["too many requests"]) {
+            ErrorClass::RateLimited
+        } else if has_code(&["500", "502", "503", "504"]) || has_text(&["internal server error", "bad gateway", "service unavailable", "gateway timeout"]) {
+            ErrorClass::Server
+        } else if has_text(&["timed out", "timeout", "connection", "dns", "error sending request"]) {
+            ErrorClass::Network
+        } else if has_code(&["400", "401", "403", "404"]) || has_text(&["unauthorized", "forbidden", "not found"]) {
+            ErrorClass::Client
+        } else if has_text(&["parse", "invalid", "expected", "deserializ", "decode"]) {
+            ErrorClass::Parse
+        } else {
+            ErrorClass::Other
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorClass::Network => "network",
+            ErrorClass::RateLimited => "rate_limited",
+            ErrorClass::Server => "server",
+            ErrorClass::Client => "client",
+            ErrorClass::Parse => "parse",
+            ErrorClass::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// One probe request was let through, its result closes or reopens the circuit
+    HalfOpen,
+}
+
+pub struct CircuitBreaker {
+    scraper: String,
+    config: CircuitBreakerConfig,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(scraper: &str, config: CircuitBreakerConfig) -> Self {
+        Self {
+            scraper: scraper.to_string(),
+            config,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a request may be sent now
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { until } if Instant::now() >= until => {
+                info!("Circuit of {} half-open, probing the API", self.scraper);
+                *state = State::HalfOpen;
+                true
+            }
+            State::Open { .. } | State::HalfOpen => false,
+        }
+    }
+
+    /// `closed`, `open` or `half_open`
+    pub fn state(&self) -> &'static str {
+        match *self.state.lock().unwrap() {
+            State::Closed { .. } => "closed",
+            State::Open { .. } => "open",
+            State::HalfOpen => "half_open",
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, State::HalfOpen | State::Open { .. }) {
+            info!("Circuit of {} closed, the API recovered", self.scraper);
+        }
+        *state = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self, class: ErrorClass) {
+        metrics::global().update(&self.scraper, |m| {
+            *m.scrape_errors.entry(class.to_string()).or_default() += 1;
+        });
+
+        let cool_down = Duration::from_millis(self.config.cool_down_ms);
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { failures } => {
+                let failures = failures + 1;
+                if failures >= self.config.failure_threshold {
+                    // The single alert for this outage, probes failing afterwards don't repeat it
+                    error!(
+                        "ALERT: circuit of {} opened after {} consecutive failures (last: {}), pausing requests for {:?}",
+                        self.scraper, failures, class, cool_down
+                    );
+                    metrics::global().update(&self.scraper, |m| m.circuit_opened += 1);
+                    *state = State::Open { until: Instant::now() + cool_down };
+                } else {
+                    *state = State::Closed { failures };
+                }
+            }
+            State::HalfOpen | State::Open { .. } => {
+                *state = State::Open { until: Instant::now() + cool_down };
+            }
+        }
+    }
+}
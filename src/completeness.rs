@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::collections::BTreeSet;
+
+use crate::calendar::CalendarConfig;
+use crate::query::Query;
+
+/// Start and end of a local day in UTC. DST transition days are 23 or 25 hours long.
+pub fn day_bounds(date: NaiveDate, tz: Tz) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let next = date.succ_opt().context("Date out of range")?;
+    Ok((local_midnight(date, tz)?, local_midnight(next, tz)?))
+}
+
+/// Local midnight, or the first valid time after it in zones that switch at midnight
+fn local_midnight(date: NaiveDate, tz: Tz) -> Result<DateTime<Utc>> {
+    let midnight = date.and_hms_opt(0, 0, 0).context("Invalid time")?;
+    for offset in 0..=2 {
+        if let Some(t) = tz.from_local_datetime(&(midnight + Duration::hours(offset))).earliest() {
+            return Ok(t.with_timezone(&Utc));
+        }
+    }
+    anyhow::bail!("No valid local midnight on {} in {}", date, tz)
+}
+
+/// Length of a local day in minutes: 1380, 1440 or 1500 in zones with DST
+pub fn day_length_minutes(date: NaiveDate, tz: Tz) -> Result<i64> {
+    let (start, end) = day_bounds(date, tz)?;
+    Ok((end - start).num_minutes())
+}
+
+/// Number of intervals in a local day, e.g. 92, 96 or 100 quarter-hours
+pub fn expected_intervals(date: NaiveDate, tz: Tz, interval_minutes: i64) -> Result<usize> {
+    anyhow::ensure!(interval_minutes > 0, "interval_minutes must be positive");
+    Ok((day_length_minutes(date, tz)? / interval_minutes) as usize)
+}
+
+/// Scale a row count given for a regular 24 hour day to the length of this day
+pub fn scale_to_day(rows_per_regular_day: usize, date: NaiveDate, tz: Tz) -> Result<usize> {
+    Ok((rows_per_regular_day as i64 * day_length_minutes(date, tz)? / (24 * 60)) as usize)
+}
+
+/// Result of checking one local day against the expected intervals
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayCompleteness {
+    pub date: NaiveDate,
+    pub expected: usize,
+    /// Expected intervals that are stored
+    pub present: usize,
+    /// Ranges of missing intervals, in UTC
+    pub gaps: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    /// Intervals not expected because the day is closed or they fall into maintenance
+    pub excused: usize,
+    /// Whether the calendar expects no data on the whole day
+    pub closed: bool,
+    /// Whether the day has a DST transition
+    pub dst_transition: bool,
+}
+
+impl DayCompleteness {
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty()
+    }
+}
+
+/// Compare stored intervals of a day with the expected grid. The grid is built in UTC from the
+/// local day bounds, so the skipped hour in March isn't expected and the repeated hour in October
+/// is expected twice. Missing intervals the calendar expects are excused instead of being gaps.
+pub fn check_day(date: NaiveDate, tz: Tz, interval_minutes: i64, intervals: &[(DateTime<Utc>, DateTime<Utc>)], calendar: &CalendarConfig) -> Result<DayCompleteness> {
+    let (day_start, day_end) = day_bounds(date, tz)?;
+    let step = Duration::minutes(interval_minutes);
+    let stored: BTreeSet<DateTime<Utc>> = intervals.iter()
+        .filter(|(start, end)| *end - *start == step)
+        .map(|(start, _)| *start)
+        .collect();
+
+    let mut gaps: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+    let mut expected = 0;
+    let mut present = 0;
+    let mut excused = 0;
+    let mut slot = day_start;
+    while slot + step <= day_end {
+        expected += 1;
+        if stored.contains(&slot) {
+            present += 1;
+        } else if calendar.is_expected_gap(slot, slot + step, tz) {
+            excused += 1;
+        } else {
+            match gaps.last_mut() {
+                Some((_, gap_end)) if *gap_end == slot => *gap_end = slot + step,
+                _ => gaps.push((slot, slot + step)),
+            }
+        }
+        slot += step;
+    }
+
+    Ok(DayCompleteness {
+        date,
+        expected,
+        present,
+        gaps,
+        excused,
+        closed: calendar.is_closed(date),
+        dst_transition: (day_end - day_start) != Duration::hours(24),
+    })
+}
+
+/// Check a stored partition, see `check_day`
+pub fn check_partition(query: &Query, folder: &str, date: NaiveDate, tz: Tz, interval_minutes: i64, calendar: &CalendarConfig) -> Result<DayCompleteness> {
+    let intervals = query.intervals(folder, date)?;
+    check_day(date, tz, interval_minutes, &intervals, calendar)
+}
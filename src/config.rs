@@ -1,51 +1,451 @@
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use ve_energy_scrapers::models::strategy_information_scraper_config::StrategyInformationScraperConfig;
 
+use crate::admin::AdminConfig;
+use crate::aggregate::AggregationConfig;
+use crate::anomaly::{AnomalyConfig, ANOMALY_COLUMN};
+use crate::backpressure::BackpressureConfig;
+use crate::calendar::CalendarConfig;
+use crate::circuit_breaker::CircuitBreakerConfig;
+use crate::conflict::ConflictPolicy;
+use crate::delta::{DeltaConfig, DeltaLog};
+use crate::derived::DerivedConfig;
+use crate::disk::DiskGuardConfig;
+use crate::forecast::ForecastConfig;
+use crate::http_client::HttpClientConfig;
+use crate::import::ImportMapping;
+use crate::lock::LockConfig;
+use crate::metadata::ScraperMetadata;
+use crate::notify::NotifyConfig;
+use crate::parquet_config::ParquetConfig;
+use crate::partition::{parse_timezone, Granularity};
+use crate::postgres::PostgresConfig;
+use crate::query::Query;
+use crate::rate_limit::RateLimitConfig;
+use crate::secrets::{self, SecretsConfig};
+use crate::uploader::{ReplicaConfig, UploadConfig};
+use crate::storage::BufferConfig;
+use crate::stream::StreamConfig;
+use crate::telemetry::TelemetryConfig;
+use crate::transform::{self, TransformConfig, Transforms};
+use crate::validation::ValidationConfig;
+use crate::values::{ValueSchema, ValueType};
+
+/// What happens to local partitions older than the retention period
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionMode {
+    /// Delete local data outright
+    #[default]
+    Delete,
+    /// Only delete local data once it is verified to exist in S3
+    Archive,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ScraperConfig {
     #[serde(flatten)]
     pub scraper_config: StrategyInformationScraperConfig,
     pub sub_data_folder: Option<String>,
+    pub validation: Option<ValidationConfig>,
+    /// Maximum time range per API request during backfill, larger windows mean fewer requests
+    pub backfill_window_hours: Option<i64>,
+    /// How far back from now the service scrapes, and the padding before each backfilled day (default 24)
+    pub lookback_hours: Option<i64>,
+    /// How far ahead of now the service scrapes, and the padding after each backfilled day (default 24)
+    pub lookahead_hours: Option<i64>,
+    /// Re-scrape this many days before the lookback window to pick up late corrections
+    pub revision_window_days: Option<i64>,
+    /// How often the revision window is re-scraped (default 24)
+    pub revision_interval_hours: Option<u64>,
+    /// On startup, scrape the gap since the last stored interval, at most this many days back (default 7, 0 disables)
+    pub catch_up_days: Option<i64>,
+    pub requests_per_minute: Option<u32>,
+    /// Overrides the global retention_days for this scraper
+    pub retention_days: Option<u64>,
+    #[serde(default)]
+    pub retention_mode: RetentionMode,
+    /// Storage type per value column, columns not listed are stored as f64
+    #[serde(default)]
+    pub value_types: HashMap<String, ValueType>,
+    /// Labels of utf8 columns, indexed by the scraped value
+    #[serde(default)]
+    pub categories: HashMap<String, Vec<String>>,
+    /// Also upsert this scraper's values into the configured Postgres table
+    #[serde(default)]
+    pub postgres: bool,
+    /// Overrides the global circuit_breaker for this scraper
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Timezone whose local day defines the daily partitions, e.g. Europe/Oslo (default Europe/Vienna).
+    /// Changing it requires re-partitioning existing data with the repartition tool.
+    pub partition_timezone: Option<String>,
+    /// Span of one partition file: `day` (default), `month` or `year`. Changing it requires
+    /// re-partitioning existing data with the repartition tool.
+    #[serde(default)]
+    pub partition_granularity: Granularity,
+    /// Keep every response in `data/raw/<folder>` for the reprocess tool
+    #[serde(default)]
+    pub raw_archive: bool,
+    /// Proxy, timeouts, headers and TLS options of the scraper's HTTP client
+    pub http: Option<HttpClientConfig>,
+    /// Hourly or daily aggregates kept up to date in `data/agg/<window>/<folder>`
+    #[serde(default)]
+    pub aggregations: Vec<AggregationConfig>,
+    /// Scale, unit conversion and sign per column, applied before storage
+    #[serde(default)]
+    pub transforms: HashMap<String, TransformConfig>,
+    /// How intervals returned twice in one response are merged
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+    /// Never slowed down by backpressure
+    #[serde(default)]
+    pub critical: bool,
+    /// Holidays, closed weekdays and maintenance windows without data, not reported as gaps
+    #[serde(default)]
+    pub calendar: CalendarConfig,
+    /// Shared calendars of the top-level `calendars` merged into `calendar`
+    #[serde(default)]
+    pub calendars: Vec<String>,
+    /// Column mapping of vendor dumps ingested with the import tool
+    pub import: Option<ImportMapping>,
+    /// Flag or reject values far outside the rolling statistics of their time of day
+    pub anomaly: Option<AnomalyConfig>,
+    /// Keep every published vintage of a forecast feed with its publication time
+    pub forecast: Option<ForecastConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl ScraperConfig {
+    /// Settings that can't be checked by deserializing, checked before any scraper starts
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.revision_interval_hours == Some(0) {
+            anyhow::bail!("revision_interval_hours of {} must be at least 1", self.scraper_config.name);
+        }
+        self.partition_timezone()?;
+        Ok(())
+    }
+
+    /// Folder below the data directory where this scraper's partitions are stored
+    pub fn data_folder(&self) -> &str {
+        self.sub_data_folder.as_deref().unwrap_or(&self.scraper_config.name)
+    }
+
+    pub fn partition_timezone(&self) -> anyhow::Result<Tz> {
+        match &self.partition_timezone {
+            Some(tz) => parse_timezone(tz),
+            None => Ok(chrono_tz::Europe::Vienna),
+        }
+    }
+
+    pub fn lookback(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.lookback_hours.unwrap_or(24))
+    }
+
+    pub fn lookahead(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.lookahead_hours.unwrap_or(24))
+    }
+
+    /// How often the revision window is re-scraped, None without a revision window
+    pub fn revision_interval(&self) -> Option<std::time::Duration> {
+        self.revision_window_days?;
+        // Zero is rejected by `validate`
+        let hours = self.revision_interval_hours.unwrap_or(24).max(1);
+        Some(std::time::Duration::from_secs(hours * 60 * 60))
+    }
+
+    /// Request windows of one re-scrape of the revision window at `now`: the `revision_window_days`
+    /// days before the lookback window, which the regular scrapes already cover, split by
+    /// `backfill_window_hours`
+    pub fn revision_windows(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> {
+        let Some(days) = self.revision_window_days else {
+            return Vec::new();
+        };
+        let end = now - self.lookback();
+        let start = end - chrono::Duration::days(days);
+        let step = self.backfill_window_hours.filter(|h| *h > 0).map(chrono::Duration::hours).unwrap_or(end - start);
+
+        let mut windows = Vec::new();
+        let mut window_start = start;
+        while window_start < end {
+            let window_end = (window_start + step).min(end);
+            windows.push((window_start, window_end));
+            window_start = window_end;
+        }
+        windows
+    }
+
+    pub fn transforms(&self) -> anyhow::Result<Transforms> {
+        Transforms::new(&self.transforms)
+    }
+
+    /// Stored unit per column
+    pub fn units(&self) -> std::collections::BTreeMap<String, String> {
+        transform::units(&self.transforms)
+    }
+
+    /// Resolved parameters of this scraper, `retention_days` being the global default
+    pub fn metadata(&self, base_path: &str, retention_days: Option<u64>) -> anyhow::Result<ScraperMetadata> {
+        ScraperMetadata::new(self, base_path, retention_days)
+    }
+
+    pub fn value_schema(&self) -> ValueSchema {
+        let mut types = self.value_types.clone();
+        if self.anomaly.is_some() {
+            types.entry(ANOMALY_COLUMN.to_string()).or_insert(ValueType::Bool);
+        }
+        ValueSchema {
+            types,
+            categories: self.categories.clone(),
+        }
+    }
+}
+
+/// Data directory of the default tenant
+pub const DEFAULT_BASE_PATH: &str = "data";
+
+/// A business unit with its own data directory, bucket and scrapers, run by the same service
+/// as the top-level scrapers. Sections not listed here are shared with the top level.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TenantConfig {
+    pub name: String,
+    /// Local data directory, default `data-<name>`
+    pub base_path: Option<String>,
+    /// S3 settings are not inherited from the top level, a tenant without bucket stays local
+    pub s3_bucket: Option<String>,
+    pub s3_region: Option<String>,
+    pub s3_endpoint: Option<String>,
+    pub s3_prefix: Option<String>,
+    #[serde(default)]
+    pub replicas: Vec<ReplicaConfig>,
+    /// Falls back to the top-level retention_days
+    pub retention_days: Option<u64>,
+    pub scrapers: Vec<ScraperConfig>,
+    #[serde(default)]
+    pub derived: Vec<DerivedConfig>,
+    /// Admin API of the tenant, the top-level one only serves the default tenant
+    pub admin: Option<AdminConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct AppConfig {
     pub s3_bucket: Option<String>,
     pub s3_region: Option<String>,
     pub s3_endpoint: Option<String>,
     pub s3_prefix: Option<String>,
+    /// Encryption, storage class and tags of uploaded objects
+    #[serde(default)]
+    pub upload: UploadConfig,
+    /// Further buckets every file is uploaded to, e.g. for disaster recovery
+    #[serde(default)]
+    pub replicas: Vec<ReplicaConfig>,
     pub scrapers: Vec<ScraperConfig>,
     pub retention_days: Option<u64>,
+    pub rate_limits: Option<RateLimitConfig>,
+    /// Batch writes in memory instead of rewriting partitions on every scrape
+    pub buffer: Option<BufferConfig>,
+    #[serde(default)]
+    pub parquet: ParquetConfig,
+    /// Per-scraper leases for running several instances
+    pub locking: Option<LockConfig>,
+    /// Webhook and Kafka events for uploaded partitions
+    pub notify: Option<NotifyConfig>,
+    /// Publish new values to Kafka or NATS as they are written
+    pub stream: Option<StreamConfig>,
+    /// Postgres/TimescaleDB table for scrapers with `postgres: true`
+    pub postgres: Option<PostgresConfig>,
+    /// Pause scrapers whose API keeps failing
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// AWS Secrets Manager or Vault for `secret://path#field` values
+    pub secrets: Option<SecretsConfig>,
+    /// HTTP API to trigger scrapes, flushes and backfills
+    pub admin: Option<AdminConfig>,
+    /// Export traces of every run over OTLP
+    pub telemetry: Option<TelemetryConfig>,
+    /// Slow down non-critical scrapers while the upload queue or the disk fill up
+    pub backpressure: Option<BackpressureConfig>,
+    /// Alert on low disk space and delete the oldest uploaded partitions before the disk is full
+    pub disk_guard: Option<DiskGuardConfig>,
+    /// Series computed from the stored data of other scrapers
+    #[serde(default)]
+    pub derived: Vec<DerivedConfig>,
+    /// Delta Lake transaction logs over the data folders, for Trino and Spark
+    pub delta: Option<DeltaConfig>,
+    /// Named holiday and maintenance calendars that scrapers refer to in `calendars`
+    #[serde(default)]
+    pub calendars: HashMap<String, CalendarConfig>,
+    /// Further business units with their own data directory and bucket
+    #[serde(default)]
+    pub tenants: Vec<TenantConfig>,
+    /// Name of the tenant this config was expanded for, None for the default tenant
+    #[serde(skip)]
+    pub tenant: Option<String>,
+    #[serde(skip)]
+    pub(crate) base_path: Option<String>,
 }
 
 impl AppConfig {
     /// Get S3 bucket from env var S3_BUCKET, falling back to config file
     pub fn get_s3_bucket(&self) -> Option<String> {
-        env::var("S3_BUCKET").ok().or_else(|| self.s3_bucket.clone())
+        self.env("S3_BUCKET").or_else(|| self.s3_bucket.clone())
     }
     
     /// Get S3 region from env var S3_REGION, falling back to config file
     pub fn get_s3_region(&self) -> Option<String> {
-        env::var("S3_REGION").ok().or_else(|| self.s3_region.clone())
+        self.env("S3_REGION").or_else(|| self.s3_region.clone())
     }
     
     /// Get S3 endpoint from env var S3_ENDPOINT, falling back to config file
     pub fn get_s3_endpoint(&self) -> Option<String> {
-        env::var("S3_ENDPOINT").ok().or_else(|| self.s3_endpoint.clone())
+        self.env("S3_ENDPOINT").or_else(|| self.s3_endpoint.clone())
     }
     
     /// Get S3 prefix from env var S3_PREFIX, falling back to config file, default "data/"
     pub fn get_s3_prefix(&self) -> String {
-        env::var("S3_PREFIX")
-            .ok()
+        self.env("S3_PREFIX")
             .or_else(|| self.s3_prefix.clone())
             .unwrap_or_else(|| "data/".to_string())
     }
+
+    /// Env overrides only apply to the default tenant, so they can't point a tenant at another's bucket
+    fn env(&self, name: &str) -> Option<String> {
+        match self.tenant {
+            Some(_) => None,
+            None => env::var(name).ok(),
+        }
+    }
+
+    /// Local data directory of this tenant
+    pub fn base_path(&self) -> &str {
+        self.base_path.as_deref().unwrap_or(DEFAULT_BASE_PATH)
+    }
+
+    /// Run history directory of this tenant
+    pub fn history_dir(&self) -> String {
+        match &self.tenant {
+            Some(name) => format!("{}-{}", crate::history::HISTORY_DIR, name),
+            None => crate::history::HISTORY_DIR.to_string(),
+        }
+    }
+
+    /// Config of the tenant `name` for the maintenance tools, or of the default tenant if None
+    /// or "default"
+    pub fn tenant(&self, name: Option<&str>) -> anyhow::Result<AppConfig> {
+        let tenants = self.tenants()?;
+        match name.filter(|name| *name != "default") {
+            Some(name) => tenants.into_iter()
+                .find(|t| t.tenant.as_deref() == Some(name))
+                .ok_or_else(|| anyhow::anyhow!("No tenant named '{}' in config.json", name)),
+            None => Ok(tenants.into_iter()
+                .find(|t| t.tenant.is_none())
+                .unwrap_or_else(|| AppConfig { tenants: Vec::new(), ..self.clone() })),
+        }
+    }
+
+    /// One config per tenant: the top-level scrapers as the default tenant, if any, and every
+    /// entry of `tenants` with the shared sections of the top level. Fails if two tenants
+    /// share a data directory, a bucket or a scraper name.
+    pub fn tenants(&self) -> anyhow::Result<Vec<AppConfig>> {
+        let mut configs = Vec::new();
+        if !self.scrapers.is_empty() || !self.derived.is_empty() || self.tenants.is_empty() {
+            configs.push(AppConfig { tenants: Vec::new(), ..self.clone() });
+        }
+        for tenant in &self.tenants {
+            configs.push(AppConfig {
+                s3_bucket: tenant.s3_bucket.clone(),
+                s3_region: tenant.s3_region.clone(),
+                s3_endpoint: tenant.s3_endpoint.clone(),
+                s3_prefix: tenant.s3_prefix.clone(),
+                replicas: tenant.replicas.clone(),
+                retention_days: tenant.retention_days.or(self.retention_days),
+                scrapers: tenant.scrapers.clone(),
+                derived: tenant.derived.clone(),
+                admin: tenant.admin.clone(),
+                tenants: Vec::new(),
+                tenant: Some(tenant.name.clone()),
+                base_path: Some(tenant.base_path.clone().unwrap_or_else(|| format!("{}-{}", DEFAULT_BASE_PATH, tenant.name))),
+                ..self.clone()
+            });
+        }
+
+        let mut names = HashSet::new();
+        let mut base_paths = HashSet::new();
+        let mut buckets = HashSet::new();
+        let mut scrapers = HashSet::new();
+        for config in &configs {
+            let name = config.tenant.as_deref().unwrap_or("default");
+            if !names.insert(name) {
+                anyhow::bail!("Tenant '{}' is configured twice", name);
+            }
+            if !base_paths.insert(config.base_path().trim_end_matches('/').to_string()) {
+                anyhow::bail!("Tenant '{}' shares its base_path {} with another tenant", name, config.base_path());
+            }
+            let destinations: HashSet<String> = config.get_s3_bucket().into_iter()
+                .chain(config.replicas.iter().map(|replica| replica.bucket.clone()))
+                .collect();
+            for bucket in destinations {
+                if !buckets.insert(bucket.clone()) {
+                    anyhow::bail!("Tenant '{}' shares the bucket {} with another tenant", name, bucket);
+                }
+            }
+            for scraper in &config.scrapers {
+                scraper.validate()?;
+                if !scrapers.insert(scraper.scraper_config.name.clone()) {
+                    anyhow::bail!("Scraper '{}' of tenant '{}' is configured in another tenant as well", scraper.scraper_config.name, name);
+                }
+            }
+        }
+        Ok(configs)
+    }
+
+    /// Query over the data directory that knows the partitioning of every scraper and derived series
+    pub fn query(&self, base_path: &str) -> anyhow::Result<Query> {
+        let mut query = Query::new(base_path);
+        for scraper in &self.scrapers {
+            query = query.with_partitioning(scraper.data_folder(), scraper.partition_granularity, scraper.partition_timezone()?);
+        }
+        for derived in &self.derived {
+            query = query.with_partitioning(derived.data_folder(), derived.partition_granularity, derived.partition_timezone()?);
+        }
+        Ok(query)
+    }
+
+    /// Calendar of a scraper with the shared calendars it refers to merged in
+    pub fn calendar(&self, scraper: &ScraperConfig) -> anyhow::Result<CalendarConfig> {
+        let mut calendar = scraper.calendar.clone();
+        for name in &scraper.calendars {
+            let shared = self.calendars.get(name)
+                .ok_or_else(|| anyhow::anyhow!("Calendar '{}' of {} is not defined in calendars", name, scraper.scraper_config.name))?;
+            calendar.merge(shared);
+        }
+        Ok(calendar)
+    }
+
+    /// Transaction log for the configured Delta tables, or None if `delta` is not configured
+    pub fn delta_log(&self, base_path: &str) -> Option<DeltaLog> {
+        let config = self.delta.clone()?;
+        let tables: BTreeMap<String, Granularity> = self.scrapers.iter()
+            .map(|scraper| (scraper.data_folder().to_string(), scraper.partition_granularity))
+            .chain(self.derived.iter().map(|derived| (derived.data_folder().to_string(), derived.partition_granularity)))
+            .filter(|(folder, _)| config.folders.is_empty() || config.folders.contains(folder))
+            .collect();
+        Some(DeltaLog::new(config, base_path, tables))
+    }
 }
 
 pub fn load_config(path: &str) -> anyhow::Result<AppConfig> {
     let content = std::fs::read_to_string(path)?;
     let config: AppConfig = serde_json::from_str(&content)?;
+    for scraper in config.scrapers.iter().chain(config.tenants.iter().flat_map(|t| &t.scrapers)) {
+        scraper.validate()?;
+    }
+    config.upload.validate()?;
+    for replica in config.replicas.iter().chain(config.tenants.iter().flat_map(|t| &t.replicas)) {
+        if let Some(upload) = &replica.upload {
+            upload.validate()?;
+        }
+    }
+    secrets::configure(config.secrets.clone());
     Ok(config)
 }
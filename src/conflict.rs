@@ -0,0 +1,107 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use crate::metrics;
+
+/// Folder below the data directory holding every variant of conflicting intervals
+pub const CONFLICTS_DIR: &str = "conflicts";
+
+/// What to do when a scrape returns the same interval twice with different values
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Fail the scrape
+    Error,
+    KeepFirst,
+    #[default]
+    KeepLast,
+    /// Keep the highest value per column
+    KeepMax,
+    /// Keep the last value and store every variant in `conflicts/<folder>`
+    StoreBothWithVersion,
+}
+
+pub struct Resolved {
+    /// One record per interval
+    pub data: Vec<ScraperData>,
+    /// One record per conflicting interval with every variant as `<column>_v<n>`, numbered from 1
+    /// in response order. Only filled with `StoreBothWithVersion`.
+    pub versions: Vec<ScraperData>,
+}
+
+/// Merge the records of intervals returned more than once. Columns only one of the records has
+/// are merged, a conflict is a column with different values. Balancing bids are passed through.
+pub fn resolve(scraper_name: &str, policy: ConflictPolicy, data: Vec<ScraperData>) -> Result<Resolved> {
+    let mut resolved: Vec<ScraperData> = Vec::with_capacity(data.len());
+    // Index into `resolved` and every variant of each value interval
+    let mut intervals: HashMap<(DateTime<Utc>, DateTime<Utc>), (usize, Vec<HashMap<String, f64>>)> = HashMap::new();
+    let mut conflicting = Vec::new();
+
+    for item in data {
+        let ScraperPayload::Values(new_values) = &item.payload else {
+            resolved.push(item);
+            continue;
+        };
+        let key = (item.delivery_from, item.delivery_to);
+        let Some((index, variants)) = intervals.get_mut(&key) else {
+            intervals.insert(key, (resolved.len(), vec![new_values.clone()]));
+            resolved.push(item);
+            continue;
+        };
+        let ScraperPayload::Values(values) = &mut resolved[*index].payload else {
+            unreachable!("Only value records are indexed");
+        };
+
+        let conflicts: Vec<&String> = new_values.iter()
+            .filter(|(column, value)| values.get(*column).is_some_and(|old| (old - *value).abs() > f64::EPSILON))
+            .map(|(column, _)| column)
+            .collect();
+        if !conflicts.is_empty() {
+            if policy == ConflictPolicy::Error {
+                bail!("Conflicting values for {} - {} in column {}", key.0, key.1, conflicts[0]);
+            }
+            if !conflicting.contains(&key) {
+                conflicting.push(key);
+            }
+            warn!("[{}] Conflicting values for {} - {} in {:?}, {:?}", scraper_name, key.0, key.1, conflicts, policy);
+        }
+
+        for (column, value) in new_values {
+            match values.get_mut(column) {
+                None => {
+                    values.insert(column.clone(), *value);
+                }
+                Some(old) => match policy {
+                    ConflictPolicy::KeepFirst => {}
+                    ConflictPolicy::KeepMax => *old = old.max(*value),
+                    _ => *old = *value,
+                },
+            }
+        }
+        variants.push(new_values.clone());
+    }
+
+    if !conflicting.is_empty() {
+        let count = conflicting.len() as u64;
+        metrics::global().update(scraper_name, |m| m.interval_conflicts += count);
+    }
+
+    let mut versions = Vec::new();
+    if policy == ConflictPolicy::StoreBothWithVersion {
+        for key in conflicting {
+            let mut values = HashMap::new();
+            for (version, variant) in intervals.remove(&key).map(|(_, v)| v).unwrap_or_default().into_iter().enumerate() {
+                for (column, value) in variant {
+                    values.insert(format!("{}_v{}", column, version + 1), value);
+                }
+            }
+            versions.push(ScraperData { delivery_from: key.0, delivery_to: key.1, payload: ScraperPayload::Values(values) });
+        }
+    }
+
+    Ok(Resolved { data: resolved, versions })
+}
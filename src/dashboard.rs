@@ -0,0 +1,157 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::calendar::CalendarConfig;
+use crate::completeness;
+use crate::history::RunRecord;
+use crate::partition::Granularity;
+use crate::query::Query;
+use crate::uploader::Uploader;
+
+/// The dashboard page, it loads its data from `/dashboard/data` with the admin token
+pub const PAGE: &str = include_str!("dashboard.html");
+
+/// How long the result of an S3 lookup is reused before the partition is checked again
+const REMOTE_CHECK_SECS: u64 = 600;
+
+/// Where the coverage of a scraper is read from
+#[derive(Debug, Clone)]
+pub struct CoverageSource {
+    pub folder: String,
+    pub tz: Tz,
+    pub granularity: Granularity,
+    /// Days are only rated complete or partial when the interval length is known
+    pub interval_minutes: Option<i64>,
+    /// Holidays and maintenance windows whose missing data isn't a gap
+    pub calendar: CalendarConfig,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DayStatus {
+    Complete,
+    Partial,
+    /// Stored locally, interval length unknown
+    Present,
+    /// Only in S3, e.g. after local retention
+    Archived,
+    /// Nothing stored on a holiday or closed weekday of the calendar
+    Closed,
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayCoverage {
+    pub date: NaiveDate,
+    pub status: DayStatus,
+    pub present: Option<usize>,
+    pub expected: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScraperOverview {
+    pub name: String,
+    pub last_run: Option<RunRecord>,
+    /// Runs in the history window and the share of them without error
+    pub runs: usize,
+    pub success_rate: Option<f64>,
+    pub coverage: Vec<DayCoverage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Overview {
+    pub generated_at: chrono::DateTime<Utc>,
+    /// Files waiting for upload, None without S3
+    pub upload_queue: Option<usize>,
+    pub scrapers: Vec<ScraperOverview>,
+}
+
+/// S3 lookups of partitions missing locally, remembered across dashboard refreshes so a refresh
+/// doesn't send one request per missing day
+pub struct RemoteCoverage {
+    uploader: Arc<Uploader>,
+    checked: Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl RemoteCoverage {
+    pub fn new(uploader: Arc<Uploader>) -> Self {
+        Self { uploader, checked: Mutex::new(HashMap::new()) }
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        if let Some((exists, checked_at)) = self.checked.lock().unwrap().get(path) {
+            if checked_at.elapsed().as_secs() < REMOTE_CHECK_SECS {
+                return Ok(*exists);
+            }
+        }
+        let exists = self.uploader.exists(path).await?;
+        self.checked.lock().unwrap().insert(path.to_string(), (exists, Instant::now()));
+        Ok(exists)
+    }
+}
+
+impl CoverageSource {
+    fn query(&self, base_path: &str) -> Query {
+        Query::new(base_path).with_partitioning(&self.folder, self.granularity, self.tz)
+    }
+}
+
+/// Share of runs without error, None without runs
+pub fn success_rate(runs: &[&RunRecord]) -> Option<f64> {
+    if runs.is_empty() {
+        return None;
+    }
+    Some(runs.iter().filter(|r| r.error.is_none()).count() as f64 / runs.len() as f64)
+}
+
+/// Coverage of the last `days` local days, newest last. Days missing locally are looked up in S3.
+pub async fn coverage(base_path: &str, source: &CoverageSource, days: i64, remote: Option<&RemoteCoverage>) -> Result<Vec<DayCoverage>> {
+    let today = Utc::now().with_timezone(&source.tz).date_naive();
+    let dates: Vec<NaiveDate> = (0..days).rev().map(|d| today - Duration::days(d)).collect();
+
+    let local = {
+        let base_path = base_path.to_string();
+        let source = source.clone();
+        let dates = dates.clone();
+        tokio::task::spawn_blocking(move || local_coverage(&base_path, &source, &dates)).await??
+    };
+
+    let mut result = Vec::with_capacity(local.len());
+    for day in local {
+        match (day.status, remote) {
+            (DayStatus::Missing, Some(remote)) => {
+                let path = source.query(base_path).partition_path(&source.folder, day.date);
+                let status = if remote.exists(&path.to_string_lossy()).await? { DayStatus::Archived } else { DayStatus::Missing };
+                result.push(DayCoverage { status, ..day });
+            }
+            _ => result.push(day),
+        }
+    }
+    Ok(result)
+}
+
+fn local_coverage(base_path: &str, source: &CoverageSource, dates: &[NaiveDate]) -> Result<Vec<DayCoverage>> {
+    let query = source.query(base_path);
+    dates.iter().map(|&date| {
+        // Month and year partitions exist before all of their days are stored
+        let missing = !query.partition_path(&source.folder, date).exists()
+            || (source.granularity != Granularity::Day && query.intervals(&source.folder, date)?.is_empty());
+        if missing {
+            let status = if source.calendar.is_closed(date) { DayStatus::Closed } else { DayStatus::Missing };
+            return Ok(DayCoverage { date, status, present: None, expected: None });
+        }
+        Ok(match source.interval_minutes {
+            Some(minutes) => {
+                let day = completeness::check_partition(&query, &source.folder, date, source.tz, minutes, &source.calendar)?;
+                let status = if day.is_complete() { DayStatus::Complete } else { DayStatus::Partial };
+                DayCoverage { date, status, present: Some(day.present), expected: Some(day.expected) }
+            }
+            None => DayCoverage { date, status: DayStatus::Present, present: query.interval_count(&source.folder, date)?, expected: None },
+        })
+    }).collect()
+}
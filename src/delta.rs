@@ -0,0 +1,475 @@
+use anyhow::{bail, Context, Result};
+use arrow::datatypes::{DataType, Schema};
+use chrono::Utc;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::partition::{Granularity, DATA_FILE};
+use crate::uploader::Uploader;
+
+/// Transaction log directory at the root of every table
+pub const LOG_DIR: &str = "_delta_log";
+
+/// Prefix of the immutable files a table version references, next to `data.parquet`
+pub const PART_PREFIX: &str = "part-";
+
+/// Extension of part files. They hold Parquet, but a different extension keeps tools that
+/// glob `*.parquet` from reading every row twice.
+pub const PART_EXTENSION: &str = ".pq";
+
+/// Attempts to commit a version before giving up, when other processes commit at the same time
+const MAX_COMMIT_ATTEMPTS: usize = 10;
+
+/// Maintain Delta Lake transaction logs over the stored partitions, so Trino and Spark can read
+/// the data folders as tables with snapshots and schema evolution
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DeltaConfig {
+    /// Data folders kept as tables, every scraper and derived series if empty
+    #[serde(default)]
+    pub folders: Vec<String>,
+    /// Partitions written since the last commit are committed as one version this often
+    #[serde(default = "default_commit_interval_secs")]
+    pub commit_interval_secs: u64,
+    /// Files of replaced versions are deleted locally after this many hours, which limits time travel
+    #[serde(default = "default_retention_hours")]
+    pub retention_hours: u64,
+}
+
+fn default_commit_interval_secs() -> u64 {
+    300
+}
+
+fn default_retention_hours() -> u64 {
+    168
+}
+
+/// Current version of a table, replayed from its log
+struct TableState {
+    version: i64,
+    id: String,
+    /// Data columns and their Delta type, in schema order
+    schema: Vec<(String, String)>,
+    /// Current file per partition directory, relative to the table root
+    files: HashMap<String, String>,
+    /// Replaced files with the time they were removed, in milliseconds
+    tombstones: Vec<(String, i64)>,
+}
+
+pub struct DeltaLog {
+    config: DeltaConfig,
+    base_path: String,
+    /// Table folders and the granularity of their partitions
+    tables: BTreeMap<String, Granularity>,
+    uploader: Option<Arc<Uploader>>,
+    /// Partition files written since the last commit
+    pending: Mutex<BTreeSet<String>>,
+    state: Mutex<HashMap<String, TableState>>,
+    /// Committed versions not uploaded yet, uploaded in order before the next commit
+    unpublished: Mutex<Vec<String>>,
+}
+
+impl DeltaLog {
+    pub fn new(config: DeltaConfig, base_path: &str, tables: BTreeMap<String, Granularity>) -> Self {
+        Self {
+            config,
+            base_path: base_path.to_string(),
+            tables,
+            uploader: None,
+            pending: Mutex::new(BTreeSet::new()),
+            state: Mutex::new(HashMap::new()),
+            unpublished: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Upload new files before the version referencing them, and versions in order
+    pub fn with_uploader(mut self, uploader: Arc<Uploader>) -> Self {
+        self.uploader = Some(uploader);
+        self
+    }
+
+    /// Table folder a partition file belongs to
+    fn table_of(&self, file_path: &str) -> Option<&str> {
+        let folder = file_path.strip_prefix(&format!("{}/", self.base_path))?;
+        self.tables.keys()
+            .filter(|table| folder.starts_with(&format!("{}/", table)))
+            .max_by_key(|table| table.len())
+            .map(|table| table.as_str())
+    }
+
+    /// Remember a written partition for the next commit
+    pub async fn record(&self, file_path: &str) {
+        if self.table_of(file_path).is_some() {
+            self.pending.lock().await.insert(file_path.to_string());
+        }
+    }
+
+    /// Commit every `commit_interval_secs` until the process exits
+    pub async fn run(&self) {
+        loop {
+            sleep(Duration::from_secs(self.config.commit_interval_secs.max(1))).await;
+            if let Err(e) = self.commit().await {
+                error!("Delta commit failed: {:?}", e);
+            }
+        }
+    }
+
+    /// Commit the partitions written since the last commit, one version per table.
+    /// Returns the number of committed partitions.
+    pub async fn commit(&self) -> Result<usize> {
+        self.publish().await?;
+
+        let pending = std::mem::take(&mut *self.pending.lock().await);
+        let mut by_table: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for file_path in pending {
+            if let Some(table) = self.table_of(&file_path) {
+                by_table.entry(table.to_string()).or_default().push(file_path);
+            }
+        }
+
+        let mut committed = 0;
+        let mut first_error = None;
+        for (table, files) in by_table {
+            match self.commit_table(&table, &files).await {
+                Ok(count) => committed += count,
+                Err(e) => {
+                    warn!("Failed to commit {}, retrying with the next commit: {:?}", table, e);
+                    self.pending.lock().await.extend(files);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        self.vacuum().await;
+        self.publish().await?;
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(committed),
+        }
+    }
+
+    async fn commit_table(&self, table: &str, files: &[String]) -> Result<usize> {
+        let root = Path::new(&self.base_path).join(table);
+        let granularity = self.tables.get(table).copied().unwrap_or_default();
+        let mut states = self.state.lock().await;
+
+        for attempt in 0..MAX_COMMIT_ATTEMPTS {
+            let mut state = match states.remove(table) {
+                Some(state) => state,
+                None => load(&root)?,
+            };
+            // A new table starts with every partition already stored
+            let files: Vec<String> = if state.version < 0 {
+                let mut existing = Vec::new();
+                find_partitions(&root, &mut existing)?;
+                existing.into_iter().map(|p| p.to_string_lossy().to_string()).collect()
+            } else {
+                files.to_vec()
+            };
+
+            let now = Utc::now().timestamp_millis();
+            let mut actions = Vec::new();
+            let mut new_files = Vec::new();
+            let mut schema_changed = state.version < 0;
+
+            for file_path in &files {
+                let path = Path::new(file_path);
+                // Deleted by retention in the meantime, the table keeps its last version
+                if !path.exists() {
+                    continue;
+                }
+                let dir = path.parent().context("Partition without a directory")?;
+                let relative_dir = dir.strip_prefix(&root)?.to_string_lossy().replace('\\', "/");
+                let part_name = format!("{}{}{}", PART_PREFIX, uuid::Uuid::new_v4(), PART_EXTENSION);
+                let part_path = dir.join(&part_name);
+                // Storage replaces data.parquet by renaming, so the link keeps this version unchanged
+                if std::fs::hard_link(path, &part_path).is_err() {
+                    std::fs::copy(path, &part_path)?;
+                }
+                new_files.push(part_path.to_string_lossy().to_string());
+
+                let (fields, rows) = read_schema(&part_path)?;
+                for (name, delta_type) in fields {
+                    match state.schema.iter_mut().find(|(existing, _)| *existing == name) {
+                        // A column stored with another type, e.g. promoted to utf8
+                        Some((_, existing_type)) if *existing_type != delta_type => {
+                            *existing_type = delta_type;
+                            schema_changed = true;
+                        }
+                        Some(_) => {}
+                        None => {
+                            state.schema.push((name, delta_type));
+                            schema_changed = true;
+                        }
+                    }
+                }
+
+                let relative_path = format!("{}/{}", relative_dir, part_name);
+                if let Some(previous) = state.files.insert(relative_dir.clone(), relative_path.clone()) {
+                    actions.push(json!({ "remove": { "path": previous, "deletionTimestamp": now, "dataChange": true } }));
+                    state.tombstones.push((previous, now));
+                }
+                actions.push(json!({ "add": {
+                    "path": relative_path,
+                    "partitionValues": partition_values(&relative_dir),
+                    "size": std::fs::metadata(&part_path)?.len(),
+                    "modificationTime": now,
+                    "dataChange": true,
+                    "stats": json!({ "numRecords": rows }).to_string(),
+                } }));
+            }
+
+            if actions.is_empty() && !schema_changed {
+                states.insert(table.to_string(), state);
+                return Ok(0);
+            }
+
+            let mut header = Vec::new();
+            if state.version < 0 {
+                header.push(json!({ "protocol": { "minReaderVersion": 1, "minWriterVersion": 2 } }));
+            }
+            if schema_changed {
+                header.push(json!({ "metaData": metadata(&state, granularity, now) }));
+            }
+            header.push(json!({ "commitInfo": { "timestamp": now, "operation": "WRITE", "engineInfo": "scraping_service" } }));
+
+            let version = state.version + 1;
+            let log_path = root.join(LOG_DIR).join(format!("{:020}.json", version));
+            std::fs::create_dir_all(root.join(LOG_DIR))?;
+            // Another process, e.g. a backfill, committed this version first: replay and try again
+            let mut file = match std::fs::OpenOptions::new().write(true).create_new(true).open(&log_path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    info!("Version {} of {} was committed concurrently, retrying (attempt {})", version, table, attempt + 1);
+                    for part in &new_files {
+                        let _ = std::fs::remove_file(part);
+                    }
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            for action in header.iter().chain(&actions) {
+                writeln!(file, "{}", action)?;
+            }
+            file.sync_all()?;
+            state.version = version;
+            let committed = actions.iter().filter(|a| a.get("add").is_some()).count();
+            states.insert(table.to_string(), state);
+            info!("Committed version {} of {} with {} partitions", version, table, committed);
+
+            // Files first, so a reader never sees a version whose files are missing
+            if let Some(uploader) = &self.uploader {
+                for part in &new_files {
+                    uploader.upload_file(part).await.with_context(|| format!("Failed to upload {}", part))?;
+                }
+            }
+            self.unpublished.lock().await.push(log_path.to_string_lossy().to_string());
+            return Ok(committed);
+        }
+        bail!("Gave up committing {} after {} concurrent commits", table, MAX_COMMIT_ATTEMPTS)
+    }
+
+    /// Part files still needed per table, relative to the data directory: those of the current
+    /// version and those replaced less than `retention_hours` ago, for time travel. Tables without
+    /// a log are left out.
+    pub fn referenced_files(&self) -> Result<HashMap<String, HashSet<String>>> {
+        let cutoff = Utc::now().timestamp_millis() - self.config.retention_hours as i64 * 3_600_000;
+        let mut referenced = HashMap::new();
+        for table in self.tables.keys() {
+            let state = load(&Path::new(&self.base_path).join(table))?;
+            if state.version < 0 {
+                continue;
+            }
+            let files = state.files.values()
+                .chain(state.tombstones.iter().filter(|(_, removed_at)| *removed_at > cutoff).map(|(path, _)| path))
+                .map(|path| format!("{}/{}", table, path))
+                .collect();
+            referenced.insert(table.clone(), files);
+        }
+        Ok(referenced)
+    }
+
+    /// Upload committed versions in order, stopping at the first failure
+    async fn publish(&self) -> Result<()> {
+        let Some(uploader) = &self.uploader else {
+            self.unpublished.lock().await.clear();
+            return Ok(());
+        };
+        let mut unpublished = self.unpublished.lock().await;
+        while let Some(log_path) = unpublished.first() {
+            uploader.upload_file(log_path).await.with_context(|| format!("Failed to upload {}", log_path))?;
+            unpublished.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Delete the files of versions replaced longer than `retention_hours` ago, locally and in S3.
+    /// Files that fail to delete are retried with the next commit.
+    async fn vacuum(&self) {
+        let cutoff = Utc::now().timestamp_millis() - self.config.retention_hours as i64 * 3_600_000;
+        let mut states = self.state.lock().await;
+        for (table, state) in states.iter_mut() {
+            let root = Path::new(&self.base_path).join(table);
+            let mut kept = Vec::new();
+            for (path, removed_at) in std::mem::take(&mut state.tombstones) {
+                if removed_at > cutoff {
+                    kept.push((path, removed_at));
+                    continue;
+                }
+                let local_path = root.join(&path);
+                if let Some(uploader) = &self.uploader {
+                    if let Err(e) = uploader.delete_file(&local_path.to_string_lossy()).await {
+                        warn!("Failed to vacuum {}/{} in S3: {:?}", table, path, e);
+                        kept.push((path, removed_at));
+                        continue;
+                    }
+                }
+                if let Err(e) = std::fs::remove_file(&local_path) {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        warn!("Failed to vacuum {}/{}: {:?}", table, path, e);
+                        kept.push((path, removed_at));
+                    }
+                }
+            }
+            state.tombstones = kept;
+        }
+    }
+}
+
+/// Replay the log of a table, version -1 if it has none yet
+fn load(root: &Path) -> Result<TableState> {
+    let mut state = TableState {
+        version: -1,
+        id: uuid::Uuid::new_v4().to_string(),
+        schema: Vec::new(),
+        files: HashMap::new(),
+        tombstones: Vec::new(),
+    };
+    let log_dir = root.join(LOG_DIR);
+    if !log_dir.is_dir() {
+        return Ok(state);
+    }
+
+    let mut versions: Vec<(i64, PathBuf)> = std::fs::read_dir(&log_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| Some((path.file_name()?.to_str()?.strip_suffix(".json")?.parse().ok()?, path)))
+        .collect();
+    versions.sort();
+
+    for (version, path) in versions {
+        for line in BufReader::new(File::open(&path)?).lines() {
+            let action: Value = serde_json::from_str(&line?).with_context(|| format!("Invalid action in {:?}", path))?;
+            if let Some(add) = action.get("add") {
+                let file = add["path"].as_str().context("add without path")?.to_string();
+                let dir = file.rsplit_once('/').map(|(dir, _)| dir.to_string()).unwrap_or_default();
+                state.files.insert(dir, file);
+            } else if let Some(remove) = action.get("remove") {
+                let file = remove["path"].as_str().context("remove without path")?.to_string();
+                let dir = file.rsplit_once('/').map(|(dir, _)| dir.to_string()).unwrap_or_default();
+                if state.files.get(&dir) == Some(&file) {
+                    state.files.remove(&dir);
+                }
+                if root.join(&file).exists() {
+                    state.tombstones.push((file, remove["deletionTimestamp"].as_i64().unwrap_or(0)));
+                }
+            } else if let Some(metadata) = action.get("metaData") {
+                state.id = metadata["id"].as_str().unwrap_or(&state.id).to_string();
+                let partition_columns: Vec<&str> = metadata["partitionColumns"].as_array()
+                    .map(|columns| columns.iter().filter_map(|c| c.as_str()).collect())
+                    .unwrap_or_default();
+                let schema: Value = serde_json::from_str(metadata["schemaString"].as_str().unwrap_or("{}"))?;
+                state.schema = schema["fields"].as_array().into_iter().flatten()
+                    .filter_map(|field| Some((field["name"].as_str()?.to_string(), field["type"].as_str()?.to_string())))
+                    .filter(|(name, _)| !partition_columns.contains(&name.as_str()))
+                    .collect();
+            }
+        }
+        state.version = version;
+    }
+    Ok(state)
+}
+
+/// Every `data.parquet` below a table root
+fn find_partitions(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if path.file_name().is_some_and(|n| n != LOG_DIR) {
+                find_partitions(&path, files)?;
+            }
+        } else if path.file_name().is_some_and(|n| n == DATA_FILE) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(())
+}
+
+/// Data columns of a Parquet file with their Delta type, and its row count
+fn read_schema(path: &Path) -> Result<(Vec<(String, String)>, i64)> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+    let rows = builder.metadata().file_metadata().num_rows();
+    Ok((delta_fields(builder.schema())?, rows))
+}
+
+fn delta_fields(schema: &Schema) -> Result<Vec<(String, String)>> {
+    schema.fields().iter().map(|field| {
+        let delta_type = match field.data_type() {
+            DataType::Timestamp(_, _) => "timestamp",
+            DataType::Float64 => "double",
+            DataType::Float32 => "float",
+            DataType::Int64 => "long",
+            DataType::Int32 => "integer",
+            DataType::Boolean => "boolean",
+            DataType::Utf8 | DataType::LargeUtf8 => "string",
+            other => bail!("Column {} of type {} has no Delta type", field.name(), other),
+        };
+        Ok((field.name().clone(), delta_type.to_string()))
+    }).collect()
+}
+
+/// `year`, `month` and `day` of a partition directory like `year=2025/month=06`, as Delta
+/// serializes integers
+fn partition_values(relative_dir: &str) -> BTreeMap<String, String> {
+    relative_dir.split('/')
+        .filter_map(|component| component.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.parse::<i32>().map(|v| v.to_string()).unwrap_or_else(|_| value.to_string())))
+        .collect()
+}
+
+fn partition_columns(granularity: Granularity) -> &'static [&'static str] {
+    match granularity {
+        Granularity::Day => &["year", "month", "day"],
+        Granularity::Month => &["year", "month"],
+        Granularity::Year => &["year"],
+    }
+}
+
+fn metadata(state: &TableState, granularity: Granularity, now: i64) -> Value {
+    let columns = partition_columns(granularity);
+    let fields: Vec<Value> = state.schema.iter()
+        .map(|(name, delta_type)| (name.as_str(), delta_type.as_str()))
+        .chain(columns.iter().map(|column| (*column, "integer")))
+        .map(|(name, delta_type)| json!({ "name": name, "type": delta_type, "nullable": true, "metadata": {} }))
+        .collect();
+    json!({
+        "id": state.id,
+        "format": { "provider": "parquet", "options": {} },
+        "schemaString": json!({ "type": "struct", "fields": fields }).to_string(),
+        "partitionColumns": columns,
+        "configuration": {},
+        "createdTime": now,
+    })
+}
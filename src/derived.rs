@@ -0,0 +1,251 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use crate::partition::{parse_timezone, Granularity};
+use crate::values::Value;
+
+/// A series computed from the stored data of other scrapers, e.g. a net position or a price spread
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DerivedConfig {
+    pub name: String,
+    /// Data folder of the series, `name` if not set
+    pub folder: Option<String>,
+    /// Expression per column over `<data folder>.<column>` references, e.g.
+    /// `"apg_imb_15min.import - apg_imb_15min.export"`
+    pub columns: BTreeMap<String, String>,
+    pub partition_timezone: Option<String>,
+    #[serde(default)]
+    pub partition_granularity: Granularity,
+}
+
+impl DerivedConfig {
+    pub fn data_folder(&self) -> &str {
+        self.folder.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn partition_timezone(&self) -> Result<Tz> {
+        match &self.partition_timezone {
+            Some(tz) => parse_timezone(tz),
+            None => Ok(chrono_tz::Europe::Vienna),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Column { folder: String, column: String },
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn inputs(&self, inputs: &mut BTreeSet<String>) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Column { folder, .. } => {
+                inputs.insert(folder.clone());
+            }
+            Expr::Neg(expr) => expr.inputs(inputs),
+            Expr::Binary(_, left, right) => {
+                left.inputs(inputs);
+                right.inputs(inputs);
+            }
+        }
+    }
+
+    /// None if a referenced value is missing or not numeric, or on a division by zero
+    fn eval(&self, values: &HashMap<&str, &BTreeMap<String, Value>>) -> Option<f64> {
+        match self {
+            Expr::Number(n) => Some(*n),
+            Expr::Column { folder, column } => values.get(folder.as_str())?.get(column)?.as_f64(),
+            Expr::Neg(expr) => Some(-expr.eval(values)?),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(values)?, right.eval(values)?);
+                match op {
+                    '+' => Some(left + right),
+                    '-' => Some(left - right),
+                    '*' => Some(left * right),
+                    _ if right == 0.0 => None,
+                    _ => Some(left / right),
+                }
+            }
+        }
+    }
+}
+
+/// Recursive descent parser for `+ - * /`, unary minus, parentheses, numbers and references
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(input: &'a str) -> Result<Expr> {
+        let mut parser = Parser { input, pos: 0 };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos < input.len() {
+            bail!("Unexpected '{}' at position {}", &input[parser.pos..], parser.pos);
+        }
+        Ok(expr)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut left = self.term()?;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(op @ ('+' | '-')) => {
+                    self.pos += 1;
+                    left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+                }
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut left = self.factor()?;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(op @ ('*' | '/')) => {
+                    self.pos += 1;
+                    left = Expr::Binary(op, Box::new(left), Box::new(self.factor()?));
+                }
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<Expr> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.skip_whitespace();
+                if self.peek() != Some(')') {
+                    bail!("Missing ')' at position {}", self.pos);
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let token = self.take(|c| c.is_ascii_digit() || c == '.');
+                Ok(Expr::Number(token.parse().with_context(|| format!("Invalid number {}", token))?))
+            }
+            Some(c) if c.is_alphanumeric() || c == '_' => {
+                let token = self.take(|c| c.is_alphanumeric() || c == '_' || c == '/' || c == '.');
+                let Some((folder, column)) = token.rsplit_once('.') else {
+                    bail!("Reference {} needs the form <data folder>.<column>", token);
+                };
+                Ok(Expr::Column { folder: folder.to_string(), column: column.to_string() })
+            }
+            Some(c) => bail!("Unexpected '{}' at position {}", c, self.pos),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+
+    fn take(&mut self, matches: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&matches) {
+            self.pos += self.peek().map(|c| c.len_utf8()).unwrap_or(1);
+        }
+        &self.input[start..self.pos]
+    }
+}
+
+/// A parsed derived series
+#[derive(Debug, Clone)]
+pub struct Derived {
+    pub config: DerivedConfig,
+    columns: Vec<(String, Expr)>,
+}
+
+impl Derived {
+    pub fn new(config: DerivedConfig) -> Result<Self> {
+        let mut columns = Vec::new();
+        for (column, expression) in &config.columns {
+            let expr = Parser::parse(expression)
+                .with_context(|| format!("Invalid expression for {}.{}: {}", config.name, column, expression))?;
+            columns.push((column.clone(), expr));
+        }
+        let derived = Self { config, columns };
+        if derived.inputs().contains(derived.config.data_folder()) {
+            bail!("Derived series {} can't reference its own folder", derived.config.name);
+        }
+        Ok(derived)
+    }
+
+    /// Data folders the series is computed from
+    pub fn inputs(&self) -> BTreeSet<String> {
+        let mut inputs = BTreeSet::new();
+        for (_, expr) in &self.columns {
+            expr.inputs(&mut inputs);
+        }
+        inputs
+    }
+
+    /// Evaluate every column per interval. Intervals and columns whose inputs are missing are skipped,
+    /// so a series is only written once all of its inputs arrived.
+    pub fn compute(&self, inputs: &HashMap<String, Vec<(DateTime<Utc>, DateTime<Utc>, BTreeMap<String, Value>)>>) -> Vec<ScraperData> {
+        let mut intervals: BTreeMap<(DateTime<Utc>, DateTime<Utc>), HashMap<&str, &BTreeMap<String, Value>>> = BTreeMap::new();
+        for (folder, rows) in inputs {
+            for (start, end, values) in rows {
+                intervals.entry((*start, *end)).or_default().insert(folder.as_str(), values);
+            }
+        }
+
+        intervals.into_iter().filter_map(|((delivery_from, delivery_to), values)| {
+            let computed: HashMap<String, f64> = self.columns.iter()
+                .filter_map(|(column, expr)| Some((column.clone(), expr.eval(&values)?)))
+                .collect();
+            if computed.is_empty() {
+                return None;
+            }
+            Some(ScraperData { delivery_from, delivery_to, payload: ScraperPayload::Values(computed) })
+        }).collect()
+    }
+}
+
+/// Parse every derived series and reject chains that feed back into themselves
+pub fn from_config(configs: &[DerivedConfig]) -> Result<Vec<Derived>> {
+    let derived = configs.iter().cloned().map(Derived::new).collect::<Result<Vec<_>>>()?;
+    let graph: HashMap<&str, BTreeSet<String>> = derived.iter().map(|d| (d.config.data_folder(), d.inputs())).collect();
+
+    for start in graph.keys() {
+        let mut stack: Vec<String> = graph[start].iter().cloned().collect();
+        let mut seen = BTreeSet::new();
+        while let Some(folder) = stack.pop() {
+            if folder == *start {
+                bail!("Derived series {} depends on itself", start);
+            }
+            if seen.insert(folder.clone()) {
+                if let Some(inputs) = graph.get(folder.as_str()) {
+                    stack.extend(inputs.iter().cloned());
+                }
+            }
+        }
+    }
+    Ok(derived)
+}
@@ -0,0 +1,224 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::config::ScraperConfig;
+use crate::storage::Storage;
+use crate::uploader::Uploader;
+
+/// Space of the filesystem holding a path
+#[derive(Debug, Clone, Copy)]
+pub struct DiskUsage {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+}
+
+impl DiskUsage {
+    pub fn used_percent(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        100.0 * (self.total_bytes - self.available_bytes.min(self.total_bytes)) as f64 / self.total_bytes as f64
+    }
+}
+
+pub fn usage(path: &Path) -> Result<DiskUsage> {
+    Ok(DiskUsage {
+        total_bytes: fs4::total_space(path).with_context(|| format!("Failed to get the disk size of {:?}", path))?,
+        available_bytes: fs4::available_space(path).with_context(|| format!("Failed to get the free space of {:?}", path))?,
+    })
+}
+
+/// Watch the free space of the data directory and free it up before writes start failing
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DiskGuardConfig {
+    #[serde(default = "default_warn_percent")]
+    pub warn_percent: f64,
+    #[serde(default = "default_critical_percent")]
+    pub critical_percent: f64,
+    /// Also critical once less than this many MB are free
+    pub min_free_mb: Option<u64>,
+    /// Delete the oldest partitions verified in S3 while critical
+    #[serde(default)]
+    pub emergency_cleanup: bool,
+    /// Emergency cleanup stops once the disk is less full than this, `warn_percent` if not set
+    pub cleanup_target_percent: Option<f64>,
+    /// Partitions of the last days are never deleted by the emergency cleanup. Must be longer
+    /// than the revision and catch-up windows of every scraper, which default it.
+    pub min_age_days: Option<u64>,
+    /// POST a JSON alert here whenever the level changes
+    pub webhook_url: Option<String>,
+    #[serde(default = "default_check_interval_secs")]
+    pub check_interval_secs: u64,
+}
+
+fn default_warn_percent() -> f64 {
+    80.0
+}
+
+fn default_critical_percent() -> f64 {
+    90.0
+}
+
+impl DiskGuardConfig {
+    /// Days the emergency cleanup keeps. Revisions and the catch-up rewrite the days of their
+    /// window, a day deleted before them would be replaced by a partition of only the new rows.
+    pub fn min_age_days(&self, scrapers: &[ScraperConfig]) -> Result<u64> {
+        let window = scrapers.iter()
+            .map(|s| {
+                let lookback_days = (s.lookback().num_hours().max(0) as u64).div_ceil(24);
+                let revision_days = s.revision_window_days.unwrap_or(0).max(0) as u64;
+                let catch_up_days = s.catch_up_days.unwrap_or(7).max(0) as u64;
+                lookback_days + revision_days.max(catch_up_days)
+            })
+            .max()
+            .unwrap_or(0);
+        match self.min_age_days {
+            Some(days) if days <= window => bail!(
+                "disk_guard.min_age_days ({}) must be longer than the revision and catch-up windows ({} days)", days, window),
+            Some(days) => Ok(days),
+            None => Ok((window + 1).max(2)),
+        }
+    }
+}
+
+fn default_check_interval_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskLevel {
+    Ok,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Serialize)]
+struct DiskAlert<'a> {
+    level: DiskLevel,
+    path: &'a str,
+    used_percent: f64,
+    available_bytes: u64,
+}
+
+pub struct DiskGuard {
+    config: DiskGuardConfig,
+    min_age_days: u64,
+    base_path: PathBuf,
+    storage: Arc<Storage>,
+    uploader: Option<Arc<Uploader>>,
+    client: reqwest::Client,
+}
+
+impl DiskGuard {
+    /// Fails if `min_age_days` doesn't cover the windows `scrapers` rewrite
+    pub fn new(config: DiskGuardConfig, base_path: &str, storage: Arc<Storage>, uploader: Option<Arc<Uploader>>, scrapers: &[ScraperConfig]) -> Result<Self> {
+        Ok(Self {
+            min_age_days: config.min_age_days(scrapers)?,
+            config,
+            base_path: PathBuf::from(base_path),
+            storage,
+            uploader,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub fn level(&self, usage: &DiskUsage) -> DiskLevel {
+        let low_space = self.config.min_free_mb.is_some_and(|mb| usage.available_bytes < mb * 1024 * 1024);
+        if low_space || usage.used_percent() >= self.config.critical_percent {
+            DiskLevel::Critical
+        } else if usage.used_percent() >= self.config.warn_percent {
+            DiskLevel::Warning
+        } else {
+            DiskLevel::Ok
+        }
+    }
+
+    /// Check the disk every `check_interval_secs` until the process exits
+    pub async fn run(&self) {
+        if self.config.emergency_cleanup && self.uploader.is_none() {
+            error!("Emergency cleanup requires S3 to verify partitions before deleting them, it is disabled");
+        }
+        let mut level = DiskLevel::Ok;
+        loop {
+            match usage(&self.base_path) {
+                Ok(current) => level = self.check(current, level).await,
+                Err(e) => warn!("Disk guard can't check the disk usage: {:?}", e),
+            }
+            sleep(Duration::from_secs(self.config.check_interval_secs.max(1))).await;
+        }
+    }
+
+    async fn check(&self, current: DiskUsage, previous: DiskLevel) -> DiskLevel {
+        let mut level = self.level(&current);
+        if level != previous {
+            self.alert(level, &current).await;
+        }
+
+        if level == DiskLevel::Critical && self.config.emergency_cleanup {
+            if let Some(uploader) = &self.uploader {
+                match self.cleanup(uploader).await {
+                    Ok(0) => warn!("Emergency cleanup found no partitions verified in S3 to delete"),
+                    Ok(deleted) => {
+                        info!("Emergency cleanup deleted {} partitions", deleted);
+                        if let Ok(usage_after) = usage(&self.base_path) {
+                            let after = self.level(&usage_after);
+                            if after != level {
+                                self.alert(after, &usage_after).await;
+                                level = after;
+                            }
+                        }
+                    }
+                    Err(e) => error!("Emergency cleanup failed: {:?}", e),
+                }
+            }
+        }
+        level
+    }
+
+    async fn cleanup(&self, uploader: &Uploader) -> Result<usize> {
+        let target = self.config.cleanup_target_percent.unwrap_or(self.config.warn_percent);
+        let min_free_bytes = self.config.min_free_mb.unwrap_or(0) * 1024 * 1024;
+        let base_path = self.base_path.clone();
+        self.storage.emergency_cleanup(uploader, self.min_age_days, move || {
+            usage(&base_path)
+                .map(|usage| usage.used_percent() < target && usage.available_bytes >= min_free_bytes)
+                .unwrap_or(false)
+        }).await
+    }
+
+    async fn alert(&self, level: DiskLevel, usage: &DiskUsage) {
+        let free_mb = usage.available_bytes / (1024 * 1024);
+        match level {
+            DiskLevel::Critical => error!("ALERT: disk of {:?} is {:.1}% full, {} MB free", self.base_path, usage.used_percent(), free_mb),
+            DiskLevel::Warning => warn!("Disk of {:?} is {:.1}% full, {} MB free", self.base_path, usage.used_percent(), free_mb),
+            DiskLevel::Ok => info!("Disk of {:?} back to {:.1}% full, {} MB free", self.base_path, usage.used_percent(), free_mb),
+        }
+        if let Err(e) = self.send_webhook(level, usage).await {
+            warn!("Disk alert webhook failed: {:?}", e);
+        }
+    }
+
+    async fn send_webhook(&self, level: DiskLevel, usage: &DiskUsage) -> Result<()> {
+        let Some(url) = &self.config.webhook_url else {
+            return Ok(());
+        };
+        let path = self.base_path.to_string_lossy();
+        let alert = DiskAlert {
+            level,
+            path: &path,
+            used_percent: usage.used_percent(),
+            available_bytes: usage.available_bytes,
+        };
+        let response = self.client.post(url).json(&alert).timeout(Duration::from_secs(10)).send().await?;
+        if !response.status().is_success() {
+            bail!("Webhook returned {}", response.status());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use ve_energy_scrapers::models::scraper_data::ScraperData;
+
+use crate::config::{AppConfig, ScraperConfig};
+use crate::conflict::{self, CONFLICTS_DIR};
+use crate::derived::{self, Derived};
+use crate::partition_manifest;
+use crate::provenance;
+use crate::raw_archive::{self, RAW_DIR};
+use crate::scraper_factory::RefreshingScraper;
+use crate::storage::{PlannedWrite, Storage};
+use crate::validation::Validator;
+
+/// What one scrape of a scraper would store, found without writing to disk or S3
+pub struct DryRunReport {
+    pub scraper: String,
+    /// `api` or the replayed fixture
+    pub source: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub records_fetched: usize,
+    pub error: Option<String>,
+    pub conflicting_intervals: usize,
+    pub rejected: usize,
+    pub writes: Vec<PlannedWrite>,
+    pub rejected_writes: Vec<PlannedWrite>,
+    pub conflict_writes: Vec<PlannedWrite>,
+    /// Folder of the raw response, if the scraper archives them
+    pub raw_folder: Option<String>,
+    /// Aggregate and derived folders recomputed when the scraper's data changes
+    pub dependents: Vec<String>,
+    /// Whether changed files would be uploaded to S3
+    pub upload: bool,
+}
+
+impl DryRunReport {
+    pub fn rows(&self) -> usize {
+        self.writes.iter().map(|w| w.new_rows + w.changed_rows).sum()
+    }
+
+    /// Files a run would queue for upload: every changed partition with its provenance manifest and summary
+    pub fn uploads(&self) -> Vec<String> {
+        self.writes.iter().chain(&self.rejected_writes).chain(&self.conflict_writes)
+            .flat_map(|w| [w.file_path.clone(), provenance::manifest_path(&w.file_path), partition_manifest::manifest_path(&w.file_path)])
+            .collect()
+    }
+
+}
+
+/// The report as printed by `--dry-run`
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "=== {} ({}) ===", self.scraper, self.source)?;
+        writeln!(f, "Window: {} to {}", self.window_start, self.window_end)?;
+        if let Some(error) = &self.error {
+            writeln!(f, "✗ {}", error)?;
+        }
+        writeln!(f, "Fetched {} records, {} conflicting intervals, {} rejected", self.records_fetched, self.conflicting_intervals, self.rejected)?;
+
+        for (label, writes) in [("Would write", &self.writes), ("Would quarantine", &self.rejected_writes), ("Would store conflicts in", &self.conflict_writes)] {
+            for write in writes {
+                writeln!(f, "  {} {}: {} new, {} changed", label, write.file_path, write.new_rows, write.changed_rows)?;
+            }
+        }
+        if self.writes.is_empty() && self.error.is_none() {
+            writeln!(f, "  Nothing new to write")?;
+        }
+        if let Some(folder) = &self.raw_folder {
+            if self.records_fetched > 0 {
+                writeln!(f, "  Would archive the raw response in {}", folder)?;
+            }
+        }
+        if !self.writes.is_empty() {
+            for dependent in &self.dependents {
+                writeln!(f, "  Would recompute {} for the changed days", dependent)?;
+            }
+        }
+        if self.upload {
+            let uploads = self.uploads();
+            if !uploads.is_empty() {
+                writeln!(f, "  Would upload {} files", uploads.len())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Totals of a dry run over several scrapers
+pub fn summary(reports: &[DryRunReport]) -> String {
+    let rows: usize = reports.iter().map(|r| r.rows()).sum();
+    let partitions: usize = reports.iter().map(|r| r.writes.len()).sum();
+    let failed = reports.iter().filter(|r| r.error.is_some()).count();
+    format!("Dry run: {} rows would be written to {} partitions, {} scrapers failed. Nothing was written.", rows, partitions, failed)
+}
+
+/// Scrape the regular window of a scraper, or replay recorded responses, and run conflict
+/// resolution, transforms, validation and deduplication against the stored partitions
+pub async fn run_scraper(storage: &Storage, config: &ScraperConfig, derived: &[Derived], replay: Option<&Path>, upload: bool) -> Result<DryRunReport> {
+    let name = &config.scraper_config.name;
+    let folder = config.data_folder();
+    let now = Utc::now();
+
+    let (source, window_start, window_end, fetched) = match replay {
+        Some(path) => {
+            let (start, end, data) = replay_responses(path)?;
+            (path.display().to_string(), start, end, Ok(data))
+        }
+        None => {
+            let (start, end) = (now - config.lookback(), now + config.lookahead());
+            let fetched = match RefreshingScraper::new(&config.scraper_config, config.http.as_ref()).await {
+                Ok(scraper) => scraper.scrape_data(start, end).await,
+                Err(e) => Err(e),
+            };
+            ("api".to_string(), start, end, fetched)
+        }
+    };
+
+    let mut report = DryRunReport {
+        scraper: name.clone(),
+        source,
+        window_start,
+        window_end,
+        records_fetched: 0,
+        error: None,
+        conflicting_intervals: 0,
+        rejected: 0,
+        writes: Vec::new(),
+        rejected_writes: Vec::new(),
+        conflict_writes: Vec::new(),
+        raw_folder: config.raw_archive.then(|| format!("{}/{}", RAW_DIR, folder)),
+        dependents: config.aggregations.iter().map(|a| a.window.folder(folder))
+            .chain(derived.iter().filter(|d| d.inputs().contains(folder)).map(|d| d.config.data_folder().to_string()))
+            .collect(),
+        upload,
+    };
+
+    let data = match fetched {
+        Ok(data) => data,
+        Err(e) => {
+            report.error = Some(format!("scrape: {:#}", e));
+            return Ok(report);
+        }
+    };
+    report.records_fetched = data.len();
+
+    let data = match conflict::resolve(name, config.conflict_policy, data) {
+        Ok(resolved) => {
+            report.conflicting_intervals = resolved.versions.len();
+            report.conflict_writes = storage.plan(&format!("{}/{}", CONFLICTS_DIR, folder), &resolved.versions)?;
+            resolved.data
+        }
+        Err(e) => {
+            report.error = Some(format!("conflict: {:#}", e));
+            Vec::new()
+        }
+    };
+    let data = config.transforms()?.apply(data);
+    let data = match Validator::for_scraper(config, storage.base_path())? {
+        Some(validator) => {
+            let result = validator.validate(name, data);
+            report.rejected = result.rejected.len();
+            if validator.rules().quarantine {
+                report.rejected_writes = storage.plan(&format!("rejected/{}", folder), &result.rejected)?;
+            }
+            result.accepted
+        }
+        None => data,
+    };
+    report.writes = storage.plan(folder, &data)?;
+    Ok(report)
+}
+
+/// Records and window of a recorded response, or of every `.json.gz` response in a directory
+fn replay_responses(path: &Path) -> Result<(DateTime<Utc>, DateTime<Utc>, Vec<ScraperData>)> {
+    let files: Vec<PathBuf> = if path.is_dir() {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.to_string_lossy().ends_with(".json.gz"))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut window: Option<(DateTime<Utc>, DateTime<Utc>)> = None;
+    let mut data = Vec::new();
+    for file in &files {
+        let response = raw_archive::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let (start, end) = (response.provenance.request_start, response.provenance.request_end);
+        window = Some(match window {
+            Some((s, e)) => (s.min(start), e.max(end)),
+            None => (start, end),
+        });
+        data.extend(response.to_scraper_data().0);
+    }
+    let (start, end) = window.with_context(|| format!("No recorded responses in {}", path.display()))?;
+    Ok((start, end, data))
+}
+
+/// Dry run of every scraper, or only `scraper`, reporting what would be written and uploaded
+pub async fn run(config: &AppConfig, storage: &Storage, scraper: Option<&str>, replay: Option<&Path>) -> Result<Vec<DryRunReport>> {
+    let scrapers: Vec<&ScraperConfig> = config.scrapers.iter()
+        .filter(|s| scraper.map(|name| s.scraper_config.name == name).unwrap_or(true))
+        .collect();
+    if scrapers.is_empty() {
+        anyhow::bail!("No scraper named '{}' in config.json", scraper.unwrap_or_default());
+    }
+    if replay.is_some() && scrapers.len() > 1 {
+        anyhow::bail!("--replay requires --scraper");
+    }
+
+    let derived = derived::from_config(&config.derived)?;
+    let upload = config.get_s3_bucket().is_some();
+    let mut reports = Vec::new();
+    for scraper_config in scrapers {
+        reports.push(run_scraper(storage, scraper_config, &derived, replay, upload).await?);
+    }
+    Ok(reports)
+}
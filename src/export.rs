@@ -0,0 +1,234 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow::array::{Array, Float64Array, Int32Array, StringArray, TimestampMicrosecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+
+use crate::forecast::PUBLICATION_COLUMN;
+use crate::query::{BidRow, QueryResult, ValueRow};
+use crate::values::{self, ValueType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "parquet" => Ok(ExportFormat::Parquet),
+            other => Err(anyhow::anyhow!("Unknown export format: {}", other)),
+        }
+    }
+}
+
+fn format_time(t: DateTime<Utc>, tz: Tz) -> String {
+    t.with_timezone(&tz).to_rfc3339()
+}
+
+fn value_columns(rows: &[ValueRow]) -> Vec<String> {
+    let mut columns: Vec<String> = rows.iter()
+        .flat_map(|r| r.values.keys().cloned())
+        .collect();
+    columns.sort();
+    columns.dedup();
+    columns
+}
+
+/// Whether the rows come from a folder stored by forecast vintage
+fn has_publication_time(rows: &[ValueRow]) -> bool {
+    rows.iter().any(|r| r.publication_time.is_some())
+}
+
+/// Type of a column, taken from its first value
+fn column_type(rows: &[ValueRow], column: &str) -> ValueType {
+    let value_type = rows.iter()
+        .find_map(|r| r.values.get(column))
+        .map(|v| v.value_type())
+        .unwrap_or_default();
+    values::lossless_type(value_type, rows.iter().filter_map(|r| r.values.get(column)))
+}
+
+/// Write a query result as CSV with timestamps in the given timezone
+pub fn write_csv<W: Write>(result: &QueryResult, out: W, tz: Tz) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+
+    match result {
+        QueryResult::Values(rows) => {
+            let columns = value_columns(rows);
+            let publication = has_publication_time(rows);
+
+            let mut header = vec!["start".to_string(), "end".to_string(), "scraped_at".to_string()];
+            if publication {
+                header.push(PUBLICATION_COLUMN.to_string());
+            }
+            header.extend(columns.iter().cloned());
+            writer.write_record(&header)?;
+
+            for row in rows {
+                let mut record = vec![
+                    format_time(row.start, tz),
+                    format_time(row.end, tz),
+                    row.scraped_at.map(|t| format_time(t, tz)).unwrap_or_default(),
+                ];
+                if publication {
+                    record.push(row.publication_time.map(|t| format_time(t, tz)).unwrap_or_default());
+                }
+                for col in &columns {
+                    record.push(row.values.get(col).map(|v| v.to_string()).unwrap_or_default());
+                }
+                writer.write_record(&record)?;
+            }
+        }
+        QueryResult::Bids(rows) => {
+            writer.write_record(["start", "end", "bid_type", "direction", "rank", "price", "volume", "scraped_at"])?;
+
+            for row in rows {
+                writer.write_record([
+                    format_time(row.start, tz),
+                    format_time(row.end, tz),
+                    row.bid_type.clone(),
+                    row.direction.clone(),
+                    row.rank.to_string(),
+                    row.price.map(|v| v.to_string()).unwrap_or_default(),
+                    row.volume.map(|v| v.to_string()).unwrap_or_default(),
+                    row.scraped_at.map(|t| format_time(t, tz)).unwrap_or_default(),
+                ])?;
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write a query result as a JSON array of objects with timestamps in the given timezone
+pub fn write_json<W: Write>(result: &QueryResult, out: W, tz: Tz) -> Result<()> {
+    let records: Vec<Value> = match result {
+        QueryResult::Values(rows) => rows.iter().map(|row| {
+            let mut obj = Map::new();
+            obj.insert("start".to_string(), json!(format_time(row.start, tz)));
+            obj.insert("end".to_string(), json!(format_time(row.end, tz)));
+            obj.insert("scraped_at".to_string(), json!(row.scraped_at.map(|t| format_time(t, tz))));
+            if let Some(publication_time) = row.publication_time {
+                obj.insert(PUBLICATION_COLUMN.to_string(), json!(format_time(publication_time, tz)));
+            }
+            for (k, v) in &row.values {
+                obj.insert(k.clone(), json!(v));
+            }
+            Value::Object(obj)
+        }).collect(),
+        QueryResult::Bids(rows) => rows.iter().map(|row| json!({
+            "start": format_time(row.start, tz),
+            "end": format_time(row.end, tz),
+            "bid_type": row.bid_type,
+            "direction": row.direction,
+            "rank": row.rank,
+            "price": row.price,
+            "volume": row.volume,
+            "scraped_at": row.scraped_at.map(|t| format_time(t, tz)),
+        })).collect(),
+    };
+
+    serde_json::to_writer_pretty(out, &records)?;
+    Ok(())
+}
+
+/// Write a query result as a single Parquet file, timestamps are annotated with the given timezone
+pub fn write_parquet(result: &QueryResult, path: &str, tz: Tz) -> Result<()> {
+    let batch = match result {
+        QueryResult::Values(rows) => values_batch(rows, tz)?,
+        QueryResult::Bids(rows) => bids_batch(rows, tz)?,
+    };
+
+    let file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn timestamp_field(name: &str, tz: Tz, nullable: bool) -> Field {
+    Field::new(name, DataType::Timestamp(TimeUnit::Microsecond, Some(tz.name().into())), nullable)
+}
+
+fn values_batch(rows: &[ValueRow], tz: Tz) -> Result<RecordBatch> {
+    let columns = value_columns(rows);
+
+    let mut fields = vec![
+        timestamp_field("start", tz, false),
+        timestamp_field("end", tz, false),
+        timestamp_field("scraped_at", tz, true),
+    ];
+    let publication = has_publication_time(rows);
+    if publication {
+        fields.push(timestamp_field(PUBLICATION_COLUMN, tz, true));
+    }
+    for col in &columns {
+        fields.push(Field::new(col, column_type(rows, col).data_type(), true));
+    }
+    let schema = Arc::new(Schema::new(fields));
+
+    let starts = TimestampMicrosecondArray::from(rows.iter().map(|r| r.start.timestamp_micros()).collect::<Vec<_>>());
+    let ends = TimestampMicrosecondArray::from(rows.iter().map(|r| r.end.timestamp_micros()).collect::<Vec<_>>());
+    let scraped_ats = TimestampMicrosecondArray::from(rows.iter().map(|r| r.scraped_at.map(|t| t.timestamp_micros())).collect::<Vec<_>>());
+
+    let mut arrays: Vec<Arc<dyn Array>> = vec![
+        Arc::new(starts.with_timezone(tz.name())),
+        Arc::new(ends.with_timezone(tz.name())),
+        Arc::new(scraped_ats.with_timezone(tz.name())),
+    ];
+    if publication {
+        let publications = TimestampMicrosecondArray::from(rows.iter().map(|r| r.publication_time.map(|t| t.timestamp_micros())).collect::<Vec<_>>());
+        arrays.push(Arc::new(publications.with_timezone(tz.name())));
+    }
+    for col in &columns {
+        arrays.push(values::build_column(column_type(rows, col), rows.iter().map(|r| r.values.get(col))));
+    }
+
+    Ok(RecordBatch::try_new(schema, arrays)?)
+}
+
+fn bids_batch(rows: &[BidRow], tz: Tz) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        timestamp_field("start", tz, false),
+        timestamp_field("end", tz, false),
+        Field::new("bid_type", DataType::Utf8, false),
+        Field::new("direction", DataType::Utf8, false),
+        Field::new("rank", DataType::Int32, false),
+        Field::new("price", DataType::Float64, true),
+        Field::new("volume", DataType::Float64, true),
+        timestamp_field("scraped_at", tz, true),
+    ]));
+
+    let starts = TimestampMicrosecondArray::from(rows.iter().map(|r| r.start.timestamp_micros()).collect::<Vec<_>>());
+    let ends = TimestampMicrosecondArray::from(rows.iter().map(|r| r.end.timestamp_micros()).collect::<Vec<_>>());
+    let scraped_ats = TimestampMicrosecondArray::from(rows.iter().map(|r| r.scraped_at.map(|t| t.timestamp_micros())).collect::<Vec<_>>());
+
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(starts.with_timezone(tz.name())),
+            Arc::new(ends.with_timezone(tz.name())),
+            Arc::new(StringArray::from(rows.iter().map(|r| r.bid_type.clone()).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(rows.iter().map(|r| r.direction.clone()).collect::<Vec<_>>())),
+            Arc::new(Int32Array::from(rows.iter().map(|r| r.rank).collect::<Vec<_>>())),
+            Arc::new(Float64Array::from(rows.iter().map(|r| r.price).collect::<Vec<_>>())),
+            Arc::new(Float64Array::from(rows.iter().map(|r| r.volume).collect::<Vec<_>>())),
+            Arc::new(scraped_ats.with_timezone(tz.name())),
+        ],
+    )?;
+
+    Ok(batch)
+}
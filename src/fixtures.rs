@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use tracing::info;
+use ve_energy_scrapers::models::scraper_data::ScraperData;
+use ve_energy_scrapers::models::strategy_information_scraper_config::StrategyInformationScraperConfig;
+use ve_energy_scrapers::scraper::Scraper;
+
+use crate::config::ScraperConfig;
+use crate::provenance::Provenance;
+use crate::raw_archive::{self, RawRecord, RawResponse};
+use crate::scraper_factory;
+
+/// `type` of a scraper config that replays fixtures instead of calling an API
+pub const MOCK_TYPE: &str = "mock";
+
+/// Fixtures are scraper outputs in the raw archive format, either pretty printed `.json` or
+/// archived `.json.gz` from `data/raw`
+fn is_fixture(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".json") || name.ends_with(".json.gz")
+}
+
+/// A fixture file, or every fixture in a directory ordered by name
+pub fn load(path: &Path) -> Result<Vec<RawResponse>> {
+    let files: Vec<PathBuf> = if path.is_dir() {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| is_fixture(p))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    files.iter().map(|file| {
+        if file.to_string_lossy().ends_with(".gz") {
+            raw_archive::read(file)
+        } else {
+            let json = std::fs::read(file).with_context(|| format!("Failed to read fixture {:?}", file))?;
+            serde_json::from_slice(&json).with_context(|| format!("Invalid fixture {:?}", file))
+        }
+    }).collect()
+}
+
+pub fn save(path: &Path, response: &RawResponse) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_vec_pretty(response)?)?;
+    Ok(())
+}
+
+/// Scrape a window from the real API and store the scraper's output as a fixture. The scrapers
+/// don't expose their HTTP exchange, so the fixture holds the parsed records and their provenance,
+/// never the config or its credentials. Replaying it covers everything after the scraper, not
+/// the scraper's own request building and parsing.
+pub async fn record(config: &ScraperConfig, start: DateTime<Utc>, end: DateTime<Utc>, output: &Path) -> Result<RawResponse> {
+    let scraper = scraper_factory::create_scraper(&config.scraper_config, config.http.as_ref()).await?;
+    let data = scraper.scrape_data(start, end).await?;
+    let endpoint = config.scraper_config.values.get("url").and_then(|v| v.as_str()).map(String::from);
+    let provenance = Provenance::new(&config.scraper_config.name, endpoint, start, end, Utc::now(), &data);
+    let response = RawResponse::new(provenance, &data);
+    save(output, &response)?;
+    info!("Recorded {} records of {} to {:?}", data.len(), config.scraper_config.name, output);
+    Ok(response)
+}
+
+/// Scraper returning recorded records in place of a real scraper, registered in the factory as
+/// `"type": "mock"`.
+///
+/// Records come from `fixture` (a file or directory, see `load`) and/or inline `records` in the
+/// raw response format. `"fail": true` makes every scrape fail, e.g. to test the circuit breaker.
+pub struct MockScraper {
+    config: StrategyInformationScraperConfig,
+    responses: Vec<RawResponse>,
+    fail: bool,
+}
+
+impl MockScraper {
+    pub fn new(config: StrategyInformationScraperConfig) -> Result<Self> {
+        let mut responses = Vec::new();
+        if let Some(path) = config.values.get("fixture").and_then(|v| v.as_str()) {
+            responses.extend(load(Path::new(path))?);
+        }
+        if let Some(records) = config.values.get("records") {
+            let records: Vec<RawRecord> = serde_json::from_value(records.clone())
+                .with_context(|| format!("Invalid records of mock scraper {}", config.name))?;
+            let (start, end) = (
+                records.iter().map(|r| r.delivery_from).min().unwrap_or_default(),
+                records.iter().map(|r| r.delivery_to).max().unwrap_or_default(),
+            );
+            let mut response = RawResponse { provenance: Provenance::new(&config.name, None, start, end, Utc::now(), &[]), records };
+            response.provenance = Provenance::new(&config.name, None, start, end, Utc::now(), &response.to_scraper_data().0);
+            responses.push(response);
+        }
+        let fail = config.values.get("fail").and_then(|v| v.as_bool()).unwrap_or(false);
+        Ok(Self { config, responses, fail })
+    }
+
+    pub fn from_responses(config: StrategyInformationScraperConfig, responses: Vec<RawResponse>) -> Self {
+        Self { config, responses, fail: false }
+    }
+}
+
+#[async_trait]
+impl Scraper for MockScraper {
+    fn get_config(&self) -> &StrategyInformationScraperConfig {
+        &self.config
+    }
+
+    /// Recorded records starting inside the window, in recording order. Bid records are skipped
+    /// like in the reprocess tool.
+    async fn scrape_data(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<ScraperData>> {
+        if self.fail {
+            anyhow::bail!("Mock scraper {} is configured to fail", self.config.name);
+        }
+        Ok(self.responses.iter()
+            .flat_map(|response| response.to_scraper_data().0)
+            .filter(|item| item.delivery_from >= start && item.delivery_from < end)
+            .collect())
+    }
+}
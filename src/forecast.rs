@@ -0,0 +1,90 @@
+use chrono::{DateTime, Days, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::query;
+use crate::values::Value;
+
+/// Column recording which publication of a forecast a row belongs to
+pub const PUBLICATION_COLUMN: &str = "publication_time";
+
+/// Store a forecast feed by vintage: every publication of an interval is kept as its own row
+/// with a `publication_time`, instead of only the scrapes that changed a value
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ForecastConfig {
+    /// Value holding the publication time as Unix seconds or an RFC 3339 / `YYYY-MM-DD HH:MM:SS`
+    /// (UTC) string, e.g. mapped from the `published` column of a vendor dump. It is taken out of
+    /// the stored values.
+    pub publication_column: Option<String>,
+    /// Without a publication column, the scrapes within each period of this many minutes belong
+    /// to one vintage, e.g. 60 for a forecast updated hourly. Without either, a new vintage is
+    /// stored whenever the values change, published at the time of the scrape.
+    pub publication_interval_minutes: Option<i64>,
+    /// Gate closure `read-latest --gate-closure` reconstructs the forecast for
+    pub gate_closure: Option<GateClosure>,
+}
+
+impl ForecastConfig {
+    /// Whether the publication time identifies a vintage, so an unchanged re-publication is
+    /// stored as well
+    pub fn has_vintages(&self) -> bool {
+        self.publication_column.is_some() || self.publication_interval_minutes.is_some()
+    }
+
+    /// Publication time of a record in microseconds, taking the publication column out of its
+    /// values. 0 if unknown, e.g. for a backfill of a feed without publication column. A
+    /// publication column that isn't a time falls back to the interval or the scrape time.
+    pub fn publication_micros(&self, values: &mut HashMap<String, Value>, scraped_at_micros: i64) -> i64 {
+        let published = self.publication_column.as_ref()
+            .and_then(|column| values.remove(column).map(|value| (column, value)));
+        match published {
+            Some((_, Value::Utf8(text))) => match query::parse_timestamp(&text) {
+                Ok(time) => return time.timestamp_micros(),
+                Err(e) => warn!("Ignoring the publication time: {:#}", e),
+            },
+            Some((column, value)) => match value.as_f64() {
+                Some(seconds) => return (seconds * 1_000_000.0) as i64,
+                None => warn!("Ignoring the publication time: {} is {:?}, not a time", column, value),
+            },
+            None => {}
+        }
+        match self.publication_interval_minutes {
+            _ if scraped_at_micros == 0 => 0,
+            Some(minutes) if minutes > 0 => {
+                let period = minutes * 60 * 1_000_000;
+                scraped_at_micros - scraped_at_micros.rem_euclid(period)
+            }
+            _ => scraped_at_micros,
+        }
+    }
+}
+
+/// Deadline after which a forecast can't influence trading of an interval anymore
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GateClosure {
+    /// Minutes before the start of each interval, e.g. 60 for most intraday markets
+    LeadMinutes(i64),
+    /// Local time of the day before delivery, e.g. `"12:00:00"` for the day-ahead auction
+    DayAhead(NaiveTime),
+}
+
+impl GateClosure {
+    /// Gate closure of the interval starting at `start`, local times are in `tz`
+    pub fn at(&self, start: DateTime<Utc>, tz: Tz) -> DateTime<Utc> {
+        match self {
+            GateClosure::LeadMinutes(minutes) => start - Duration::minutes(*minutes),
+            GateClosure::DayAhead(time) => {
+                let day = start.with_timezone(&tz).date_naive() - Days::new(1);
+                let local = day.and_time(*time);
+                // A time in the spring DST gap is taken as the first valid instant after it
+                tz.from_local_datetime(&local).earliest()
+                    .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|| local.and_utc())
+            }
+        }
+    }
+}
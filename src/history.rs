@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+use arrow::array::{Array, StringArray, TimestampMicrosecondArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+
+/// Default directory of the run history ledger
+pub const HISTORY_DIR: &str = "history";
+
+/// One scrape attempt, successful or not
+#[derive(Debug, Clone, Serialize)]
+pub struct RunRecord {
+    /// Also a field of the run's log span, None for runs recorded before run ids existed
+    pub run_id: Option<String>,
+    pub scraper: String,
+    /// `service`, `backfill`, `revision` or `catch-up`
+    pub source: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub records_fetched: u64,
+    pub records_written: u64,
+    pub error: Option<String>,
+}
+
+/// Id correlating a run's log lines with its run history record
+pub fn new_run_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Ledger of scrape attempts, stored as Parquet partitioned by the UTC day the run started.
+/// Records are kept in memory until `flush` so a run doesn't rewrite the day file.
+pub struct RunLedger {
+    base_path: String,
+    pending: Mutex<Vec<RunRecord>>,
+    /// Latest attempt per scraper, for status reporting
+    last_runs: Mutex<HashMap<String, RunRecord>>,
+}
+
+impl RunLedger {
+    pub fn new(base_path: &str) -> Self {
+        Self {
+            base_path: base_path.to_string(),
+            pending: Mutex::new(Vec::new()),
+            last_runs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Directory the run history is written to
+    pub fn base_path(&self) -> &str {
+        &self.base_path
+    }
+
+    pub fn record(&self, record: RunRecord) {
+        self.last_runs.lock().unwrap().insert(record.scraper.clone(), record.clone());
+        self.pending.lock().unwrap().push(record);
+    }
+
+    /// Latest attempt of a scraper since the process started
+    pub fn last_run(&self, scraper: &str) -> Option<RunRecord> {
+        self.last_runs.lock().unwrap().get(scraper).cloned()
+    }
+
+    /// Append all pending records to their day files, returns the number of records written
+    pub fn flush(&self) -> Result<usize> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let mut days: BTreeMap<NaiveDate, Vec<RunRecord>> = BTreeMap::new();
+        for record in &pending {
+            days.entry(record.started_at.date_naive()).or_default().push(record.clone());
+        }
+
+        for (date, records) in days {
+            let path = partition_path(&self.base_path, date);
+            if let Err(e) = append_records(&path, &records) {
+                // Keep everything not yet written for the next flush
+                let mut current = self.pending.lock().unwrap();
+                current.extend(pending.into_iter().filter(|r| r.started_at.date_naive() >= date));
+                return Err(e.context(format!("Failed to write run history {:?}", path)));
+            }
+        }
+
+        Ok(pending.len())
+    }
+}
+
+fn partition_path(base_path: &str, date: NaiveDate) -> PathBuf {
+    Path::new(base_path).join(format!(
+        "year={}/month={:02}/day={:02}/runs.parquet",
+        date.year(), date.month(), date.day()
+    ))
+}
+
+fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("scraper", DataType::Utf8, false),
+        Field::new("source", DataType::Utf8, false),
+        Field::new("window_start", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
+        Field::new("window_end", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
+        Field::new("started_at", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
+        Field::new("duration_ms", DataType::UInt64, false),
+        Field::new("records_fetched", DataType::UInt64, false),
+        Field::new("records_written", DataType::UInt64, false),
+        Field::new("error", DataType::Utf8, true),
+        Field::new("run_id", DataType::Utf8, true),
+    ]))
+}
+
+fn append_records(path: &Path, records: &[RunRecord]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut all = if path.exists() { read_file(path)? } else { Vec::new() };
+    all.extend_from_slice(records);
+
+    let batch = RecordBatch::try_new(schema(), vec![
+        Arc::new(StringArray::from_iter_values(all.iter().map(|r| r.scraper.as_str()))),
+        Arc::new(StringArray::from_iter_values(all.iter().map(|r| r.source.as_str()))),
+        Arc::new(TimestampMicrosecondArray::from_iter_values(all.iter().map(|r| r.window_start.timestamp_micros())).with_timezone("UTC")),
+        Arc::new(TimestampMicrosecondArray::from_iter_values(all.iter().map(|r| r.window_end.timestamp_micros())).with_timezone("UTC")),
+        Arc::new(TimestampMicrosecondArray::from_iter_values(all.iter().map(|r| r.started_at.timestamp_micros())).with_timezone("UTC")),
+        Arc::new(UInt64Array::from_iter_values(all.iter().map(|r| r.duration_ms))),
+        Arc::new(UInt64Array::from_iter_values(all.iter().map(|r| r.records_fetched))),
+        Arc::new(UInt64Array::from_iter_values(all.iter().map(|r| r.records_written))),
+        Arc::new(StringArray::from(all.iter().map(|r| r.error.clone()).collect::<Vec<_>>())),
+        Arc::new(StringArray::from(all.iter().map(|r| r.run_id.clone()).collect::<Vec<_>>())),
+    ])?;
+
+    let tmp_path = path.with_extension("parquet.tmp");
+    let file = File::create(&tmp_path)?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<Vec<RunRecord>> {
+    let file = File::open(path)?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+
+    let mut records = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        let string = |i: usize| batch.column(i).as_any().downcast_ref::<StringArray>().context("Invalid run history column");
+        let time = |i: usize| batch.column(i).as_any().downcast_ref::<TimestampMicrosecondArray>().context("Invalid run history column");
+        let count = |i: usize| batch.column(i).as_any().downcast_ref::<UInt64Array>().context("Invalid run history column");
+
+        let (scraper, source, error) = (string(0)?, string(1)?, string(8)?);
+        let (window_start, window_end, started_at) = (time(2)?, time(3)?, time(4)?);
+        let (duration_ms, fetched, written) = (count(5)?, count(6)?, count(7)?);
+        // Files written before run ids have 9 columns
+        let run_id = if batch.num_columns() > 9 { Some(string(9)?) } else { None };
+
+        for i in 0..batch.num_rows() {
+            records.push(RunRecord {
+                run_id: run_id.filter(|ids| !ids.is_null(i)).map(|ids| ids.value(i).to_string()),
+                scraper: scraper.value(i).to_string(),
+                source: source.value(i).to_string(),
+                window_start: to_datetime(window_start.value(i))?,
+                window_end: to_datetime(window_end.value(i))?,
+                started_at: to_datetime(started_at.value(i))?,
+                duration_ms: duration_ms.value(i),
+                records_fetched: fetched.value(i),
+                records_written: written.value(i),
+                error: if error.is_null(i) { None } else { Some(error.value(i).to_string()) },
+            });
+        }
+    }
+    Ok(records)
+}
+
+fn to_datetime(micros: i64) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp_micros(micros).context("Timestamp out of range")
+}
+
+/// Read all runs that started between start_date and end_date (inclusive, UTC), ordered by start time
+pub fn read_runs(base_path: &str, start_date: NaiveDate, end_date: NaiveDate) -> Result<Vec<RunRecord>> {
+    let mut records = Vec::new();
+    let mut date = start_date;
+    while date <= end_date {
+        let path = partition_path(base_path, date);
+        if path.exists() {
+            records.extend(read_file(&path).with_context(|| format!("Failed to read {:?}", path))?);
+        }
+        date += Duration::days(1);
+    }
+    records.sort_by_key(|r| r.started_at);
+    Ok(records)
+}
+
+/// Flush the ledger periodically, used by the long running binaries
+pub async fn run_flush_loop(ledger: Arc<RunLedger>, interval: std::time::Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match ledger.flush() {
+            Ok(0) => {}
+            Ok(n) => info!("Wrote {} runs to the run history", n),
+            Err(e) => error!("Failed to write run history: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::{bail, Context, Result};
+use arrow::array::{Array, Float64Array, StringArray, TimestampMicrosecondArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use crate::partition::parse_timezone;
+
+/// How the columns of an external CSV or Parquet dump map to a scraper's stored series
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ImportMapping {
+    /// Column holding the start of each interval
+    pub start: String,
+    /// Column holding the end of each interval, `start + interval_minutes` if not set
+    pub end: Option<String>,
+    pub interval_minutes: Option<i64>,
+    /// Source column to stored column. Other columns are ignored.
+    pub columns: HashMap<String, String>,
+    /// chrono format of text timestamps, RFC 3339 or `YYYY-MM-DD HH:MM[:SS]` if not set
+    pub timestamp_format: Option<String>,
+    /// Timezone of timestamps without an offset (default UTC). The repeated hour in October is
+    /// resolved by row order, so rows must be sorted by time.
+    pub timezone: Option<String>,
+    /// CSV field delimiter (default `,`)
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    /// CSV numbers use a decimal comma, e.g. `42,5`
+    #[serde(default)]
+    pub decimal_comma: bool,
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+/// A timestamp as read from a file, before the mapping's timezone is applied
+enum RawTime {
+    Utc(DateTime<Utc>),
+    Local(NaiveDateTime),
+}
+
+/// One source row: start, optional end and the mapped values that could be parsed
+type Row = (RawTime, Option<RawTime>, HashMap<String, f64>);
+
+/// Records read from one file
+pub struct ImportedFile {
+    pub data: Vec<ScraperData>,
+    pub rows: usize,
+    /// Values that were empty or not numeric
+    pub skipped_values: usize,
+}
+
+/// The file itself, or every `.csv` and `.parquet` file below a directory ordered by path
+pub fn find_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|e| e == "csv" || e == "parquet") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Read a CSV or Parquet file, by its extension, into records of the mapped columns
+pub fn read_file(path: &Path, mapping: &ImportMapping) -> Result<ImportedFile> {
+    anyhow::ensure!(!mapping.columns.is_empty(), "The import mapping has no columns");
+    anyhow::ensure!(mapping.end.is_some() || mapping.interval_minutes.is_some(), "The import mapping needs end or interval_minutes");
+
+    let mut skipped_values = 0;
+    let rows = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => read_csv(path, mapping, &mut skipped_values)?,
+        Some("parquet") => read_parquet(path, mapping, &mut skipped_values)?,
+        _ => bail!("Unsupported file {:?}, expected .csv or .parquet", path),
+    };
+    let row_count = rows.len();
+    let tz = match &mapping.timezone {
+        Some(tz) => parse_timezone(tz)?,
+        None => chrono_tz::UTC,
+    };
+
+    let mut data = Vec::with_capacity(rows.len());
+    let mut previous_start = None;
+    for (index, (start, end, values)) in rows.into_iter().enumerate() {
+        let start = resolve(start, tz, previous_start).with_context(|| format!("Row {} of {:?}", index + 1, path))?;
+        previous_start = Some(start);
+        let end = match (end, mapping.interval_minutes) {
+            (Some(end), _) => resolve(end, tz, Some(start)).with_context(|| format!("Row {} of {:?}", index + 1, path))?,
+            (None, Some(minutes)) => start + Duration::minutes(minutes),
+            (None, None) => unreachable!("checked above"),
+        };
+        if values.is_empty() {
+            continue;
+        }
+        data.push(ScraperData { delivery_from: start, delivery_to: end, payload: ScraperPayload::Values(values.into_iter().collect()) });
+    }
+
+    Ok(ImportedFile { data, rows: row_count, skipped_values })
+}
+
+fn read_csv(path: &Path, mapping: &ImportMapping, skipped_values: &mut usize) -> Result<Vec<Row>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(mapping.delimiter as u8)
+        .from_path(path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    let headers = reader.headers()?.clone();
+    let index = |name: &str| headers.iter().position(|h| h.trim() == name)
+        .with_context(|| format!("Column {} not found in {:?}", name, path));
+
+    let start_index = index(&mapping.start)?;
+    let end_index = mapping.end.as_deref().map(index).transpose()?;
+    let columns: Vec<(usize, &String)> = mapping.columns.iter()
+        .map(|(source, stored)| Ok((index(source)?, stored)))
+        .collect::<Result<_>>()?;
+
+    let mut rows = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+        let record = record.with_context(|| format!("Invalid CSV record {} of {:?}", line + 1, path))?;
+        let field = |i: usize| record.get(i).map(str::trim).unwrap_or_default();
+        let start = parse_text_time(field(start_index), mapping)?;
+        let end = end_index.map(|i| parse_text_time(field(i), mapping)).transpose()?;
+        let mut values = HashMap::new();
+        for (i, stored) in &columns {
+            let text = field(*i);
+            let text = if mapping.decimal_comma { text.replace(',', ".") } else { text.to_string() };
+            match text.parse::<f64>() {
+                Ok(value) if value.is_finite() => {
+                    values.insert((*stored).clone(), value);
+                }
+                _ => *skipped_values += 1,
+            }
+        }
+        rows.push((start, end, values));
+    }
+    Ok(rows)
+}
+
+fn read_parquet(path: &Path, mapping: &ImportMapping, skipped_values: &mut usize) -> Result<Vec<Row>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+
+    let mut rows = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        let starts = time_column(&batch, &mapping.start, mapping)?;
+        let ends = mapping.end.as_deref().map(|name| time_column(&batch, name, mapping)).transpose()?;
+        let mut columns = Vec::new();
+        for (source, stored) in &mapping.columns {
+            let column = batch.column(batch.schema().index_of(source).with_context(|| format!("Column {} not found in {:?}", source, path))?);
+            let values = cast(column, &DataType::Float64).with_context(|| format!("Column {} is not numeric", source))?;
+            columns.push((stored, values));
+        }
+
+        let mut ends = ends.map(|ends| ends.into_iter());
+        for start in starts {
+            let end = ends.as_mut().and_then(|ends| ends.next());
+            rows.push((start, end, HashMap::new()));
+        }
+        let first = rows.len() - batch.num_rows();
+        for (stored, values) in &columns {
+            let values = values.as_any().downcast_ref::<Float64Array>().context("Cast to Float64 failed")?;
+            for i in 0..batch.num_rows() {
+                if values.is_null(i) || !values.value(i).is_finite() {
+                    *skipped_values += 1;
+                } else {
+                    rows[first + i].2.insert((*stored).clone(), values.value(i));
+                }
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Timestamps of a Parquet column: timestamps with a timezone are UTC instants, timestamps
+/// without one and text are local to the mapping's timezone
+fn time_column(batch: &RecordBatch, name: &str, mapping: &ImportMapping) -> Result<Vec<RawTime>> {
+    let column = batch.column(batch.schema().index_of(name).with_context(|| format!("Column {} not found", name))?);
+    match column.data_type() {
+        DataType::Timestamp(_, tz) => {
+            let utc = tz.is_some();
+            let micros = cast(column, &DataType::Timestamp(TimeUnit::Microsecond, None))?;
+            let micros = micros.as_any().downcast_ref::<TimestampMicrosecondArray>().context("Cast to timestamp failed")?;
+            (0..micros.len()).map(|i| {
+                anyhow::ensure!(!micros.is_null(i), "Column {} has an empty timestamp", name);
+                let t = DateTime::from_timestamp_micros(micros.value(i)).context("Timestamp out of range")?;
+                Ok(if utc { RawTime::Utc(t) } else { RawTime::Local(t.naive_utc()) })
+            }).collect()
+        }
+        DataType::Utf8 | DataType::LargeUtf8 => {
+            let text = cast(column, &DataType::Utf8)?;
+            let text = text.as_any().downcast_ref::<StringArray>().context("Cast to text failed")?;
+            (0..text.len()).map(|i| parse_text_time(text.value(i), mapping)).collect()
+        }
+        other => bail!("Column {} of type {} is not a timestamp", name, other),
+    }
+}
+
+fn parse_text_time(s: &str, mapping: &ImportMapping) -> Result<RawTime> {
+    if let Some(format) = &mapping.timestamp_format {
+        if let Ok(t) = DateTime::parse_from_str(s, format) {
+            return Ok(RawTime::Utc(t.with_timezone(&Utc)));
+        }
+        return NaiveDateTime::parse_from_str(s, format)
+            .map(RawTime::Local)
+            .with_context(|| format!("Timestamp {} doesn't match {}", s, format));
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(RawTime::Utc(t.with_timezone(&Utc)));
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"].iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .map(RawTime::Local)
+        .with_context(|| format!("Invalid timestamp {}, set timestamp_format in the import mapping", s))
+}
+
+/// UTC instant of a timestamp. A local time in the repeated hour of October is the second
+/// occurrence once the previous row reached the first one.
+fn resolve(time: RawTime, tz: Tz, previous: Option<DateTime<Utc>>) -> Result<DateTime<Utc>> {
+    let local = match time {
+        RawTime::Utc(t) => return Ok(t),
+        RawTime::Local(local) => local,
+    };
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(t) => Ok(t.with_timezone(&Utc)),
+        LocalResult::Ambiguous(first, second) => {
+            let first = first.with_timezone(&Utc);
+            match previous {
+                Some(previous) if previous >= first => Ok(second.with_timezone(&Utc)),
+                _ => Ok(first),
+            }
+        }
+        LocalResult::None => bail!("{} doesn't exist in {}, it falls into the DST gap", local, tz),
+    }
+}
+
+/// First and last interval start of the records, for provenance
+pub fn time_range(data: &[ScraperData]) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    Some((data.iter().map(|d| d.delivery_from).min()?, data.iter().map(|d| d.delivery_to).max()?))
+}
+
+/// Records grouped by month of their start, so large dumps are stored in bounded chunks
+pub fn chunks(mut data: Vec<ScraperData>) -> Vec<Vec<ScraperData>> {
+    data.sort_by_key(|d| d.delivery_from);
+    let mut chunks: BTreeMap<String, Vec<ScraperData>> = BTreeMap::new();
+    for item in data {
+        chunks.entry(item.delivery_from.format("%Y-%m").to_string()).or_default().push(item);
+    }
+    chunks.into_values().collect()
+}
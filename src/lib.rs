@@ -2,3 +2,48 @@ pub mod config;
 pub mod storage;
 pub mod uploader;
 pub mod scraper_factory;
+pub mod metrics;
+pub mod validation;
+pub mod query;
+pub mod export;
+pub mod checkpoint;
+pub mod rate_limit;
+pub mod parquet_config;
+pub mod schema;
+pub mod values;
+pub mod history;
+pub mod lock;
+pub mod notify;
+pub mod stream;
+pub mod backend;
+pub mod postgres;
+pub mod circuit_breaker;
+pub mod completeness;
+pub mod provenance;
+pub mod raw_archive;
+pub mod http_client;
+pub mod secrets;
+pub mod admin;
+pub mod dashboard;
+pub mod logging;
+pub mod telemetry;
+pub mod aggregate;
+pub mod transform;
+pub mod conflict;
+pub mod disk;
+pub mod backpressure;
+pub mod derived;
+pub mod snapshot;
+pub mod dry_run;
+pub mod fixtures;
+pub mod partition;
+pub mod delta;
+pub mod metadata;
+pub mod calendar;
+pub mod import;
+pub mod anomaly;
+pub mod forecast;
+pub mod partition_manifest;
+pub mod remote_cleanup;
+pub mod service;
+pub mod backfill;
@@ -0,0 +1,270 @@
+use anyhow::{Context, Result};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// Where scraper leases are stored
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LockBackendKind {
+    /// Lock files in a directory shared by all instances
+    #[default]
+    File,
+    /// Lock objects in the S3 bucket, written with conditional puts
+    S3,
+}
+
+/// Per-scraper leases so several instances can run without scraping and writing the same data
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LockConfig {
+    #[serde(default)]
+    pub backend: LockBackendKind,
+    /// Directory for the file backend, or key prefix for the S3 backend (default "locks")
+    pub path: Option<String>,
+    /// A lease not renewed for this long is taken over by another instance
+    #[serde(default = "default_lease_ms")]
+    pub lease_ms: u64,
+    /// Identifies this instance in the lock, defaults to hostname and process id
+    pub instance_id: Option<String>,
+    /// Maximum number of scrapers this instance takes, so scrapers are spread across instances
+    pub max_scrapers: Option<usize>,
+}
+
+fn default_lease_ms() -> u64 {
+    60_000
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Lease {
+    owner: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// A stored lease and the version it was read at, used for compare-and-swap
+struct StoredLease {
+    lease: Lease,
+    version: String,
+}
+
+enum Backend {
+    File { dir: PathBuf },
+    S3 { client: Client, bucket: String, prefix: String },
+}
+
+pub struct LockManager {
+    backend: Backend,
+    owner: String,
+    lease: Duration,
+    max_scrapers: Option<usize>,
+    /// Scrapers currently held by this instance and when the lease runs out
+    held: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl LockManager {
+    pub fn file(config: &LockConfig) -> Self {
+        let dir = PathBuf::from(config.path.as_deref().unwrap_or("locks"));
+        Self::new(config, Backend::File { dir })
+    }
+
+    pub fn s3(config: &LockConfig, client: Client, bucket: String) -> Self {
+        let prefix = config.path.clone().unwrap_or_else(|| "locks".to_string());
+        Self::new(config, Backend::S3 { client, bucket, prefix })
+    }
+
+    fn new(config: &LockConfig, backend: Backend) -> Self {
+        let owner = config.instance_id.clone().unwrap_or_else(|| {
+            let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string());
+            format!("{}-{}", host, std::process::id())
+        });
+
+        Self {
+            backend,
+            owner,
+            lease: Duration::milliseconds(config.lease_ms as i64),
+            max_scrapers: config.max_scrapers,
+            held: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether this instance currently holds the lease of a scraper
+    pub fn holds(&self, scraper: &str) -> bool {
+        self.held.lock().unwrap()
+            .get(scraper)
+            .map(|expires_at| *expires_at > Utc::now())
+            .unwrap_or(false)
+    }
+
+    /// Wait until this instance holds the lease of a scraper, false if it didn't get it within `timeout`
+    pub async fn wait_for(&self, scraper: &str, timeout: std::time::Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while !self.holds(scraper) {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        true
+    }
+
+    /// Renew held leases and try to take free ones, every third of the lease duration
+    pub async fn run(&self, scrapers: Vec<String>) {
+        let interval = (self.lease / 3).to_std().unwrap_or(std::time::Duration::from_secs(10));
+        loop {
+            for scraper in &scrapers {
+                let held = self.holds(scraper);
+                let held_count = self.held.lock().unwrap().values().filter(|e| **e > Utc::now()).count();
+                if !held && self.max_scrapers.map(|max| held_count >= max).unwrap_or(false) {
+                    continue;
+                }
+
+                match self.try_acquire(scraper).await {
+                    Ok(Some(expires_at)) => {
+                        if !held {
+                            info!("Acquired lease for {} as {}", scraper, self.owner);
+                        }
+                        self.held.lock().unwrap().insert(scraper.clone(), expires_at);
+                    }
+                    Ok(None) => {
+                        if held {
+                            warn!("Lost lease for {}", scraper);
+                        }
+                        self.held.lock().unwrap().remove(scraper);
+                    }
+                    // Keep the local expiry, the lease stays valid until then
+                    Err(e) => warn!("Failed to renew lease for {}: {:?}", scraper, e),
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Take or renew a lease. Returns when it expires, or None if another instance holds it.
+    async fn try_acquire(&self, scraper: &str) -> Result<Option<DateTime<Utc>>> {
+        let now = Utc::now();
+        let lease = Lease {
+            owner: self.owner.clone(),
+            expires_at: now + self.lease,
+        };
+
+        let written = match self.read(scraper).await? {
+            None => self.write(scraper, &lease, None).await?,
+            Some(stored) if stored.lease.owner == self.owner || stored.lease.expires_at < now => {
+                self.write(scraper, &lease, Some(&stored.version)).await?
+            }
+            Some(_) => false,
+        };
+
+        Ok(written.then_some(lease.expires_at))
+    }
+
+    async fn read(&self, scraper: &str) -> Result<Option<StoredLease>> {
+        match &self.backend {
+            Backend::File { dir } => {
+                let path = dir.join(format!("{}.lock", scraper));
+                let content = match std::fs::read_to_string(&path) {
+                    Ok(content) => content,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                };
+                let lease: Lease = serde_json::from_str(&content)
+                    .with_context(|| format!("Invalid lock file {:?}", path))?;
+                Ok(Some(StoredLease { lease, version: content }))
+            }
+            Backend::S3 { client, bucket, prefix } => {
+                let key = format!("{}/{}.lock", prefix, scraper);
+                let output = match client.get_object().bucket(bucket).key(&key).send().await {
+                    Ok(output) => output,
+                    Err(e) => {
+                        let service_error = e.into_service_error();
+                        if service_error.is_no_such_key() {
+                            return Ok(None);
+                        }
+                        return Err(service_error.into());
+                    }
+                };
+                let version = output.e_tag().context("Lock object has no ETag")?.to_string();
+                let bytes = output.body.collect().await?.into_bytes();
+                let lease: Lease = serde_json::from_slice(&bytes)
+                    .with_context(|| format!("Invalid lock object {}", key))?;
+                Ok(Some(StoredLease { lease, version }))
+            }
+        }
+    }
+
+    /// Write a lease if the stored one is still at `version` (or doesn't exist when None).
+    /// Returns false if another instance changed it in between.
+    async fn write(&self, scraper: &str, lease: &Lease, version: Option<&str>) -> Result<bool> {
+        let content = serde_json::to_string(lease)?;
+
+        match &self.backend {
+            Backend::File { dir } => {
+                std::fs::create_dir_all(dir)?;
+                let path = dir.join(format!("{}.lock", scraper));
+                write_lock_file(&path, &content, version)
+            }
+            Backend::S3 { client, bucket, prefix } => {
+                let key = format!("{}/{}.lock", prefix, scraper);
+                let mut request = client.put_object()
+                    .bucket(bucket)
+                    .key(&key)
+                    .body(ByteStream::from(content.into_bytes()));
+                request = match version {
+                    Some(etag) => request.if_match(etag),
+                    None => request.if_none_match("*"),
+                };
+
+                match request.send().await {
+                    Ok(_) => Ok(true),
+                    // 412 Precondition Failed or 409 Conflict: someone else wrote the lock first
+                    Err(e) if matches!(e.raw_response().map(|r| r.status().as_u16()), Some(412) | Some(409)) => Ok(false),
+                    Err(e) => Err(e.into_service_error().into()),
+                }
+            }
+        }
+    }
+}
+
+/// Compare-and-swap on a lock file. New locks are created exclusively. Existing ones are replaced
+/// by renaming over them, after checking the content is unchanged, which leaves a small window
+/// between the check and the rename; use the S3 backend where that matters.
+fn write_lock_file(path: &Path, content: &str, version: Option<&str>) -> Result<bool> {
+    use std::io::Write;
+
+    match version {
+        None => match std::fs::OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                Ok(true)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e.into()),
+        },
+        Some(expected) => {
+            let tmp_path = path.with_extension(format!("lock.{}.tmp", std::process::id()));
+            std::fs::write(&tmp_path, content)?;
+            if std::fs::read_to_string(path).ok().as_deref() != Some(expected) {
+                std::fs::remove_file(&tmp_path)?;
+                return Ok(false);
+            }
+            std::fs::rename(&tmp_path, path)?;
+            Ok(true)
+        }
+    }
+}
+
+/// Build the lock manager for the configured backend
+pub fn from_config(config: &LockConfig, s3: Option<(Client, String)>) -> Result<Arc<LockManager>> {
+    let manager = match config.backend {
+        LockBackendKind::File => LockManager::file(config),
+        LockBackendKind::S3 => {
+            let (client, bucket) = s3.context("The S3 lock backend requires an S3 bucket")?;
+            LockManager::s3(config, client, bucket)
+        }
+    };
+    Ok(Arc::new(manager))
+}
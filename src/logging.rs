@@ -0,0 +1,60 @@
+use anyhow::Result;
+use std::env;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry};
+
+use crate::telemetry::{self, TelemetryConfig, TracerGuard};
+
+fn filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+fn json_layer<W>(writer: W) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_writer(writer)
+        .with_filter(filter())
+        .boxed()
+}
+
+/// Keep alive until the process exits so buffered log lines and spans are written
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+    _tracer: Option<TracerGuard>,
+}
+
+/// Log to stdout as text, or as one JSON object per line with `LOG_FORMAT=json`.
+/// With `file_name` the logs are also written as JSON to a daily file in `logs/`,
+/// with `telemetry` spans are also exported over OTLP.
+pub fn init(file_name: Option<&str>, telemetry: Option<&TelemetryConfig>) -> Result<LogGuard> {
+    let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = Vec::new();
+
+    if env::var("LOG_FORMAT").map(|f| f.eq_ignore_ascii_case("json")).unwrap_or(false) {
+        layers.push(json_layer(std::io::stdout));
+    } else {
+        layers.push(tracing_subscriber::fmt::layer().with_filter(filter()).boxed());
+    }
+
+    let file = file_name.map(|name| {
+        let (writer, guard) = tracing_appender::non_blocking(tracing_appender::rolling::daily("logs", name));
+        layers.push(json_layer(writer));
+        guard
+    });
+
+    let tracer = match telemetry {
+        Some(config) => {
+            let (layer, guard) = telemetry::layer(config)?;
+            layers.push(layer.with_filter(filter()).boxed());
+            Some(guard)
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry().with(layers).init();
+    Ok(LogGuard { _file: file, _tracer: tracer })
+}
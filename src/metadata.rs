@@ -0,0 +1,133 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::config::{RetentionMode, ScraperConfig};
+use crate::partition::{Granularity, DATA_FILE};
+use crate::query::Query;
+use crate::scraper_factory::Source;
+
+/// Days searched back for the last stored interval when a scraper has no retention period
+const DEFAULT_SEARCH_DAYS: u64 = 30;
+
+/// Resolved parameters of a configured scraper, as printed by the `scrapers` tool
+#[derive(Debug, Clone, Serialize)]
+pub struct ScraperMetadata {
+    pub name: String,
+    /// None if neither `type` nor `url` select a known implementation
+    pub source: Option<Source>,
+    pub url: Option<String>,
+    pub url_template: Option<String>,
+    /// Length of one interval, from `validation.expected_interval_minutes` or the ISO 8601 period
+    /// (e.g. `PT15M`) in the URL template
+    pub resolution_minutes: Option<i64>,
+    /// `value_column` and `value_columns` of the scraper
+    pub value_columns: Vec<String>,
+    pub schedule: Schedule,
+    pub data_folder: String,
+    /// Partition file with the placeholders of its granularity, relative to the working directory
+    pub partition_path: String,
+    pub partition_timezone: String,
+    pub partition_granularity: Granularity,
+    pub retention: Retention,
+    /// End of the latest stored interval, set by `with_last_data_point`
+    pub last_data_point: Option<DateTime<Utc>>,
+}
+
+/// When and which windows the service scrapes
+#[derive(Debug, Clone, Serialize)]
+pub struct Schedule {
+    pub workers: usize,
+    /// Delay between two scrapes of the service
+    pub delay_ms: u64,
+    pub lookback_hours: i64,
+    pub lookahead_hours: i64,
+    pub revision_window_days: Option<i64>,
+    pub revision_interval_hours: Option<u64>,
+    pub catch_up_days: i64,
+    pub requests_per_minute: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Retention {
+    /// None if local data is kept forever
+    pub days: Option<u64>,
+    pub mode: RetentionMode,
+}
+
+impl ScraperMetadata {
+    /// Resolve a scraper config, `retention_days` being the global default. Secrets in `values`
+    /// are never read, only the known keys are.
+    pub fn new(config: &ScraperConfig, base_path: &str, retention_days: Option<u64>) -> Result<Self> {
+        let values = &config.scraper_config.values;
+        let string = |key: &str| values.get(key).and_then(|v| v.as_str()).map(String::from);
+
+        let url_template = string("url_template");
+        let resolution_minutes = config.validation.as_ref()
+            .and_then(|v| v.expected_interval_minutes)
+            .or_else(|| url_template.as_deref().and_then(period_minutes));
+
+        let mut value_columns: Vec<String> = string("value_column").into_iter().collect();
+        if let Some(columns) = values.get("value_columns").and_then(|v| v.as_array()) {
+            value_columns.extend(columns.iter().filter_map(|c| c.as_str()).map(String::from));
+        }
+
+        let placeholders = match config.partition_granularity {
+            Granularity::Day => "year=YYYY/month=MM/day=DD",
+            Granularity::Month => "year=YYYY/month=MM",
+            Granularity::Year => "year=YYYY",
+        };
+
+        Ok(Self {
+            name: config.scraper_config.name.clone(),
+            source: Source::of(&config.scraper_config).ok(),
+            url: string("url"),
+            url_template,
+            resolution_minutes,
+            value_columns,
+            schedule: Schedule {
+                workers: config.scraper_config.workers as usize,
+                delay_ms: config.scraper_config.task_generator_delay_ms as u64,
+                lookback_hours: config.lookback().num_hours(),
+                lookahead_hours: config.lookahead().num_hours(),
+                revision_window_days: config.revision_window_days,
+                revision_interval_hours: config.revision_window_days.map(|_| config.revision_interval_hours.unwrap_or(24)),
+                catch_up_days: config.catch_up_days.unwrap_or(7),
+                requests_per_minute: config.requests_per_minute,
+            },
+            data_folder: config.data_folder().to_string(),
+            partition_path: format!("{}/{}/{}/{}", base_path, config.data_folder(), placeholders, DATA_FILE),
+            partition_timezone: config.partition_timezone()?.name().to_string(),
+            partition_granularity: config.partition_granularity,
+            retention: Retention {
+                days: config.retention_days.or(retention_days),
+                mode: config.retention_mode,
+            },
+            last_data_point: None,
+        })
+    }
+
+    /// Look up the latest stored interval, searching the retention period or the last 30 days
+    pub fn with_last_data_point(mut self, query: &Query, config: &ScraperConfig) -> Result<Self> {
+        let tz = config.partition_timezone()?;
+        let days = self.retention.days.unwrap_or(DEFAULT_SEARCH_DAYS) as i64;
+        let end = (Utc::now() + config.lookahead()).with_timezone(&tz).date_naive() + Duration::days(1);
+        let start = (Utc::now() - Duration::days(days)).with_timezone(&tz).date_naive();
+        self.last_data_point = query.last_interval_end(&self.data_folder, start, end, None)?;
+        Ok(self)
+    }
+}
+
+/// Minutes of the first ISO 8601 period like `PT15M` or `PT1H` in a URL template
+fn period_minutes(template: &str) -> Option<i64> {
+    template.match_indices("PT").find_map(|(i, _)| {
+        let rest = &template[i + 2..];
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let value: i64 = digits.parse().ok()?;
+        match rest[digits.len()..].chars().next()? {
+            'M' => Some(value),
+            'H' => Some(value * 60),
+            _ => None,
+        }
+    })
+}
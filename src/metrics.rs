@@ -0,0 +1,41 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+
+/// Counters tracked per scraper
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ScraperMetrics {
+    pub validation_violations: u64,
+    pub records_rejected: u64,
+    /// Failed scrapes per error class
+    pub scrape_errors: BTreeMap<String, u64>,
+    pub circuit_opened: u64,
+    /// Intervals returned more than once with different values
+    pub interval_conflicts: u64,
+    /// Records with a value outside the usual range of its time of day
+    pub anomalies: u64,
+}
+
+/// Process-wide metrics registry, keyed by scraper name
+#[derive(Debug, Default)]
+pub struct Metrics {
+    scrapers: Mutex<HashMap<String, ScraperMetrics>>,
+}
+
+impl Metrics {
+    /// Apply an update to the counters of a scraper, creating them if needed
+    pub fn update<F: FnOnce(&mut ScraperMetrics)>(&self, scraper: &str, f: F) {
+        let mut scrapers = self.scrapers.lock().unwrap();
+        f(scrapers.entry(scraper.to_string()).or_default());
+    }
+
+    /// Get a copy of the counters of all scrapers
+    pub fn snapshot(&self) -> HashMap<String, ScraperMetrics> {
+        self.scrapers.lock().unwrap().clone()
+    }
+}
+
+pub fn global() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::default)
+}
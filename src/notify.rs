@@ -0,0 +1,163 @@
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::ScraperConfig;
+
+/// Where to announce newly uploaded partitions
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NotifyConfig {
+    /// Every event is POSTed as JSON to this URL
+    pub webhook_url: Option<String>,
+    #[serde(default = "default_webhook_timeout_ms")]
+    pub webhook_timeout_ms: u64,
+    /// Requires building with the `kafka` feature
+    pub kafka: Option<KafkaConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct KafkaConfig {
+    /// Comma separated `host:port` list
+    pub brokers: String,
+    pub topic: String,
+}
+
+fn default_webhook_timeout_ms() -> u64 {
+    10_000
+}
+
+/// A partition that was uploaded to S3
+#[derive(Debug, Serialize, Clone)]
+pub struct UploadEvent {
+    pub scraper: String,
+    pub partition_date: Option<NaiveDate>,
+    pub row_count: Option<i64>,
+    pub bucket: String,
+    pub key: String,
+}
+
+pub struct Notifier {
+    webhook: Option<(reqwest::Client, String)>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<(rdkafka::producer::FutureProducer, String)>,
+    /// Data folder to scraper name
+    scrapers: HashMap<String, String>,
+    /// Data directory the uploaded files are in
+    base_path: String,
+}
+
+impl Notifier {
+    pub fn new(config: &NotifyConfig, scrapers: &[ScraperConfig], base_path: &str) -> Result<Self> {
+        let webhook = match &config.webhook_url {
+            Some(url) => {
+                let client = reqwest::Client::builder()
+                    .timeout(Duration::from_millis(config.webhook_timeout_ms))
+                    .build()?;
+                Some((client, url.clone()))
+            }
+            None => None,
+        };
+
+        #[cfg(feature = "kafka")]
+        let kafka = match &config.kafka {
+            Some(kafka) => {
+                use anyhow::Context;
+
+                let producer = rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", &kafka.brokers)
+                    .create()
+                    .context("Failed to create Kafka producer")?;
+                Some((producer, kafka.topic.clone()))
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "kafka"))]
+        if config.kafka.is_some() {
+            bail!("Kafka notifications require building with the `kafka` feature");
+        }
+
+        Ok(Self {
+            webhook,
+            #[cfg(feature = "kafka")]
+            kafka,
+            scrapers: scrapers.iter()
+                .map(|s| (s.data_folder().to_string(), s.scraper_config.name.clone()))
+                .collect(),
+            base_path: base_path.to_string(),
+        })
+    }
+
+    /// Build the event for an uploaded local file
+    pub fn event_for(&self, file_path: &str, bucket: &str, key: &str, partition_date: Option<NaiveDate>) -> UploadEvent {
+        let folder = data_folder(file_path, &self.base_path);
+        UploadEvent {
+            scraper: self.scrapers.get(&folder).cloned().unwrap_or(folder),
+            partition_date,
+            row_count: row_count(file_path).ok(),
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    /// Send the event to every configured target. Failures are logged, the upload itself succeeded.
+    pub async fn notify(&self, event: &UploadEvent) {
+        if let Err(e) = self.send_webhook(event).await {
+            warn!("Webhook notification for {} failed: {:?}", event.key, e);
+        }
+        #[cfg(feature = "kafka")]
+        if let Err(e) = self.send_kafka(event).await {
+            warn!("Kafka notification for {} failed: {:?}", event.key, e);
+        }
+    }
+
+    async fn send_webhook(&self, event: &UploadEvent) -> Result<()> {
+        let Some((client, url)) = &self.webhook else {
+            return Ok(());
+        };
+        let response = client.post(url).json(event).send().await?;
+        if !response.status().is_success() {
+            bail!("Webhook returned {}", response.status());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "kafka")]
+    async fn send_kafka(&self, event: &UploadEvent) -> Result<()> {
+        use anyhow::Context;
+        use rdkafka::producer::FutureRecord;
+
+        let Some((producer, topic)) = &self.kafka else {
+            return Ok(());
+        };
+        let payload = serde_json::to_string(event)?;
+        // Keyed by scraper so events of one scraper stay ordered
+        let record = FutureRecord::to(topic).key(&event.scraper).payload(&payload);
+        producer.send(record, Duration::from_secs(10)).await
+            .map_err(|(e, _)| e)
+            .context("Failed to deliver Kafka message")?;
+        Ok(())
+    }
+}
+
+/// Data folder of a `<base_path>/<folder>/year=.../data.parquet` path
+fn data_folder(file_path: &str, base_path: &str) -> String {
+    let path = Path::new(file_path);
+    let path = path.strip_prefix(base_path).unwrap_or(path);
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .take_while(|c| !c.starts_with("year="))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Row count from the Parquet footer, without reading the data
+fn row_count(file_path: &str) -> Result<i64> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let reader = SerializedFileReader::new(std::fs::File::open(file_path)?)?;
+    Ok(reader.metadata().file_metadata().num_rows())
+}
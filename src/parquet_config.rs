@@ -0,0 +1,89 @@
+use anyhow::Result;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::{EnabledStatistics, WriterProperties};
+use parquet::format::SortingColumn;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionCodec {
+    /// As partitions were written before the codec was configurable
+    #[default]
+    Uncompressed,
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StatisticsLevel {
+    None,
+    Chunk,
+    #[default]
+    Page,
+}
+
+/// Writer properties for the Parquet partitions written by `Storage`
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ParquetConfig {
+    #[serde(default)]
+    pub compression: CompressionCodec,
+    /// Level for gzip (0-10) and zstd (1-22), the codec default when not set
+    pub compression_level: Option<u32>,
+    pub dictionary: Option<bool>,
+    pub max_row_group_size: Option<usize>,
+    #[serde(default)]
+    pub statistics: StatisticsLevel,
+    /// Sort rows by interval and record the sort order in the file metadata,
+    /// so readers can skip row groups when filtering on time
+    #[serde(default)]
+    pub sorted: bool,
+}
+
+impl ParquetConfig {
+    /// Build the writer properties. `sort_columns` are the column indices the rows are sorted by,
+    /// they are only recorded when `sorted` is enabled.
+    pub fn writer_properties(&self, sort_columns: &[usize]) -> Result<WriterProperties> {
+        let compression = match self.compression {
+            CompressionCodec::Uncompressed => Compression::UNCOMPRESSED,
+            CompressionCodec::Snappy => Compression::SNAPPY,
+            CompressionCodec::Gzip => Compression::GZIP(match self.compression_level {
+                Some(level) => GzipLevel::try_new(level)?,
+                None => GzipLevel::default(),
+            }),
+            CompressionCodec::Lz4 => Compression::LZ4_RAW,
+            CompressionCodec::Zstd => Compression::ZSTD(match self.compression_level {
+                Some(level) => ZstdLevel::try_new(level as i32)?,
+                None => ZstdLevel::default(),
+            }),
+        };
+
+        let statistics = match self.statistics {
+            StatisticsLevel::None => EnabledStatistics::None,
+            StatisticsLevel::Chunk => EnabledStatistics::Chunk,
+            StatisticsLevel::Page => EnabledStatistics::Page,
+        };
+
+        let mut builder = WriterProperties::builder()
+            .set_compression(compression)
+            .set_statistics_enabled(statistics);
+
+        if let Some(dictionary) = self.dictionary {
+            builder = builder.set_dictionary_enabled(dictionary);
+        }
+        if let Some(size) = self.max_row_group_size {
+            builder = builder.set_max_row_group_size(size);
+        }
+        if self.sorted && !sort_columns.is_empty() {
+            builder = builder.set_sorting_columns(Some(
+                sort_columns.iter()
+                    .map(|&idx| SortingColumn::new(idx as i32, false, false))
+                    .collect(),
+            ));
+        }
+
+        Ok(builder.build())
+    }
+}
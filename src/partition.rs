@@ -0,0 +1,126 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::aggregate::AGG_DIR;
+use crate::conflict::CONFLICTS_DIR;
+
+/// File name of a partition inside its directory
+pub const DATA_FILE: &str = "data.parquet";
+
+/// Time span covered by one partition file of a data folder
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    /// `year=YYYY/month=MM/day=DD`
+    #[default]
+    Day,
+    /// `year=YYYY/month=MM`, for series with a few values per day
+    Month,
+    /// `year=YYYY`, for daily or less frequent series
+    Year,
+}
+
+impl Granularity {
+    /// Directory of the partition holding a local day, relative to the data folder
+    pub fn dir(&self, date: NaiveDate) -> String {
+        match self {
+            Granularity::Day => format!("year={}/month={:02}/day={:02}", date.year(), date.month(), date.day()),
+            Granularity::Month => format!("year={}/month={:02}", date.year(), date.month()),
+            Granularity::Year => format!("year={}", date.year()),
+        }
+    }
+
+    /// Path of the partition file holding a local day
+    pub fn path(&self, folder_path: &str, date: NaiveDate) -> String {
+        format!("{}/{}/{}", folder_path, self.dir(date), DATA_FILE)
+    }
+
+    /// First local day of the partition holding `date`
+    pub fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => date,
+            Granularity::Month => date.with_day(1).unwrap_or(date),
+            Granularity::Year => date.with_ordinal(1).unwrap_or(date),
+        }
+    }
+
+    /// Last local day of the partition holding `date`
+    pub fn end(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => date,
+            Granularity::Month => {
+                let next = if date.month() == 12 {
+                    NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd_opt(date.year(), date.month() + 1, 1)
+                };
+                next.and_then(|d| d.pred_opt()).unwrap_or(date)
+            }
+            Granularity::Year => NaiveDate::from_ymd_opt(date.year(), 12, 31).unwrap_or(date),
+        }
+    }
+
+    /// First day of every partition overlapping `start..=end`
+    pub fn partitions(&self, start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+        let mut partitions = Vec::new();
+        let mut date = self.start(start);
+        while date <= end {
+            partitions.push(date);
+            match self.end(date).succ_opt() {
+                Some(next) => date = next,
+                None => break,
+            }
+        }
+        partitions
+    }
+}
+
+/// Granularity and first and last local day of the partition a file or directory is in,
+/// from its `year=`, `month=` and `day=` components
+pub fn date_range(path: &Path) -> Option<(Granularity, NaiveDate, NaiveDate)> {
+    let (mut year, mut month, mut day) = (None, None, None);
+    for component in path.components() {
+        let part = component.as_os_str().to_str()?;
+        if let Some(v) = part.strip_prefix("year=") {
+            year = v.parse().ok();
+        } else if let Some(v) = part.strip_prefix("month=") {
+            month = v.parse().ok();
+        } else if let Some(v) = part.strip_prefix("day=") {
+            day = v.parse().ok();
+        }
+    }
+    let granularity = match (month, day) {
+        (Some(_), Some(_)) => Granularity::Day,
+        (Some(_), None) => Granularity::Month,
+        _ => Granularity::Year,
+    };
+    let date = NaiveDate::from_ymd_opt(year?, month.unwrap_or(1), day.unwrap_or(1))?;
+    Some((granularity, granularity.start(date), granularity.end(date)))
+}
+
+/// Data folder path a partition directory or file is in, the part of its path before `year=`
+pub fn folder_path(path: &Path) -> Option<&str> {
+    let path = path.to_str()?;
+    path.find("/year=").map(|i| &path[..i])
+}
+
+/// Timezone by IANA name, e.g. of a folder's `partition_timezone`
+pub fn parse_timezone(s: &str) -> Result<Tz> {
+    s.parse::<Tz>().map_err(|e| anyhow::anyhow!("Invalid timezone {}: {}", s, e))
+}
+
+/// Folder whose partition timezone a folder follows: rejected records, conflicts and aggregates are
+/// partitioned by the local day of the folder they were derived from
+pub fn source_folder(folder: &str) -> &str {
+    let nested = |dir: &str| folder.strip_prefix(dir).and_then(|f| f.strip_prefix('/'));
+    if let Some(source) = nested("rejected").or_else(|| nested(CONFLICTS_DIR)) {
+        return source;
+    }
+    match nested(AGG_DIR).and_then(|f| f.split_once('/')) {
+        Some((_, source)) => source,
+        None => folder,
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::path::Path;
+
+use arrow::array::{Array, TimestampMicrosecondArray};
+use arrow::record_batch::RecordBatch;
+
+use crate::schema;
+
+/// Summary next to every `data.parquet`, rewritten with each write of the partition
+pub const MANIFEST_FILE: &str = "_manifest.json";
+
+/// What a partition holds, so it can be checked without downloading the Parquet file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartitionManifest {
+    /// Rows of every stored version
+    pub rows: usize,
+    /// Distinct (start, end) intervals
+    pub intervals: usize,
+    pub min_start: Option<DateTime<Utc>>,
+    pub max_start: Option<DateTime<Utc>>,
+    /// Latest scraped_at, None if every row was backfilled
+    pub last_scraped_at: Option<DateTime<Utc>>,
+    pub schema_version: u32,
+    /// SHA-256 of the Parquet file
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub written_at: DateTime<Utc>,
+}
+
+/// Manifest path of the partition at `data_file`
+pub fn manifest_path(data_file: &str) -> String {
+    Path::new(data_file).with_file_name(MANIFEST_FILE).to_string_lossy().to_string()
+}
+
+/// SHA-256 of a file as hex
+pub fn checksum(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Summarize the partition at `data_file`
+pub fn build(data_file: &str) -> Result<PartitionManifest> {
+    let path = Path::new(data_file);
+    let partition = schema::read_partition(path).with_context(|| format!("Failed to read {:?}", path))?;
+
+    let mut rows = 0;
+    let mut intervals = BTreeSet::new();
+    let mut last_scraped_at = None;
+    for batch in &partition.batches {
+        rows += batch.num_rows();
+        let (start, end, scraped_at) = (timestamp_column(batch, "start")?, timestamp_column(batch, "end")?, timestamp_column(batch, "scraped_at")?);
+        for i in 0..batch.num_rows() {
+            intervals.insert((start.value(i), end.value(i)));
+            // Backfilled rows store 0 or null
+            if !scraped_at.is_null(i) && scraped_at.value(i) != 0 {
+                last_scraped_at = last_scraped_at.max(Some(scraped_at.value(i)));
+            }
+        }
+    }
+
+    let to_datetime = |micros: i64| DateTime::from_timestamp_micros(micros).context("Timestamp out of range");
+    Ok(PartitionManifest {
+        rows,
+        intervals: intervals.len(),
+        min_start: intervals.first().map(|(start, _)| to_datetime(*start)).transpose()?,
+        max_start: intervals.last().map(|(start, _)| to_datetime(*start)).transpose()?,
+        last_scraped_at: last_scraped_at.map(to_datetime).transpose()?,
+        schema_version: partition.stored_version,
+        sha256: checksum(path)?,
+        size_bytes: std::fs::metadata(path)?.len(),
+        written_at: Utc::now(),
+    })
+}
+
+fn timestamp_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a TimestampMicrosecondArray> {
+    batch.column(batch.schema().index_of(name)?)
+        .as_any()
+        .downcast_ref::<TimestampMicrosecondArray>()
+        .with_context(|| format!("Column {} is not a timestamp", name))
+}
+
+/// Rewrite the manifest of the partition at `data_file`, returns the manifest path
+pub fn write(data_file: &str) -> Result<String> {
+    let manifest = build(data_file)?;
+    let manifest_path = manifest_path(data_file);
+    let tmp_path = format!("{}.tmp", manifest_path);
+    std::fs::write(&tmp_path, serde_json::to_vec_pretty(&manifest)?)?;
+    std::fs::rename(&tmp_path, &manifest_path)?;
+    Ok(manifest_path)
+}
+
+/// Manifest of the partition at `data_file`, None if it has none
+pub fn read(data_file: &str) -> Result<Option<PartitionManifest>> {
+    let manifest_path = manifest_path(data_file);
+    if !Path::new(&manifest_path).exists() {
+        return Ok(None);
+    }
+    let manifest = serde_json::from_str(&std::fs::read_to_string(&manifest_path)?)
+        .with_context(|| format!("Invalid manifest {}", manifest_path))?;
+    Ok(Some(manifest))
+}
@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use tokio::sync::Mutex;
+use tokio_postgres::{Client, NoTls};
+use tracing::{error, info};
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use crate::backend::StorageBackend;
+
+/// Upsert scraped values into a Postgres table, for scrapers with `postgres: true`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PostgresConfig {
+    /// Connection string, overridden by the POSTGRES_URL env var
+    pub url: Option<String>,
+    #[serde(default = "default_table")]
+    pub table: String,
+    /// Turn the table into a TimescaleDB hypertable on `start`
+    #[serde(default)]
+    pub timescale: bool,
+}
+
+fn default_table() -> String {
+    "scraper_values".to_string()
+}
+
+impl PostgresConfig {
+    pub fn get_url(&self) -> Option<String> {
+        env::var("POSTGRES_URL").ok().or_else(|| self.url.clone())
+    }
+}
+
+pub struct PostgresSink {
+    url: String,
+    table: String,
+    timescale: bool,
+    /// Reconnected lazily after the connection drops
+    client: Mutex<Option<Client>>,
+}
+
+impl PostgresSink {
+    pub async fn connect(config: &PostgresConfig) -> Result<Self> {
+        let url = config.get_url().context("No Postgres URL configured, set `postgres.url` or POSTGRES_URL")?;
+        let sink = Self {
+            url,
+            table: config.table.clone(),
+            timescale: config.timescale,
+            client: Mutex::new(None),
+        };
+        sink.create_table().await?;
+        Ok(sink)
+    }
+
+    async fn client(&self) -> Result<tokio::sync::MutexGuard<'_, Option<Client>>> {
+        let mut client = self.client.lock().await;
+        if client.as_ref().map(|c| c.is_closed()).unwrap_or(true) {
+            let (new_client, connection) = tokio_postgres::connect(&self.url, NoTls).await
+                .context("Failed to connect to Postgres")?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    error!("Postgres connection error: {:?}", e);
+                }
+            });
+            *client = Some(new_client);
+        }
+        Ok(client)
+    }
+
+    async fn create_table(&self) -> Result<()> {
+        let guard = self.client().await?;
+        let client = guard.as_ref().context("Not connected")?;
+        client.batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                scraper TEXT NOT NULL,
+                folder TEXT NOT NULL,
+                series TEXT NOT NULL,
+                start TIMESTAMPTZ NOT NULL,
+                \"end\" TIMESTAMPTZ NOT NULL,
+                value DOUBLE PRECISION,
+                scraped_at TIMESTAMPTZ,
+                PRIMARY KEY (scraper, folder, series, start, \"end\")
+            )",
+            table = self.table
+        )).await?;
+
+        // Tables created before the folder column was added are keyed without it
+        let keyed_by_folder: i64 = client.query_one(
+            "SELECT count(*) FROM information_schema.table_constraints c
+             JOIN information_schema.key_column_usage k
+               ON k.constraint_name = c.constraint_name AND k.table_schema = c.table_schema
+             WHERE c.table_name = $1 AND c.constraint_type = 'PRIMARY KEY' AND k.column_name = 'folder'",
+            &[&table_name(&self.table)],
+        ).await?.get(0);
+        if keyed_by_folder == 0 {
+            info!("Adding the folder column to the key of {}", self.table);
+            client.batch_execute(&format!(
+                "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS folder TEXT;
+                 UPDATE {table} SET folder = scraper WHERE folder IS NULL;
+                 ALTER TABLE {table} ALTER COLUMN folder SET NOT NULL,
+                     DROP CONSTRAINT {name}_pkey,
+                     ADD PRIMARY KEY (scraper, folder, series, start, \"end\")",
+                table = self.table,
+                name = table_name(&self.table)
+            )).await.context("Failed to add the folder column to the key")?;
+        }
+        if self.timescale {
+            client.execute(
+                "SELECT create_hypertable($1, 'start', if_not_exists => TRUE)",
+                &[&self.table],
+            ).await.context("Failed to create hypertable, is the timescaledb extension installed?")?;
+        }
+        info!("Writing to Postgres table {}", self.table);
+        Ok(())
+    }
+
+    /// Upsert one row per interval and value column of a data folder. Unchanged values keep their scraped_at.
+    async fn upsert(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData], scraped_at: Option<DateTime<Utc>>) -> Result<usize> {
+        let folder = subfolder.unwrap_or(name);
+        let mut series = Vec::new();
+        let mut starts = Vec::new();
+        let mut ends = Vec::new();
+        let mut values = Vec::new();
+        // Postgres rejects a batch that updates the same row twice, the last value of a key wins
+        let mut rows: HashMap<(&str, DateTime<Utc>, DateTime<Utc>), usize> = HashMap::new();
+        for item in data {
+            // Bids have no single value per interval and are only stored in Parquet
+            if let ScraperPayload::Values(map) = &item.payload {
+                for (column, value) in map {
+                    match rows.get(&(column.as_str(), item.delivery_from, item.delivery_to)) {
+                        Some(&i) => values[i] = *value,
+                        None => {
+                            rows.insert((column.as_str(), item.delivery_from, item.delivery_to), series.len());
+                            series.push(column.clone());
+                            starts.push(item.delivery_from);
+                            ends.push(item.delivery_to);
+                            values.push(*value);
+                        }
+                    }
+                }
+            }
+        }
+        if series.is_empty() {
+            return Ok(0);
+        }
+
+        let statement = format!(
+            "INSERT INTO {table} AS t (scraper, folder, series, start, \"end\", value, scraped_at)
+             SELECT $1, $2, s, st, en, v, $7
+             FROM UNNEST($3::text[], $4::timestamptz[], $5::timestamptz[], $6::float8[]) AS u(s, st, en, v)
+             ON CONFLICT (scraper, folder, series, start, \"end\") DO UPDATE
+             SET value = EXCLUDED.value, scraped_at = EXCLUDED.scraped_at
+             WHERE t.value IS DISTINCT FROM EXCLUDED.value",
+            table = self.table
+        );
+
+        let mut guard = self.client().await?;
+        let client = guard.as_ref().context("Not connected")?;
+        match client.execute(&statement, &[&name, &folder, &series, &starts, &ends, &values, &scraped_at]).await {
+            Ok(rows) => Ok(rows as usize),
+            Err(e) => {
+                if client.is_closed() {
+                    *guard = None;
+                }
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for PostgresSink {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn save_if_new(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData]) -> Result<usize> {
+        self.upsert(name, subfolder, data, Some(Utc::now())).await
+    }
+
+    async fn save_backfill(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData]) -> Result<usize> {
+        self.upsert(name, subfolder, data, None).await
+    }
+}
+
+/// Table name without its schema, as in the name of its primary key constraint
+fn table_name(table: &str) -> &str {
+    table.rsplit('.').next().unwrap_or(table)
+}
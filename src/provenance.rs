@@ -0,0 +1,113 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use parquet::format::KeyValue;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+/// Parquet key-value metadata key holding the sources of the last write
+pub const PROVENANCE_KEY: &str = "scraping_service.provenance";
+
+/// Sidecar file next to `data.parquet` with one line per write
+pub const MANIFEST_FILE: &str = "data.provenance.jsonl";
+
+/// Where a batch of scraped records came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub scraper: String,
+    /// Configured `url` of the scraper, the scrapers don't expose the exact request
+    #[serde(alias = "source_url")]
+    pub endpoint: Option<String>,
+    pub request_start: DateTime<Utc>,
+    pub request_end: DateTime<Utc>,
+    pub fetched_at: DateTime<Utc>,
+    pub records: usize,
+    /// SHA-256 of the records the scraper parsed, see `records_hash`. The raw body isn't
+    /// available, and neither is the HTTP status.
+    #[serde(alias = "response_sha256")]
+    pub records_sha256: String,
+}
+
+impl Provenance {
+    pub fn new(scraper: &str, endpoint: Option<String>, request_start: DateTime<Utc>, request_end: DateTime<Utc>, fetched_at: DateTime<Utc>, data: &[ScraperData]) -> Self {
+        Self {
+            scraper: scraper.to_string(),
+            endpoint,
+            request_start,
+            request_end,
+            fetched_at,
+            records: data.len(),
+            records_sha256: records_hash(data),
+        }
+    }
+}
+
+/// One write of a partition and the scrapes its rows came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub written_at: DateTime<Utc>,
+    pub rows_written: usize,
+    pub sources: Vec<Provenance>,
+}
+
+/// Hash of the records of a scrape in a canonical form, independent of map order, so the same
+/// API response always gives the same hash
+pub fn records_hash(data: &[ScraperData]) -> String {
+    let mut hasher = Sha256::new();
+    for item in data {
+        hasher.update(item.delivery_from.timestamp_micros().to_le_bytes());
+        hasher.update(item.delivery_to.timestamp_micros().to_le_bytes());
+        match &item.payload {
+            ScraperPayload::Values(map) => {
+                let mut values: Vec<_> = map.iter().collect();
+                values.sort_by(|a, b| a.0.cmp(b.0));
+                for (column, value) in values {
+                    hasher.update(column.as_bytes());
+                    hasher.update(value.to_bits().to_le_bytes());
+                }
+            }
+            ScraperPayload::Bids(bids) => {
+                for bid in bids {
+                    hasher.update(format!("{:?}|{:?}|{}|{:?}|{:?}", bid.bid_type, bid.direction, bid.rank, bid.price, bid.volume).as_bytes());
+                }
+            }
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Sources of a write as Parquet key-value metadata
+pub fn metadata(sources: &[Provenance]) -> Result<Option<KeyValue>> {
+    if sources.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(KeyValue::new(PROVENANCE_KEY.to_string(), serde_json::to_string(sources)?)))
+}
+
+/// Manifest path of the partition at `data_file`
+pub fn manifest_path(data_file: &str) -> String {
+    Path::new(data_file).with_file_name(MANIFEST_FILE).to_string_lossy().to_string()
+}
+
+/// Append a write to the manifest of the partition at `data_file`, returns the manifest path
+pub fn append_manifest(data_file: &str, entry: &ManifestEntry) -> Result<String> {
+    let manifest = manifest_path(data_file);
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&manifest)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(manifest)
+}
+
+/// Read all writes recorded for a partition
+pub fn read_manifest(data_file: &str) -> Result<Vec<ManifestEntry>> {
+    let manifest = manifest_path(data_file);
+    if !Path::new(&manifest).exists() {
+        return Ok(Vec::new());
+    }
+    std::fs::read_to_string(&manifest)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
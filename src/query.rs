@@ -0,0 +1,462 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use arrow::array::{Array, BooleanArray, Float64Array, Int32Array, StringArray, TimestampMicrosecondArray};
+use arrow::compute::filter_record_batch;
+use arrow::record_batch::RecordBatch;
+
+use crate::completeness;
+use crate::forecast::{GateClosure, PUBLICATION_COLUMN};
+use crate::partition::Granularity;
+use crate::schema;
+use crate::values::{self, Value, ValueType};
+
+/// A single interval of a value series
+#[derive(Debug, Clone)]
+pub struct ValueRow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub scraped_at: Option<DateTime<Utc>>,
+    /// Publication of the forecast vintage, for folders stored by vintage
+    pub publication_time: Option<DateTime<Utc>>,
+    pub values: BTreeMap<String, Value>,
+}
+
+impl ValueRow {
+    /// When the row became available: its publication time, or the time it was scraped
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.publication_time.or(self.scraped_at)
+    }
+}
+
+/// A single balancing bid for an interval
+#[derive(Debug, Clone)]
+pub struct BidRow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub bid_type: String,
+    pub direction: String,
+    pub rank: i32,
+    pub price: Option<f64>,
+    pub volume: Option<f64>,
+    pub scraped_at: Option<DateTime<Utc>>,
+}
+
+/// A change of a stored value between two scrapes of the same interval
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Value column, or `bid_type/direction/rank price|volume` for balancing bids
+    pub series: String,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+    pub previous_scraped_at: Option<DateTime<Utc>>,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+pub enum QueryResult {
+    Values(Vec<ValueRow>),
+    Bids(Vec<BidRow>),
+}
+
+/// Read path over the partitioned Parquet store written by `Storage`
+pub struct Query {
+    base_path: String,
+    /// Granularity and timezone of data folders not partitioned by day
+    partitioning: HashMap<String, (Granularity, Tz)>,
+}
+
+impl Query {
+    pub fn new(base_path: &str) -> Self {
+        Self {
+            base_path: base_path.to_string(),
+            partitioning: HashMap::new(),
+        }
+    }
+
+    /// Read a data folder stored in month or year partitions. Reads of such folders are limited to
+    /// the intervals starting on the requested local days of `tz`.
+    pub fn with_partitioning(mut self, folder: &str, granularity: Granularity, tz: Tz) -> Self {
+        if granularity != Granularity::Day {
+            self.partitioning.insert(folder.to_string(), (granularity, tz));
+        }
+        self
+    }
+
+    fn partitioning(&self, folder: &str) -> Option<(Granularity, Tz)> {
+        self.partitioning.get(folder).copied()
+    }
+
+    pub fn granularity(&self, folder: &str) -> Granularity {
+        self.partitioning(folder).map(|(g, _)| g).unwrap_or_default()
+    }
+
+    /// Start of `start_date` and end of `end_date` for folders whose partitions span several days
+    fn bounds(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        match self.partitioning(folder) {
+            Some((_, tz)) => Ok(Some((completeness::day_bounds(start_date, tz)?.0, completeness::day_bounds(end_date, tz)?.1))),
+            None => Ok(None),
+        }
+    }
+
+    /// Paths of the partitions holding `start_date..=end_date`, oldest first
+    fn partition_paths(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate) -> Vec<PathBuf> {
+        self.granularity(folder).partitions(start_date, end_date).into_iter()
+            .map(|date| self.partition_path(folder, date))
+            .collect()
+    }
+
+    /// Get the latest value per interval for all partitions between start_date and end_date (inclusive).
+    /// When several rows exist for the same interval the one with the latest scraped_at wins.
+    ///
+    /// With `as_of` set, only rows scraped at or before that time are considered, which reconstructs
+    /// the data as it looked at that moment. Backfilled rows have no scraped_at and are always included.
+    pub fn latest(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate, as_of: Option<DateTime<Utc>>) -> Result<QueryResult> {
+        let batches = self.read_range(folder, start_date, end_date)?;
+
+        let is_bids = batches.first()
+            .map(|b| b.schema().index_of("bid_type").is_ok())
+            .unwrap_or(false);
+
+        let visible = |scraped_at: Option<DateTime<Utc>>| match (as_of, scraped_at) {
+            (Some(t), Some(scraped_at)) => scraped_at <= t,
+            _ => true,
+        };
+
+        if is_bids {
+            let mut rows = read_bid_rows(&batches)?;
+            rows.retain(|r| visible(r.scraped_at));
+            Ok(QueryResult::Bids(latest_bids(rows)))
+        } else {
+            let mut rows = read_value_rows(&batches)?;
+            rows.retain(|r| visible(r.scraped_at));
+            Ok(QueryResult::Values(latest_values(rows)))
+        }
+    }
+
+    /// Latest vintage per interval of a forecast folder
+    pub fn latest_forecast(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate) -> Result<Vec<ValueRow>> {
+        self.forecast(folder, start_date, end_date, |_| None)
+    }
+
+    /// Latest vintage per interval published at or before `at`, e.g. the forecast a model run at
+    /// that time saw. Rows without publication time count as published when they were scraped,
+    /// backfilled rows without either are always included.
+    pub fn forecast_as_of(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate, at: DateTime<Utc>) -> Result<Vec<ValueRow>> {
+        self.forecast(folder, start_date, end_date, |_| Some(at))
+    }
+
+    /// Latest vintage per interval published at or before the gate closure of that interval,
+    /// i.e. the forecast that could still be traded on. Local gate closure times are in `tz`.
+    pub fn forecast_at_gate_closure(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate, gate_closure: GateClosure, tz: Tz) -> Result<Vec<ValueRow>> {
+        self.forecast(folder, start_date, end_date, |row| Some(gate_closure.at(row.start, tz)))
+    }
+
+    /// Latest vintage per interval among the rows published at or before the cutoff of their interval
+    fn forecast(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate, cutoff: impl Fn(&ValueRow) -> Option<DateTime<Utc>>) -> Result<Vec<ValueRow>> {
+        let batches = self.read_range(folder, start_date, end_date)?;
+        if batches.iter().any(|b| b.schema().index_of("bid_type").is_ok()) {
+            anyhow::bail!("{} holds bids, forecast vintages are only stored for values", folder);
+        }
+        let mut rows = read_value_rows(&batches)?;
+        rows.retain(|row| match (cutoff(row), row.published_at()) {
+            (Some(cutoff), Some(published)) => published <= cutoff,
+            _ => true,
+        });
+        Ok(latest_values(rows))
+    }
+
+    /// List every value that changed between consecutive scrapes, for all partitions between
+    /// start_date and end_date (inclusive)
+    pub fn revisions(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate) -> Result<Vec<Revision>> {
+        let batches = self.read_range(folder, start_date, end_date)?;
+
+        let is_bids = batches.first()
+            .map(|b| b.schema().index_of("bid_type").is_ok())
+            .unwrap_or(false);
+
+        // Every version of a series as (scraped_at, value), in file order
+        let mut versions: BTreeMap<(DateTime<Utc>, DateTime<Utc>, String), Vec<(Option<DateTime<Utc>>, Option<Value>)>> = BTreeMap::new();
+
+        if is_bids {
+            for row in read_bid_rows(&batches)? {
+                let key = format!("{}/{}/{}", row.bid_type, row.direction, row.rank);
+                versions.entry((row.start, row.end, format!("{} price", key))).or_default().push((row.scraped_at, row.price.map(Value::F64)));
+                versions.entry((row.start, row.end, format!("{} volume", key))).or_default().push((row.scraped_at, row.volume.map(Value::F64)));
+            }
+        } else {
+            for row in read_value_rows(&batches)? {
+                for (column, value) in row.values {
+                    versions.entry((row.start, row.end, column)).or_default().push((row.scraped_at, Some(value)));
+                }
+            }
+        }
+
+        let mut revisions = Vec::new();
+        for ((start, end, series), mut history) in versions {
+            // Stable sort keeps file order for rows with the same scraped_at
+            history.sort_by_key(|(scraped_at, _)| *scraped_at);
+
+            for pair in history.windows(2) {
+                let (previous_scraped_at, old_value) = pair[0].clone();
+                let (changed_at, new_value) = pair[1].clone();
+
+                let changed = match (&old_value, &new_value) {
+                    (Some(old), Some(new)) => old.differs(new),
+                    (None, None) => false,
+                    _ => true,
+                };
+
+                if changed {
+                    revisions.push(Revision {
+                        start,
+                        end,
+                        series: series.clone(),
+                        old_value,
+                        new_value,
+                        previous_scraped_at,
+                        changed_at,
+                    });
+                }
+            }
+        }
+
+        Ok(revisions)
+    }
+
+    /// Path of the partition holding a local day
+    pub fn partition_path(&self, folder: &str, date: NaiveDate) -> PathBuf {
+        PathBuf::from(self.granularity(folder).path(&format!("{}/{}", self.base_path, folder), date))
+    }
+
+    /// Number of distinct intervals stored for a day, None if the partition doesn't exist
+    pub fn interval_count(&self, folder: &str, date: NaiveDate) -> Result<Option<usize>> {
+        if !self.partition_path(folder, date).exists() {
+            return Ok(None);
+        }
+        Ok(Some(self.intervals(folder, date)?.len()))
+    }
+
+    /// Distinct (start, end) intervals stored for a day, ordered by start
+    pub fn intervals(&self, folder: &str, date: NaiveDate) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>> {
+        let path = self.partition_path(folder, date);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let bounds = self.bounds(folder, date, date)?;
+        let mut intervals = BTreeSet::new();
+        for batch in read_batches(&path).with_context(|| format!("Failed to read {:?}", path))? {
+            let start = timestamp_column(&batch, "start")?;
+            let end = timestamp_column(&batch, "end")?;
+            for i in 0..batch.num_rows() {
+                if in_bounds(bounds, start.value(i)) {
+                    intervals.insert((start.value(i), end.value(i)));
+                }
+            }
+        }
+        intervals.into_iter()
+            .map(|(start, end)| Ok((to_datetime(start)?, to_datetime(end)?)))
+            .collect()
+    }
+
+    /// End of the latest stored interval, searching partitions from end_date back to start_date.
+    /// With `until`, intervals ending later are ignored, e.g. forecasts stored ahead of time.
+    pub fn last_interval_end(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate, until: Option<DateTime<Utc>>) -> Result<Option<DateTime<Utc>>> {
+        let bounds = self.bounds(folder, start_date, end_date)?;
+        let until = until.map(|t| t.timestamp_micros()).unwrap_or(i64::MAX);
+        for path in self.partition_paths(folder, start_date, end_date).into_iter().rev() {
+            if path.exists() {
+                let mut last = None;
+                for batch in read_batches(&path).with_context(|| format!("Failed to read {:?}", path))? {
+                    let start = timestamp_column(&batch, "start")?;
+                    let end = timestamp_column(&batch, "end")?;
+                    for i in 0..batch.num_rows() {
+                        if in_bounds(bounds, start.value(i)) && end.value(i) <= until {
+                            last = last.max(Some(end.value(i)));
+                        }
+                    }
+                }
+                if let Some(micros) = last {
+                    return Ok(Some(to_datetime(micros)?));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_range(&self, folder: &str, start_date: NaiveDate, end_date: NaiveDate) -> Result<Vec<RecordBatch>> {
+        let bounds = self.bounds(folder, start_date, end_date)?;
+        let mut batches = Vec::new();
+
+        for path in self.partition_paths(folder, start_date, end_date) {
+            if path.exists() {
+                for batch in read_batches(&path).with_context(|| format!("Failed to read {:?}", path))? {
+                    if bounds.is_none() {
+                        batches.push(batch);
+                        continue;
+                    }
+                    let start = timestamp_column(&batch, "start")?;
+                    let mask: BooleanArray = (0..batch.num_rows()).map(|i| Some(in_bounds(bounds, start.value(i)))).collect();
+                    batches.push(filter_record_batch(&batch, &mask)?);
+                }
+            }
+        }
+
+        Ok(batches)
+    }
+}
+
+/// Parse a timestamp given on the command line, either RFC 3339 or `YYYY-MM-DD HH:MM:SS` in UTC
+pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .with_context(|| format!("Invalid timestamp {}. Use RFC 3339 or YYYY-MM-DD HH:MM:SS (UTC)", s))?;
+    Ok(naive.and_utc())
+}
+
+/// Read a partition, upgraded to the current schema version
+pub fn read_batches(path: &Path) -> Result<Vec<RecordBatch>> {
+    Ok(schema::read_partition(path)?.batches)
+}
+
+fn timestamp_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a TimestampMicrosecondArray> {
+    let idx = batch.schema().index_of(name)?;
+    batch.column(idx)
+        .as_any()
+        .downcast_ref::<TimestampMicrosecondArray>()
+        .with_context(|| format!("Column {} is not a timestamp", name))
+}
+
+/// Whether an interval starting at `start` lies within the bounds of a read, true without bounds
+fn in_bounds(bounds: Option<(DateTime<Utc>, DateTime<Utc>)>, start: i64) -> bool {
+    bounds.map(|(from, to)| start >= from.timestamp_micros() && start < to.timestamp_micros()).unwrap_or(true)
+}
+
+fn to_datetime(micros: i64) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp_micros(micros).context("Timestamp out of range")
+}
+
+/// Backfilled rows store 0 or null as scraped_at, both are treated as unknown
+fn scraped_at_value(col: Option<&TimestampMicrosecondArray>, i: usize) -> Result<Option<DateTime<Utc>>> {
+    match col {
+        Some(c) if !c.is_null(i) && c.value(i) != 0 => Ok(Some(to_datetime(c.value(i))?)),
+        _ => Ok(None),
+    }
+}
+
+pub fn read_value_rows(batches: &[RecordBatch]) -> Result<Vec<ValueRow>> {
+    let mut rows = Vec::new();
+
+    for batch in batches {
+        let schema = batch.schema();
+        let start_col = timestamp_column(batch, "start")?;
+        let end_col = timestamp_column(batch, "end")?;
+        let scraped_at_col = timestamp_column(batch, "scraped_at").ok();
+        let publication_col = timestamp_column(batch, PUBLICATION_COLUMN).ok();
+
+        let mut value_cols = Vec::new();
+        for (i, field) in schema.fields().iter().enumerate() {
+            let name = field.name();
+            if name != "start" && name != "end" && name != "scraped_at" && ValueType::from_data_type(field.data_type()).is_some() {
+                value_cols.push((name.clone(), batch.column(i)));
+            }
+        }
+
+        for i in 0..batch.num_rows() {
+            let mut values = BTreeMap::new();
+            for (name, col) in &value_cols {
+                if let Some(value) = values::read_value(col.as_ref(), i) {
+                    values.insert(name.clone(), value);
+                }
+            }
+
+            rows.push(ValueRow {
+                start: to_datetime(start_col.value(i))?,
+                end: to_datetime(end_col.value(i))?,
+                scraped_at: scraped_at_value(scraped_at_col, i)?,
+                publication_time: scraped_at_value(publication_col, i)?,
+                values,
+            });
+        }
+    }
+
+    Ok(rows)
+}
+
+pub fn read_bid_rows(batches: &[RecordBatch]) -> Result<Vec<BidRow>> {
+    let mut rows = Vec::new();
+
+    for batch in batches {
+        let schema = batch.schema();
+        let start_col = timestamp_column(batch, "start")?;
+        let end_col = timestamp_column(batch, "end")?;
+        let scraped_at_col = timestamp_column(batch, "scraped_at").ok();
+        let bid_type_col = batch.column(schema.index_of("bid_type")?).as_any().downcast_ref::<StringArray>().context("Invalid bid_type column")?;
+        let direction_col = batch.column(schema.index_of("direction")?).as_any().downcast_ref::<StringArray>().context("Invalid direction column")?;
+        let rank_col = batch.column(schema.index_of("rank")?).as_any().downcast_ref::<Int32Array>().context("Invalid rank column")?;
+        let price_col = batch.column(schema.index_of("price")?).as_any().downcast_ref::<Float64Array>().context("Invalid price column")?;
+        let volume_col = batch.column(schema.index_of("volume")?).as_any().downcast_ref::<Float64Array>().context("Invalid volume column")?;
+
+        for i in 0..batch.num_rows() {
+            rows.push(BidRow {
+                start: to_datetime(start_col.value(i))?,
+                end: to_datetime(end_col.value(i))?,
+                bid_type: bid_type_col.value(i).to_string(),
+                direction: direction_col.value(i).to_string(),
+                rank: rank_col.value(i),
+                price: if price_col.is_null(i) { None } else { Some(price_col.value(i)) },
+                volume: if volume_col.is_null(i) { None } else { Some(volume_col.value(i)) },
+                scraped_at: scraped_at_value(scraped_at_col, i)?,
+            });
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Keep the latest row per interval, for forecasts the row of the latest vintage. Rows are
+/// expected in file order, so on equal scraped_at the row written last wins.
+pub fn latest_values(rows: Vec<ValueRow>) -> Vec<ValueRow> {
+    let mut latest: HashMap<(DateTime<Utc>, DateTime<Utc>), ValueRow> = HashMap::new();
+
+    for row in rows {
+        let key = (row.start, row.end);
+        match latest.get(&key) {
+            Some(existing) if (existing.publication_time, existing.scraped_at) > (row.publication_time, row.scraped_at) => {}
+            _ => {
+                latest.insert(key, row);
+            }
+        }
+    }
+
+    let mut result: Vec<ValueRow> = latest.into_values().collect();
+    result.sort_by_key(|r| (r.start, r.end));
+    result
+}
+
+/// Keep the latest bid per interval, bid type, direction and rank
+pub fn latest_bids(rows: Vec<BidRow>) -> Vec<BidRow> {
+    let mut latest: HashMap<(DateTime<Utc>, DateTime<Utc>, String, String, i32), BidRow> = HashMap::new();
+
+    for row in rows {
+        let key = (row.start, row.end, row.bid_type.clone(), row.direction.clone(), row.rank);
+        match latest.get(&key) {
+            Some(existing) if existing.scraped_at > row.scraped_at => {}
+            _ => {
+                latest.insert(key, row);
+            }
+        }
+    }
+
+    let mut result: Vec<BidRow> = latest.into_values().collect();
+    result.sort_by(|a, b| {
+        (a.start, &a.bid_type, &a.direction, a.rank).cmp(&(b.start, &b.bid_type, &b.direction, b.rank))
+    });
+    result
+}
@@ -0,0 +1,198 @@
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Duration, Instant, Sleep};
+use url::Url;
+
+use crate::config::ScraperConfig;
+
+/// API quota budgets shared by all scrapers talking to the same host
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RateLimitConfig {
+    /// Requests per minute per host, e.g. {"web-api.tp.entsoe.eu": 300}
+    #[serde(default)]
+    pub hosts: HashMap<String, u32>,
+    /// Fraction of every budget reserved for backfill. When set, the live service uses the rest,
+    /// so running both at the same time stays within the quota.
+    pub backfill_share: Option<f64>,
+}
+
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_minute: f64) -> Self {
+        let requests_per_minute = requests_per_minute.max(1.0);
+        // Allow bursts of up to one second worth of requests
+        Self::per_second(requests_per_minute / 60.0, (requests_per_minute / 60.0).max(1.0))
+    }
+
+    /// Bucket refilled with `per_second` tokens per second, holding up to `capacity`
+    fn per_second(per_second: f64, capacity: f64) -> Self {
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: per_second,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token, or return how long to wait until one is available
+    fn try_take(&mut self) -> Option<Duration> {
+        self.try_take_n(1.0)
+    }
+
+    /// Take `n` tokens, at most the capacity, or return how long to wait until they are available
+    fn try_take_n(&mut self, n: f64) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= n {
+            self.tokens -= n;
+            None
+        } else {
+            Some(Duration::from_secs_f64((n - self.tokens) / self.refill_per_sec))
+        }
+    }
+}
+
+/// Request body released in chunks of a tenth of a second, so an upload never sends faster than
+/// `bytes_per_second`, not only on average
+pub struct ThrottledBody {
+    data: Bytes,
+    offset: usize,
+    chunk: usize,
+    bucket: TokenBucket,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl ThrottledBody {
+    pub fn new(data: Bytes, bytes_per_second: u64) -> Self {
+        let bytes_per_second = bytes_per_second.max(1);
+        let chunk = (bytes_per_second as usize / 10).clamp(1, 64 * 1024);
+        Self {
+            data,
+            offset: 0,
+            chunk,
+            bucket: TokenBucket::per_second(bytes_per_second as f64, chunk as f64),
+            delay: None,
+        }
+    }
+}
+
+impl Body for ThrottledBody {
+    type Data = Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = &mut *self;
+        let len = this.chunk.min(this.data.len() - this.offset);
+        if len == 0 {
+            return Poll::Ready(None);
+        }
+        loop {
+            if let Some(delay) = &mut this.delay {
+                ready!(delay.as_mut().poll(cx));
+                this.delay = None;
+            }
+            match this.bucket.try_take_n(len as f64) {
+                None => break,
+                Some(wait) => this.delay = Some(Box::pin(sleep(wait))),
+            }
+        }
+        let chunk = this.data.slice(this.offset..this.offset + len);
+        this.offset += len;
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact((self.data.len() - self.offset) as u64)
+    }
+}
+
+/// Rate limiter for a single scraper, combining its own budget with the budget of its host
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    buckets: Vec<Arc<Mutex<TokenBucket>>>,
+}
+
+impl RateLimiter {
+    /// Wait until a request is allowed by all budgets
+    pub async fn acquire(&self) {
+        for bucket in &self.buckets {
+            loop {
+                let wait = bucket.lock().await.try_take();
+                match wait {
+                    None => break,
+                    Some(duration) => sleep(duration).await,
+                }
+            }
+        }
+    }
+}
+
+/// Hands out rate limiters so that scrapers on the same host share one bucket
+pub struct RateLimiters {
+    hosts: HashMap<String, Arc<Mutex<TokenBucket>>>,
+    share: f64,
+}
+
+impl RateLimiters {
+    /// Budgets for the live service
+    pub fn for_service(config: Option<&RateLimitConfig>) -> Self {
+        let share = config.and_then(|c| c.backfill_share).map(|s| 1.0 - s).unwrap_or(1.0);
+        Self::new(config, share)
+    }
+
+    /// Budgets for the backfill tool
+    pub fn for_backfill(config: Option<&RateLimitConfig>) -> Self {
+        let share = config.and_then(|c| c.backfill_share).unwrap_or(1.0);
+        Self::new(config, share)
+    }
+
+    fn new(config: Option<&RateLimitConfig>, share: f64) -> Self {
+        let hosts = config
+            .map(|c| c.hosts.iter()
+                .map(|(host, rpm)| (host.clone(), Arc::new(Mutex::new(TokenBucket::new(*rpm as f64 * share)))))
+                .collect())
+            .unwrap_or_default();
+
+        Self { hosts, share }
+    }
+
+    pub fn for_scraper(&self, config: &ScraperConfig) -> RateLimiter {
+        let mut buckets = Vec::new();
+
+        // Scraper budget first, so waiting for it doesn't consume host tokens
+        if let Some(rpm) = config.requests_per_minute {
+            buckets.push(Arc::new(Mutex::new(TokenBucket::new(rpm as f64 * self.share))));
+        }
+
+        let host = config.scraper_config.values.get("url")
+            .and_then(|v| v.as_str())
+            .and_then(|url| Url::parse(url).ok())
+            .and_then(|url| url.host_str().map(|h| h.to_string()));
+
+        if let Some(bucket) = host.and_then(|h| self.hosts.get(&h)) {
+            buckets.push(bucket.clone());
+        }
+
+        RateLimiter { buckets }
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Utc};
+use chrono_tz::Tz;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use crate::provenance::Provenance;
+
+/// Folder below the data directory holding the archived responses
+pub const RAW_DIR: &str = "raw";
+
+/// One archived scraper response: the records the scraper parsed, before validation. The HTTP
+/// body itself isn't available from the scrapers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawResponse {
+    pub provenance: Provenance,
+    pub records: Vec<RawRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawRecord {
+    pub delivery_from: DateTime<Utc>,
+    pub delivery_to: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<BTreeMap<String, f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bids: Option<Vec<RawBid>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawBid {
+    pub bid_type: String,
+    pub direction: String,
+    pub rank: i32,
+    pub price: Option<f64>,
+    pub volume: Option<f64>,
+}
+
+impl RawResponse {
+    pub fn new(provenance: Provenance, data: &[ScraperData]) -> Self {
+        let records = data.iter().map(|item| {
+            let (values, bids) = match &item.payload {
+                ScraperPayload::Values(map) => (Some(map.iter().map(|(k, v)| (k.clone(), *v)).collect()), None),
+                ScraperPayload::Bids(bids) => (None, Some(bids.iter().map(|bid| RawBid {
+                    bid_type: format!("{:?}", bid.bid_type),
+                    direction: format!("{:?}", bid.direction),
+                    rank: bid.rank,
+                    price: bid.price,
+                    volume: bid.volume,
+                }).collect())),
+            };
+            RawRecord { delivery_from: item.delivery_from, delivery_to: item.delivery_to, values, bids }
+        }).collect();
+        Self { provenance, records }
+    }
+
+    /// Records that can be stored again. Bid types are upstream enums without a parser,
+    /// so bid records are returned separately as a count.
+    pub fn to_scraper_data(&self) -> (Vec<ScraperData>, usize) {
+        let mut data = Vec::new();
+        let mut skipped_bids = 0;
+        for record in &self.records {
+            match (&record.values, &record.bids) {
+                (Some(values), _) => data.push(ScraperData {
+                    delivery_from: record.delivery_from,
+                    delivery_to: record.delivery_to,
+                    payload: ScraperPayload::Values(values.iter().map(|(k, v)| (k.clone(), *v)).collect()),
+                }),
+                (None, Some(_)) => skipped_bids += 1,
+                (None, None) => {}
+            }
+        }
+        (data, skipped_bids)
+    }
+}
+
+/// Path of a response: partitioned by the local day of the requested window, named by fetch time
+pub fn response_path(base_path: &str, folder: &str, tz: Tz, provenance: &Provenance) -> PathBuf {
+    let day = provenance.request_start.with_timezone(&tz);
+    Path::new(base_path).join(RAW_DIR).join(folder)
+        .join(format!("year={}/month={:02}/day={:02}", day.year(), day.month(), day.day()))
+        .join(format!("{}_{}.json.gz", provenance.fetched_at.format("%Y%m%dT%H%M%S%6f"), &provenance.records_sha256[..12]))
+}
+
+/// Write a gzip compressed response, returns its path
+pub fn write(base_path: &str, folder: &str, tz: Tz, response: &RawResponse) -> Result<String> {
+    let path = response_path(base_path, folder, tz, &response.provenance);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("gz.tmp");
+    let mut encoder = GzEncoder::new(std::fs::File::create(&tmp_path)?, Compression::default());
+    encoder.write_all(&serde_json::to_vec(response)?)?;
+    encoder.finish()?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+pub fn read(path: &Path) -> Result<RawResponse> {
+    let mut json = Vec::new();
+    GzDecoder::new(std::fs::File::open(path)?).read_to_end(&mut json)?;
+    serde_json::from_slice(&json).with_context(|| format!("Invalid raw response {:?}", path))
+}
+
+/// Archived responses of a local day, oldest first
+pub fn list_day(base_path: &str, folder: &str, date: chrono::NaiveDate) -> Result<Vec<PathBuf>> {
+    let dir = Path::new(base_path).join(RAW_DIR).join(folder)
+        .join(format!("year={}/month={:02}/day={:02}", date.year(), date.month(), date.day()));
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.to_string_lossy().ends_with(".json.gz"))
+        .collect();
+    // File names start with the fetch time
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::config::AppConfig;
+use crate::conflict::CONFLICTS_DIR;
+use crate::delta::{PART_EXTENSION, PART_PREFIX};
+use crate::partition::{self, Granularity};
+use crate::partition_manifest;
+use crate::provenance;
+use crate::uploader::RemoteObject;
+
+/// Why a remote object can be deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Reason {
+    /// Temporary file of an interrupted write
+    Temporary,
+    /// Partition of a folder no configured scraper, aggregation or derived series writes
+    UnknownFolder,
+    /// Partition in a granularity the folder isn't stored in anymore, e.g. after `repartition`
+    SupersededLayout,
+    /// Delta part file no version within the retention references anymore
+    SupersededPart,
+    /// Older version of an object in a bucket with versioning
+    NoncurrentVersion,
+}
+
+impl Reason {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Reason::Temporary => "temporary file",
+            Reason::UnknownFolder => "unknown folder",
+            Reason::SupersededLayout => "superseded partition layout",
+            Reason::SupersededPart => "superseded Delta part file",
+            Reason::NoncurrentVersion => "noncurrent version",
+        }
+    }
+}
+
+/// Files stored in every partition directory
+const PARTITION_FILES: [&str; 3] = [partition::DATA_FILE, provenance::MANIFEST_FILE, partition_manifest::MANIFEST_FILE];
+
+/// Decides which objects below the bucket prefix are left over. Only partition files, Delta part
+/// files and temporary files are ever selected, anything else in the bucket (raw archive, run
+/// history, Delta logs, locks) is kept.
+pub struct RemoteCleanup {
+    base_path: String,
+    /// Granularity of every folder written with the current config
+    folders: HashMap<String, Granularity>,
+    /// Part files still referenced per Delta table with a log
+    delta_files: HashMap<String, HashSet<String>>,
+    /// Whether partitions of folders missing from the config are selected
+    unknown_folders: bool,
+    /// Objects modified more recently are kept, they may belong to a write in progress
+    min_age: Duration,
+    now: DateTime<Utc>,
+}
+
+impl RemoteCleanup {
+    pub fn new(config: &AppConfig, base_path: &str, min_age: Duration) -> Result<Self> {
+        let delta_files = match config.delta_log(base_path) {
+            Some(delta_log) => delta_log.referenced_files()?,
+            None => HashMap::new(),
+        };
+        Ok(Self {
+            base_path: base_path.to_string(),
+            folders: expected_folders(config),
+            delta_files,
+            unknown_folders: false,
+            min_age,
+            now: Utc::now(),
+        })
+    }
+
+    /// Also select partitions of folders no configured scraper, aggregation or derived series
+    /// writes. A folder missing from the config by mistake would lose its remote copy.
+    pub fn with_unknown_folders(mut self) -> Self {
+        self.unknown_folders = true;
+        self
+    }
+
+    /// Why an object can be deleted, None to keep it. Partition files are only selected when
+    /// no local copy exists.
+    pub fn classify(&self, object: &RemoteObject) -> Option<Reason> {
+        let old_enough = object.last_modified.is_some_and(|t| self.now - t >= self.min_age);
+        if !old_enough {
+            return None;
+        }
+        if object.version_id.is_some() {
+            return Some(Reason::NoncurrentVersion);
+        }
+
+        let path = Path::new(&object.path);
+        let file_name = path.file_name()?.to_str()?;
+        if file_name.ends_with(".tmp") {
+            return Some(Reason::Temporary);
+        }
+        // Part files committed before they got their own extension end in .parquet
+        let is_part = file_name.starts_with(PART_PREFIX) && (file_name.ends_with(PART_EXTENSION) || file_name.ends_with(".parquet"));
+        if !(is_part || PARTITION_FILES.contains(&file_name)) || Path::new(&self.base_path).join(path).exists() {
+            return None;
+        }
+
+        // The folder is everything before the partition directories
+        let folder = object.path.split("/year=").next().filter(|folder| *folder != object.path)?;
+        if is_part {
+            // Only tables whose log is known locally, parts of other tables are kept
+            let referenced = self.delta_files.get(folder)?;
+            return (!referenced.contains(&object.path)).then_some(Reason::SupersededPart);
+        }
+        let Some(expected) = self.folders.get(folder) else {
+            return self.unknown_folders.then_some(Reason::UnknownFolder);
+        };
+        let (granularity, _, _) = partition::date_range(path)?;
+        (granularity != *expected).then_some(Reason::SupersededLayout)
+    }
+
+    /// Objects to delete and why, ordered by path
+    pub fn select(&self, objects: Vec<RemoteObject>) -> Vec<(RemoteObject, Reason)> {
+        let mut selected: Vec<(RemoteObject, Reason)> = objects.into_iter()
+            .filter_map(|object| self.classify(&object).map(|reason| (object, reason)))
+            .collect();
+        selected.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+        selected
+    }
+}
+
+/// Every folder the config writes partitions to, with its granularity. Rejected records,
+/// conflicts and aggregates are always stored by day.
+pub fn expected_folders(config: &AppConfig) -> HashMap<String, Granularity> {
+    let mut folders = HashMap::new();
+    for scraper in &config.scrapers {
+        let folder = scraper.data_folder();
+        folders.insert(folder.to_string(), scraper.partition_granularity);
+        folders.insert(format!("rejected/{}", folder), Granularity::Day);
+        folders.insert(format!("{}/{}", CONFLICTS_DIR, folder), Granularity::Day);
+        for aggregation in &scraper.aggregations {
+            folders.insert(aggregation.window.folder(folder), Granularity::Day);
+        }
+    }
+    for derived in &config.derived {
+        folders.insert(derived.data_folder().to_string(), derived.partition_granularity);
+    }
+    folders
+}
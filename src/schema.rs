@@ -0,0 +1,111 @@
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::array::{new_null_array, Array};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::format::KeyValue;
+
+/// Parquet key-value metadata entry holding the schema version of a partition
+pub const SCHEMA_VERSION_KEY: &str = "scraping_service.schema_version";
+
+/// Version written by this build. Bump it together with a new entry in `MIGRATIONS`.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+/// Upgrades a batch from version `from` to `from + 1`
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    apply: fn(RecordBatch) -> Result<RecordBatch>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "add scraped_at column",
+        apply: add_scraped_at,
+    },
+];
+
+/// A partition read from disk, upgraded to the current schema version
+pub struct Partition {
+    pub batches: Vec<RecordBatch>,
+    /// Version the file was stored with
+    pub stored_version: u32,
+}
+
+impl Partition {
+    pub fn is_outdated(&self) -> bool {
+        self.stored_version < CURRENT_SCHEMA_VERSION
+    }
+}
+
+/// Metadata entry to attach to every written partition
+pub fn version_metadata() -> KeyValue {
+    KeyValue::new(SCHEMA_VERSION_KEY.to_string(), CURRENT_SCHEMA_VERSION.to_string())
+}
+
+/// Read a partition and run all migrations it needs, so callers only ever see the current schema
+pub fn read_partition(path: &Path) -> Result<Partition> {
+    let file = File::open(path)?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+
+    let stored_version = builder.metadata().file_metadata().key_value_metadata()
+        .and_then(|kv| kv.iter().find(|kv| kv.key == SCHEMA_VERSION_KEY))
+        .and_then(|kv| kv.value.as_deref())
+        .map(|v| v.parse::<u32>().with_context(|| format!("Invalid schema version {} in {:?}", v, path)))
+        .transpose()?
+        // Files written before versioning was introduced
+        .unwrap_or_else(|| detect_version(builder.schema()));
+
+    if stored_version > CURRENT_SCHEMA_VERSION {
+        bail!("{:?} has schema version {}, this build only supports up to {}", path, stored_version, CURRENT_SCHEMA_VERSION);
+    }
+
+    let mut batches = Vec::new();
+    for batch in builder.build()? {
+        batches.push(migrate_batch(batch?, stored_version)?);
+    }
+
+    Ok(Partition { batches, stored_version })
+}
+
+fn detect_version(schema: &Schema) -> u32 {
+    if schema.index_of("scraped_at").is_ok() {
+        2
+    } else {
+        1
+    }
+}
+
+fn migrate_batch(mut batch: RecordBatch, from: u32) -> Result<RecordBatch> {
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from) {
+        batch = (migration.apply)(batch)
+            .with_context(|| format!("Migration from version {} ({}) failed", migration.from, migration.description))?;
+    }
+    Ok(batch)
+}
+
+/// Version 2 records when each row was scraped. Rows from before have no scraped_at (null),
+/// the column goes after end for value partitions and last for bid partitions.
+fn add_scraped_at(batch: RecordBatch) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    let field = Field::new("scraped_at", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), true);
+    let column: Arc<dyn Array> = new_null_array(field.data_type(), batch.num_rows());
+
+    let idx = if schema.index_of("bid_type").is_ok() {
+        schema.fields().len()
+    } else {
+        schema.index_of("end")? + 1
+    };
+
+    let mut fields: Vec<Arc<Field>> = schema.fields().iter().cloned().collect();
+    let mut columns = batch.columns().to_vec();
+    fields.insert(idx, Arc::new(field));
+    columns.insert(idx, column);
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+}
@@ -1,19 +1,132 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::RwLock;
+use tracing::info;
 use ve_energy_scrapers::scraper::Scraper;
 use ve_energy_scrapers::apg_information_scraper::APGInformationScraper;
 use ve_energy_scrapers::entsoe_information_scraper::EntsoeInformationScraper;
+use ve_energy_scrapers::models::scraper_data::ScraperData;
 use ve_energy_scrapers::models::strategy_information_scraper_config::StrategyInformationScraperConfig;
 
-pub fn create_scraper(config: &StrategyInformationScraperConfig) -> Result<Box<dyn Scraper>> {
-    if let Some(url) = config.values.get("url").and_then(|v| v.as_str()) {
-        if url.contains("entsoe") {
-            Ok(Box::new(EntsoeInformationScraper::new(config.clone())?))
-        } else if url.contains("apg") {
-            Ok(Box::new(APGInformationScraper::new(config.clone())?))
+use crate::fixtures::{self, MockScraper};
+use crate::http_client::HttpClientConfig;
+use crate::secrets;
+
+/// Create the scraper for a config, with an HTTP client built from `http` if set
+pub async fn create_scraper(config: &StrategyInformationScraperConfig, http: Option<&HttpClientConfig>) -> Result<Box<dyn Scraper>> {
+    let http = resolve_http(&config.name, http).await?;
+    build_scraper(&resolve_config(config).await?, http.as_ref())
+}
+
+/// The config as handed to the scraper, with `secret://` values replaced
+pub async fn resolve_config(config: &StrategyInformationScraperConfig) -> Result<StrategyInformationScraperConfig> {
+    let mut config = config.clone();
+    for value in config.values.values_mut() {
+        secrets::resolve_json(value).await?;
+    }
+    Ok(config)
+}
+
+/// HTTP client settings with `secret://` values replaced and env overrides applied
+async fn resolve_http(scraper: &str, http: Option<&HttpClientConfig>) -> Result<Option<HttpClientConfig>> {
+    let Some(http) = http else {
+        return Ok(None);
+    };
+    let mut http_value = serde_json::to_value(http)?;
+    secrets::resolve_json(&mut http_value).await?;
+    let http: HttpClientConfig = serde_json::from_value(http_value)?;
+    Ok(Some(http.resolve(scraper)))
+}
+
+/// Implementation a scraper config is built with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Entsoe,
+    Apg,
+    Mock,
+}
+
+impl Source {
+    /// Mock scrapers are selected by `type`, the others by their `url`
+    pub fn of(config: &StrategyInformationScraperConfig) -> Result<Self> {
+        if config.values.get("type").and_then(|v| v.as_str()) == Some(fixtures::MOCK_TYPE) {
+            return Ok(Source::Mock);
+        }
+        if let Some(url) = config.values.get("url").and_then(|v| v.as_str()) {
+            if url.contains("entsoe") {
+                Ok(Source::Entsoe)
+            } else if url.contains("apg") {
+                Ok(Source::Apg)
+            } else {
+                Err(anyhow::anyhow!("Unknown scraper URL type: {}", url))
+            }
         } else {
-            Err(anyhow::anyhow!("Unknown scraper URL type: {}", url))
+            Err(anyhow::anyhow!("Missing URL in config for {}", config.name))
+        }
+    }
+}
+
+/// Fails on invalid `http` settings, and on `http` for the APG and ENTSO-E scrapers: they create
+/// their own client, so the settings would be silently ignored. Mock scrapers make no requests.
+fn build_scraper(config: &StrategyInformationScraperConfig, http: Option<&HttpClientConfig>) -> Result<Box<dyn Scraper>> {
+    if let Some(http) = http {
+        http.build_client().with_context(|| format!("Invalid http settings of {}", config.name))?;
+    }
+    let source = Source::of(config)?;
+    if http.is_some() && source != Source::Mock {
+        bail!("{} sets http, but the {:?} scraper of ve_energy_scrapers creates its own HTTP client and can't take one", config.name, source);
+    }
+    match source {
+        Source::Mock => Ok(Box::new(MockScraper::new(config.clone())?)),
+        Source::Entsoe => Ok(Box::new(EntsoeInformationScraper::new(config.clone())?)),
+        Source::Apg => Ok(Box::new(APGInformationScraper::new(config.clone())?)),
+    }
+}
+
+/// A scraper that is rebuilt when one of its secrets changes, e.g. after a token rotation
+pub struct RefreshingScraper {
+    config: StrategyInformationScraperConfig,
+    http: Option<HttpClientConfig>,
+    uses_secrets: bool,
+    /// Resolved values and HTTP settings the current scraper was built from
+    current: RwLock<(Value, Box<dyn Scraper>)>,
+}
+
+impl RefreshingScraper {
+    pub async fn new(config: &StrategyInformationScraperConfig, http: Option<&HttpClientConfig>) -> Result<Self> {
+        let resolved = resolve_config(config).await?;
+        let resolved_http = resolve_http(&config.name, http).await?;
+        let uses_secrets = config.values.values().any(secrets::contains_reference)
+            || http.map(|h| secrets::contains_reference(&serde_json::to_value(h).unwrap_or_default())).unwrap_or(false);
+        Ok(Self {
+            config: config.clone(),
+            http: http.cloned(),
+            uses_secrets,
+            current: RwLock::new((serde_json::to_value((&resolved.values, &resolved_http))?, build_scraper(&resolved, resolved_http.as_ref())?)),
+        })
+    }
+
+    pub async fn scrape_data(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<ScraperData>> {
+        if self.uses_secrets {
+            self.refresh().await?;
+        }
+        self.current.read().await.1.scrape_data(start, end).await
+    }
+
+    /// Secrets are cached by the provider, so this only rebuilds after a refresh returned new values
+    async fn refresh(&self) -> Result<()> {
+        let resolved = resolve_config(&self.config).await?;
+        let resolved_http = resolve_http(&self.config.name, self.http.as_ref()).await?;
+        let values = serde_json::to_value((&resolved.values, &resolved_http))?;
+        if self.current.read().await.0 == values {
+            return Ok(());
         }
-    } else {
-        Err(anyhow::anyhow!("Missing URL in config for {}", config.name))
+        info!("Secrets of {} changed, recreating the scraper", self.config.name);
+        let scraper = build_scraper(&resolved, resolved_http.as_ref())?;
+        *self.current.write().await = (values, scraper);
+        Ok(())
     }
 }
@@ -0,0 +1,276 @@
+use anyhow::{bail, Context, Result};
+use aws_config::Region;
+use aws_credential_types::provider::{self, error::CredentialsError, ProvideCredentials};
+use aws_credential_types::Credentials;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{Mutex, OnceCell};
+use tracing::warn;
+
+/// Prefix of config values that are looked up in the secrets provider: `secret://path#field`
+pub const SCHEME: &str = "secret://";
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretsProviderKind {
+    AwsSecretsManager,
+    /// KV version 2 secrets engine
+    Vault,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SecretsConfig {
+    pub provider: SecretsProviderKind,
+    /// AWS region of the secrets, default from the AWS environment
+    pub region: Option<String>,
+    /// Overridden by the VAULT_ADDR env var
+    pub vault_address: Option<String>,
+    /// Overridden by the VAULT_TOKEN env var
+    pub vault_token: Option<String>,
+    #[serde(default = "default_vault_mount")]
+    pub vault_mount: String,
+    /// Secrets are fetched again once they are older than this
+    #[serde(default = "default_refresh_secs")]
+    pub refresh_secs: u64,
+    /// S3 credentials, used when S3_ACCESS_KEY/AWS_ACCESS_KEY_ID aren't set
+    pub s3_access_key: Option<String>,
+    pub s3_secret_key: Option<String>,
+}
+
+fn default_vault_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_refresh_secs() -> u64 {
+    300
+}
+
+enum Provider {
+    Aws(aws_sdk_secretsmanager::Client),
+    Vault {
+        client: reqwest::Client,
+        address: String,
+        token: String,
+        mount: String,
+    },
+}
+
+impl Provider {
+    async fn connect(config: &SecretsConfig) -> Result<Self> {
+        match config.provider {
+            SecretsProviderKind::AwsSecretsManager => {
+                let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+                if let Some(region) = &config.region {
+                    loader = loader.region(Region::new(region.clone()));
+                }
+                Ok(Provider::Aws(aws_sdk_secretsmanager::Client::new(&loader.load().await)))
+            }
+            SecretsProviderKind::Vault => Ok(Provider::Vault {
+                client: reqwest::Client::new(),
+                address: env::var("VAULT_ADDR").ok().or_else(|| config.vault_address.clone())
+                    .context("Vault requires vault_address or VAULT_ADDR")?,
+                token: env::var("VAULT_TOKEN").ok().or_else(|| config.vault_token.clone())
+                    .context("Vault requires vault_token or VAULT_TOKEN")?,
+                mount: config.vault_mount.clone(),
+            }),
+        }
+    }
+
+    /// The whole secret at `path`, a JSON object or a plain string
+    async fn fetch(&self, path: &str) -> Result<Value> {
+        match self {
+            Provider::Aws(client) => {
+                let output = client.get_secret_value().secret_id(path).send().await
+                    .map_err(|e| anyhow::anyhow!("{}", e.into_service_error()))?;
+                let secret = output.secret_string().context("Binary secrets are not supported")?;
+                Ok(serde_json::from_str(secret).unwrap_or_else(|_| Value::String(secret.to_string())))
+            }
+            Provider::Vault { client, address, token, mount } => {
+                let url = format!("{}/v1/{}/data/{}", address.trim_end_matches('/'), mount, path);
+                let response: Value = client.get(&url)
+                    .header("X-Vault-Token", token)
+                    .send().await?
+                    .error_for_status()?
+                    .json().await?;
+                response.pointer("/data/data").cloned().context("Unexpected Vault response")
+            }
+        }
+    }
+}
+
+/// Cached access to the configured secrets provider
+pub struct Secrets {
+    config: SecretsConfig,
+    provider: OnceCell<Provider>,
+    cache: Mutex<HashMap<String, (Value, Instant)>>,
+}
+
+impl Secrets {
+    pub fn new(config: SecretsConfig) -> Self {
+        Self {
+            config,
+            provider: OnceCell::new(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Resolve a `secret://path#field` reference
+    pub async fn resolve(&self, reference: &str) -> Result<String> {
+        let (path, field) = parse_reference(reference)?;
+        let secret = self.secret(path).await?;
+        let value = match field {
+            Some(field) => secret.get(field).with_context(|| format!("Secret {} has no field {}", path, field))?,
+            None => &secret,
+        };
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            Value::Object(_) => bail!("Secret {} has several fields, reference one with #field", path),
+            other => Ok(other.to_string()),
+        }
+    }
+
+    async fn secret(&self, path: &str) -> Result<Value> {
+        let refresh = Duration::from_secs(self.config.refresh_secs);
+        let cached = self.cache.lock().await.get(path).cloned();
+        if let Some((value, fetched)) = &cached {
+            if fetched.elapsed() < refresh {
+                return Ok(value.clone());
+            }
+        }
+
+        let provider = self.provider.get_or_try_init(|| Provider::connect(&self.config)).await?;
+        match provider.fetch(path).await {
+            Ok(value) => {
+                self.cache.lock().await.insert(path.to_string(), (value.clone(), Instant::now()));
+                Ok(value)
+            }
+            // Keep working with the last known value while the provider is unreachable
+            Err(e) => match cached {
+                Some((value, _)) => {
+                    warn!("Failed to refresh secret {}, using the cached value: {:?}", path, e);
+                    Ok(value)
+                }
+                None => Err(e.context(format!("Failed to fetch secret {}", path))),
+            },
+        }
+    }
+}
+
+fn parse_reference(reference: &str) -> Result<(&str, Option<&str>)> {
+    let rest = reference.strip_prefix(SCHEME).with_context(|| format!("Not a secret reference: {}", reference))?;
+    let (path, field) = match rest.split_once('#') {
+        Some((path, field)) => (path, Some(field)),
+        None => (rest, None),
+    };
+    if path.is_empty() {
+        bail!("Secret reference without a path: {}", reference);
+    }
+    Ok((path, field))
+}
+
+static SECRETS: OnceLock<Option<Secrets>> = OnceLock::new();
+
+/// Set up the process-wide provider, called by `load_config` and `ScrapingServiceBuilder::build`.
+/// Only the first call takes effect.
+pub fn configure(config: Option<SecretsConfig>) {
+    SECRETS.get_or_init(|| config.map(Secrets::new));
+}
+
+pub fn global() -> Option<&'static Secrets> {
+    SECRETS.get().and_then(|s| s.as_ref())
+}
+
+/// Resolve a config value, values without `secret://` are returned unchanged
+pub async fn resolve(value: &str) -> Result<String> {
+    if !value.starts_with(SCHEME) {
+        return Ok(value.to_string());
+    }
+    global().context("Config references a secret but has no secrets section")?.resolve(value).await
+}
+
+/// Whether any string in a JSON value is a secret reference
+pub fn contains_reference(value: &Value) -> bool {
+    match value {
+        Value::String(s) => s.starts_with(SCHEME),
+        Value::Array(items) => items.iter().any(contains_reference),
+        Value::Object(map) => map.values().any(contains_reference),
+        _ => false,
+    }
+}
+
+/// Replace every secret reference in a JSON value
+pub async fn resolve_json(value: &mut Value) -> Result<()> {
+    let mut references = Vec::new();
+    collect_references(value, &mut references);
+    let mut resolved = HashMap::new();
+    for reference in references {
+        let secret = resolve(&reference).await?;
+        resolved.insert(reference, secret);
+    }
+    replace_references(value, &resolved);
+    Ok(())
+}
+
+fn collect_references(value: &Value, references: &mut Vec<String>) {
+    match value {
+        Value::String(s) if s.starts_with(SCHEME) => references.push(s.clone()),
+        Value::Array(items) => items.iter().for_each(|v| collect_references(v, references)),
+        Value::Object(map) => map.values().for_each(|v| collect_references(v, references)),
+        _ => {}
+    }
+}
+
+fn replace_references(value: &mut Value, resolved: &HashMap<String, String>) {
+    match value {
+        Value::String(s) => {
+            if let Some(secret) = resolved.get(s.as_str()) {
+                *s = secret.clone();
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| replace_references(v, resolved)),
+        Value::Object(map) => map.values_mut().for_each(|v| replace_references(v, resolved)),
+        _ => {}
+    }
+}
+
+/// S3 credentials from `s3_access_key`/`s3_secret_key`, if both are configured
+pub fn s3_credentials() -> Option<SecretCredentials> {
+    let secrets = global()?;
+    Some(SecretCredentials {
+        access_key: secrets.config.s3_access_key.clone()?,
+        secret_key: secrets.config.s3_secret_key.clone()?,
+        refresh: Duration::from_secs(secrets.config.refresh_secs),
+    })
+}
+
+/// Credentials provider for the S3 client. The credentials expire after `refresh_secs`,
+/// so the SDK asks again and picks up rotated keys.
+#[derive(Debug)]
+pub struct SecretCredentials {
+    access_key: String,
+    secret_key: String,
+    refresh: Duration,
+}
+
+impl SecretCredentials {
+    async fn load(&self) -> Result<Credentials> {
+        let access_key = resolve(&self.access_key).await?;
+        let secret_key = resolve(&self.secret_key).await?;
+        Ok(Credentials::new(access_key, secret_key, None, Some(SystemTime::now() + self.refresh), "secrets"))
+    }
+}
+
+impl ProvideCredentials for SecretCredentials {
+    fn provide_credentials<'a>(&'a self) -> provider::future::ProvideCredentials<'a>
+    where
+        Self: 'a,
+    {
+        provider::future::ProvideCredentials::new(async move {
+            self.load().await.map_err(|e| CredentialsError::provider_error(Box::<dyn std::error::Error + Send + Sync>::from(e)))
+        })
+    }
+}
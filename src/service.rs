@@ -0,0 +1,825 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::sleep;
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::admin::{self, AdminState, ScrapeTrigger};
+use crate::anomaly::AnomalyDetector;
+use crate::backend::StorageBackend;
+use crate::backpressure::Backpressure;
+use crate::calendar::CalendarConfig;
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, ErrorClass};
+use crate::completeness;
+use crate::config::{AppConfig, RetentionMode, ScraperConfig, TenantConfig};
+use crate::conflict::{self, ConflictPolicy};
+use crate::dashboard::CoverageSource;
+use crate::delta::DeltaLog;
+use crate::derived::{self, DerivedConfig};
+use crate::disk::DiskGuard;
+use crate::dry_run::{self, DryRunReport};
+use crate::history::{self, RunLedger, RunRecord};
+use crate::lock::{self, LockManager};
+use crate::notify::Notifier;
+use crate::postgres::PostgresSink;
+use crate::provenance::Provenance;
+use crate::query::Query;
+use crate::raw_archive::{self, RawResponse};
+use crate::rate_limit::{RateLimiter, RateLimiters};
+use crate::scraper_factory::RefreshingScraper;
+use crate::secrets;
+use crate::storage::Storage;
+use crate::stream::StreamSink;
+use crate::transform::Transforms;
+use crate::uploader::{self, Uploader};
+use crate::validation::Validator;
+
+/// The scraping pipeline for embedding in other services: scrapers are scheduled, validated,
+/// written to Parquet and further sinks, and uploaded to S3, for every tenant of the config.
+///
+/// ```ignore
+/// let service = ScrapingService::builder()
+///     .with_config(load_config("config.json")?)
+///     .with_scraper(scraper)
+///     .with_sink(Arc::new(sink))
+///     .build()?;
+/// let running = service.start().await?;
+/// ```
+pub struct ScrapingService {
+    tenants: Vec<AppConfig>,
+    sinks: Vec<Arc<dyn StorageBackend>>,
+}
+
+/// Collects the config and sinks of a `ScrapingService`
+#[derive(Default)]
+pub struct ScrapingServiceBuilder {
+    config: AppConfig,
+    sinks: Vec<Arc<dyn StorageBackend>>,
+}
+
+impl ScrapingServiceBuilder {
+    /// Start from a loaded config, replacing everything set so far except the sinks
+    pub fn with_config(mut self, config: AppConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Run a scraper in the default tenant
+    pub fn with_scraper(mut self, scraper: ScraperConfig) -> Self {
+        self.config.scrapers.push(scraper);
+        self
+    }
+
+    /// Compute a derived series in the default tenant
+    pub fn with_derived(mut self, derived: DerivedConfig) -> Self {
+        self.config.derived.push(derived);
+        self
+    }
+
+    /// Run a further tenant with its own data directory and bucket
+    pub fn with_tenant(mut self, tenant: TenantConfig) -> Self {
+        self.config.tenants.push(tenant);
+        self
+    }
+
+    /// Data directory of the default tenant, `data` if not set
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        self.config.base_path = Some(base_path.to_string());
+        self
+    }
+
+    /// Upload the default tenant to a bucket, the region and endpoint come from the AWS environment
+    pub fn with_s3_bucket(mut self, bucket: &str) -> Self {
+        self.config.s3_bucket = Some(bucket.to_string());
+        self
+    }
+
+    /// Write every scraper's accepted values to this backend as well, after Parquet
+    pub fn with_sink(mut self, sink: Arc<dyn StorageBackend>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Check the tenants, fails if two of them share a data directory, bucket or scraper
+    pub fn build(self) -> Result<ScrapingService> {
+        // Configs built in code don't go through `load_config`
+        secrets::configure(self.config.secrets.clone());
+        Ok(ScrapingService {
+            tenants: self.config.tenants()?,
+            sinks: self.sinks,
+        })
+    }
+}
+
+impl ScrapingService {
+    pub fn builder() -> ScrapingServiceBuilder {
+        ScrapingServiceBuilder::default()
+    }
+
+    /// Config of every tenant, the default tenant first
+    pub fn tenants(&self) -> &[AppConfig] {
+        &self.tenants
+    }
+
+    /// Scrape every scraper, or only `scraper`, once and report what would be written and
+    /// uploaded, without writing anything
+    pub async fn dry_run(&self, scraper: Option<&str>, replay: Option<&Path>) -> Result<Vec<DryRunReport>> {
+        // Only the tenants with the requested scraper, or every tenant with scrapers
+        let tenants: Vec<&AppConfig> = self.tenants.iter()
+            .filter(|t| t.scrapers.iter().any(|s| scraper.map(|name| s.scraper_config.name == name).unwrap_or(true)))
+            .collect();
+        if tenants.is_empty() {
+            anyhow::bail!("No scraper named '{}' in config.json", scraper.unwrap_or_default());
+        }
+        let mut reports = Vec::new();
+        for tenant in tenants {
+            let mut storage = Storage::new(tenant.base_path(), None).with_parquet_config(tenant.parquet.clone());
+            for scraper in &tenant.scrapers {
+                storage = storage.with_scraper(scraper)?;
+            }
+            reports.extend(dry_run::run(tenant, &storage, scraper, replay).await?);
+        }
+        Ok(reports)
+    }
+
+    /// Start every tenant. Each has its own storage, uploader and scraper pools, they only
+    /// share the process. Tasks run until the process exits, call `shutdown` before.
+    pub async fn start(self) -> Result<RunningService> {
+        let mut tenants = Vec::new();
+        for tenant in self.tenants {
+            let name = tenant.tenant.clone().unwrap_or_else(|| "default".to_string());
+            let service = start_tenant(tenant, &self.sinks).instrument(info_span!("tenant", tenant = %name)).await
+                .with_context(|| format!("Failed to start tenant {}", name))?;
+            tenants.push(service);
+        }
+        Ok(RunningService { tenants })
+    }
+}
+
+/// Handle of a started `ScrapingService`
+pub struct RunningService {
+    tenants: Vec<TenantService>,
+}
+
+impl RunningService {
+    /// Storage of a tenant, None for the default tenant
+    pub fn storage(&self, tenant: Option<&str>) -> Option<Arc<Storage>> {
+        self.tenant(tenant).map(|t| t.storage.clone())
+    }
+
+    /// A running scraper of a tenant, to scrape a window now
+    pub fn scraper(&self, tenant: Option<&str>, name: &str) -> Option<Arc<dyn ScrapeTrigger>> {
+        self.tenant(tenant)?.scrapers.get(name).map(|job| job.clone() as Arc<dyn ScrapeTrigger>)
+    }
+
+    fn tenant(&self, tenant: Option<&str>) -> Option<&TenantService> {
+        self.tenants.iter().find(|t| t.name == tenant.unwrap_or("default"))
+    }
+
+    /// Flush buffered data, commit Delta tables and write the run history of every tenant
+    pub async fn shutdown(&self) {
+        for tenant in &self.tenants {
+            tenant.shutdown().await;
+        }
+    }
+}
+
+/// Storage and scrapers of a running tenant, flushed on shutdown
+struct TenantService {
+    name: String,
+    storage: Arc<Storage>,
+    delta_log: Option<Arc<DeltaLog>>,
+    ledger: Arc<RunLedger>,
+    scrapers: BTreeMap<String, Arc<ScrapeJob>>,
+}
+
+impl TenantService {
+    async fn shutdown(&self) {
+        if let Err(e) = self.storage.flush().await {
+            error!("Failed to flush buffered data of {} on shutdown: {:?}", self.name, e);
+        }
+        if let Some(delta_log) = &self.delta_log {
+            if let Err(e) = delta_log.commit().await {
+                error!("Failed to commit Delta tables of {} on shutdown: {:?}", self.name, e);
+            }
+        }
+        if let Err(e) = self.ledger.flush() {
+            error!("Failed to write run history of {} on shutdown: {:?}", self.name, e);
+        }
+    }
+}
+
+/// Start the uploader, background tasks, scraper pools and admin API of one tenant.
+/// `extra_sinks` are written after Parquet for every scraper.
+async fn start_tenant(config: AppConfig, extra_sinks: &[Arc<dyn StorageBackend>]) -> Result<TenantService> {
+    let name = config.tenant.clone().unwrap_or_else(|| "default".to_string());
+    let base_path = config.base_path().to_string();
+    info!("Starting tenant {} with {} scrapers in {}", name, config.scrapers.len(), base_path);
+
+    let mut dirty_files_handle = None;
+    let mut s3_uploader = None;
+    
+    // Use env vars with fallback to config file values
+    if let Some(mut uploader) = Uploader::from_config(&config).await? {
+        if let Some(notify_config) = &config.notify {
+            uploader = uploader.with_notifier(Arc::new(Notifier::new(notify_config, &config.scrapers, &base_path)?));
+        }
+        let uploader = Arc::new(uploader);
+        dirty_files_handle = Some(uploader.get_pending_files_handle());
+        s3_uploader = Some(uploader.clone());
+        
+        tokio::spawn(async move {
+            uploader.run().await;
+        });
+    }
+
+    let mut storage = Storage::new(&base_path, dirty_files_handle).with_parquet_config(config.parquet.clone());
+    // Partitions deleted locally, e.g. by the disk guard, are merged with S3 instead of replaced
+    if let Some(uploader) = &s3_uploader {
+        storage = storage.with_hydration(uploader.clone());
+    }
+    for scraper in &config.scrapers {
+        storage = storage.with_scraper(scraper)?;
+    }
+    for derived in derived::from_config(&config.derived)? {
+        let tz = derived.config.partition_timezone()?;
+        storage = storage.with_derived(derived, tz);
+    }
+    if let Some(buffer) = config.buffer.clone() {
+        storage = storage.with_buffer(buffer);
+    }
+    if let Some(stream_config) = &config.stream {
+        let sink = StreamSink::connect(stream_config).await.context("Failed to connect the stream sink")?;
+        storage = storage.with_stream(Arc::new(sink));
+    }
+    let delta_log = config.delta_log(&base_path).map(|delta_log| match &s3_uploader {
+        Some(uploader) => Arc::new(delta_log.with_uploader(uploader.clone())),
+        None => Arc::new(delta_log),
+    });
+    if let Some(delta_log) = &delta_log {
+        storage = storage.with_delta(delta_log.clone());
+        let delta_log = delta_log.clone();
+        tokio::spawn(async move {
+            delta_log.run().await;
+        });
+    }
+    let storage = Arc::new(storage);
+
+    if let Some(interval) = storage.flush_interval() {
+        let storage_flush = storage.clone();
+        tokio::spawn(async move {
+            info!("Flushing buffered data every {:?}", interval);
+            loop {
+                sleep(interval).await;
+                if let Err(e) = storage_flush.flush().instrument(info_span!("flush")).await {
+                    error!("Flush failed: {:?}", e);
+                }
+            }
+        });
+    }
+
+    // Retention per data folder, scrapers without their own retention_days use the global one
+    let mut retention_targets: Vec<(String, u64, RetentionMode)> = config.scrapers.iter()
+        .filter_map(|s| s.retention_days.or(config.retention_days)
+            .map(|days| (s.data_folder().to_string(), days, s.retention_mode)))
+        .collect();
+    if let Some(retention_days) = config.retention_days {
+        retention_targets.push(("rejected".to_string(), retention_days, RetentionMode::Delete));
+        retention_targets.push((conflict::CONFLICTS_DIR.to_string(), retention_days, RetentionMode::Delete));
+    }
+    // Raw responses follow the retention of their scraper
+    let raw_targets: Vec<(String, u64, RetentionMode)> = config.scrapers.iter()
+        .filter(|s| s.raw_archive)
+        .filter_map(|s| s.retention_days.or(config.retention_days)
+            .map(|days| (format!("{}/{}", raw_archive::RAW_DIR, s.data_folder()), days, s.retention_mode)))
+        .collect();
+    retention_targets.extend(raw_targets);
+    if let Some(retention_days) = config.retention_days {
+        retention_targets.extend(config.derived.iter()
+            .map(|d| (d.data_folder().to_string(), retention_days, RetentionMode::Delete)));
+    }
+
+    if !retention_targets.is_empty() {
+        let storage_cleanup = storage.clone();
+        let s3_uploader = s3_uploader.clone();
+        tokio::spawn(async move {
+            info!("Starting cleanup task for {} folders", retention_targets.len());
+            loop {
+                for (folder, retention_days, mode) in &retention_targets {
+                    let archive = match mode {
+                        RetentionMode::Archive => match &s3_uploader {
+                            Some(uploader) => Some(uploader.as_ref()),
+                            None => {
+                                error!("Archive retention for {} requires S3, skipping cleanup", folder);
+                                continue;
+                            }
+                        },
+                        RetentionMode::Delete => None,
+                    };
+                    if let Err(e) = storage_cleanup.cleanup_folder(folder, *retention_days, archive).await {
+                        error!("Cleanup of {} failed: {:?}", folder, e);
+                    }
+                }
+                sleep(Duration::from_secs(24 * 60 * 60)).await;
+            }
+        });
+    }
+
+    if let Some(guard_config) = config.disk_guard.clone() {
+        let guard = DiskGuard::new(guard_config, &base_path, storage.clone(), s3_uploader.clone(), &config.scrapers)?;
+        tokio::spawn(async move {
+            guard.run().await;
+        });
+    }
+
+    let ledger = Arc::new(RunLedger::new(&config.history_dir()));
+    tokio::spawn(history::run_flush_loop(ledger.clone(), Duration::from_secs(60)));
+
+    let lock_manager = match &config.locking {
+        Some(lock_config) => {
+            let s3 = match config.get_s3_bucket() {
+                Some(bucket) => Some((uploader::s3_client(config.get_s3_region(), config.get_s3_endpoint()).await, bucket)),
+                None => None,
+            };
+            let manager = lock::from_config(lock_config, s3)?;
+            let names: Vec<String> = config.scrapers.iter().map(|s| s.scraper_config.name.clone()).collect();
+            let manager_run = manager.clone();
+            tokio::spawn(async move {
+                manager_run.run(names).await;
+            });
+            Some(manager)
+        }
+        None => None,
+    };
+
+    let postgres = match &config.postgres {
+        Some(pg_config) if config.scrapers.iter().any(|s| s.postgres) => {
+            Some(Arc::new(PostgresSink::connect(pg_config).await.context("Failed to set up Postgres")?))
+        }
+        _ => None,
+    };
+
+    let rate_limiters = RateLimiters::for_service(config.rate_limits.as_ref());
+
+    let backpressure = config.backpressure.clone().map(|bp_config| {
+        let backpressure = Arc::new(Backpressure::new(bp_config, &base_path, s3_uploader.clone()));
+        let monitor = backpressure.clone();
+        tokio::spawn(async move {
+            monitor.run().await;
+        });
+        backpressure
+    });
+
+    let mut admin = match &config.admin {
+        Some(admin_config) => Some(AdminState::new(admin_config, storage.clone(), s3_uploader.clone(), ledger.clone())?.with_tenant(config.tenant.clone())),
+        None => None,
+    };
+
+    let mut scrapers = BTreeMap::new();
+    let calendars = config.scrapers.iter().map(|s| config.calendar(s)).collect::<Result<Vec<_>>>()?;
+    for (scraper_config, calendar) in config.scrapers.into_iter().zip(calendars) {
+        let storage_clone = storage.clone();
+        let rate_limiter = rate_limiters.for_scraper(&scraper_config);
+        let breaker = scraper_config.circuit_breaker.clone().or_else(|| config.circuit_breaker.clone());
+        let mut sinks = extra_sinks.to_vec();
+        if scraper_config.postgres {
+            match &postgres {
+                Some(pg) => sinks.push(pg.clone()),
+                None => error!("{} has postgres enabled but no postgres section is configured", scraper_config.scraper_config.name),
+            }
+        }
+        let name = scraper_config.scraper_config.name.clone();
+        let coverage = CoverageSource {
+            folder: scraper_config.data_folder().to_string(),
+            tz: scraper_config.partition_timezone()?,
+            granularity: scraper_config.partition_granularity,
+            interval_minutes: scraper_config.validation.as_ref().and_then(|v| v.expected_interval_minutes),
+            calendar: calendar.clone(),
+        };
+        match start_scraper_pool(scraper_config, calendar, storage_clone, sinks, rate_limiter, ledger.clone(), lock_manager.clone(), breaker, backpressure.clone()).await {
+            Ok(job) => {
+                admin = admin.map(|state| state.with_scraper(&name, job.clone()).with_coverage(&name, coverage));
+                scrapers.insert(name, job);
+            }
+            Err(e) => error!("Failed to start scraper pool: {:?}", e),
+        }
+    }
+
+    if let (Some(state), Some(admin_config)) = (admin, &config.admin) {
+        let bind = admin_config.bind.clone();
+        let state = Arc::new(state);
+        tokio::spawn(async move {
+            if let Err(e) = admin::serve(&bind, state).await {
+                error!("Admin API stopped: {:?}", e);
+            }
+        });
+    }
+
+    Ok(TenantService {
+        name,
+        storage,
+        delta_log,
+        ledger,
+        scrapers,
+    })
+}
+
+/// Everything needed to scrape a time range and store the result, shared by the workers of a scraper
+struct ScrapeJob {
+    scraper_name: String,
+    subfolder: Option<String>,
+    transforms: Transforms,
+    validator: Option<Validator>,
+    conflict_policy: ConflictPolicy,
+    scraper: RefreshingScraper,
+    storage: Arc<Storage>,
+    /// Backends written after Parquet, e.g. Postgres
+    sinks: Vec<Arc<dyn StorageBackend>>,
+    rate_limiter: RateLimiter,
+    ledger: Arc<RunLedger>,
+    /// Only scrape while this instance holds the scraper's lease
+    lock: Option<Arc<LockManager>>,
+    breaker: Option<CircuitBreaker>,
+    /// Configured endpoint, recorded as the provenance of every write
+    endpoint: Option<String>,
+    raw_archive: bool,
+    /// Regular scrape window around now, also used for scrapes triggered by the admin API
+    lookback: ChronoDuration,
+    lookahead: ChronoDuration,
+    /// Maintenance windows in which failures are expected, and days the catch-up skips
+    calendar: CalendarConfig,
+    anomaly: Option<AnomalyDetector>,
+}
+
+impl ScrapeJob {
+    /// Scrape one time range, validate and store it, and record the attempt in the run history.
+    /// Everything logged during the run carries the scraper, run id and window of its span.
+    async fn run(&self, worker_name: &str, source: &str, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Option<RunRecord> {
+        let run_id = history::new_run_id();
+        let span = info_span!(
+            "scrape",
+            scraper = %self.scraper_name,
+            run_id = %run_id,
+            source,
+            worker = worker_name,
+            window_start = %start_date,
+            window_end = %end_date,
+            otel.status_code = tracing::field::Empty,
+        );
+        self.run_in_span(run_id, source, start_date, end_date).instrument(span).await
+    }
+
+    async fn run_in_span(&self, run_id: String, source: &str, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Option<RunRecord> {
+        if let Some(lock) = &self.lock {
+            if !lock.holds(&self.scraper_name) {
+                return None;
+            }
+        }
+
+        if let Some(breaker) = &self.breaker {
+            if !breaker.allow() {
+                return None;
+            }
+        }
+
+        self.rate_limiter.acquire().await;
+        let started_at = Utc::now();
+        let timer = std::time::Instant::now();
+        let mut run = RunRecord {
+            run_id: Some(run_id),
+            scraper: self.scraper_name.clone(),
+            source: source.to_string(),
+            window_start: start_date,
+            window_end: end_date,
+            started_at,
+            duration_ms: 0,
+            records_fetched: 0,
+            records_written: 0,
+            error: None,
+        };
+
+        let fetch = info_span!("fetch", otel.kind = "client", url = self.endpoint.as_deref());
+        match self.scraper.scrape_data(start_date, end_date).instrument(fetch).await {
+            Ok(data) => {
+                if let Some(breaker) = &self.breaker {
+                    breaker.record_success();
+                }
+                run.records_fetched = data.len() as u64;
+                let provenance = Provenance::new(&self.scraper_name, self.endpoint.clone(), start_date, end_date, started_at, &data);
+                if self.raw_archive && !data.is_empty() {
+                    let response = RawResponse::new(provenance.clone(), &data);
+                    if let Err(e) = self.storage.archive_raw(&self.scraper_name, self.subfolder.as_deref(), &response).instrument(info_span!("archive")).await {
+                        error!("Failed to archive raw response: {:?}", e);
+                    }
+                }
+                // With the error policy nothing of a conflicting response is stored
+                let data = match conflict::resolve(&self.scraper_name, self.conflict_policy, data) {
+                    Ok(resolved) => {
+                        if !resolved.versions.is_empty() {
+                            if let Err(e) = self.storage.save_conflicts(&self.scraper_name, self.subfolder.as_deref(), &resolved.versions, Some(&provenance)).await {
+                                error!("Failed to save conflicting values: {:?}", e);
+                            }
+                        }
+                        resolved.data
+                    }
+                    Err(e) => {
+                        error!("Conflicting response: {:?}", e);
+                        run.error = Some(format!("conflict: {:#}", e));
+                        Vec::new()
+                    }
+                };
+                // Validation rules and anomaly statistics are in stored units, so both run after the transforms
+                let data = self.transforms.apply(data);
+                let data = match &self.validator {
+                    Some(validator) => {
+                        let result = info_span!("validate").in_scope(|| validator.validate(&self.scraper_name, data));
+                        if validator.rules().quarantine && !result.rejected.is_empty() {
+                            if let Err(e) = self.storage.save_rejected(&self.scraper_name, self.subfolder.as_deref(), &result.rejected, Some(&provenance)).instrument(info_span!("quarantine")).await {
+                                error!("Failed to save rejected data: {:?}", e);
+                            }
+                        }
+                        result.accepted
+                    }
+                    None => data,
+                };
+                let data = match &self.anomaly {
+                    Some(detector) => {
+                        let result = info_span!("anomalies").in_scope(|| detector.check(&self.scraper_name, data));
+                        if !result.rejected.is_empty() {
+                            if let Err(e) = self.storage.save_rejected(&self.scraper_name, self.subfolder.as_deref(), &result.rejected, Some(&provenance)).await {
+                                error!("Failed to save anomalous data: {:?}", e);
+                            }
+                        }
+                        result.accepted
+                    }
+                    None => data,
+                };
+
+                if !data.is_empty() {
+                    match self.storage.save_if_new(&self.scraper_name, self.subfolder.as_deref(), &data, Some(&provenance)).instrument(info_span!("store", backend = "parquet")).await {
+                        Ok(saved) => {
+                            run.records_written = saved as u64;
+                            if saved > 0 {
+                                info!(rows = saved, "Saved new data");
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to save data: {:?}", e);
+                            run.error = Some(format!("save: {:#}", e));
+                        }
+                    }
+
+                    for sink in &self.sinks {
+                        if let Err(e) = sink.save_if_new(&self.scraper_name, self.subfolder.as_deref(), &data).instrument(info_span!("store", backend = sink.name())).await {
+                            error!(backend = sink.name(), "Failed to save data: {:?}", e);
+                            run.error.get_or_insert(format!("{}: {:#}", sink.name(), e));
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                let class = ErrorClass::classify(&format!("{:#}", e));
+                match self.calendar.maintenance_at(Utc::now()) {
+                    // Announced downtime of the source: no alert and the circuit stays closed
+                    Some(window) => warn!(error_class = %class, "Error scraping during maintenance until {} ({}): {:?}",
+                        window.end, window.reason.as_deref().unwrap_or("no reason given"), e),
+                    None => {
+                        error!(error_class = %class, "Error scraping: {:?}", e);
+                        if let Some(breaker) = &self.breaker {
+                            breaker.record_failure(class);
+                        }
+                    }
+                }
+                run.error = Some(format!("scrape ({}): {:#}", class, e));
+            }
+        }
+
+        run.duration_ms = timer.elapsed().as_millis() as u64;
+        if run.error.is_some() {
+            tracing::Span::current().record("otel.status_code", "ERROR");
+        }
+        self.ledger.record(run.clone());
+        Some(run)
+    }
+}
+
+#[async_trait]
+impl ScrapeTrigger for ScrapeJob {
+    async fn scrape_now(&self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Option<RunRecord> {
+        let now = Utc::now();
+        let worker_name = format!("{}-admin", self.scraper_name);
+        self.run(&worker_name, "admin", start.unwrap_or(now - self.lookback), end.unwrap_or(now + self.lookahead)).await
+    }
+
+    fn circuit_state(&self) -> Option<&'static str> {
+        self.breaker.as_ref().map(|b| b.state())
+    }
+
+    fn holds_lease(&self) -> bool {
+        self.lock.as_ref().map(|l| l.holds(&self.scraper_name)).unwrap_or(true)
+    }
+}
+
+/// Scrape the gaps before the regular scrape window, e.g. after the service was down over a
+/// weekend. With a known interval length every missing interval since the first stored day of
+/// the window is scraped, otherwise the range after the last stored interval.
+#[allow(clippy::too_many_arguments)]
+async fn catch_up(job: &ScrapeJob, query: &Query, folder: &str, tz: Tz, max_days: i64, window: Option<ChronoDuration>, lookback: ChronoDuration, interval_minutes: Option<i64>) -> Result<()> {
+    if let Some(lock) = &job.lock {
+        if !lock.wait_for(&job.scraper_name, Duration::from_secs(10)).await {
+            info!("Skipping catch-up of {}, another instance holds its lease", job.scraper_name);
+            return Ok(());
+        }
+    }
+
+    let now = Utc::now();
+    let end = now - lookback;
+    let earliest = end - ChronoDuration::days(max_days);
+    let first_day = earliest.with_timezone(&tz).date_naive();
+    let last_day = end.with_timezone(&tz).date_naive();
+    // Intervals stored ahead, e.g. by the lookahead, don't hide a gap before them
+    let last = query.last_interval_end(folder, first_day, last_day + ChronoDuration::days(1), Some(end))?;
+
+    // Nothing stored yet is a new scraper, not an outage; use the backfill tool for history
+    let Some(last) = last else {
+        return Ok(());
+    };
+
+    let mut gaps: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+    match interval_minutes {
+        Some(interval_minutes) => {
+            let mut stored_before = false;
+            let mut date = first_day;
+            while date <= last_day {
+                let day = completeness::check_partition(query, folder, date, tz, interval_minutes, &job.calendar)?;
+                // Days before the scraper first stored data aren't an outage either
+                stored_before |= day.present > 0;
+                if stored_before {
+                    for (gap_start, gap_end) in day.gaps {
+                        let (gap_start, gap_end) = (gap_start.max(earliest), gap_end.min(end));
+                        if gap_start >= gap_end {
+                            continue;
+                        }
+                        match gaps.last_mut() {
+                            Some((_, previous_end)) if *previous_end == gap_start => *previous_end = gap_end,
+                            _ => gaps.push((gap_start, gap_end)),
+                        }
+                    }
+                }
+                date += ChronoDuration::days(1);
+            }
+        }
+        None if last < end => {
+            let start = last.max(earliest);
+            // A gap over holidays or announced maintenance is what the source published
+            if job.calendar.covers(start, end, tz, ChronoDuration::minutes(15)) {
+                info!("No catch-up of {} from {} to {}, no data expected by its calendar", job.scraper_name, start, end);
+            } else {
+                gaps.push((start, end));
+            }
+        }
+        None => {}
+    }
+
+    let step = window.unwrap_or(ChronoDuration::hours(24));
+    let worker_name = format!("{}-catch-up", job.scraper_name);
+    for (start, end) in gaps {
+        info!("Catching up {} from {} to {}", job.scraper_name, start, end);
+        let mut window_start = start;
+        while window_start < end {
+            let window_end = (window_start + step).min(end);
+            job.run(&worker_name, "catch-up", window_start, window_end).await;
+            window_start = window_end;
+        }
+    }
+    Ok(())
+}
+
+async fn start_scraper_pool(
+    config: ScraperConfig,
+    calendar: CalendarConfig,
+    storage: Arc<Storage>,
+    sinks: Vec<Arc<dyn StorageBackend>>,
+    rate_limiter: RateLimiter,
+    ledger: Arc<RunLedger>,
+    lock: Option<Arc<LockManager>>,
+    breaker: Option<CircuitBreakerConfig>,
+    backpressure: Option<Arc<Backpressure>>,
+) -> Result<Arc<ScrapeJob>> {
+    let name = config.scraper_config.name.clone();
+    let workers = config.scraper_config.workers;
+    let delay = config.scraper_config.task_generator_delay_ms as u64;
+    let lookback = config.lookback();
+    let lookahead = config.lookahead();
+
+    let scraper = RefreshingScraper::new(&config.scraper_config, config.http.as_ref()).await?;
+    // Seeded with the stored window, so a restart doesn't start from scratch
+    let anomaly = AnomalyDetector::for_scraper(&config, storage.base_path(), Utc::now())?;
+    let job = Arc::new(ScrapeJob {
+        scraper_name: name.clone(),
+        subfolder: config.sub_data_folder.clone(),
+        transforms: config.transforms()?,
+        validator: Validator::for_scraper(&config, storage.base_path())?,
+        conflict_policy: config.conflict_policy,
+        scraper,
+        storage,
+        sinks,
+        rate_limiter,
+        ledger,
+        lock,
+        breaker: breaker.map(|config| CircuitBreaker::new(&name, config)),
+        endpoint: config.scraper_config.values.get("url").and_then(|v| v.as_str()).map(String::from),
+        raw_archive: config.raw_archive,
+        lookback,
+        lookahead,
+        calendar,
+        anomaly,
+    });
+    
+    // Create a channel for tasks. The buffer size can be adjusted.
+    // Using a buffer of workers * 2 to allow some queuing but provide backpressure if workers are slow.
+    let buffer_size = if workers > 0 { workers as usize * 2 } else { 10 };
+    let (tx, rx) = mpsc::channel::<()>(buffer_size);
+    let rx = Arc::new(Mutex::new(rx));
+
+    info!("Starting scraper pool for {}: {} workers, {}ms delay", name, workers, delay);
+
+    let catch_up_days = config.catch_up_days.unwrap_or(7);
+    let catch_up_window = config.backfill_window_hours.filter(|h| *h > 0).map(ChronoDuration::hours);
+    let interval_minutes = config.validation.as_ref().and_then(|v| v.expected_interval_minutes);
+    let folder = config.data_folder().to_string();
+    let partition_tz = config.partition_timezone()?;
+    let catch_up_query = Query::new(job.storage.base_path()).with_partitioning(&folder, config.partition_granularity, partition_tz);
+    let critical = config.critical;
+
+    // Task Generator, started once the gap since the last run is filled
+    let name_gen = name.clone();
+    let job_catch_up = job.clone();
+    tokio::spawn(async move {
+        if catch_up_days > 0 {
+            if let Err(e) = catch_up(&job_catch_up, &catch_up_query, &folder, partition_tz, catch_up_days, catch_up_window, lookback, interval_minutes).await {
+                error!("Catch-up of {} failed: {:?}", name_gen, e);
+            }
+        }
+
+        loop {
+            if tx.send(()).await.is_err() {
+                error!("Receiver dropped for {}, stopping generator", name_gen);
+                break;
+            }
+            let delay = Duration::from_millis(delay);
+            sleep(backpressure.as_ref().map(|bp| bp.delay(delay, critical)).unwrap_or(delay)).await;
+        }
+    });
+
+    // Workers
+    for i in 0..workers {
+        let rx = rx.clone();
+        let job = job.clone();
+        let worker_name = format!("{}-worker-{}", name, i);
+
+        tokio::spawn(async move {
+            loop {
+                // Acquire lock just to get the task
+                {
+                    let mut lock = rx.lock().await;
+                    if lock.recv().await.is_none() {
+                        break; // Channel closed
+                    }
+                } // Lock released here
+
+                // Scrape the configured window around now, by default yesterday to tomorrow
+                let now = Utc::now();
+                job.run(&worker_name, "service", now - lookback, now + lookahead).await;
+            }
+        });
+    }
+
+    // Re-scrape the revision window to pick up late corrections
+    if let Some(interval) = config.revision_interval() {
+        let worker_name = format!("{}-revisions", name);
+
+        info!("Re-scraping the last {} days of {} every {:?}", config.revision_window_days.unwrap_or_default(), name, interval);
+
+        let job = job.clone();
+        tokio::spawn(async move {
+            // The first pass runs right away, so a restart doesn't postpone corrections by a whole interval
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                for (window_start, window_end) in config.revision_windows(Utc::now()) {
+                    job.run(&worker_name, "revision", window_start, window_end).await;
+                }
+            }
+        });
+    }
+
+    Ok(job)
+}
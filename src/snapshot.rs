@@ -0,0 +1,191 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::aggregate::AggregationWindow;
+use crate::conflict::CONFLICTS_DIR;
+use crate::partition;
+use crate::raw_archive::RAW_DIR;
+
+/// Name of the manifest inside a snapshot archive, next to the `data/` tree
+pub const MANIFEST_NAME: &str = "snapshot-manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub created_at: DateTime<Utc>,
+    pub files: Vec<SnapshotFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// Path relative to the data directory
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Files to include in a snapshot, everything if empty
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotFilter {
+    /// Path prefixes relative to the data directory, e.g. from `scraper_prefixes`
+    pub prefixes: Vec<String>,
+    /// Partitions holding days from this day on, files outside partitions are always included
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl SnapshotFilter {
+    fn matches(&self, relative: &str) -> bool {
+        if !self.prefixes.is_empty() && !self.prefixes.iter().any(|p| relative.starts_with(&format!("{}/", p.trim_end_matches('/')))) {
+            return false;
+        }
+        match partition::date_range(Path::new(relative)) {
+            Some((_, first_day, last_day)) => !self.start.is_some_and(|start| last_day < start) && !self.end.is_some_and(|end| first_day > end),
+            None => true,
+        }
+    }
+}
+
+/// Folders holding the data of a scraper: its own, and its rejected records, conflicts, raw
+/// responses and aggregates
+pub fn scraper_prefixes(folder: &str) -> Vec<String> {
+    vec![
+        folder.to_string(),
+        format!("rejected/{}", folder),
+        format!("{}/{}", CONFLICTS_DIR, folder),
+        format!("{}/{}", RAW_DIR, folder),
+        AggregationWindow::Hourly.folder(folder),
+        AggregationWindow::Daily.folder(folder),
+    ]
+}
+
+/// Every file below `dir`, skipping files that are being written and lock files
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if !path.extension().is_some_and(|e| e == "tmp" || e == "lock") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Write the selected files of `base_path` to a gzipped tar at `output`, as `data/<path>` entries
+/// followed by a manifest with the size and SHA-256 of every file
+pub fn create(base_path: &str, output: &Path, filter: &SnapshotFilter) -> Result<SnapshotManifest> {
+    let base = Path::new(base_path);
+    let mut paths = Vec::new();
+    if base.is_dir() {
+        collect_files(base, &mut paths)?;
+    }
+    paths.sort();
+
+    let tmp_path = output.with_extension("tmp");
+    let mut builder = tar::Builder::new(GzEncoder::new(File::create(&tmp_path)?, Compression::default()));
+    let mut files = Vec::new();
+    for path in paths {
+        let relative = path.strip_prefix(base)?.to_string_lossy().replace('\\', "/");
+        if !filter.matches(&relative) {
+            continue;
+        }
+        // Hash and archive the same bytes, even if the service rewrites the file meanwhile
+        let bytes = std::fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&std::fs::metadata(&path)?);
+        header.set_size(bytes.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, format!("data/{}", relative), bytes.as_slice())?;
+        files.push(SnapshotFile { path: relative, size: bytes.len() as u64, sha256: format!("{:x}", Sha256::digest(&bytes)) });
+    }
+
+    let manifest = SnapshotManifest { created_at: Utc::now(), files };
+    let json = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.created_at.timestamp() as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_NAME, json.as_slice())?;
+    builder.into_inner()?.finish()?;
+    std::fs::rename(&tmp_path, output)?;
+
+    info!("Snapshot {:?} holds {} files", output, manifest.files.len());
+    Ok(manifest)
+}
+
+pub struct RestoreSummary {
+    pub restored: usize,
+    pub skipped: usize,
+}
+
+/// Unpack a snapshot next to `base_path`, verify every file against the manifest and only then move
+/// the files into place. Existing files are kept unless `overwrite` is set. Modification times are
+/// preserved and nothing is queued for upload, so restored files count as already uploaded.
+pub fn restore(archive: &Path, base_path: &str, overwrite: bool) -> Result<RestoreSummary> {
+    let base = Path::new(base_path);
+    let staging = PathBuf::from(format!("{}.restore", base_path.trim_end_matches('/')));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(&staging)?;
+
+    let result = unpack_and_verify(archive, &staging).and_then(|manifest| {
+        let mut summary = RestoreSummary { restored: 0, skipped: 0 };
+        for file in &manifest.files {
+            let target = base.join(&file.path);
+            if target.exists() && !overwrite {
+                warn!("Keeping existing {:?}", target);
+                summary.skipped += 1;
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(staging.join("data").join(&file.path), &target)
+                .with_context(|| format!("Failed to move {:?} into place", target))?;
+            summary.restored += 1;
+        }
+        Ok(summary)
+    });
+    std::fs::remove_dir_all(&staging)?;
+    result
+}
+
+fn unpack_and_verify(archive: &Path, staging: &Path) -> Result<SnapshotManifest> {
+    let mut tar = tar::Archive::new(GzDecoder::new(File::open(archive)?));
+    tar.set_preserve_mtime(true);
+    tar.unpack(staging).with_context(|| format!("Failed to unpack {:?}", archive))?;
+
+    let manifest: SnapshotManifest = serde_json::from_slice(&std::fs::read(staging.join(MANIFEST_NAME))
+        .with_context(|| format!("{:?} has no {}", archive, MANIFEST_NAME))?)
+        .context("Invalid snapshot manifest")?;
+
+    for file in &manifest.files {
+        if !Path::new(&file.path).components().all(|c| matches!(c, Component::Normal(_))) {
+            bail!("Invalid path {} in the snapshot manifest", file.path);
+        }
+        let path = staging.join("data").join(&file.path);
+        let size = std::fs::metadata(&path).with_context(|| format!("{} is missing from the snapshot", file.path))?.len();
+        if size != file.size || file_sha256(&path)? != file.sha256 {
+            bail!("Checksum mismatch for {}, the snapshot is corrupt", file.path);
+        }
+    }
+    info!("Verified {} files of {:?}", manifest.files.len(), archive);
+    Ok(manifest)
+}
@@ -1,23 +1,138 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc, Datelike, TimeZone};
-use chrono_tz::Europe::Vienna;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use std::borrow::Cow;
 use std::fs::File;
-use std::path::Path;
-use std::collections::{HashSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet, HashSet, HashMap};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tracing::info;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tracing::{error, info, warn};
 
 use arrow::array::{Float64Array, TimestampMicrosecondArray, Array, Int32Array, StringArray};
-use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::compute::{concat_batches, lexsort_to_indices, take_record_batch, SortColumn};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
 use arrow::record_batch::RecordBatch;
-use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 use parquet::arrow::ArrowWriter;
 use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload, Bid};
 
+use crate::aggregate::{self, AggregationConfig};
+use crate::completeness;
+use crate::config::ScraperConfig;
+use crate::conflict;
+use crate::derived::Derived;
+use crate::delta::DeltaLog;
+use crate::forecast::{ForecastConfig, PUBLICATION_COLUMN};
+use crate::parquet_config::ParquetConfig;
+use crate::partition::{self, Granularity};
+use crate::partition_manifest;
+use crate::query::{self, Query, QueryResult};
+use crate::provenance::{self, ManifestEntry, Provenance};
+use crate::raw_archive::{self, RawResponse};
+use crate::schema;
+use crate::stream::{StreamRecord, StreamSink};
+use crate::telemetry;
+use crate::transform;
+use crate::uploader::Uploader;
+use crate::values::{self, Value, ValueSchema, ValueType};
+
+/// Latest value per interval of a value partition, with the scraped_at of that version
+type ValuesState = HashMap<(i64, i64), (i64, HashMap<String, Value>)>;
+/// Latest price and volume per bid of a bids partition
+type BidsState = HashMap<(i64, i64, String, String, i32), (Option<f64>, Option<f64>)>;
+
+enum PartitionState {
+    Values(ValuesState),
+    Bids(BidsState),
+}
+
+/// Cached latest state of a partition, valid as long as the file wasn't modified by anyone else
+struct CachedPartition {
+    state: PartitionState,
+    modified: Option<SystemTime>,
+    last_used: Instant,
+}
+
+const MAX_CACHED_PARTITIONS: usize = 512;
+
+/// Buffer scraped data in memory and write it to Parquet in batches.
+/// Data buffered since the last flush is lost if the process is killed.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BufferConfig {
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Flush early once this many intervals are buffered
+    #[serde(default = "default_max_rows")]
+    pub max_rows: usize,
+}
+
+fn default_flush_interval_ms() -> u64 {
+    5 * 60 * 1000
+}
+
+fn default_max_rows() -> usize {
+    10_000
+}
+
+/// Pending versions per data folder and interval with the time each was scraped, in microseconds
+/// and 0 for backfills. A repeated scrape of an unchanged interval isn't buffered again.
+type PendingRecords = HashMap<String, HashMap<(DateTime<Utc>, DateTime<Utc>), Vec<(i64, ScraperData)>>>;
+
+/// Interval, scraped_at in microseconds (0 for backfills) and typed values of a record to store
+type StampedValues = (DateTime<Utc>, DateTime<Utc>, i64, HashMap<String, Value>);
+
+struct WriteBuffer {
+    config: BufferConfig,
+    pending: Mutex<PendingRecords>,
+    /// Scrapes behind the pending records, recorded when they are flushed
+    provenance: Mutex<HashMap<String, Vec<Provenance>>>,
+}
+
 pub struct Storage {
     base_path: String,
     dirty_files: Option<Arc<Mutex<HashSet<String>>>>,
+    hydration: Option<Arc<Uploader>>,
+    hydrated: Mutex<HashSet<String>>,
+    cache: Mutex<HashMap<String, CachedPartition>>,
+    buffer: Option<WriteBuffer>,
+    parquet: ParquetConfig,
+    /// Column types per data folder path
+    value_schemas: HashMap<String, ValueSchema>,
+    /// Partition timezone per data folder path, Vienna if not set
+    timezones: HashMap<String, Tz>,
+    /// Partition granularity per data folder path, daily if not set
+    granularities: HashMap<String, Granularity>,
+    /// Aggregations kept up to date per data folder path
+    aggregations: HashMap<String, Vec<AggregationConfig>>,
+    /// Unit per column per data folder path, recorded in the Parquet metadata
+    units: HashMap<String, BTreeMap<String, String>>,
+    /// Series computed from other folders, recomputed whenever one of their inputs changes
+    derived: Vec<Derived>,
+    /// Folders storing every vintage of a forecast, per data folder path
+    forecasts: HashMap<String, ForecastConfig>,
+    partition_locks: std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    stream: Option<Arc<StreamSink>>,
+    delta: Option<Arc<DeltaLog>>,
+}
+
+/// A partition a save would change, as reported by `Storage::plan`
+#[derive(Debug, Clone)]
+pub struct PlannedWrite {
+    pub file_path: String,
+    /// Intervals, or bids, not stored yet
+    pub new_rows: usize,
+    /// Intervals, or bids, stored with different values
+    pub changed_rows: usize,
+}
+
+/// Exclusive access to a partition, released on drop
+struct PartitionGuard {
+    _local: OwnedMutexGuard<()>,
+    _file: File,
 }
 
 impl Storage {
@@ -25,150 +140,906 @@ impl Storage {
         Self {
             base_path: base_path.to_string(),
             dirty_files,
+            hydration: None,
+            hydrated: Mutex::new(HashSet::new()),
+            cache: Mutex::new(HashMap::new()),
+            buffer: None,
+            parquet: ParquetConfig::default(),
+            value_schemas: HashMap::new(),
+            timezones: HashMap::new(),
+            granularities: HashMap::new(),
+            aggregations: HashMap::new(),
+            units: HashMap::new(),
+            derived: Vec::new(),
+            forecasts: HashMap::new(),
+            partition_locks: std::sync::Mutex::new(HashMap::new()),
+            stream: None,
+            delta: None,
         }
     }
 
-    pub async fn save_if_new(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData]) -> Result<bool> {
-        self.save_with_scraped_at(name, subfolder, data, true).await
+    /// Download partitions missing locally from S3 before deduplicating against them. Without it
+    /// a partition deleted locally is replaced by one holding only the new rows when it is written.
+    pub fn with_hydration(mut self, uploader: Arc<Uploader>) -> Self {
+        self.hydration = Some(uploader);
+        self
     }
 
-    pub async fn save_backfill(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData]) -> Result<bool> {
-        self.save_with_scraped_at(name, subfolder, data, false).await
+    /// Buffer saves in memory and only write them on `flush`, or once `max_rows` versions are pending.
+    /// Each changed version keeps the time it was scraped, and saves return the versions buffered.
+    pub fn with_buffer(mut self, config: BufferConfig) -> Self {
+        self.buffer = Some(WriteBuffer {
+            config,
+            pending: Mutex::new(HashMap::new()),
+            provenance: Mutex::new(HashMap::new()),
+        });
+        self
     }
 
-    async fn save_with_scraped_at(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData], set_scraped_at: bool) -> Result<bool> {
-        let mut saved_any = false;
+    pub fn with_parquet_config(mut self, config: ParquetConfig) -> Self {
+        self.parquet = config;
+        self
+    }
+
+    /// Set the column types of a data folder, e.g. from `ScraperConfig::value_schema`
+    pub fn with_value_schema(mut self, folder: &str, schema: ValueSchema) -> Self {
+        self.value_schemas.insert(format!("{}/{}", self.base_path, folder), schema);
+        self
+    }
+
+    /// Partition a data folder by the local day of this timezone instead of Vienna
+    pub fn with_partition_timezone(mut self, folder: &str, tz: Tz) -> Self {
+        self.timezones.insert(format!("{}/{}", self.base_path, folder), tz);
+        self
+    }
+
+    /// Store a data folder in month or year partitions instead of one file per day
+    pub fn with_partition_granularity(mut self, folder: &str, granularity: Granularity) -> Self {
+        self.granularities.insert(format!("{}/{}", self.base_path, folder), granularity);
+        self
+    }
+
+    /// Record the unit of columns of a data folder in its partitions' metadata
+    pub fn with_units(mut self, folder: &str, units: BTreeMap<String, String>) -> Self {
+        if !units.is_empty() {
+            self.units.insert(format!("{}/{}", self.base_path, folder), units);
+        }
+        self
+    }
+
+    /// Store a data folder by forecast vintage, with a `publication_time` per row
+    pub fn with_forecast(mut self, folder: &str, forecast: Option<ForecastConfig>) -> Self {
+        if let Some(forecast) = forecast {
+            self.forecasts.insert(format!("{}/{}", self.base_path, folder), forecast);
+        }
+        self
+    }
+
+    /// Register the value schema, partitioning, aggregations, units and forecast settings of a scraper
+    pub fn with_scraper(self, scraper: &ScraperConfig) -> Result<Self> {
+        let folder = scraper.data_folder();
+        Ok(self.with_value_schema(folder, scraper.value_schema())
+            .with_partition_timezone(folder, scraper.partition_timezone()?)
+            .with_partition_granularity(folder, scraper.partition_granularity)
+            .with_aggregations(folder, scraper.aggregations.clone())
+            .with_units(folder, scraper.units())
+            .with_forecast(folder, scraper.forecast.clone()))
+    }
+
+    /// Units metadata of the partition at `file_path`
+    fn units_metadata(&self, file_path: &str) -> Result<Option<parquet::format::KeyValue>> {
+        match self.units.iter().find(|(folder_path, _)| file_path.starts_with(&format!("{}/", folder_path))) {
+            Some((_, units)) => transform::metadata(units),
+            None => Ok(None),
+        }
+    }
+
+    /// Rewrite the aggregates of a data folder in `agg/<window>/<folder>` whenever it changes
+    pub fn with_aggregations(mut self, folder: &str, aggregations: Vec<AggregationConfig>) -> Self {
+        if !aggregations.is_empty() {
+            self.aggregations.insert(format!("{}/{}", self.base_path, folder), aggregations);
+        }
+        self
+    }
+
+    /// Keep a derived series up to date in its data folder
+    pub fn with_derived(mut self, derived: Derived, tz: Tz) -> Self {
+        let folder_path = format!("{}/{}", self.base_path, derived.config.data_folder());
+        self.granularities.insert(folder_path.clone(), derived.config.partition_granularity);
+        self.timezones.insert(folder_path, tz);
+        self.derived.push(derived);
+        self
+    }
+
+    /// Rejected records, conflicts and aggregates are partitioned in the timezone of the folder they were derived from
+    fn source_folder_path(&self, folder_path: &str) -> String {
+        match folder_path.strip_prefix(&format!("{}/", self.base_path)) {
+            Some(folder) => format!("{}/{}", self.base_path, partition::source_folder(folder)),
+            None => folder_path.to_string(),
+        }
+    }
+
+    fn partition_timezone(&self, folder_path: &str) -> Tz {
+        self.timezones.get(&self.source_folder_path(folder_path)).copied().unwrap_or(Tz::Europe__Vienna)
+    }
+
+    /// Rejected records, conflicts and aggregates keep daily partitions
+    fn partition_granularity(&self, folder_path: &str) -> Granularity {
+        self.granularities.get(folder_path).copied().unwrap_or_default()
+    }
+
+    /// Query over the data directory with the partitioning of every configured folder
+    fn query(&self) -> Query {
+        let prefix = format!("{}/", self.base_path);
+        self.granularities.iter()
+            .filter_map(|(folder_path, granularity)| Some((folder_path.strip_prefix(&prefix)?, *granularity, self.partition_timezone(folder_path))))
+            .fold(Query::new(&self.base_path), |query, (folder, granularity, tz)| query.with_partitioning(folder, granularity, tz))
+    }
+
+    /// Publish every new value version to a stream as soon as it is written
+    pub fn with_stream(mut self, sink: Arc<StreamSink>) -> Self {
+        self.stream = Some(sink);
+        self
+    }
+
+    /// Commit written partitions of Delta tables to their transaction log
+    pub fn with_delta(mut self, delta: Arc<DeltaLog>) -> Self {
+        self.delta = Some(delta);
+        self
+    }
+
+    /// Data directory the partitions are written to
+    pub fn base_path(&self) -> &str {
+        &self.base_path
+    }
+
+    pub fn flush_interval(&self) -> Option<std::time::Duration> {
+        self.buffer.as_ref().map(|b| std::time::Duration::from_millis(b.config.flush_interval_ms))
+    }
+
+    /// Save new and changed records. `provenance` describes the scrape they came from and is
+    /// recorded with every partition it changes.
+    pub async fn save_if_new(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData], provenance: Option<&Provenance>) -> Result<usize> {
+        self.save_with_scraped_at(name, subfolder, data, true, provenance).await
+    }
+
+    pub async fn save_backfill(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData], provenance: Option<&Provenance>) -> Result<usize> {
+        self.save_with_scraped_at(name, subfolder, data, false, provenance).await
+    }
+
+    /// Save new and changed records scraped at an earlier time, e.g. replayed from the raw archive.
+    /// Written directly, also with write buffering.
+    pub async fn save_as_of(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData], scraped_at: DateTime<Utc>, provenance: Option<&Provenance>) -> Result<usize> {
+        let folder_path = format!("{}/{}", self.base_path, subfolder.unwrap_or(name));
+        let data: Vec<(i64, &ScraperData)> = data.iter().map(|item| (scraped_at.timestamp_micros(), item)).collect();
+        self.save_stamped(&folder_path, &data, provenance.map(std::slice::from_ref).unwrap_or_default()).await
+    }
+
+    /// Save records that failed validation into the `rejected/` partition
+    pub async fn save_rejected(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData], provenance: Option<&Provenance>) -> Result<usize> {
+        let folder_path = format!("{}/rejected/{}", self.base_path, subfolder.unwrap_or(name));
+        self.save_partitions(&folder_path, data, true, provenance.map(std::slice::from_ref).unwrap_or_default()).await
+    }
+
+    /// Save every variant of conflicting intervals into the `conflicts/` partition
+    pub async fn save_conflicts(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData], provenance: Option<&Provenance>) -> Result<usize> {
+        let folder_path = format!("{}/{}/{}", self.base_path, conflict::CONFLICTS_DIR, subfolder.unwrap_or(name));
+        self.save_partitions(&folder_path, data, true, provenance.map(std::slice::from_ref).unwrap_or_default()).await
+    }
+
+    /// Partitions of `folder` that `save_if_new` would change and how, without writing anything.
+    /// Partitions that only exist in S3 are compared as if they were empty. Forecast vintages
+    /// are planned as if scraped now.
+    pub fn plan(&self, folder: &str, data: &[ScraperData]) -> Result<Vec<PlannedWrite>> {
+        let folder_path = format!("{}/{}", self.base_path, folder);
+        let tz = self.partition_timezone(&folder_path);
+        let granularity = self.partition_granularity(&folder_path);
+        let default_schema = ValueSchema::default();
+        let value_schema = self.value_schemas.get(&folder_path).unwrap_or(&default_schema);
+        let forecast = self.forecasts.get(&folder_path);
+        let vintages = forecast.is_some_and(|f| f.has_vintages());
+        let now_micros = Utc::now().timestamp_micros();
+
+        type Records = (Vec<((i64, i64), i64, HashMap<String, Value>)>, Vec<((i64, i64), Bid)>);
+        let mut partitions: BTreeMap<String, Records> = BTreeMap::new();
+        for item in data {
+            let file_path = granularity.path(&folder_path, item.delivery_from.with_timezone(&tz).date_naive());
+            let (values, bids) = partitions.entry(file_path).or_default();
+            let key = (item.delivery_from.timestamp_micros(), item.delivery_to.timestamp_micros());
+            match &item.payload {
+                ScraperPayload::Values(map) => {
+                    let mut typed = HashMap::new();
+                    for (column, value) in map {
+                        typed.insert(column.clone(), value_schema.convert(column, *value)?);
+                    }
+                    let publication = match forecast {
+                        Some(forecast) => forecast.publication_micros(&mut typed, now_micros),
+                        None => 0,
+                    };
+                    values.push((key, publication, typed));
+                }
+                ScraperPayload::Bids(item_bids) => bids.extend(item_bids.iter().map(|bid| (key, bid.clone()))),
+            }
+        }
+
+        let mut planned = Vec::new();
+        for (file_path, (values, bids)) in partitions {
+            let path = Path::new(&file_path);
+            let batches = if path.exists() { query::read_batches(path)? } else { Vec::new() };
+            let mut write = PlannedWrite { file_path, new_rows: 0, changed_rows: 0 };
+
+            if !values.is_empty() {
+                // Like when saving, a vintage is compared to the stored version of the same
+                // publication, and other rows to the latest version of their interval
+                type Stored = ((i64, i64), HashMap<String, Value>);
+                let mut latest: HashMap<(i64, i64, i64), Stored> = HashMap::new();
+                for row in query::read_value_rows(&batches)? {
+                    let publication = row.publication_time.map(|t| t.timestamp_micros()).unwrap_or(0);
+                    let scraped_at = row.scraped_at.map(|t| t.timestamp_micros()).unwrap_or(0);
+                    let key = (row.start.timestamp_micros(), row.end.timestamp_micros(), if vintages { publication } else { 0 });
+                    match latest.get(&key) {
+                        Some((version, _)) if *version > (publication, scraped_at) => {}
+                        _ => {
+                            latest.insert(key, ((publication, scraped_at), row.values.into_iter().collect()));
+                        }
+                    }
+                }
+                for ((start, end), publication, new_values) in &values {
+                    match latest.get(&(*start, *end, if vintages { *publication } else { 0 })) {
+                        None => write.new_rows += 1,
+                        Some(((_, scraped_at), stored)) if value_changed(Some((*scraped_at, stored)), new_values, true) => write.changed_rows += 1,
+                        Some(_) => {}
+                    }
+                }
+            }
+            if !bids.is_empty() {
+                let latest: BidsState = query::latest_bids(query::read_bid_rows(&batches)?).into_iter()
+                    .map(|row| (
+                        (row.start.timestamp_micros(), row.end.timestamp_micros(), row.bid_type, row.direction, row.rank),
+                        (row.price, row.volume),
+                    ))
+                    .collect();
+                for ((start, end), bid) in &bids {
+                    let key = (*start, *end, format!("{:?}", bid.bid_type), format!("{:?}", bid.direction), bid.rank);
+                    match latest.get(&key) {
+                        None => write.new_rows += 1,
+                        Some(last) if bid_changed(Some(last), bid.price, bid.volume) => write.changed_rows += 1,
+                        Some(_) => {}
+                    }
+                }
+            }
+
+            if write.new_rows + write.changed_rows > 0 {
+                planned.push(write);
+            }
+        }
+        Ok(planned)
+    }
+
+    async fn save_with_scraped_at(&self, name: &str, subfolder: Option<&str>, data: &[ScraperData], set_scraped_at: bool, provenance: Option<&Provenance>) -> Result<usize> {
+        let folder_path = if let Some(sub) = subfolder {
+            format!("{}/{}", self.base_path, sub)
+        } else {
+            format!("{}/{}", self.base_path, name)
+        };
+
+        let buffer = match &self.buffer {
+            Some(buffer) => buffer,
+            None => return self.save_partitions(&folder_path, data, set_scraped_at, provenance.map(std::slice::from_ref).unwrap_or_default()).await,
+        };
+
+        // Buffered records keep the time they were scraped, not the time of the flush
+        let scraped_at = if set_scraped_at { Utc::now().timestamp_micros() } else { 0 };
+        let (buffered, buffered_rows) = {
+            let mut pending = buffer.pending.lock().await;
+            if let Some(provenance) = provenance {
+                buffer.provenance.lock().await.entry(folder_path.clone()).or_default().push(provenance.clone());
+            }
+            let records = pending.entry(folder_path).or_default();
+            let buffered = data.iter().filter(|item| buffer_record(records, scraped_at, (*item).clone())).count();
+            (buffered, pending_rows(&pending))
+        };
+
+        if buffered_rows >= buffer.config.max_rows {
+            self.flush().await?;
+        }
+        Ok(buffered)
+    }
+
+    /// Write all buffered data to Parquet. Returns the number of rows written.
+    /// Data of folders that fail to save stays buffered for the next flush.
+    pub async fn flush(&self) -> Result<usize> {
+        let buffer = match &self.buffer {
+            Some(buffer) => buffer,
+            None => return Ok(0),
+        };
+
+        let (pending, mut sources) = {
+            let mut pending = buffer.pending.lock().await;
+            (std::mem::take(&mut *pending), std::mem::take(&mut *buffer.provenance.lock().await))
+        };
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        info!("Flushing {} buffered versions", pending_rows(&pending));
+
+        let mut rows_written = 0;
+        let mut first_error = None;
+
+        for (folder_path, records) in pending {
+            let data: Vec<(i64, &ScraperData)> = records.values().flatten().map(|(scraped_at, item)| (*scraped_at, item)).collect();
+            let provenance = sources.remove(&folder_path).unwrap_or_default();
+            match self.save_stamped(&folder_path, &data, &provenance).await {
+                Ok(written) => rows_written += written,
+                Err(e) => {
+                    warn!("Failed to flush {}, keeping data buffered: {:?}", folder_path, e);
+                    let mut pending = buffer.pending.lock().await;
+                    let mut buffered = buffer.provenance.lock().await;
+                    let current = buffered.entry(folder_path.clone()).or_default();
+                    current.splice(0..0, provenance);
+                    let current = pending.entry(folder_path).or_default();
+                    // Versions buffered during the flush are newer and stay last
+                    for (key, versions) in records {
+                        current.entry(key).or_default().splice(0..0, versions);
+                    }
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(rows_written),
+        }
+    }
+
+    /// Save records scraped now, or backfilled records without scraped_at
+    async fn save_partitions(&self, folder_path: &str, data: &[ScraperData], set_scraped_at: bool, provenance: &[Provenance]) -> Result<usize> {
+        let scraped_at = if set_scraped_at {
+            Utc::now().timestamp_micros()
+        } else {
+            0 // null for backfilled data
+        };
+        let data: Vec<(i64, &ScraperData)> = data.iter().map(|item| (scraped_at, item)).collect();
+        self.save_stamped(folder_path, &data, provenance).await
+    }
+
+    /// Save records with the time each was scraped in microseconds, 0 for backfilled records.
+    /// Several versions of an interval are stored in the order they were scraped.
+    async fn save_stamped(&self, folder_path: &str, data: &[(i64, &ScraperData)], provenance: &[Provenance]) -> Result<usize> {
+        let mut rows_written = 0;
+        // Live scrapes are streamed, backfills, rejected records and conflicts only go to Parquet
+        let stream_folder = folder_path.strip_prefix(&format!("{}/", self.base_path))
+            .filter(|folder| !folder.starts_with("rejected/") && !folder.starts_with(&format!("{}/", conflict::CONFLICTS_DIR)))
+            .map(|folder| folder.to_string());
+        let mut stream_records = Vec::new();
+        let tz = self.partition_timezone(folder_path);
+        let granularity = self.partition_granularity(folder_path);
+        // Latest state of every changed partition, to derive the aggregates from
+        let mut aggregate_days = Vec::new();
+        // Changed days of a folder derived series are computed from
+        let mut derived_days = BTreeSet::new();
+        let is_derived_input = folder_path.strip_prefix(&format!("{}/", self.base_path))
+            .is_some_and(|folder| self.derived.iter().any(|d| d.inputs().contains(folder)));
         
         // Separate data by type
-        let mut values_data: Vec<(DateTime<Utc>, DateTime<Utc>, HashMap<String, f64>)> = Vec::new();
-        let mut bids_data: Vec<(DateTime<Utc>, DateTime<Utc>, Bid)> = Vec::new();
+        let mut values_data: Vec<StampedValues> = Vec::new();
+        let default_schema = ValueSchema::default();
+        let value_schema = self.value_schemas.get(folder_path).unwrap_or(&default_schema);
+        let mut bids_data: Vec<(DateTime<Utc>, DateTime<Utc>, i64, Bid)> = Vec::new();
+        // Records with a value that doesn't fit its declared type
+        let mut untyped = Vec::new();
 
-        for item in data {
+        for (scraped_at, item) in data {
             match &item.payload {
                 ScraperPayload::Values(map) => {
-                    values_data.push((item.delivery_from, item.delivery_to, map.clone()));
+                    let typed: Result<HashMap<String, Value>> = map.iter()
+                        .map(|(column, value)| Ok((column.clone(), value_schema.convert(column, *value)?)))
+                        .collect();
+                    match typed {
+                        Ok(typed) => values_data.push((item.delivery_from, item.delivery_to, *scraped_at, typed)),
+                        Err(e) => {
+                            warn!("{}: record of {} doesn't fit the value types: {:?}", folder_path, item.delivery_from, e);
+                            untyped.push((*scraped_at, *item));
+                        }
+                    }
                 }
                 ScraperPayload::Bids(bids) => {
                     for bid in bids {
-                        bids_data.push((item.delivery_from, item.delivery_to, bid.clone()));
+                        bids_data.push((item.delivery_from, item.delivery_to, *scraped_at, bid.clone()));
                     }
                 }
             }
         }
 
         if !values_data.is_empty() {
-            let mut groups: HashMap<(i32, u32, u32), Vec<(DateTime<Utc>, DateTime<Utc>, HashMap<String, f64>)>> = HashMap::new();
-            for (start, end, map) in values_data {
-                let start_cet = start.with_timezone(&Vienna);
-                let year = start_cet.year();
-                let month = start_cet.month();
-                let day = start_cet.day();
-                groups.entry((year, month, day)).or_default().push((start, end, map));
-            }
-
-            for ((year, month, day), group_data) in groups {
-                let folder_path = if let Some(sub) = subfolder {
-                    format!("{}/{}", self.base_path, sub)
-                } else {
-                    format!("{}/{}", self.base_path, name)
-                };
-
-                let file_path = format!("{}/year={}/month={:02}/day={:02}/data.parquet", folder_path, year, month, day);
-                if self.process_values_partition(&file_path, &group_data, set_scraped_at)? {
-                    saved_any = true;
-                    if let Some(dirty) = &self.dirty_files {
-                        dirty.lock().await.insert(file_path);
+            let mut groups: HashMap<NaiveDate, Vec<StampedValues>> = HashMap::new();
+            for (start, end, scraped_at, map) in values_data {
+                let date = granularity.start(start.with_timezone(&tz).date_naive());
+                groups.entry(date).or_default().push((start, end, scraped_at, map));
+            }
+
+            let forecast = self.forecasts.get(folder_path);
+            // The cached state only knows the latest values, not which vintages are stored
+            let has_vintages = forecast.is_some_and(|f| f.has_vintages());
+            for (date, group_data) in groups {
+                let file_path = granularity.path(folder_path, date);
+                let _guard = self.lock_partition(&file_path).await?;
+                self.hydrate(&file_path).await?;
+                if !has_vintages && self.is_cached_unchanged(&file_path, |state| match state {
+                    PartitionState::Values(latest) => !group_data.iter().any(|(start, end, scraped_at, values)| {
+                        value_changed(
+                            latest.get(&(start.timestamp_micros(), end.timestamp_micros())).map(|(scraped_at, values)| (*scraped_at, values)),
+                            values,
+                            *scraped_at != 0,
+                        )
+                    }),
+                    PartitionState::Bids(_) => false,
+                }).await {
+                    continue;
+                }
+
+                let (changed, state) = self.process_values_partition(&file_path, &group_data, value_schema, forecast, provenance)?;
+                if !changed.is_empty() && self.aggregations.contains_key(folder_path) {
+                    aggregate_days.push(state.clone());
+                }
+                if !changed.is_empty() && is_derived_input {
+                    derived_days.extend(changed.iter()
+                        .filter_map(|(start, ..)| DateTime::from_timestamp_micros(*start))
+                        .map(|start| start.with_timezone(&tz).date_naive()));
+                }
+                self.cache_partition(&file_path, PartitionState::Values(state)).await;
+                if !changed.is_empty() {
+                    let changed_rows = changed.len();
+                    rows_written += changed_rows;
+                    if let (Some(_), Some(folder)) = (&self.stream, &stream_folder) {
+                        // Backfilled versions aren't streamed
+                        stream_records.extend(changed.into_iter().filter(|(_, _, scraped_at, _)| *scraped_at != 0).filter_map(|(start, end, scraped_at, values)| Some(StreamRecord {
+                            folder: folder.clone(),
+                            start: DateTime::from_timestamp_micros(start)?,
+                            end: DateTime::from_timestamp_micros(end)?,
+                            values: values.into_iter().collect(),
+                            scraped_at: DateTime::from_timestamp_micros(scraped_at)?,
+                        })));
                     }
+                    self.record_write(file_path, changed_rows, provenance).await?;
                 }
             }
         }
 
+        for state in aggregate_days {
+            // Parquet holds the raw data, failed aggregates are rewritten with the next change of the day
+            if let Err(e) = self.update_aggregates(folder_path, &state, provenance).await {
+                warn!("Failed to update the aggregates of {}: {:?}", folder_path, e);
+            }
+        }
+
+        for date in derived_days {
+            // Like aggregates, a failed derived series is recomputed with the next change of its inputs
+            if let Err(e) = self.update_derived(folder_path, date, provenance).await {
+                warn!("Failed to update the series derived from {}: {:?}", folder_path, e);
+            }
+        }
+
         if !bids_data.is_empty() {
-             let mut groups: HashMap<(i32, u32, u32), Vec<(DateTime<Utc>, DateTime<Utc>, Bid)>> = HashMap::new();
-            for (start, end, bid) in bids_data {
-                let start_cet = start.with_timezone(&Vienna);
-                let year = start_cet.year();
-                let month = start_cet.month();
-                let day = start_cet.day();
-                groups.entry((year, month, day)).or_default().push((start, end, bid));
-            }
-
-            for ((year, month, day), group_data) in groups {
-                let folder_path = if let Some(sub) = subfolder {
-                    format!("{}/{}", self.base_path, sub)
-                } else {
-                    format!("{}/{}", self.base_path, name)
-                };
-
-                let file_path = format!("{}/year={}/month={:02}/day={:02}/data.parquet", folder_path, year, month, day);
-                if self.process_bids_partition(&file_path, &group_data, set_scraped_at)? {
-                    saved_any = true;
-                    if let Some(dirty) = &self.dirty_files {
-                        dirty.lock().await.insert(file_path);
-                    }
+            let mut groups: HashMap<NaiveDate, Vec<(DateTime<Utc>, DateTime<Utc>, i64, Bid)>> = HashMap::new();
+            for (start, end, scraped_at, bid) in bids_data {
+                let date = granularity.start(start.with_timezone(&tz).date_naive());
+                groups.entry(date).or_default().push((start, end, scraped_at, bid));
+            }
+
+            for (date, group_data) in groups {
+                let file_path = granularity.path(folder_path, date);
+                let _guard = self.lock_partition(&file_path).await?;
+                self.hydrate(&file_path).await?;
+                if self.is_cached_unchanged(&file_path, |state| match state {
+                    PartitionState::Bids(latest) => !group_data.iter().any(|(start, end, _, bid)| {
+                        let key = (start.timestamp_micros(), end.timestamp_micros(), format!("{:?}", bid.bid_type), format!("{:?}", bid.direction), bid.rank);
+                        bid_changed(latest.get(&key), bid.price, bid.volume)
+                    }),
+                    PartitionState::Values(_) => false,
+                }).await {
+                    continue;
+                }
+
+                let (written, state) = self.process_bids_partition(&file_path, &group_data, provenance)?;
+                self.cache_partition(&file_path, PartitionState::Bids(state)).await;
+                if written > 0 {
+                    rows_written += written;
+                    self.record_write(file_path, written, provenance).await?;
+                }
+            }
+        }
+
+        // The other records are still written, the rest is quarantined like a validation failure
+        if !untyped.is_empty() {
+            let folder = folder_path.strip_prefix(&format!("{}/", self.base_path)).unwrap_or(folder_path);
+            error!("ALERT: {} records of {} don't fit the value types, storing them in rejected/", untyped.len(), folder);
+            let rejected_path = format!("{}/rejected/{}", self.base_path, folder);
+            let save: Pin<Box<dyn Future<Output = Result<usize>> + Send + '_>> =
+                Box::pin(self.save_stamped(&rejected_path, &untyped, provenance));
+            save.await?;
+        }
+
+        if let Some(stream) = &self.stream {
+            if !stream_records.is_empty() {
+                // Parquet is the source of truth, a failed publish doesn't fail the save
+                if let Err(e) = stream.publish(&stream_records).await {
+                    warn!("Failed to stream {} records of {}: {:?}", stream_records.len(), folder_path, e);
                 }
             }
         }
 
-        Ok(saved_any)
+        Ok(rows_written)
+    }
+
+    /// Recompute the aggregates of a stored day, e.g. after adding an aggregation to a folder with data.
+    /// Returns the number of aggregate rows written.
+    pub async fn aggregate_day(&self, folder: &str, date: NaiveDate) -> Result<usize> {
+        let folder_path = format!("{}/{}", self.base_path, folder);
+        let rows = match self.query().latest(folder, date, date, None)? {
+            QueryResult::Values(rows) => rows,
+            QueryResult::Bids(_) => anyhow::bail!("{} holds bids, only values can be aggregated", folder),
+        };
+        let state: ValuesState = rows.into_iter().map(|row| (
+            (row.start.timestamp_micros(), row.end.timestamp_micros()),
+            (row.scraped_at.map(|t| t.timestamp_micros()).unwrap_or(0), row.values.into_iter().collect()),
+        )).collect();
+        self.update_aggregates(&folder_path, &state, &[]).await
+    }
+
+    /// Aggregates are stamped with the time they were computed, so a recomputed window always
+    /// replaces the previous one, also after a backfill of the raw data
+    async fn update_aggregates(&self, folder_path: &str, state: &ValuesState, provenance: &[Provenance]) -> Result<usize> {
+        let Some(aggregations) = self.aggregations.get(folder_path) else {
+            return Ok(0);
+        };
+        let folder = folder_path.strip_prefix(&format!("{}/", self.base_path)).unwrap_or(folder_path);
+        let tz = self.partition_timezone(folder_path);
+        let intervals: Vec<(DateTime<Utc>, &HashMap<String, Value>)> = state.iter()
+            .filter_map(|((start, _), (_, values))| Some((DateTime::from_timestamp_micros(*start)?, values)))
+            .collect();
+
+        let mut rows_written = 0;
+        for config in aggregations {
+            let data = aggregate::aggregate(config, intervals.iter().copied(), tz)?;
+            let agg_path = format!("{}/{}", self.base_path, config.window.folder(folder));
+            // Boxed since aggregates are saved like any other folder
+            let save: Pin<Box<dyn Future<Output = Result<usize>> + Send + '_>> =
+                Box::pin(self.save_partitions(&agg_path, &data, true, provenance));
+            rows_written += save.await?;
+        }
+        Ok(rows_written)
+    }
+
+    /// Recompute a derived series for a day of its own partitioning, e.g. after adding it to the config.
+    /// Returns the number of rows written.
+    pub async fn derive_day(&self, name: &str, date: NaiveDate) -> Result<usize> {
+        let Some(derived) = self.derived.iter().find(|d| d.config.name == name) else {
+            anyhow::bail!("No derived series named {}", name);
+        };
+        let tz = self.partition_timezone(&format!("{}/{}", self.base_path, derived.config.data_folder()));
+        self.save_derived(derived, date, tz, &[]).await
+    }
+
+    /// Recompute every series derived from a folder for a changed day of that folder
+    async fn update_derived(&self, folder_path: &str, date: NaiveDate, provenance: &[Provenance]) -> Result<usize> {
+        let folder = folder_path.strip_prefix(&format!("{}/", self.base_path)).unwrap_or(folder_path);
+        let tz = self.partition_timezone(folder_path);
+        let mut rows_written = 0;
+        for derived in self.derived.iter().filter(|d| d.inputs().contains(folder)) {
+            rows_written += self.save_derived(derived, date, tz, provenance).await?;
+        }
+        Ok(rows_written)
+    }
+
+    /// Compute the intervals of a derived series starting on the local day `date` in `tz`. The inputs
+    /// are read from the neighbouring days as well, since they may be partitioned in other timezones.
+    async fn save_derived(&self, derived: &Derived, date: NaiveDate, tz: Tz, provenance: &[Provenance]) -> Result<usize> {
+        let (day_start, day_end) = completeness::day_bounds(date, tz)?;
+        let query = self.query();
+        let mut inputs = HashMap::new();
+        for input in derived.inputs() {
+            let from = date.pred_opt().unwrap_or(date);
+            let to = date.succ_opt().unwrap_or(date);
+            let rows = match query.latest(&input, from, to, None)? {
+                QueryResult::Values(rows) => rows,
+                QueryResult::Bids(_) => anyhow::bail!("{} holds bids, derived series need values", input),
+            };
+            inputs.insert(input, rows.into_iter()
+                .filter(|row| row.start >= day_start && row.start < day_end)
+                .map(|row| (row.start, row.end, row.values))
+                .collect());
+        }
+
+        let data = derived.compute(&inputs);
+        let derived_path = format!("{}/{}", self.base_path, derived.config.data_folder());
+        // Boxed since derived series are saved like any other folder and may feed further series
+        let save: Pin<Box<dyn Future<Output = Result<usize>> + Send + '_>> =
+            Box::pin(self.save_partitions(&derived_path, &data, true, provenance));
+        save.await
+    }
+
+    /// Keep a scraper response in `raw/<folder>` so it can be reprocessed later
+    pub async fn archive_raw(&self, name: &str, subfolder: Option<&str>, response: &RawResponse) -> Result<()> {
+        let folder = subfolder.unwrap_or(name);
+        let tz = self.partition_timezone(&format!("{}/{}", self.base_path, folder));
+        let path = raw_archive::write(&self.base_path, folder, tz, response)?;
+        if let Some(dirty) = &self.dirty_files {
+            telemetry::mark_for_upload(&path);
+            dirty.lock().await.insert(path);
+        }
+        Ok(())
+    }
+
+    /// Append a changed partition to its provenance manifest, rewrite its `_manifest.json` and
+    /// mark all three files for upload
+    async fn record_write(&self, file_path: String, rows_written: usize, sources: &[Provenance]) -> Result<()> {
+        let entry = ManifestEntry {
+            written_at: Utc::now(),
+            rows_written,
+            sources: sources.to_vec(),
+        };
+        let manifest = provenance::append_manifest(&file_path, &entry)?;
+        let summary = partition_manifest::write(&file_path)?;
+        if let Some(delta) = &self.delta {
+            delta.record(&file_path).await;
+        }
+        if let Some(dirty) = &self.dirty_files {
+            telemetry::mark_for_upload(&file_path);
+            telemetry::mark_for_upload(&manifest);
+            telemetry::mark_for_upload(&summary);
+            let mut dirty = dirty.lock().await;
+            dirty.insert(file_path);
+            dirty.insert(manifest);
+            dirty.insert(summary);
+        }
+        Ok(())
+    }
+
+    /// Serialize writers of a partition: an async lock for tasks of this process and an advisory
+    /// file lock on `data.parquet.lock` for other processes, e.g. a backfill next to the service
+    async fn lock_partition(&self, file_path: &str) -> Result<PartitionGuard> {
+        let local = {
+            let mut locks = self.partition_locks.lock().unwrap();
+            if locks.len() > 1024 {
+                locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            }
+            locks.entry(file_path.to_string()).or_default().clone()
+        };
+        let local = local.lock_owned().await;
+
+        if let Some(parent) = Path::new(file_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(format!("{}.lock", file_path))?;
+
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(std::fs::TryLockError::WouldBlock) => tokio::time::sleep(std::time::Duration::from_millis(50)).await,
+                Err(std::fs::TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+
+        Ok(PartitionGuard { _local: local, _file: file })
+    }
+
+    /// Check new data against the cached state of a partition, without touching the Parquet file.
+    /// Returns false if the partition isn't cached or the file was modified since it was cached.
+    async fn is_cached_unchanged<F: FnOnce(&PartitionState) -> bool>(&self, file_path: &str, unchanged: F) -> bool {
+        let mut cache = self.cache.lock().await;
+        let cached = match cache.get_mut(file_path) {
+            Some(cached) => cached,
+            None => return false,
+        };
+
+        if file_modified(file_path) != cached.modified {
+            cache.remove(file_path);
+            return false;
+        }
+
+        cached.last_used = Instant::now();
+        unchanged(&cached.state)
+    }
+
+    async fn cache_partition(&self, file_path: &str, state: PartitionState) {
+        let mut cache = self.cache.lock().await;
+
+        if cache.len() >= MAX_CACHED_PARTITIONS && !cache.contains_key(file_path) {
+            let oldest = cache.iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+
+        cache.insert(file_path.to_string(), CachedPartition {
+            state,
+            modified: file_modified(file_path),
+            last_used: Instant::now(),
+        });
+    }
+
+    /// Fetch a partition from S3 if it doesn't exist locally, e.g. after starting with an empty data
+    /// directory. Each partition is only looked up once per process.
+    async fn hydrate(&self, file_path: &str) -> Result<()> {
+        let uploader = match &self.hydration {
+            Some(uploader) => uploader,
+            None => return Ok(()),
+        };
+
+        if Path::new(file_path).exists() || !self.hydrated.lock().await.insert(file_path.to_string()) {
+            return Ok(());
+        }
+
+        match uploader.download(file_path).await {
+            Ok(true) => {
+                info!("Hydrated {} from S3", file_path);
+                // Keep appending to the archived manifest instead of replacing it
+                uploader.download(&provenance::manifest_path(file_path)).await?;
+                Ok(())
+            }
+            Ok(false) => Ok(()),
+            Err(e) => {
+                // Retry on the next save instead of writing a partition without the S3 history
+                self.hydrated.lock().await.remove(file_path);
+                Err(e)
+            }
+        }
     }
 
     pub async fn cleanup(&self, retention_days: u64) -> Result<()> {
+        self.cleanup_path(Path::new(&self.base_path), retention_days, None).await
+    }
+
+    /// Clean up the partitions below one folder of the data directory.
+    /// In archive mode a partition is only deleted once all its files are verified in S3.
+    pub async fn cleanup_folder(&self, folder: &str, retention_days: u64, archive: Option<&Uploader>) -> Result<()> {
+        let path = Path::new(&self.base_path).join(folder);
+        self.cleanup_path(&path, retention_days, archive).await
+    }
+
+    async fn cleanup_path(&self, base: &Path, retention_days: u64, archive: Option<&Uploader>) -> Result<()> {
         let cutoff = Utc::now() - chrono::Duration::days(retention_days as i64);
-        info!("Cleaning up files older than {} days (cutoff: {})", retention_days, cutoff);
+        info!("Cleaning up files in {:?} older than {} days (cutoff: {})", base, retention_days, cutoff);
         
-        let base = Path::new(&self.base_path);
-        if base.exists() {
-            self.cleanup_recursive(base, cutoff)?;
+        if !base.exists() {
+            return Ok(());
         }
+
+        let mut expired = Vec::new();
+        self.find_expired(base, cutoff, &mut expired)?;
+
+        for path in expired {
+            if let Some(uploader) = archive {
+                match self.is_archived(&path, uploader).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        warn!("Not deleting {:?}: not verified in S3", path);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Not deleting {:?}: S3 verification failed: {:?}", path, e);
+                        continue;
+                    }
+                }
+            }
+
+            info!("Deleting old data: {:?}", path);
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        self.remove_empty_dirs(base)?;
         Ok(())
     }
 
-    fn cleanup_recursive(&self, path: &Path, cutoff: DateTime<Utc>) -> Result<()> {
+    /// Delete the oldest partitions of every folder, as long as they are verified in S3, until `enough`
+    /// returns true. Partitions of the last `min_age_days` days are kept. Returns the number of
+    /// deleted partitions.
+    pub async fn emergency_cleanup(&self, uploader: &Uploader, min_age_days: u64, enough: impl Fn() -> bool) -> Result<usize> {
+        let base = Path::new(&self.base_path);
+        if !base.exists() {
+            return Ok(0);
+        }
+        let cutoff = Utc::now() - chrono::Duration::days(min_age_days as i64);
+        let mut candidates = Vec::new();
+        // Only data folders, the raw archive, quarantined records and conflicts aren't hydrated
+        for entry in std::fs::read_dir(base)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            if [raw_archive::RAW_DIR, "rejected", conflict::CONFLICTS_DIR].contains(&name) {
+                continue;
+            }
+            self.find_expired(&path, cutoff, &mut candidates)?;
+        }
+        candidates.sort_by_key(|path| partition::date_range(path).map(|(_, start, _)| start));
+
+        let mut deleted = 0;
+        for path in candidates {
+            if enough() {
+                break;
+            }
+            let file_path = path.join(partition::DATA_FILE).to_string_lossy().to_string();
+            let _guard = self.lock_partition(&file_path).await?;
+            match self.is_archived(&path, uploader).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    warn!("Not deleting {:?}: S3 verification failed: {:?}", path, e);
+                    continue;
+                }
+            }
+            warn!("Emergency cleanup: deleting {:?}", path);
+            std::fs::remove_dir_all(&path)?;
+            self.cache.lock().await.remove(&file_path);
+            deleted += 1;
+        }
+
+        self.remove_empty_dirs(base)?;
+        Ok(deleted)
+    }
+
+    /// Check that every file of a partition exists in S3 with the same size
+    async fn is_archived(&self, path: &Path, uploader: &Uploader) -> Result<bool> {
+        for entry in std::fs::read_dir(path)? {
+            let file_path = entry?.path();
+            if !file_path.is_file() || file_path.extension().map(|e| e == "tmp" || e == "lock").unwrap_or(false) {
+                continue;
+            }
+            if !uploader.is_uploaded(&file_path.to_string_lossy()).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Collect all partition directories whose last local day is older than the cutoff: 'day=DD' directories,
+    /// and 'month=MM' and 'year=YYYY' directories of month and year partitions
+    fn find_expired(&self, path: &Path, cutoff: DateTime<Utc>, expired: &mut Vec<PathBuf>) -> Result<()> {
         if path.is_dir() {
-            // Check if this is a 'day=DD' directory
-            if let Some(day_val) = self.extract_date_part(path, "day=") {
-                if let Some(parent) = path.parent() {
-                    if let Some(month_val) = self.extract_date_part(parent, "month=") {
-                        if let Some(grandparent) = parent.parent() {
-                            if let Some(year_val) = self.extract_date_part(grandparent, "year=") {
-                                if let Some(date) = Vienna.with_ymd_and_hms(year_val, month_val as u32, day_val as u32, 0, 0, 0).single() {
-                                     let cutoff_cet = cutoff.with_timezone(&Vienna);
-                                     // Compare dates only
-                                     if date.date_naive() < cutoff_cet.date_naive() {
-                                         info!("Deleting old data: {:?}", path);
-                                         std::fs::remove_dir_all(path)?;
-                                         return Ok(()); 
-                                     }
-                                }
-                            }
-                        }
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            let is_partition = name.starts_with("day=")
+                || ((name.starts_with("month=") || name.starts_with("year=")) && path.join(partition::DATA_FILE).exists());
+            if is_partition {
+                if let Some((_, _, last_day)) = partition::date_range(path) {
+                    // Compare dates only, on the folder's partition day
+                    let tz = self.partition_timezone(partition::folder_path(path).unwrap_or_default());
+                    if last_day < cutoff.with_timezone(&tz).date_naive() {
+                        expired.push(path.to_path_buf());
                     }
+                    return Ok(());
                 }
             }
             
-            // Read dir again in case we deleted it (though we return above)
-            if path.exists() {
-                for entry in std::fs::read_dir(path)? {
-                    let entry = entry?;
-                    self.cleanup_recursive(&entry.path(), cutoff)?;
-                }
-                
-                // Try to remove empty directories
-                let _ = std::fs::remove_dir(path);
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                self.find_expired(&entry.path(), cutoff, expired)?;
             }
         }
         Ok(())
     }
-    
-    fn extract_date_part(&self, path: &Path, prefix: &str) -> Option<i32> {
-        path.file_name()
-            .and_then(|n| n.to_str())
-            .and_then(|s| s.strip_prefix(prefix))
-            .and_then(|s| s.parse().ok())
+
+    fn remove_empty_dirs(&self, path: &Path) -> Result<()> {
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                self.remove_empty_dirs(&entry.path())?;
+            }
+            
+            // Try to remove empty directories
+            let _ = std::fs::remove_dir(path);
+        }
+        Ok(())
     }
 
-    fn process_values_partition(&self, file_path: &str, data: &[(DateTime<Utc>, DateTime<Utc>, HashMap<String, f64>)], set_scraped_at: bool) -> Result<bool> {
+    fn process_values_partition(&self, file_path: &str, data: &[StampedValues], value_schema: &ValueSchema, forecast: Option<&ForecastConfig>, provenance: &[Provenance]) -> Result<(Vec<(i64, i64, i64, HashMap<String, Value>)>, ValuesState)> {
         let path = Path::new(file_path);
 
         // Create directory if it doesn't exist
@@ -176,137 +1047,163 @@ impl Storage {
             std::fs::create_dir_all(parent)?;
         }
 
-        let mut all_rows: HashMap<(i64, i64), (i64, HashMap<String, f64>)> = HashMap::new();
-        let mut all_columns: HashSet<String> = HashSet::new();
+        // Every stored version of every interval, the scraped_at column records when each version was seen.
+        // Forecast folders also record the publication time of the version, 0 if unknown.
+        let mut history: Vec<(i64, i64, i64, i64, HashMap<String, Value>)> = Vec::new();
+        // Index of the latest version per interval, used for change detection
+        let mut latest: HashMap<(i64, i64), usize> = HashMap::new();
+        // Index of the latest version per interval and publication time
+        let mut vintages: HashMap<(i64, i64, i64), usize> = HashMap::new();
+        let mut has_publication_column = forecast.is_some();
+        // Type of every column, declared types take precedence over the stored ones
+        let mut all_columns: HashMap<String, ValueType> = HashMap::new();
 
         if path.exists() {
-            let file = File::open(path)?;
-            let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
-            let mut reader = builder.build()?;
-            
-            while let Some(batch) = reader.next() {
-                let batch = batch?;
+            for batch in schema::read_partition(path)?.batches {
                 let schema = batch.schema();
                 
                 let start_col = batch.column(0).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
                 let end_col = batch.column(1).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
-                let scraped_at_idx = schema.index_of("scraped_at").ok();
-                let scraped_at_col = if let Some(idx) = scraped_at_idx {
-                    Some(batch.column(idx).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap())
-                } else {
-                    None
-                };
+                let scraped_at_col = batch.column(schema.index_of("scraped_at")?).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
+                let publication_col = schema.index_of(PUBLICATION_COLUMN).ok()
+                    .and_then(|i| batch.column(i).as_any().downcast_ref::<TimestampMicrosecondArray>());
+                has_publication_column |= publication_col.is_some();
 
                 // Identify value columns
                 let mut value_cols = Vec::new();
                 for (i, field) in schema.fields().iter().enumerate() {
                     let name = field.name();
                     if name != "start" && name != "end" && name != "scraped_at" {
-                        all_columns.insert(name.clone());
-                        value_cols.push((name.clone(), batch.column(i).as_any().downcast_ref::<Float64Array>().unwrap()));
+                        if let Some(value_type) = ValueType::from_data_type(field.data_type()) {
+                            all_columns.insert(name.clone(), value_type);
+                            value_cols.push((name.clone(), batch.column(i).clone()));
+                        }
                     }
                 }
 
                 for i in 0..start_col.len() {
                     let start = start_col.value(i);
                     let end = end_col.value(i);
-                    let scraped_at = scraped_at_col.map(|c| c.value(i)).unwrap_or(0);
-                    
-                    let entry = all_rows.entry((start, end)).or_insert((scraped_at, HashMap::new()));
+                    let scraped_at = if scraped_at_col.is_null(i) { 0 } else { scraped_at_col.value(i) };
+                    let publication = publication_col.filter(|c| !c.is_null(i)).map(|c| c.value(i)).unwrap_or(0);
                     
+                    let mut values = HashMap::new();
                     for (name, col) in &value_cols {
-                        if !col.is_null(i) {
-                            entry.1.insert(name.clone(), col.value(i));
+                        if let Some(value) = values::read_value(col.as_ref(), i) {
+                            values.insert(name.clone(), value);
+                        }
+                    }
+
+                    history.push((start, end, scraped_at, publication, values));
+                    let idx = history.len() - 1;
+                    match latest.get(&(start, end)) {
+                        Some(&prev) if (history[prev].3, history[prev].2) > (publication, scraped_at) => {}
+                        _ => {
+                            latest.insert((start, end), idx);
+                        }
+                    }
+                    match vintages.get(&(start, end, publication)) {
+                        Some(&prev) if history[prev].2 > scraped_at => {}
+                        _ => {
+                            vintages.insert((start, end, publication), idx);
                         }
                     }
                 }
             }
         }
 
-        let now_micros = if set_scraped_at {
-            Utc::now().timestamp_micros()
-        } else {
-            0 // null for backfilled data
-        };
-        let mut has_changes = false;
+        // New versions written, with all their columns
+        let mut changed = Vec::new();
 
-        for (start, end, new_values) in data {
+        // Buffered versions of an interval are compared in the order they were scraped
+        let mut data: Vec<&StampedValues> = data.iter().collect();
+        data.sort_by_key(|(_, _, scraped_at, _)| *scraped_at);
+        for &(start, end, now_micros, ref new_values) in data {
             let start_micros = start.timestamp_micros();
             let end_micros = end.timestamp_micros();
+
+            let mut new_values = Cow::Borrowed(new_values);
+            let publication = match forecast {
+                Some(forecast) => forecast.publication_micros(new_values.to_mut(), now_micros),
+                None => 0,
+            };
             
-            for k in new_values.keys() {
-                all_columns.insert(k.clone());
-            }
-
-            let entry = all_rows.entry((start_micros, end_micros)).or_insert((0, HashMap::new()));
-            let (existing_scraped_at, existing_values) = entry;
-
-            let mut changed = false;
-            if *existing_scraped_at == 0 {
-                changed = true;
-            } else {
-                for (k, v) in new_values {
-                    match existing_values.get(k) {
-                        Some(old_v) => {
-                            if (old_v - v).abs() > f64::EPSILON {
-                                changed = true;
-                            }
-                        }
-                        None => changed = true,
-                    }
-                }
+            for (k, v) in new_values.iter() {
+                all_columns.entry(k.clone()).or_insert_with(|| v.value_type());
             }
+            // A vintage is compared to the stored version of the same publication, so republished
+            // values are kept as well
+            let current = match forecast {
+                Some(forecast) if forecast.has_vintages() => vintages.get(&(start_micros, end_micros, publication)),
+                _ => latest.get(&(start_micros, end_micros)),
+            }.map(|&idx| &history[idx]);
 
-            if changed {
-                has_changes = true;
-                *existing_scraped_at = now_micros;
-                for (k, v) in new_values {
-                    existing_values.insert(k.clone(), *v);
+            if value_changed(current.map(|(_, _, scraped_at, _, values)| (*scraped_at, values)), &new_values, now_micros != 0) {
+                // A new version keeps the columns not present in this scrape
+                let mut values = current.map(|(_, _, _, _, v)| v.clone()).unwrap_or_default();
+                for (k, v) in new_values.iter() {
+                    values.insert(k.clone(), v.clone());
+                }
+                changed.push((start_micros, end_micros, now_micros, values.clone()));
+                history.push((start_micros, end_micros, now_micros, publication, values));
+                let idx = history.len() - 1;
+                vintages.insert((start_micros, end_micros, publication), idx);
+                // A late older vintage doesn't replace the latest one
+                match latest.get(&(start_micros, end_micros)) {
+                    Some(&prev) if (history[prev].3, history[prev].2) > (publication, now_micros) => {}
+                    _ => {
+                        latest.insert((start_micros, end_micros), idx);
+                    }
                 }
             }
         }
 
-        if !has_changes {
-            return Ok(false);
+        let state: ValuesState = latest.iter()
+            .map(|(key, &idx)| (*key, (history[idx].2, history[idx].4.clone())))
+            .collect();
+
+        if changed.is_empty() {
+            return Ok((changed, state));
         }
 
-        let mut sorted_columns: Vec<String> = all_columns.into_iter().collect();
-        sorted_columns.sort();
+        // Declared types take precedence, unless a stored or new value can't be converted to
+        // them. The column is then stored as utf8 rather than nulling those values.
+        for (column, t) in all_columns.iter_mut() {
+            let value_type = value_schema.types.get(column).copied().unwrap_or(*t);
+            *t = values::lossless_type(value_type, history.iter().filter_map(|(.., values)| values.get(column)));
+            if *t != value_type {
+                warn!("{}: column {} has values that aren't {:?}, storing it as utf8", file_path, column, value_type);
+            }
+        }
+        let mut sorted_columns: Vec<(String, ValueType)> = all_columns.into_iter().collect();
+        sorted_columns.sort_by(|a, b| a.0.cmp(&b.0));
 
         let mut fields = vec![
             Field::new("start", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
             Field::new("end", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false),
             Field::new("scraped_at", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), true),
         ];
-        for col in &sorted_columns {
-            fields.push(Field::new(col, DataType::Float64, true));
+        if has_publication_column {
+            fields.push(Field::new(PUBLICATION_COLUMN, DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), true));
+        }
+        for (col, value_type) in &sorted_columns {
+            fields.push(Field::new(col, value_type.data_type(), true));
         }
         let schema = Arc::new(Schema::new(fields));
 
-        let mut sorted_rows: Vec<_> = all_rows.into_iter().collect();
-        sorted_rows.sort_by_key(|((start, _), _)| *start);
+        // Versions of the same interval are ordered by scraped_at
+        history.sort_by_key(|(start, end, scraped_at, ..)| (*start, *end, *scraped_at));
 
-        let mut start_builder = TimestampMicrosecondArray::builder(sorted_rows.len());
-        let mut end_builder = TimestampMicrosecondArray::builder(sorted_rows.len());
-        let mut scraped_at_builder = TimestampMicrosecondArray::builder(sorted_rows.len());
+        let mut start_builder = TimestampMicrosecondArray::builder(history.len());
+        let mut end_builder = TimestampMicrosecondArray::builder(history.len());
+        let mut scraped_at_builder = TimestampMicrosecondArray::builder(history.len());
+        let mut publication_builder = TimestampMicrosecondArray::builder(history.len());
         
-        let mut value_builders: Vec<arrow::array::Float64Builder> = Vec::with_capacity(sorted_columns.len());
-        for _ in 0..sorted_columns.len() {
-            value_builders.push(arrow::array::Float64Builder::new());
-        }
-
-        for ((start, end), (scraped_at, values)) in sorted_rows {
-            start_builder.append_value(start);
-            end_builder.append_value(end);
-            scraped_at_builder.append_value(scraped_at);
-
-            for (i, col_name) in sorted_columns.iter().enumerate() {
-                if let Some(val) = values.get(col_name) {
-                    value_builders[i].append_value(*val);
-                } else {
-                    value_builders[i].append_null();
-                }
-            }
+        for (start, end, scraped_at, publication, _) in &history {
+            start_builder.append_value(*start);
+            end_builder.append_value(*end);
+            scraped_at_builder.append_value(*scraped_at);
+            publication_builder.append_option((*publication != 0).then_some(*publication));
         }
 
         let mut columns: Vec<Arc<dyn Array>> = vec![
@@ -314,24 +1211,36 @@ impl Storage {
             Arc::new(end_builder.finish().with_timezone("UTC")),
             Arc::new(scraped_at_builder.finish().with_timezone("UTC")),
         ];
-        for mut builder in value_builders {
-            columns.push(Arc::new(builder.finish()));
+        if has_publication_column {
+            columns.push(Arc::new(publication_builder.finish().with_timezone("UTC")));
+        }
+        for (col, value_type) in &sorted_columns {
+            columns.push(values::build_column(*value_type, history.iter().map(|(.., values)| values.get(col))));
         }
 
         let batch = RecordBatch::try_new(schema.clone(), columns)?;
 
         let tmp_path = format!("{}.tmp", file_path);
         let file = File::create(&tmp_path)?;
-        let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
+        // Rows are sorted by start, end and scraped_at
+        let props = self.parquet.writer_properties(&[0, 1, 2])?;
+        let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(props))?;
+        writer.append_key_value_metadata(schema::version_metadata());
+        if let Some(sources) = provenance::metadata(provenance)? {
+            writer.append_key_value_metadata(sources);
+        }
+        if let Some(units) = self.units_metadata(file_path)? {
+            writer.append_key_value_metadata(units);
+        }
         writer.write(&batch)?;
         writer.close()?;
         
         std::fs::rename(&tmp_path, path)?;
         
-        Ok(true)
+        Ok((changed, state))
     }
 
-    fn process_bids_partition(&self, file_path: &str, data: &[(DateTime<Utc>, DateTime<Utc>, Bid)], set_scraped_at: bool) -> Result<bool> {
+    fn process_bids_partition(&self, file_path: &str, data: &[(DateTime<Utc>, DateTime<Utc>, i64, Bid)], provenance: &[Provenance]) -> Result<(usize, BidsState)> {
         let path = Path::new(file_path);
 
         // Create directory if it doesn't exist
@@ -339,7 +1248,7 @@ impl Storage {
             std::fs::create_dir_all(parent)?;
         }
 
-        let mut latest_values: HashMap<(i64, i64, String, String, i32), (Option<f64>, Option<f64>)> = HashMap::new();
+        let mut latest_values: BidsState = HashMap::new();
         let mut existing_batches = Vec::new();
         
         // Define the target schema
@@ -355,13 +1264,7 @@ impl Storage {
         ]));
 
         if path.exists() {
-            let file = File::open(path)?;
-            let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
-            let mut reader = builder.build()?;
-            
-            while let Some(batch) = reader.next() {
-                let batch = batch?;
-                
+            for batch in schema::read_partition(path)?.batches {
                 // Extract data for deduplication
                 let start_col = batch.column(0).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
                 let end_col = batch.column(1).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
@@ -395,13 +1298,9 @@ impl Storage {
         let mut new_volumes = Vec::new();
         let mut new_scraped_ats = Vec::new();
         
-        let now_micros = if set_scraped_at {
-            Utc::now().timestamp_micros()
-        } else {
-            0 // null for backfilled data
-        };
-
-        for (start, end, bid) in data {
+        let mut data: Vec<_> = data.iter().collect();
+        data.sort_by_key(|(_, _, scraped_at, _)| *scraped_at);
+        for &(start, end, now_micros, ref bid) in data {
             let start_micros = start.timestamp_micros();
             let end_micros = end.timestamp_micros();
             let bid_type = format!("{:?}", bid.bid_type);
@@ -410,22 +1309,7 @@ impl Storage {
             let price = bid.price;
             let volume = bid.volume;
             
-            let is_changed = match latest_values.get(&(start_micros, end_micros, bid_type.clone(), direction.clone(), rank)) {
-                Some((last_price, last_volume)) => {
-                    let price_changed = match (last_price, price) {
-                        (Some(lp), Some(p)) => (lp - p).abs() > f64::EPSILON,
-                        (None, None) => false,
-                        _ => true,
-                    };
-                    let volume_changed = match (last_volume, volume) {
-                        (Some(lv), Some(v)) => (lv - v).abs() > f64::EPSILON,
-                        (None, None) => false,
-                        _ => true,
-                    };
-                    price_changed || volume_changed
-                },
-                None => true,
-            };
+            let is_changed = bid_changed(latest_values.get(&(start_micros, end_micros, bid_type.clone(), direction.clone(), rank)), price, volume);
             
             if is_changed {
                 new_starts.push(start_micros);
@@ -442,8 +1326,9 @@ impl Storage {
         }
 
         if new_starts.is_empty() {
-            return Ok(false);
+            return Ok((0, latest_values));
         }
+        let rows_written = new_starts.len();
 
         let start_array = TimestampMicrosecondArray::from(new_starts).with_timezone("UTC");
         let end_array = TimestampMicrosecondArray::from(new_ends).with_timezone("UTC");
@@ -471,18 +1356,124 @@ impl Storage {
         // Write everything back to a temp file first for atomic updates
         let tmp_path = format!("{}.tmp", file_path);
         let file = File::create(&tmp_path)?;
-        let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
+        // start, end, bid_type, direction, rank, scraped_at
+        let props = self.parquet.writer_properties(&[0, 1, 2, 3, 4, 7])?;
+        let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(props))?;
+        writer.append_key_value_metadata(schema::version_metadata());
+        if let Some(sources) = provenance::metadata(provenance)? {
+            writer.append_key_value_metadata(sources);
+        }
+        if let Some(units) = self.units_metadata(file_path)? {
+            writer.append_key_value_metadata(units);
+        }
 
-        for batch in existing_batches {
-            writer.write(&batch)?;
+        if self.parquet.sorted {
+            let mut batches = existing_batches;
+            batches.push(new_batch);
+            writer.write(&sort_bids(&schema, &batches)?)?;
+        } else {
+            for batch in existing_batches {
+                writer.write(&batch)?;
+            }
+            writer.write(&new_batch)?;
         }
-        writer.write(&new_batch)?;
 
         writer.close()?;
         
         // Atomic rename
         std::fs::rename(&tmp_path, path)?;
         
-        Ok(true)
+        Ok((rows_written, latest_values))
+    }
+}
+
+/// Sort bid rows by interval, bid, and scraped_at
+fn sort_bids(schema: &SchemaRef, batches: &[RecordBatch]) -> Result<RecordBatch> {
+    let batch = concat_batches(schema, batches)?;
+    let sort_columns: Vec<SortColumn> = [0, 1, 2, 3, 4, 7].iter()
+        .map(|&idx| SortColumn { values: batch.column(idx).clone(), options: None })
+        .collect();
+    let indices = lexsort_to_indices(&sort_columns, None)?;
+    Ok(take_record_batch(&batch, &indices)?)
+}
+
+/// Buffer a scraped record as a new version of its interval, unless it matches the latest
+/// buffered version. Values missing from a scrape are carried over from that version.
+/// Returns whether a version was buffered.
+fn buffer_record(records: &mut HashMap<(DateTime<Utc>, DateTime<Utc>), Vec<(i64, ScraperData)>>, scraped_at: i64, mut item: ScraperData) -> bool {
+    let versions = records.entry((item.delivery_from, item.delivery_to)).or_default();
+    if let Some((latest_scraped_at, latest)) = versions.last() {
+        // Backfilled data gets stamped once it is seen by the live service
+        let restamp = *latest_scraped_at == 0 && scraped_at != 0;
+        match (&latest.payload, &mut item.payload) {
+            (ScraperPayload::Values(values), ScraperPayload::Values(new_values)) => {
+                if !restamp && new_values.iter().all(|(k, v)| values.get(k) == Some(v)) {
+                    return false;
+                }
+                for (k, v) in values {
+                    new_values.entry(k.clone()).or_insert_with(|| v.clone());
+                }
+            }
+            (ScraperPayload::Bids(bids), ScraperPayload::Bids(new_bids)) => {
+                if !restamp && bids_unchanged(bids, new_bids) {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+    }
+    versions.push((scraped_at, item));
+    true
+}
+
+fn pending_rows(pending: &PendingRecords) -> usize {
+    pending.values().flat_map(|records| records.values()).map(Vec::len).sum()
+}
+
+/// Whether two scrapes of an interval returned the same bids
+fn bids_unchanged(bids: &[Bid], new_bids: &[Bid]) -> bool {
+    bids.len() == new_bids.len()
+        && bids.iter().zip(new_bids).all(|(bid, new_bid)| {
+            format!("{:?}", bid.bid_type) == format!("{:?}", new_bid.bid_type)
+                && format!("{:?}", bid.direction) == format!("{:?}", new_bid.direction)
+                && bid.rank == new_bid.rank
+                && !bid_changed(Some(&(bid.price, bid.volume)), new_bid.price, new_bid.volume)
+        })
+}
+
+fn file_modified(file_path: &str) -> Option<SystemTime> {
+    std::fs::metadata(file_path).and_then(|m| m.modified()).ok()
+}
+
+/// Whether new values differ from the latest stored version of an interval
+fn value_changed(current: Option<(i64, &HashMap<String, Value>)>, new_values: &HashMap<String, Value>, set_scraped_at: bool) -> bool {
+    match current {
+        None => true,
+        // Backfilled data gets stamped once it is seen by the live service
+        Some((existing_scraped_at, _)) if set_scraped_at && existing_scraped_at == 0 => true,
+        Some((_, existing_values)) => new_values.iter().any(|(k, v)| match existing_values.get(k) {
+            Some(old_v) => old_v.differs(v),
+            None => true,
+        }),
+    }
+}
+
+/// Whether a bid differs from the latest stored price and volume
+fn bid_changed(last: Option<&(Option<f64>, Option<f64>)>, price: Option<f64>, volume: Option<f64>) -> bool {
+    match last {
+        Some((last_price, last_volume)) => {
+            let price_changed = match (last_price, price) {
+                (Some(lp), Some(p)) => (lp - p).abs() > f64::EPSILON,
+                (None, None) => false,
+                _ => true,
+            };
+            let volume_changed = match (last_volume, volume) {
+                (Some(lv), Some(v)) => (lv - v).abs() > f64::EPSILON,
+                (None, None) => false,
+                _ => true,
+            };
+            price_changed || volume_changed
+        },
+        None => true,
     }
 }
@@ -0,0 +1,105 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::values::Value;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamBackendKind {
+    /// Requires building with the `kafka` feature
+    Kafka,
+    /// Requires building with the `nats` feature
+    Nats,
+}
+
+/// Publish every changed value record as soon as it is written, next to the Parquet files
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StreamConfig {
+    pub backend: StreamBackendKind,
+    /// Kafka bootstrap servers or NATS server URL
+    pub url: String,
+    /// Kafka topic, or NATS subject prefix. NATS messages go to `<topic>.<data folder>`.
+    pub topic: String,
+}
+
+/// One new version of an interval
+#[derive(Debug, Serialize, Clone)]
+pub struct StreamRecord {
+    pub folder: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub values: BTreeMap<String, Value>,
+    pub scraped_at: DateTime<Utc>,
+}
+
+pub enum StreamSink {
+    #[cfg(feature = "kafka")]
+    Kafka {
+        producer: rdkafka::producer::FutureProducer,
+        topic: String,
+    },
+    #[cfg(feature = "nats")]
+    Nats {
+        client: async_nats::Client,
+        subject: String,
+    },
+}
+
+impl StreamSink {
+    pub async fn connect(config: &StreamConfig) -> Result<Self> {
+        match config.backend {
+            #[cfg(feature = "kafka")]
+            StreamBackendKind::Kafka => {
+                use anyhow::Context;
+
+                let producer = rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", &config.url)
+                    .set("linger.ms", "5")
+                    .create()
+                    .context("Failed to create Kafka producer")?;
+                Ok(StreamSink::Kafka { producer, topic: config.topic.clone() })
+            }
+            #[cfg(feature = "nats")]
+            StreamBackendKind::Nats => {
+                let client = async_nats::connect(&config.url).await?;
+                Ok(StreamSink::Nats { client, subject: config.topic.clone() })
+            }
+            #[allow(unreachable_patterns)]
+            backend => bail!("The {:?} stream backend requires building with the matching feature", backend),
+        }
+    }
+
+    /// Publish records in order. Stops at the first failure.
+    pub async fn publish(&self, records: &[StreamRecord]) -> Result<()> {
+        // Without a backend feature the sink has no variants and can't be constructed
+        #[cfg(not(any(feature = "kafka", feature = "nats")))]
+        let _ = records;
+        #[cfg(any(feature = "kafka", feature = "nats"))]
+        for record in records {
+            let payload = serde_json::to_vec(record)?;
+            match self {
+                #[cfg(feature = "kafka")]
+                StreamSink::Kafka { producer, topic } => {
+                    use rdkafka::producer::FutureRecord;
+
+                    // Keyed by folder so the records of one series stay ordered
+                    let message = FutureRecord::to(topic).key(&record.folder).payload(&payload);
+                    producer.send(message, std::time::Duration::from_secs(10)).await
+                        .map_err(|(e, _)| e)?;
+                }
+                #[cfg(feature = "nats")]
+                StreamSink::Nats { client, subject } => {
+                    let subject = format!("{}.{}", subject, record.folder.replace('/', "."));
+                    client.publish(subject, payload.into()).await?;
+                }
+            }
+        }
+        #[cfg(feature = "nats")]
+        if let StreamSink::Nats { client, .. } = self {
+            client.flush().await?;
+        }
+        Ok(())
+    }
+}
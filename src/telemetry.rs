@@ -0,0 +1,135 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::env;
+use tracing::Span;
+use tracing_subscriber::{Layer, Registry};
+
+/// OpenTelemetry trace export over OTLP, requires building with `--features otel`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TelemetryConfig {
+    /// OTLP gRPC endpoint, overridden by the OTEL_EXPORTER_OTLP_ENDPOINT env var
+    #[serde(default = "default_endpoint")]
+    pub otlp_endpoint: String,
+    /// `service.name` of the exported spans
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Share of traces to export, 0.0 to 1.0
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+fn default_endpoint() -> String {
+    "http://localhost:4317".to_string()
+}
+
+fn default_service_name() -> String {
+    "scraping_service".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+impl TelemetryConfig {
+    pub fn get_otlp_endpoint(&self) -> String {
+        env::var("OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or_else(|_| self.otlp_endpoint.clone())
+    }
+}
+
+/// Keeps the exporter running, exports the remaining spans when dropped
+pub struct TracerGuard {
+    #[cfg(feature = "otel")]
+    provider: opentelemetry_sdk::trace::TracerProvider,
+}
+
+impl Drop for TracerGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to export the remaining spans: {:?}", e);
+        }
+    }
+}
+
+/// Layer that exports spans to the OTLP endpoint. Must be called inside the tokio runtime.
+#[cfg(feature = "otel")]
+pub fn layer(config: &TelemetryConfig) -> Result<(Box<dyn Layer<Registry> + Send + Sync>, TracerGuard)> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+    use opentelemetry_sdk::{runtime, Resource};
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(config.get_otlp_endpoint())
+        .build()?;
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio.clamp(0.0, 1.0))));
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_sampler(sampler)
+        .with_resource(Resource::new(vec![KeyValue::new("service.name", config.service_name.clone())]))
+        .build();
+    let tracer = provider.tracer("scraping_service");
+
+    Ok((tracing_opentelemetry::layer().with_tracer(tracer).boxed(), TracerGuard { provider }))
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn layer(_config: &TelemetryConfig) -> Result<(Box<dyn Layer<Registry> + Send + Sync>, TracerGuard)> {
+    anyhow::bail!("The telemetry section requires building with --features otel")
+}
+
+#[cfg(feature = "otel")]
+mod links {
+    use opentelemetry::trace::{SpanContext, TraceContextExt};
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+    use tracing::Span;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    /// Links kept per file, a file that can't be uploaded for a long time drops the oldest
+    const MAX_LINKS: usize = 32;
+
+    fn pending() -> &'static Mutex<HashMap<String, Vec<SpanContext>>> {
+        static PENDING: OnceLock<Mutex<HashMap<String, Vec<SpanContext>>>> = OnceLock::new();
+        PENDING.get_or_init(Default::default)
+    }
+
+    pub fn mark_for_upload(file_path: &str) {
+        let context = Span::current().context().span().span_context().clone();
+        if !context.is_valid() {
+            return;
+        }
+        let mut pending = pending().lock().unwrap_or_else(|e| e.into_inner());
+        let links = pending.entry(file_path.to_string()).or_default();
+        if links.len() == MAX_LINKS {
+            links.remove(0);
+        }
+        links.push(context);
+    }
+
+    pub fn link_upload(span: &Span, file_path: &str) {
+        let links = pending().lock().unwrap_or_else(|e| e.into_inner()).remove(file_path);
+        for context in links.into_iter().flatten() {
+            span.add_link(context);
+        }
+    }
+}
+
+/// Remember the current trace for a file marked for upload. Uploads are batched
+/// and run in their own trace, linked back to the runs that wrote the file.
+pub fn mark_for_upload(file_path: &str) {
+    #[cfg(feature = "otel")]
+    links::mark_for_upload(file_path);
+    #[cfg(not(feature = "otel"))]
+    let _ = file_path;
+}
+
+/// Link the upload span of a file to the traces that wrote it
+pub fn link_upload(span: &Span, file_path: &str) {
+    #[cfg(feature = "otel")]
+    links::link_upload(span, file_path);
+    #[cfg(not(feature = "otel"))]
+    let _ = (span, file_path);
+}
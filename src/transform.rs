@@ -0,0 +1,147 @@
+use anyhow::{bail, Result};
+use parquet::format::KeyValue;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+/// Parquet key-value metadata entry holding the unit per column as a JSON object
+pub const UNITS_KEY: &str = "scraping_service.units";
+
+/// Conversion of a column's scraped values to the values that are stored
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct TransformConfig {
+    /// Multiply by this factor
+    pub scale: Option<f64>,
+    /// Unit the scraper returns, converted to `unit`, e.g. `kW` to `MW`
+    pub from_unit: Option<String>,
+    /// Unit of the stored values, recorded in the Parquet metadata
+    pub unit: Option<String>,
+    /// Flip the sign, e.g. for a source that counts feed-in as negative
+    #[serde(default)]
+    pub invert: bool,
+}
+
+impl TransformConfig {
+    /// Combined factor of scale, unit conversion and sign
+    pub fn factor(&self) -> Result<f64> {
+        let mut factor = self.scale.unwrap_or(1.0);
+        if let Some(from) = &self.from_unit {
+            let Some(to) = &self.unit else {
+                bail!("from_unit {} requires a unit to convert to", from);
+            };
+            factor *= conversion_factor(from, to)?;
+        }
+        if self.invert {
+            factor = -factor;
+        }
+        Ok(factor)
+    }
+}
+
+/// Transforms of one scraper, applied before validation and storage.
+/// Bid prices and volumes are transformed by the `price` and `volume` entries.
+#[derive(Debug, Clone, Default)]
+pub struct Transforms {
+    factors: HashMap<String, f64>,
+}
+
+impl Transforms {
+    pub fn new(config: &HashMap<String, TransformConfig>) -> Result<Self> {
+        let mut factors = HashMap::new();
+        for (column, transform) in config {
+            let factor = transform.factor().map_err(|e| anyhow::anyhow!("Transform of {}: {}", column, e))?;
+            if factor != 1.0 {
+                factors.insert(column.clone(), factor);
+            }
+        }
+        Ok(Self { factors })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.factors.is_empty()
+    }
+
+    pub fn apply(&self, mut data: Vec<ScraperData>) -> Vec<ScraperData> {
+        if self.is_empty() {
+            return data;
+        }
+        for item in &mut data {
+            match &mut item.payload {
+                ScraperPayload::Values(values) => {
+                    for (column, value) in values.iter_mut() {
+                        if let Some(factor) = self.factors.get(column) {
+                            *value *= factor;
+                        }
+                    }
+                }
+                ScraperPayload::Bids(bids) => {
+                    let price = self.factors.get("price");
+                    let volume = self.factors.get("volume");
+                    for bid in bids {
+                        if let (Some(p), Some(factor)) = (bid.price.as_mut(), price) {
+                            *p *= factor;
+                        }
+                        if let (Some(v), Some(factor)) = (bid.volume.as_mut(), volume) {
+                            *v *= factor;
+                        }
+                    }
+                }
+            }
+        }
+        data
+    }
+}
+
+/// Stored unit per column, for the Parquet metadata
+pub fn units(config: &HashMap<String, TransformConfig>) -> BTreeMap<String, String> {
+    config.iter()
+        .filter_map(|(column, transform)| Some((column.clone(), transform.unit.clone()?)))
+        .collect()
+}
+
+/// Units of a partition's columns as Parquet key-value metadata
+pub fn metadata(units: &BTreeMap<String, String>) -> Result<Option<KeyValue>> {
+    if units.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(KeyValue::new(UNITS_KEY.to_string(), serde_json::to_string(units)?)))
+}
+
+/// Factor converting a value in `from` to `to`. Supports power (W to GW), energy (Wh to GWh),
+/// currency (EUR, ct) and ratios of them like `EUR/MWh` to `ct/kWh`.
+pub fn conversion_factor(from: &str, to: &str) -> Result<f64> {
+    let (Some((from_base, from_factor)), Some((to_base, to_factor))) = (parse_unit(from), parse_unit(to)) else {
+        bail!("Can't convert {} to {}, unknown unit", from, to);
+    };
+    if from_base != to_base {
+        bail!("Can't convert {} to {}", from, to);
+    }
+    Ok(from_factor / to_factor)
+}
+
+/// Base unit and the factor to it, e.g. `kW` is (`W`, 1000)
+fn parse_unit(unit: &str) -> Option<(String, f64)> {
+    if let Some((numerator, denominator)) = unit.split_once('/') {
+        let (numerator_base, numerator_factor) = parse_unit(numerator)?;
+        let (denominator_base, denominator_factor) = parse_unit(denominator)?;
+        return Some((format!("{}/{}", numerator_base, denominator_base), numerator_factor / denominator_factor));
+    }
+    match unit.trim() {
+        "EUR" => return Some(("EUR".to_string(), 1.0)),
+        "ct" => return Some(("EUR".to_string(), 0.01)),
+        _ => {}
+    }
+    for base in ["Wh", "W"] {
+        if let Some(prefix) = unit.trim().strip_suffix(base) {
+            let factor = match prefix {
+                "" => 1.0,
+                "k" => 1e3,
+                "M" => 1e6,
+                "G" => 1e9,
+                _ => return None,
+            };
+            return Some((base.to_string(), factor));
+        }
+    }
+    None
+}
@@ -1,121 +1,642 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use aws_sdk_s3::Client;
 use aws_sdk_s3::config::Credentials;
+use aws_sdk_s3::types::{ServerSideEncryption, StorageClass};
 use aws_config::Region;
-use std::collections::HashSet;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_smithy_types::body::SdkBody;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::env;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::time::sleep;
-use tracing::{info, warn};
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::config::{AppConfig, DEFAULT_BASE_PATH};
+use crate::notify::Notifier;
+use crate::partition;
+use crate::rate_limit::ThrottledBody;
+use crate::secrets;
+use crate::telemetry;
+
+/// Options applied to every uploaded object
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct UploadConfig {
+    /// `AES256` or `aws:kms`
+    pub server_side_encryption: Option<String>,
+    /// KMS key for `aws:kms`, the bucket default key when not set
+    pub kms_key_id: Option<String>,
+    /// e.g. `STANDARD` or `STANDARD_IA`
+    pub storage_class: Option<String>,
+    /// Storage class for partitions older than `old_after_days`
+    pub old_storage_class: Option<String>,
+    pub old_after_days: Option<i64>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// Also send a SHA-256 checksum, for gateways that support `x-amz-checksum-sha256`.
+    /// Content-MD5 is always sent.
+    #[serde(default)]
+    pub checksum_sha256: bool,
+    /// Upload rate limit, request bodies are sent no faster than this
+    pub max_bytes_per_second: Option<u64>,
+    /// Only upload during these hours of `upload_timezone`, any time when empty
+    #[serde(default)]
+    pub upload_windows: Vec<UploadWindow>,
+    /// Timezone of the upload window hours, default Europe/Vienna
+    pub upload_timezone: Option<String>,
+}
+
+/// Another bucket every file is uploaded to as well, e.g. in another region for disaster recovery
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReplicaConfig {
+    pub name: String,
+    pub bucket: String,
+    pub region: Option<String>,
+    pub endpoint: Option<String>,
+    /// Key prefix, the primary prefix if not set
+    pub prefix: Option<String>,
+    /// Upload options, the primary ones if not set
+    pub upload: Option<UploadConfig>,
+}
+
+/// Daily range of hours, `start_hour` inclusive and `end_hour` exclusive.
+/// Wraps past midnight when `end_hour` is before `start_hour`, e.g. 18 to 8.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UploadWindow {
+    pub start_hour: u32,
+    pub end_hour: u32,
+}
+
+impl UploadWindow {
+    fn contains(&self, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            hour >= self.start_hour && hour < self.end_hour
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+impl UploadConfig {
+    /// Check the options when the config is loaded
+    pub fn validate(&self) -> Result<()> {
+        if let Some(tz) = &self.upload_timezone {
+            partition::parse_timezone(tz)?;
+        }
+        Ok(())
+    }
+
+    fn storage_class_for(&self, file_path: &str) -> Option<&str> {
+        if let (Some(class), Some(days)) = (&self.old_storage_class, self.old_after_days) {
+            // Age of the newest day in the partition, so month and year partitions move once complete
+            if let Some((_, _, last_day)) = partition::date_range(Path::new(file_path)) {
+                if (Utc::now().date_naive() - last_day).num_days() > days {
+                    return Some(class);
+                }
+            }
+        }
+        self.storage_class.as_deref()
+    }
+
+    /// Whether uploads are allowed right now
+    fn in_upload_window(&self) -> bool {
+        self.in_upload_window_at(Utc::now())
+    }
+
+    /// Whether uploads are allowed at `now`
+    pub fn in_upload_window_at(&self, now: DateTime<Utc>) -> bool {
+        if self.upload_windows.is_empty() {
+            return true;
+        }
+        let tz = self.upload_timezone.as_deref()
+            .and_then(|tz| partition::parse_timezone(tz).ok())
+            .unwrap_or(Tz::Europe__Vienna);
+        let hour = now.with_timezone(&tz).hour();
+        self.upload_windows.iter().any(|w| w.contains(hour))
+    }
+
+    fn tagging(&self) -> Option<String> {
+        if self.tags.is_empty() {
+            return None;
+        }
+        Some(url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&self.tags)
+            .finish())
+    }
+}
+
+/// An object, or an older version of one, below the prefix of a bucket
+#[derive(Debug, Clone)]
+pub struct RemoteObject {
+    /// Key relative to the prefix, i.e. the path below the data directory
+    pub path: String,
+    /// Set for older versions in a bucket with versioning
+    pub version_id: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub size: i64,
+}
 
 pub struct Uploader {
+    /// `primary` or the name of the replica
+    name: String,
     client: Client,
     bucket: String,
     prefix: String,
+    /// Local data directory, stripped from file paths to get their key
+    base_path: String,
     pending_files: Arc<Mutex<HashSet<String>>>,
+    /// Files that failed to upload to this destination or were deferred to the next upload window
+    retry_files: Mutex<HashSet<String>>,
+    options: UploadConfig,
+    notifier: Option<Arc<Notifier>>,
+    /// Further destinations, each with its own queue so a failing one doesn't hold back the others
+    replicas: Vec<Uploader>,
 }
 
 impl Uploader {
     pub async fn new(bucket: String, region: Option<String>, endpoint: Option<String>, prefix: String) -> Result<Self> {
-        let region = region.unwrap_or_else(|| "eu-central".to_string());
-        
-        let mut s3_config_builder = aws_sdk_s3::config::Builder::new()
-            .region(Region::new(region))
-            .behavior_version_latest();
-        
-        // For S3-compatible services like Hetzner Object Storage
-        if let Some(endpoint_url) = endpoint {
-            s3_config_builder = s3_config_builder
-                .endpoint_url(endpoint_url)
-                .force_path_style(true); // Required for most S3-compatible services
-        }
-        
-        // Try custom S3_* env vars first, then fall back to AWS_* env vars
-        let access_key = env::var("S3_ACCESS_KEY")
-            .or_else(|_| env::var("AWS_ACCESS_KEY_ID"));
-        let secret_key = env::var("S3_SECRET_KEY")
-            .or_else(|_| env::var("AWS_SECRET_ACCESS_KEY"));
-        
-        if let (Ok(access), Ok(secret)) = (access_key, secret_key) {
-            let credentials = Credentials::new(access, secret, None, None, "env");
-            s3_config_builder = s3_config_builder.credentials_provider(credentials);
-        } else {
-            // Fall back to default AWS credential chain
-            let shared_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-            if let Some(credentials_provider) = shared_config.credentials_provider() {
-                s3_config_builder = s3_config_builder.credentials_provider(credentials_provider);
-            }
-        }
-        
-        let client = Client::from_conf(s3_config_builder.build());
+        let client = s3_client(region, endpoint).await;
         
         Ok(Self {
+            name: "primary".to_string(),
             client,
             bucket,
             prefix,
+            base_path: DEFAULT_BASE_PATH.to_string(),
             pending_files: Arc::new(Mutex::new(HashSet::new())),
+            retry_files: Mutex::new(HashSet::new()),
+            options: UploadConfig::default(),
+            notifier: None,
+            replicas: Vec::new(),
         })
     }
 
+    /// Uploader to the configured bucket with its upload options and replicas, None without a bucket
+    pub async fn from_config(config: &AppConfig) -> Result<Option<Self>> {
+        let Some(bucket) = config.get_s3_bucket() else {
+            return Ok(None);
+        };
+        let uploader = Uploader::new(
+            bucket,
+            config.get_s3_region(),
+            config.get_s3_endpoint(),
+            config.get_s3_prefix(),
+        ).await?.with_base_path(config.base_path())
+            .with_options(config.upload.clone())
+            .with_replicas(&config.replicas).await?;
+        Ok(Some(uploader))
+    }
+
+    /// Also upload every file to these buckets, with the same credentials as the primary bucket
+    pub async fn with_replicas(mut self, replicas: &[ReplicaConfig]) -> Result<Self> {
+        for replica in replicas {
+            let mut uploader = Uploader::new(
+                replica.bucket.clone(),
+                replica.region.clone(),
+                replica.endpoint.clone(),
+                replica.prefix.clone().unwrap_or_else(|| self.prefix.clone()),
+            ).await?.with_options(replica.upload.clone().unwrap_or_else(|| self.options.clone()));
+            uploader.name = replica.name.clone();
+            uploader.base_path = self.base_path.clone();
+            self.replicas.push(uploader);
+        }
+        Ok(self)
+    }
+
+    /// Upload files of another data directory than `data`, e.g. of a tenant
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        self.base_path = base_path.to_string();
+        for replica in &mut self.replicas {
+            replica.base_path = base_path.to_string();
+        }
+        self
+    }
+
+    pub fn with_options(mut self, options: UploadConfig) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Announce every partition uploaded by `run`
+    pub fn with_notifier(mut self, notifier: Arc<Notifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     pub fn get_pending_files_handle(&self) -> Arc<Mutex<HashSet<String>>> {
         self.pending_files.clone()
     }
 
     pub async fn run(&self) {
         info!("Starting S3 uploader for bucket: {}", self.bucket);
+        for replica in &self.replicas {
+            info!("Replicating uploads to {} (bucket: {})", replica.name, replica.bucket);
+        }
         
         loop {
             sleep(Duration::from_secs(60)).await;
-            
-            let files_to_upload = {
-                let mut pending = self.pending_files.lock().await;
-                let files: Vec<String> = pending.drain().collect();
-                files
-            };
-
-            if files_to_upload.is_empty() {
-                continue;
+            self.upload_pending(false).await;
+        }
+    }
+
+    /// Upload all pending files now. Outside the upload windows files are kept for later
+    /// unless `force` is set. Returns the number of files uploaded to the primary bucket.
+    pub async fn upload_pending(&self, force: bool) -> usize {
+        let new_files: HashSet<String> = self.pending_files.lock().await.drain().collect();
+        let mut uploaded = 0;
+        for (index, destination) in std::iter::once(self).chain(&self.replicas).enumerate() {
+            let mut files = new_files.clone();
+            files.extend(destination.retry_files.lock().await.drain());
+            let count = destination.upload_files(files, force).await;
+            if index == 0 {
+                uploaded = count;
             }
+        }
+        uploaded
+    }
+
+    /// Upload files to this destination, failed ones are retried in the next cycle
+    async fn upload_files(&self, files_to_upload: HashSet<String>, force: bool) -> usize {
+
+        if files_to_upload.is_empty() {
+            return 0;
+        }
+
+        info!("Uploading {} files to S3 ({})", files_to_upload.len(), self.name);
 
-            info!("Uploading {} files to S3", files_to_upload.len());
+        let mut uploaded = 0;
+        let mut failed_uploads = Vec::new();
+        let mut deferred = 0;
 
-            let mut failed_uploads = Vec::new();
+        for file_path in files_to_upload {
+            // Keep the rest for the next window
+            if !force && !self.options.in_upload_window() {
+                deferred += 1;
+                failed_uploads.push(file_path);
+                continue;
+            }
 
-            for file_path in files_to_upload {
-                if let Err(e) = self.upload_file(&file_path).await {
-                    warn!("Failed to upload {}: {:?}. Will retry in next cycle.", file_path, e);
+            let span = info_span!("upload", file = %file_path, destination = %self.name);
+            telemetry::link_upload(&span, &file_path);
+            match self.put_file(&file_path).instrument(span).await {
+                Ok(()) => {
+                    uploaded += 1;
+                    self.notify(&file_path).await;
+                }
+                Err(e) => {
+                    warn!("Failed to upload {} to {}: {:?}. Will retry in next cycle.", file_path, self.name, e);
                     failed_uploads.push(file_path);
                 }
             }
+        }
+
+        if deferred > 0 {
+            info!("Outside the upload window, deferred {} files", deferred);
+        }
+
+        if !failed_uploads.is_empty() {
+            self.retry_files.lock().await.extend(failed_uploads);
+        }
+        uploaded
+    }
+
+    async fn notify(&self, file_path: &str) {
+        let Some(notifier) = &self.notifier else {
+            return;
+        };
+        // Sidecar files like provenance manifests aren't partitions
+        if !file_path.ends_with(".parquet") {
+            return;
+        }
+        match self.key_for(file_path) {
+            Ok(key) => {
+                let event = notifier.event_for(file_path, &self.bucket, &key, partition::date_range(Path::new(file_path)).map(|(_, first_day, _)| first_day));
+                notifier.notify(&event).await;
+            }
+            Err(e) => warn!("No notification for {}: {:?}", file_path, e),
+        }
+    }
 
-            if !failed_uploads.is_empty() {
-                let mut pending = self.pending_files.lock().await;
-                for file_path in failed_uploads {
-                    pending.insert(file_path);
+    /// Number of files waiting for the next upload cycle, for the destination with the longest queue
+    pub async fn pending_count(&self) -> usize {
+        let mut retries = self.retry_files.lock().await.len();
+        for replica in &self.replicas {
+            retries = retries.max(replica.retry_files.lock().await.len());
+        }
+        self.pending_files.lock().await.len() + retries
+    }
+
+    /// Check whether the S3 copy of a local path exists, whether or not the local file does
+    pub async fn exists(&self, file_path: &str) -> Result<bool> {
+        let key = self.key_for(file_path)?;
+
+        match self.client.head_object().bucket(&self.bucket).key(&key).send().instrument(s3_span("HeadObject", &key)).await {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let service_error = e.into_service_error();
+                if service_error.is_not_found() {
+                    Ok(false)
+                } else {
+                    Err(service_error.into())
+                }
+            }
+        }
+    }
+
+    /// Check whether a local file exists with the same size in the primary bucket and every replica
+    pub async fn is_uploaded(&self, file_path: &str) -> Result<bool> {
+        if !self.is_uploaded_here(file_path).await? {
+            return Ok(false);
+        }
+        for replica in &self.replicas {
+            if !replica.is_uploaded_here(file_path).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn is_uploaded_here(&self, file_path: &str) -> Result<bool> {
+        let key = self.key_for(file_path)?;
+        let local_size = std::fs::metadata(file_path)?.len();
+
+        match self.client.head_object().bucket(&self.bucket).key(&key).send().instrument(s3_span("HeadObject", &key)).await {
+            Ok(output) => Ok(output.content_length() == Some(local_size as i64)),
+            Err(e) => {
+                let service_error = e.into_service_error();
+                if service_error.is_not_found() {
+                    Ok(false)
+                } else {
+                    Err(service_error.into())
+                }
+            }
+        }
+    }
+
+    /// Download the S3 copy of a local file. Returns false if it doesn't exist in S3.
+    pub async fn download(&self, file_path: &str) -> Result<bool> {
+        let key = self.key_for(file_path)?;
+
+        let output = match self.client.get_object().bucket(&self.bucket).key(&key).send().instrument(s3_span("GetObject", &key)).await {
+            Ok(output) => output,
+            Err(e) => {
+                let service_error = e.into_service_error();
+                if service_error.is_no_such_key() {
+                    return Ok(false);
+                }
+                return Err(service_error.into());
+            }
+        };
+
+        let bytes = output.body.collect().await?.into_bytes();
+
+        if let Some(parent) = Path::new(file_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = format!("{}.tmp", file_path);
+        std::fs::write(&tmp_path, &bytes)?;
+        std::fs::rename(&tmp_path, file_path)?;
+
+        info!("Downloaded {}", key);
+        Ok(true)
+    }
+
+    /// Every object below the prefix of the primary bucket
+    pub async fn list_objects(&self) -> Result<Vec<RemoteObject>> {
+        let mut objects = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let output = self.client.list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(&self.prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .instrument(s3_span("ListObjectsV2", &self.prefix))
+                .await
+                .map_err(|e| e.into_service_error())?;
+            for object in output.contents() {
+                let Some(path) = object.key().and_then(|key| key.strip_prefix(&self.prefix)) else {
+                    continue;
+                };
+                objects.push(RemoteObject {
+                    path: path.to_string(),
+                    version_id: None,
+                    last_modified: object.last_modified().and_then(|t| DateTime::from_timestamp(t.secs(), t.subsec_nanos())),
+                    size: object.size().unwrap_or_default(),
+                });
+            }
+            match output.next_continuation_token() {
+                Some(token) if output.is_truncated() == Some(true) => continuation_token = Some(token.to_string()),
+                _ => return Ok(objects),
+            }
+        }
+    }
+
+    /// Versions below the prefix of the primary bucket that were overwritten or deleted, for
+    /// buckets with versioning. Empty for buckets without.
+    pub async fn list_noncurrent_versions(&self) -> Result<Vec<RemoteObject>> {
+        let mut versions = Vec::new();
+        let mut key_marker = None;
+        let mut version_id_marker = None;
+        loop {
+            let output = self.client.list_object_versions()
+                .bucket(&self.bucket)
+                .prefix(&self.prefix)
+                .set_key_marker(key_marker)
+                .set_version_id_marker(version_id_marker)
+                .send()
+                .instrument(s3_span("ListObjectVersions", &self.prefix))
+                .await
+                .map_err(|e| e.into_service_error())?;
+            for version in output.versions().iter().filter(|v| v.is_latest() == Some(false)) {
+                let Some(path) = version.key().and_then(|key| key.strip_prefix(&self.prefix)) else {
+                    continue;
+                };
+                versions.push(RemoteObject {
+                    path: path.to_string(),
+                    version_id: version.version_id().map(str::to_string),
+                    last_modified: version.last_modified().and_then(|t| DateTime::from_timestamp(t.secs(), t.subsec_nanos())),
+                    size: version.size().unwrap_or_default(),
+                });
+            }
+            if output.is_truncated() != Some(true) {
+                return Ok(versions);
+            }
+            key_marker = output.next_key_marker().map(str::to_string);
+            version_id_marker = output.next_version_id_marker().map(str::to_string);
+        }
+    }
+
+    /// Delete an object, or one version of it, from the primary bucket
+    pub async fn delete_object(&self, object: &RemoteObject) -> Result<()> {
+        let key = format!("{}{}", self.prefix, object.path);
+        self.client.delete_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .set_version_id(object.version_id.clone())
+            .send()
+            .instrument(s3_span("DeleteObject", &key))
+            .await
+            .map_err(|e| e.into_service_error())?;
+        Ok(())
+    }
+
+    fn key_for(&self, file_path: &str) -> Result<String> {
+        let path = Path::new(file_path);
+        let relative_path = path.strip_prefix(&self.base_path)?.to_string_lossy();
+        Ok(format!("{}{}", self.prefix, relative_path))
+    }
+
+    /// Upload a file to the primary bucket and every replica. All destinations are tried,
+    /// the first failure is returned.
+    pub async fn upload_file(&self, file_path: &str) -> Result<()> {
+        let mut result = self.put_file(file_path).await;
+        for replica in &self.replicas {
+            if let Err(e) = replica.put_file(file_path).await {
+                warn!("Failed to upload {} to {}: {:?}", file_path, replica.name, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Delete the S3 copy of a local path from the primary bucket and every replica. All
+    /// destinations are tried, the first failure is returned.
+    pub async fn delete_file(&self, file_path: &str) -> Result<()> {
+        let object = RemoteObject {
+            path: Path::new(file_path).strip_prefix(&self.base_path)?.to_string_lossy().replace('\\', "/"),
+            version_id: None,
+            last_modified: None,
+            size: 0,
+        };
+        let mut result = self.delete_object(&object).await;
+        for replica in &self.replicas {
+            if let Err(e) = replica.delete_object(&object).await {
+                warn!("Failed to delete {} from {}: {:?}", file_path, replica.name, e);
+                if result.is_ok() {
+                    result = Err(e);
                 }
             }
         }
+        result
     }
 
-    async fn upload_file(&self, file_path: &str) -> Result<()> {
+    async fn put_file(&self, file_path: &str) -> Result<()> {
         let path = Path::new(file_path);
-        let relative_path = path.strip_prefix("data/")?.to_string_lossy();
-        let key = format!("{}{}", self.prefix, relative_path);
+        let key = self.key_for(file_path)?;
         
-        let body = aws_sdk_s3::primitives::ByteStream::from_path(path).await?;
+        // Read once so the checksums match exactly what is sent
+        let bytes = std::fs::read(path)?;
+        let md5 = Md5::digest(&bytes);
+        let sha256 = self.options.checksum_sha256.then(|| BASE64.encode(Sha256::digest(&bytes)));
+        let length = bytes.len() as i64;
+        let body = match self.options.max_bytes_per_second.filter(|l| *l > 0) {
+            Some(limit) => {
+                // A new throttled body for every attempt, so retries are throttled as well
+                let bytes = bytes::Bytes::from(bytes);
+                ByteStream::new(SdkBody::retryable(move || SdkBody::from_body_1_x(ThrottledBody::new(bytes.clone(), limit))))
+            }
+            None => ByteStream::from(bytes),
+        };
 
-        self.client
+        let mut request = self.client
             .put_object()
             .bucket(&self.bucket)
             .key(&key)
-            .body(body)
-            .send()
-            .await?;
+            .content_md5(BASE64.encode(md5))
+            .content_length(length)
+            .body(body);
+
+        if let Some(sha256) = &sha256 {
+            request = request.checksum_sha256(sha256);
+        }
 
-        info!("Uploaded {}", key);
+        if let Some(sse) = &self.options.server_side_encryption {
+            request = request.server_side_encryption(ServerSideEncryption::from(sse.as_str()));
+        }
+        if let Some(key_id) = &self.options.kms_key_id {
+            request = request.ssekms_key_id(key_id);
+        }
+        if let Some(class) = self.options.storage_class_for(file_path) {
+            request = request.storage_class(StorageClass::from(class));
+        }
+        if let Some(tagging) = self.options.tagging() {
+            request = request.tagging(tagging);
+        }
+
+        let output = request.send().instrument(s3_span("PutObject", &key)).await?;
+
+        // The server rejects a body that doesn't match Content-MD5, but some S3-compatible
+        // gateways ignore it, so also check what was stored. The ETag is only the MD5
+        // for single-part uploads without KMS encryption.
+        if self.options.server_side_encryption.as_deref() != Some("aws:kms") {
+            if let Some(etag) = output.e_tag().map(|e| e.trim_matches('"')) {
+                let expected = format!("{:x}", md5);
+                if !etag.contains('-') && !etag.eq_ignore_ascii_case(&expected) {
+                    bail!("Checksum mismatch for {}: ETag {} but local MD5 {}", key, etag, expected);
+                }
+            }
+        }
+        if let (Some(expected), Some(returned)) = (&sha256, output.checksum_sha256()) {
+            if expected != returned {
+                bail!("Checksum mismatch for {}: SHA-256 {} but local {}", key, returned, expected);
+            }
+        }
+
+        info!("Uploaded {} to {}", key, self.name);
         Ok(())
     }
 }
+
+/// Client span of an S3 request, a child span of the upload or scrape in traces
+fn s3_span(operation: &str, key: &str) -> tracing::Span {
+    info_span!("s3", otel.kind = "client", operation, key)
+}
+
+/// Build an S3 client for the configured region and endpoint
+pub async fn s3_client(region: Option<String>, endpoint: Option<String>) -> Client {
+    let region = region.unwrap_or_else(|| "eu-central".to_string());
+    
+    let mut s3_config_builder = aws_sdk_s3::config::Builder::new()
+        .region(Region::new(region))
+        .behavior_version_latest();
+    
+    // For S3-compatible services like Hetzner Object Storage
+    if let Some(endpoint_url) = endpoint {
+        s3_config_builder = s3_config_builder
+            .endpoint_url(endpoint_url)
+            .force_path_style(true); // Required for most S3-compatible services
+    }
+    
+    // Try custom S3_* env vars first, then fall back to AWS_* env vars
+    let access_key = env::var("S3_ACCESS_KEY")
+        .or_else(|_| env::var("AWS_ACCESS_KEY_ID"));
+    let secret_key = env::var("S3_SECRET_KEY")
+        .or_else(|_| env::var("AWS_SECRET_ACCESS_KEY"));
+    
+    if let (Ok(access), Ok(secret)) = (access_key, secret_key) {
+        let credentials = Credentials::new(access, secret, None, None, "env");
+        s3_config_builder = s3_config_builder.credentials_provider(credentials);
+    } else if let Some(credentials) = secrets::s3_credentials() {
+        s3_config_builder = s3_config_builder.credentials_provider(credentials);
+    } else {
+        // Fall back to default AWS credential chain
+        let shared_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        if let Some(credentials_provider) = shared_config.credentials_provider() {
+            s3_config_builder = s3_config_builder.credentials_provider(credentials_provider);
+        }
+    }
+    
+    Client::from_conf(s3_config_builder.build())
+}
@@ -0,0 +1,209 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use tracing::warn;
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use crate::config::ScraperConfig;
+use crate::metrics;
+use crate::query::{Query, QueryResult, ValueRow};
+
+/// Data quality rules applied to scraped data before it is stored. Rules are checked after the
+/// transforms, so bounds are given in stored units.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ValidationConfig {
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+    /// Maximum absolute change of a column between two consecutive intervals, including the
+    /// stored interval right before a scrape
+    pub max_delta: Option<f64>,
+    #[serde(default)]
+    pub non_negative: bool,
+    pub expected_interval_minutes: Option<i64>,
+    /// Store rejected records in the `rejected/` partition instead of dropping them
+    #[serde(default)]
+    pub quarantine: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    BelowMin { column: String, value: f64, min: f64 },
+    AboveMax { column: String, value: f64, max: f64 },
+    Negative { column: String, value: f64 },
+    Spike { column: String, value: f64, previous: f64, max_delta: f64 },
+    IntervalLength { minutes: i64, expected: i64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::BelowMin { column, value, min } => write!(f, "{} = {} is below minimum {}", column, value, min),
+            Violation::AboveMax { column, value, max } => write!(f, "{} = {} is above maximum {}", column, value, max),
+            Violation::Negative { column, value } => write!(f, "{} = {} is negative", column, value),
+            Violation::Spike { column, value, previous, max_delta } => {
+                write!(f, "{} jumped from {} to {} (max delta {})", column, previous, value, max_delta)
+            }
+            Violation::IntervalLength { minutes, expected } => {
+                write!(f, "interval is {} minutes, expected {}", minutes, expected)
+            }
+        }
+    }
+}
+
+pub struct ValidationResult {
+    pub accepted: Vec<ScraperData>,
+    pub rejected: Vec<ScraperData>,
+}
+
+/// Validation rules of a scraper, with the stored data `max_delta` compares the first scraped
+/// interval with
+pub struct Validator {
+    rules: ValidationConfig,
+    query: Query,
+    folder: String,
+    tz: Tz,
+}
+
+impl Validator {
+    /// Validator of a scraper, None without validation rules
+    pub fn for_scraper(config: &ScraperConfig, base_path: &str) -> Result<Option<Self>> {
+        let Some(rules) = &config.validation else {
+            return Ok(None);
+        };
+        let tz = config.partition_timezone()?;
+        Ok(Some(Self {
+            rules: rules.clone(),
+            query: Query::new(base_path).with_partitioning(config.data_folder(), config.partition_granularity, tz),
+            folder: config.data_folder().to_string(),
+            tz,
+        }))
+    }
+
+    pub fn rules(&self) -> &ValidationConfig {
+        &self.rules
+    }
+
+    /// Split scraped data into accepted and rejected records, with `max_delta` seeded by the
+    /// stored values of the local days the data starts and ends on
+    pub fn validate(&self, scraper_name: &str, data: Vec<ScraperData>) -> ValidationResult {
+        let stored = match self.rules.max_delta {
+            Some(_) => self.stored(scraper_name, &data),
+            None => Vec::new(),
+        };
+        validate(scraper_name, &self.rules, data, &stored)
+    }
+
+    fn stored(&self, scraper_name: &str, data: &[ScraperData]) -> Vec<ValueRow> {
+        let (Some(first), Some(last)) = (data.iter().map(|d| d.delivery_from).min(), data.iter().map(|d| d.delivery_from).max()) else {
+            return Vec::new();
+        };
+        // The day of the interval ending at the first scraped one
+        let start = (first - Duration::seconds(1)).with_timezone(&self.tz).date_naive();
+        let end = last.with_timezone(&self.tz).date_naive();
+        match self.query.latest(&self.folder, start, end, None) {
+            Ok(QueryResult::Values(rows)) => rows,
+            Ok(QueryResult::Bids(_)) => Vec::new(),
+            Err(e) => {
+                warn!("[{}] Failed to load the stored values for max_delta: {:?}", scraper_name, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Split scraped data into accepted and rejected records. `max_delta` also compares a record with
+/// the stored row of the preceding interval when the data doesn't contain that interval.
+/// Violations are logged and counted in the metrics of the scraper.
+pub fn validate(scraper_name: &str, config: &ValidationConfig, mut data: Vec<ScraperData>, stored: &[ValueRow]) -> ValidationResult {
+    data.sort_by_key(|item| item.delivery_from);
+
+    let mut accepted = Vec::with_capacity(data.len());
+    let mut rejected = Vec::new();
+    let mut violation_count = 0;
+
+    // Stored value per column and interval end
+    let stored: HashMap<(&str, DateTime<Utc>), f64> = stored.iter()
+        .flat_map(|row| row.values.iter().filter_map(move |(column, value)| Some(((column.as_str(), row.end), value.as_f64()?))))
+        .collect();
+    // Last accepted value per column, used for spike detection
+    let mut previous: HashMap<String, (DateTime<Utc>, f64)> = HashMap::new();
+
+    for item in data {
+        let violations = check_record(config, &item, &previous, &stored);
+
+        if violations.is_empty() {
+            if let ScraperPayload::Values(map) = &item.payload {
+                for (column, value) in map {
+                    previous.insert(column.clone(), (item.delivery_to, *value));
+                }
+            }
+            accepted.push(item);
+        } else {
+            for violation in &violations {
+                warn!("[{}] Invalid record {} - {}: {}", scraper_name, item.delivery_from, item.delivery_to, violation);
+            }
+            violation_count += violations.len() as u64;
+            rejected.push(item);
+        }
+    }
+
+    if !rejected.is_empty() {
+        let rejected_count = rejected.len() as u64;
+        metrics::global().update(scraper_name, |m| {
+            m.validation_violations += violation_count;
+            m.records_rejected += rejected_count;
+        });
+    }
+
+    ValidationResult { accepted, rejected }
+}
+
+fn check_record(config: &ValidationConfig, item: &ScraperData, previous: &HashMap<String, (DateTime<Utc>, f64)>, stored: &HashMap<(&str, DateTime<Utc>), f64>) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if let Some(expected) = config.expected_interval_minutes {
+        let minutes = (item.delivery_to - item.delivery_from).num_minutes();
+        if minutes != expected {
+            violations.push(Violation::IntervalLength { minutes, expected });
+        }
+    }
+
+    // Value rules only apply to value series, not to balancing bids
+    let map = match &item.payload {
+        ScraperPayload::Values(map) => map,
+        ScraperPayload::Bids(_) => return violations,
+    };
+
+    for (column, &value) in map {
+        if let Some(min) = config.min_value {
+            if value < min {
+                violations.push(Violation::BelowMin { column: column.clone(), value, min });
+            }
+        }
+        if let Some(max) = config.max_value {
+            if value > max {
+                violations.push(Violation::AboveMax { column: column.clone(), value, max });
+            }
+        }
+        if config.non_negative && value < 0.0 {
+            violations.push(Violation::Negative { column: column.clone(), value });
+        }
+        if let Some(max_delta) = config.max_delta {
+            // Only compare against the directly preceding interval, scraped or stored
+            let prev_value = previous.get(column)
+                .filter(|(prev_end, _)| *prev_end == item.delivery_from)
+                .map(|(_, v)| *v)
+                .or_else(|| stored.get(&(column.as_str(), item.delivery_from)).copied());
+            if let Some(prev_value) = prev_value {
+                if (value - prev_value).abs() > max_delta {
+                    violations.push(Violation::Spike { column: column.clone(), value, previous: prev_value, max_delta });
+                }
+            }
+        }
+    }
+
+    violations
+}
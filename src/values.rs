@@ -0,0 +1,226 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, BooleanArray, BooleanBuilder, Float64Array, Float64Builder, Int64Array, Int64Builder, StringArray, StringBuilder};
+use arrow::datatypes::DataType;
+
+/// Storage type of a value column
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    #[default]
+    F64,
+    I64,
+    Utf8,
+    Bool,
+}
+
+impl ValueType {
+    pub fn data_type(&self) -> DataType {
+        match self {
+            ValueType::F64 => DataType::Float64,
+            ValueType::I64 => DataType::Int64,
+            ValueType::Utf8 => DataType::Utf8,
+            ValueType::Bool => DataType::Boolean,
+        }
+    }
+
+    pub fn from_data_type(data_type: &DataType) -> Option<Self> {
+        match data_type {
+            DataType::Float64 => Some(ValueType::F64),
+            DataType::Int64 => Some(ValueType::I64),
+            DataType::Utf8 => Some(ValueType::Utf8),
+            DataType::Boolean => Some(ValueType::Bool),
+            _ => None,
+        }
+    }
+}
+
+/// A single stored value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    F64(f64),
+    I64(i64),
+    Utf8(String),
+    Bool(bool),
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::F64(_) => ValueType::F64,
+            Value::I64(_) => ValueType::I64,
+            Value::Utf8(_) => ValueType::Utf8,
+            Value::Bool(_) => ValueType::Bool,
+        }
+    }
+
+    /// Numeric view of the value, categorical values have none
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::F64(v) => Some(*v),
+            Value::I64(v) => Some(*v as f64),
+            Value::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+            Value::Utf8(_) => None,
+        }
+    }
+
+    /// Whether two values differ, floats are compared with a tolerance
+    pub fn differs(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::F64(a), Value::F64(b)) => (a - b).abs() > f64::EPSILON,
+            (a, b) => a != b,
+        }
+    }
+
+    /// Convert a value stored with an older column type, None if it can't be represented
+    pub fn cast(&self, value_type: ValueType) -> Option<Value> {
+        match (self, value_type) {
+            (v, t) if v.value_type() == t => Some(v.clone()),
+            (Value::Utf8(s), ValueType::F64) => s.parse().ok().map(Value::F64),
+            (Value::Utf8(s), ValueType::I64) => s.parse().ok().map(Value::I64),
+            (Value::Utf8(s), ValueType::Bool) => s.parse().ok().map(Value::Bool),
+            (v, ValueType::Utf8) => Some(Value::Utf8(v.to_string())),
+            (v, ValueType::F64) => v.as_f64().map(Value::F64),
+            (v, t) => from_number(v.as_f64()?, t).ok(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::F64(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::Utf8(v) => write!(f, "{}", v),
+            Value::Bool(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// Column types of a scraper. Scrapers return numbers, they are converted to the declared type
+/// when written, columns without a declared type are stored as f64.
+#[derive(Debug, Clone, Default)]
+pub struct ValueSchema {
+    pub types: HashMap<String, ValueType>,
+    /// Labels of utf8 columns, the scraped number is the index into the list
+    pub categories: HashMap<String, Vec<String>>,
+}
+
+impl ValueSchema {
+    pub fn value_type(&self, column: &str) -> ValueType {
+        self.types.get(column).copied().unwrap_or_default()
+    }
+
+    /// Convert a scraped number to the declared type of its column, failing if it doesn't fit
+    pub fn convert(&self, column: &str, value: f64) -> Result<Value> {
+        match self.value_type(column) {
+            ValueType::Utf8 => {
+                let labels = match self.categories.get(column) {
+                    Some(labels) => labels,
+                    None => bail!("Column {} is utf8 but has no categories", column),
+                };
+                if value.fract() != 0.0 || value < 0.0 || value as usize >= labels.len() {
+                    bail!("Column {}: {} is not a valid category index", column, value);
+                }
+                Ok(Value::Utf8(labels[value as usize].clone()))
+            }
+            value_type => from_number(value, value_type)
+                .map_err(|e| anyhow::anyhow!("Column {}: {}", column, e)),
+        }
+    }
+}
+
+fn from_number(value: f64, value_type: ValueType) -> Result<Value> {
+    match value_type {
+        ValueType::F64 => Ok(Value::F64(value)),
+        ValueType::I64 => {
+            if !value.is_finite() || value.fract() != 0.0 || value.abs() > i64::MAX as f64 {
+                bail!("{} is not an integer", value);
+            }
+            Ok(Value::I64(value as i64))
+        }
+        ValueType::Bool => match value {
+            v if v == 0.0 => Ok(Value::Bool(false)),
+            v if v == 1.0 => Ok(Value::Bool(true)),
+            v => bail!("{} is not a boolean (0 or 1)", v),
+        },
+        ValueType::Utf8 => Ok(Value::Utf8(value.to_string())),
+    }
+}
+
+/// Read a value from a column of any supported type
+pub fn read_value(array: &dyn Array, i: usize) -> Option<Value> {
+    if array.is_null(i) {
+        return None;
+    }
+    let any = array.as_any();
+    if let Some(a) = any.downcast_ref::<Float64Array>() {
+        Some(Value::F64(a.value(i)))
+    } else if let Some(a) = any.downcast_ref::<Int64Array>() {
+        Some(Value::I64(a.value(i)))
+    } else if let Some(a) = any.downcast_ref::<StringArray>() {
+        Some(Value::Utf8(a.value(i).to_string()))
+    } else {
+        any.downcast_ref::<BooleanArray>().map(|a| Value::Bool(a.value(i)))
+    }
+}
+
+/// Type a column can be stored as without losing values: `value_type` if every value can be cast
+/// to it, utf8 otherwise
+pub fn lossless_type<'a>(value_type: ValueType, mut values: impl Iterator<Item = &'a Value>) -> ValueType {
+    if values.all(|v| v.cast(value_type).is_some()) {
+        value_type
+    } else {
+        ValueType::Utf8
+    }
+}
+
+/// Build a column of the given type. Values of another type are cast, or stored as null if that fails,
+/// use `lossless_type` to pick a type all values fit.
+pub fn build_column<'a>(value_type: ValueType, values: impl Iterator<Item = Option<&'a Value>>) -> ArrayRef {
+    let values = values.map(|v| v.and_then(|v| v.cast(value_type)));
+    match value_type {
+        ValueType::F64 => {
+            let mut builder = Float64Builder::new();
+            for v in values {
+                builder.append_option(v.and_then(|v| v.as_f64()));
+            }
+            Arc::new(builder.finish())
+        }
+        ValueType::I64 => {
+            let mut builder = Int64Builder::new();
+            for v in values {
+                builder.append_option(match v {
+                    Some(Value::I64(v)) => Some(v),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        ValueType::Utf8 => {
+            let mut builder = StringBuilder::new();
+            for v in values {
+                builder.append_option(match v {
+                    Some(Value::Utf8(v)) => Some(v),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+        ValueType::Bool => {
+            let mut builder = BooleanBuilder::new();
+            for v in values {
+                builder.append_option(match v {
+                    Some(Value::Bool(v)) => Some(v),
+                    _ => None,
+                });
+            }
+            Arc::new(builder.finish())
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use chrono::NaiveDate;
+use futures::StreamExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use scraping_service::backfill::{build_chunks, spawn_ordered};
+
+#[test]
+fn consecutive_days_share_a_window() {
+    let day = |d| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+    let days = [day(1), day(2), day(3), day(4), day(6)];
+
+    assert_eq!(build_chunks(&days, None).len(), 5);
+    // 96 hours hold three days and the padding before and after them
+    assert_eq!(build_chunks(&days, Some(96)), vec![vec![day(1), day(2), day(3)], vec![day(4)], vec![day(6)]]);
+}
+
+#[tokio::test]
+async fn chunks_are_bounded_and_yielded_in_order() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+
+    let results: Vec<(u64, u64)> = spawn_ordered(0..12u64, 3, |i| {
+        let (running, peak) = (running.clone(), peak.clone());
+        (i, async move {
+            peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            // Later chunks finish first
+            tokio::time::sleep(Duration::from_millis(5 * (12 - i))).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            i * 10
+        })
+    }).map(|(i, result)| (i, result.unwrap())).collect().await;
+
+    assert_eq!(results, (0..12).map(|i| (i, i * 10)).collect::<Vec<_>>());
+    let peak = peak.load(Ordering::SeqCst);
+    assert!(peak > 1 && peak <= 3, "{} chunks ran at once", peak);
+}
+
+#[tokio::test]
+async fn dropping_the_results_aborts_running_chunks() {
+    let finished = Arc::new(AtomicUsize::new(0));
+    let mut results = Box::pin(spawn_ordered(0..4u64, 2, |i| {
+        let finished = finished.clone();
+        (i, async move {
+            if i > 0 {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            finished.fetch_add(1, Ordering::SeqCst);
+        })
+    }));
+
+    let (first, result) = results.next().await.unwrap();
+    assert_eq!(first, 0);
+    result.unwrap();
+
+    // Like a failed save, which returns before the second chunk is done
+    drop(results);
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(finished.load(Ordering::SeqCst), 1);
+}
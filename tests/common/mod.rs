@@ -0,0 +1,46 @@
+//! In-process S3 stand-in for hydration and upload tests: a path-style endpoint keeping objects in
+//! memory by key
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub const BUCKET: &str = "test-bucket";
+
+/// Objects of the bucket by key
+pub type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+const NO_SUCH_KEY: &str = r#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>NoSuchKey</Code><Message>The specified key does not exist.</Message></Error>"#;
+
+async fn get_object(State(objects): State<Objects>, Path((_, key)): Path<(String, String)>) -> Response {
+    match objects.lock().unwrap().get(&key) {
+        Some(body) => (StatusCode::OK, body.clone()).into_response(),
+        None => (StatusCode::NOT_FOUND, [(header::CONTENT_TYPE, "application/xml")], NO_SUCH_KEY).into_response(),
+    }
+}
+
+async fn put_object(State(objects): State<Objects>, Path((_, key)): Path<(String, String)>, body: Bytes) -> StatusCode {
+    objects.lock().unwrap().insert(key, body.to_vec());
+    StatusCode::OK
+}
+
+/// Start the endpoint and return its URL and objects. Credentials are set in the environment, so
+/// clients don't look for real ones.
+pub async fn fake_s3() -> (String, Objects) {
+    std::env::set_var("S3_ACCESS_KEY", "test");
+    std::env::set_var("S3_SECRET_KEY", "test");
+
+    let objects = Objects::default();
+    let router = Router::new()
+        .route("/:bucket/*key", get(get_object).put(put_object))
+        .with_state(objects.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    (endpoint, objects)
+}
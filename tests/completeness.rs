@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::sync::Arc;
+
+use arrow::array::{Float64Array, TimestampMicrosecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Europe::Vienna;
+use parquet::arrow::ArrowWriter;
+
+use scraping_service::calendar::{CalendarConfig, MaintenanceWindow};
+use scraping_service::completeness::{check_day, check_partition, day_bounds, expected_intervals, scale_to_day};
+use scraping_service::query::Query;
+
+fn date(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+fn no_calendar() -> CalendarConfig {
+    CalendarConfig::default()
+}
+
+/// Every interval of a local day, generated in UTC like the APIs deliver them
+fn full_day(day: NaiveDate, minutes: i64) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let (start, end) = day_bounds(day, Vienna).unwrap();
+    let step = Duration::minutes(minutes);
+    let mut intervals = Vec::new();
+    let mut t = start;
+    while t < end {
+        intervals.push((t, t + step));
+        t += step;
+    }
+    intervals
+}
+
+#[test]
+fn quarter_hours_per_day_follow_dst() {
+    assert_eq!(expected_intervals(date("2025-03-30"), Vienna, 15).unwrap(), 92);
+    assert_eq!(expected_intervals(date("2025-10-26"), Vienna, 15).unwrap(), 100);
+    assert_eq!(expected_intervals(date("2025-06-01"), Vienna, 15).unwrap(), 96);
+    assert_eq!(expected_intervals(date("2025-03-30"), Vienna, 60).unwrap(), 23);
+    assert_eq!(expected_intervals(date("2025-10-26"), Vienna, 60).unwrap(), 25);
+}
+
+#[test]
+fn zones_without_dst_always_have_96() {
+    assert_eq!(expected_intervals(date("2025-03-30"), chrono_tz::Asia::Tokyo, 15).unwrap(), 96);
+    assert_eq!(expected_intervals(date("2025-10-26"), chrono_tz::UTC, 15).unwrap(), 96);
+}
+
+#[test]
+fn day_bounds_handle_dst_at_midnight() {
+    // Chile moves the clock from 00:00 to 01:00, so local midnight doesn't exist
+    let (start, end) = day_bounds(date("2024-09-08"), chrono_tz::America::Santiago).unwrap();
+    assert_eq!((end - start).num_hours(), 23);
+}
+
+#[test]
+fn full_spring_day_has_no_missing_hour() {
+    let day = date("2025-03-30");
+    let result = check_day(day, Vienna, 15, &full_day(day, 15), &no_calendar()).unwrap();
+    assert!(result.is_complete(), "unexpected gaps: {:?}", result.gaps);
+    assert!(result.dst_transition);
+    assert_eq!((result.present, result.expected), (92, 92));
+}
+
+#[test]
+fn full_autumn_day_accepts_the_repeated_hour() {
+    let day = date("2025-10-26");
+    let result = check_day(day, Vienna, 15, &full_day(day, 15), &no_calendar()).unwrap();
+    assert!(result.is_complete(), "unexpected gaps: {:?}", result.gaps);
+    assert_eq!((result.present, result.expected), (100, 100));
+}
+
+#[test]
+fn missing_interval_in_the_repeated_hour_is_reported() {
+    let day = date("2025-10-26");
+    // 02:00 local after the clocks went back is 01:00 UTC
+    let missing = Utc.with_ymd_and_hms(2025, 10, 26, 1, 0, 0).unwrap();
+    let intervals: Vec<_> = full_day(day, 15).into_iter().filter(|(start, _)| *start != missing).collect();
+
+    let result = check_day(day, Vienna, 15, &intervals, &no_calendar()).unwrap();
+    assert_eq!(result.present, 99);
+    assert_eq!(result.gaps, vec![(missing, missing + Duration::minutes(15))]);
+}
+
+#[test]
+fn consecutive_missing_intervals_form_one_gap() {
+    let day = date("2025-06-01");
+    let intervals = full_day(day, 15);
+    let (gap_start, _) = intervals[10];
+    let (_, gap_end) = intervals[13];
+    let kept: Vec<_> = intervals.iter().enumerate()
+        .filter(|(i, _)| !(10..=13).contains(i))
+        .map(|(_, interval)| *interval)
+        .collect();
+
+    let result = check_day(day, Vienna, 15, &kept, &no_calendar()).unwrap();
+    assert_eq!(result.gaps, vec![(gap_start, gap_end)]);
+    assert!(!result.dst_transition);
+}
+
+#[test]
+fn intervals_of_another_length_do_not_count() {
+    let day = date("2025-06-01");
+    let result = check_day(day, Vienna, 15, &full_day(day, 60), &no_calendar()).unwrap();
+    assert_eq!(result.present, 0);
+    assert_eq!(result.gaps.len(), 1);
+}
+
+#[test]
+fn maintenance_window_is_excused() {
+    let day = date("2025-06-01");
+    let intervals = full_day(day, 15);
+    let (window_start, _) = intervals[8];
+    let (_, window_end) = intervals[15];
+    let kept: Vec<_> = intervals.iter().filter(|(start, _)| *start < window_start || *start >= window_end).copied().collect();
+    let calendar = CalendarConfig {
+        maintenance: vec![MaintenanceWindow { start: window_start, end: window_end, reason: None }],
+        ..Default::default()
+    };
+
+    let result = check_day(day, Vienna, 15, &kept, &calendar).unwrap();
+    assert!(result.is_complete(), "unexpected gaps: {:?}", result.gaps);
+    assert_eq!((result.present, result.excused), (88, 8));
+    // Without the calendar the same day has a two hour gap
+    assert_eq!(check_day(day, Vienna, 15, &kept, &no_calendar()).unwrap().gaps, vec![(window_start, window_end)]);
+}
+
+#[test]
+fn holiday_without_data_is_complete() {
+    let day = date("2025-12-25");
+    let calendar = CalendarConfig { holidays: vec![day], ..Default::default() };
+    let result = check_day(day, Vienna, 15, &[], &calendar).unwrap();
+    assert!(result.is_complete());
+    assert!(result.closed);
+    assert_eq!(result.excused, 96);
+
+    // Closed weekdays apply to every such day, 2025-06-01 is a Sunday
+    let calendar = CalendarConfig { closed_weekdays: vec![chrono::Weekday::Sun], ..Default::default() };
+    assert!(check_day(date("2025-06-01"), Vienna, 15, &[], &calendar).unwrap().is_complete());
+    assert!(!check_day(date("2025-06-02"), Vienna, 15, &[], &calendar).unwrap().is_complete());
+}
+
+#[test]
+fn row_counts_scale_to_the_day_length() {
+    assert_eq!(scale_to_day(96, date("2025-03-30"), Vienna).unwrap(), 92);
+    assert_eq!(scale_to_day(96, date("2025-10-26"), Vienna).unwrap(), 100);
+    assert_eq!(scale_to_day(96, date("2025-06-01"), Vienna).unwrap(), 96);
+}
+
+#[test]
+fn stored_autumn_partition_is_complete() {
+    let dir = tempfile::tempdir().unwrap();
+    let day = date("2025-10-26");
+    let intervals = full_day(day, 15);
+
+    let path = dir.path().join("prices/year=2025/month=10/day=26/data.parquet");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("start", timestamp.clone(), false),
+        Field::new("end", timestamp.clone(), false),
+        Field::new("scraped_at", timestamp, true),
+        Field::new("price", DataType::Float64, true),
+    ]));
+    let batch = RecordBatch::try_new(schema.clone(), vec![
+        Arc::new(TimestampMicrosecondArray::from_iter_values(intervals.iter().map(|(s, _)| s.timestamp_micros())).with_timezone("UTC")),
+        Arc::new(TimestampMicrosecondArray::from_iter_values(intervals.iter().map(|(_, e)| e.timestamp_micros())).with_timezone("UTC")),
+        Arc::new(TimestampMicrosecondArray::from_iter_values(intervals.iter().map(|_| 0)).with_timezone("UTC")),
+        Arc::new(Float64Array::from_iter_values(intervals.iter().map(|_| 42.0))),
+    ]).unwrap();
+    let mut writer = ArrowWriter::try_new(File::create(&path).unwrap(), schema, None).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+
+    let query = Query::new(dir.path().to_str().unwrap());
+    assert_eq!(query.interval_count("prices", day).unwrap(), Some(100));
+
+    let result = check_partition(&query, "prices", day, Vienna, 15, &no_calendar()).unwrap();
+    assert!(result.is_complete(), "unexpected gaps: {:?}", result.gaps);
+    assert_eq!(result.expected, 100);
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use scraping_service::delta::{DeltaConfig, DeltaLog, LOG_DIR, PART_EXTENSION};
+use scraping_service::partition::Granularity;
+use scraping_service::storage::Storage;
+
+const FOLDER: &str = "mock/imbalance";
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap()
+}
+
+fn data(value: f64) -> Vec<ScraperData> {
+    (0..4).map(|i| ScraperData {
+        delivery_from: start() + Duration::minutes(15 * i),
+        delivery_to: start() + Duration::minutes(15 * (i + 1)),
+        payload: ScraperPayload::Values([("price".to_string(), value)].into_iter().collect()),
+    }).collect()
+}
+
+fn new_delta_log(base_path: &str, retention_hours: u64) -> Arc<DeltaLog> {
+    let config = DeltaConfig { folders: Vec::new(), commit_interval_secs: 300, retention_hours };
+    Arc::new(DeltaLog::new(config, base_path, BTreeMap::from([(FOLDER.to_string(), Granularity::Day)])))
+}
+
+fn referenced(delta_log: &DeltaLog) -> HashSet<String> {
+    delta_log.referenced_files().unwrap().remove(FOLDER).unwrap_or_default()
+}
+
+fn versions(base_path: &str) -> usize {
+    std::fs::read_dir(Path::new(base_path).join(FOLDER).join(LOG_DIR)).unwrap().count()
+}
+
+#[tokio::test]
+async fn commit_links_written_partitions() {
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    let delta_log = new_delta_log(base_path, 168);
+    let storage = Storage::new(base_path, None).with_delta(delta_log.clone());
+
+    storage.save_if_new("mock_imb", Some(FOLDER), &data(50.0), None).await.unwrap();
+    assert_eq!(delta_log.commit().await.unwrap(), 1);
+    assert_eq!(versions(base_path), 1);
+
+    let files = referenced(&delta_log);
+    assert_eq!(files.len(), 1);
+    let part = files.iter().next().unwrap();
+    assert!(part.ends_with(PART_EXTENSION));
+    assert!(Path::new(base_path).join(part).exists());
+
+    // Nothing written since, nothing to commit
+    assert_eq!(delta_log.commit().await.unwrap(), 0);
+    assert_eq!(versions(base_path), 1);
+}
+
+#[tokio::test]
+async fn vacuum_deletes_expired_parts() {
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    let delta_log = new_delta_log(base_path, 0);
+    let storage = Storage::new(base_path, None).with_delta(delta_log.clone());
+
+    storage.save_if_new("mock_imb", Some(FOLDER), &data(50.0), None).await.unwrap();
+    delta_log.commit().await.unwrap();
+    let first = referenced(&delta_log);
+
+    storage.save_if_new("mock_imb", Some(FOLDER), &data(60.0), None).await.unwrap();
+    assert_eq!(delta_log.commit().await.unwrap(), 1);
+    let second = referenced(&delta_log);
+
+    // Without retention the replaced part is deleted by the commit that replaced it
+    assert_eq!(second.len(), 1);
+    assert!(first.is_disjoint(&second));
+    for part in &first {
+        assert!(!Path::new(base_path).join(part).exists());
+    }
+}
+
+#[tokio::test]
+async fn reload_continues_from_the_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    let delta_log = new_delta_log(base_path, 168);
+    let storage = Storage::new(base_path, None).with_delta(delta_log.clone());
+    storage.save_if_new("mock_imb", Some(FOLDER), &data(50.0), None).await.unwrap();
+    delta_log.commit().await.unwrap();
+    let committed = referenced(&delta_log);
+
+    // A new process replays the log instead of importing the table again
+    let reloaded = new_delta_log(base_path, 168);
+    assert_eq!(referenced(&reloaded), committed);
+    let storage = Storage::new(base_path, None).with_delta(reloaded.clone());
+    storage.save_if_new("mock_imb", Some(FOLDER), &data(60.0), None).await.unwrap();
+    assert_eq!(reloaded.commit().await.unwrap(), 1);
+    assert_eq!(versions(base_path), 2);
+
+    // The replaced part stays referenced within the retention
+    let files = referenced(&reloaded);
+    assert_eq!(files.len(), 2);
+    assert!(committed.is_subset(&files));
+}
@@ -0,0 +1,127 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::{BTreeMap, HashMap};
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use scraping_service::derived::{self, Derived, DerivedConfig};
+use scraping_service::values::Value;
+
+type Rows = Vec<(DateTime<Utc>, DateTime<Utc>, BTreeMap<String, Value>)>;
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap()
+}
+
+fn config(name: &str, columns: &[(&str, &str)]) -> DerivedConfig {
+    DerivedConfig {
+        name: name.to_string(),
+        folder: None,
+        columns: columns.iter().map(|(c, e)| (c.to_string(), e.to_string())).collect(),
+        partition_timezone: None,
+        partition_granularity: Default::default(),
+    }
+}
+
+fn derived(expression: &str) -> anyhow::Result<Derived> {
+    Derived::new(config("spread", &[("value", expression)]))
+}
+
+/// One row per interval of 15 minutes, from the start of the day
+fn rows(values: &[&[(&str, f64)]]) -> Rows {
+    values.iter().enumerate().map(|(i, columns)| (
+        start() + Duration::minutes(15 * i as i64),
+        start() + Duration::minutes(15 * (i as i64 + 1)),
+        columns.iter().map(|(c, v)| (c.to_string(), Value::F64(*v))).collect(),
+    )).collect()
+}
+
+fn value(data: &ScraperData, column: &str) -> Option<f64> {
+    match &data.payload {
+        ScraperPayload::Values(values) => values.get(column).copied(),
+        ScraperPayload::Bids(_) => None,
+    }
+}
+
+/// Value of the single output interval of `expression` over apg.x = 6 and apg.y = 2
+fn eval(expression: &str) -> Option<f64> {
+    let inputs = HashMap::from([("apg".to_string(), rows(&[&[("x", 6.0), ("y", 2.0)]]))]);
+    let output = derived(expression).unwrap().compute(&inputs);
+    output.first().and_then(|data| value(data, "value"))
+}
+
+#[test]
+fn operators_follow_precedence() {
+    assert_eq!(eval("apg.x + apg.y * 3"), Some(12.0));
+    assert_eq!(eval("apg.x - apg.y / 2"), Some(5.0));
+    // Operators of the same precedence are left associative
+    assert_eq!(eval("apg.x - apg.y - 1"), Some(3.0));
+    assert_eq!(eval("apg.x / apg.y / 3"), Some(1.0));
+}
+
+#[test]
+fn parentheses_and_unary_minus() {
+    assert_eq!(eval("(apg.x + apg.y) * 3"), Some(24.0));
+    assert_eq!(eval("apg.x / (apg.y - 4)"), Some(-3.0));
+    assert_eq!(eval("-(apg.x - apg.y)"), Some(-4.0));
+    assert_eq!(eval("--apg.x"), Some(6.0));
+    assert_eq!(eval("  ( ( apg.y ) )  "), Some(2.0));
+    assert_eq!(eval("0.5 * apg.x"), Some(3.0));
+}
+
+#[test]
+fn invalid_expressions_are_rejected() {
+    for expression in ["apg.x +", "(apg.x + apg.y", "apg.x + apg.y)", "apg", "apg.x $ 2", "1..2 * apg.x", ""] {
+        assert!(derived(expression).is_err(), "{} should not parse", expression);
+    }
+    // A series can't read its own folder
+    assert!(derived("spread.value + 1").is_err());
+}
+
+#[test]
+fn cycles_are_rejected() {
+    let a = config("a", &[("value", "b.value + 1")]);
+    let b = config("b", &[("value", "a.value * 2")]);
+    assert!(derived::from_config(&[a, b]).is_err());
+
+    let c = config("c", &[("value", "a.value - apg.x")]);
+    let a = config("a", &[("value", "apg.x + 1")]);
+    assert!(derived::from_config(&[a, c]).is_ok());
+}
+
+#[test]
+fn inputs_are_the_referenced_folders() {
+    let derived = derived("apg/imbalance.price - entsoe/prices.price * 2").unwrap();
+    assert_eq!(derived.inputs().into_iter().collect::<Vec<_>>(), vec!["apg/imbalance", "entsoe/prices"]);
+}
+
+#[test]
+fn missing_input_interval_gives_no_row() {
+    let derived = Derived::new(config("net", &[("net", "apg.import - apg.export"), ("spread", "apg.import - entsoe.price")])).unwrap();
+    // The second interval hasn't arrived for entsoe yet, the third only exists there
+    let mut entsoe = rows(&[&[("price", 7.0)], &[], &[("price", 9.0)]]);
+    entsoe.remove(1);
+    let inputs = HashMap::from([
+        ("apg".to_string(), rows(&[&[("import", 10.0), ("export", 4.0)], &[("import", 8.0), ("export", 3.0)]])),
+        ("entsoe".to_string(), entsoe),
+    ]);
+
+    let output = derived.compute(&inputs);
+    assert_eq!(output.len(), 2);
+    assert_eq!(value(&output[0], "net"), Some(6.0));
+    assert_eq!(value(&output[0], "spread"), Some(3.0));
+    // Columns whose inputs are all there are still written
+    assert_eq!(output[1].delivery_from, start() + Duration::minutes(15));
+    assert_eq!(value(&output[1], "net"), Some(5.0));
+    assert_eq!(value(&output[1], "spread"), None);
+    // No column of the third interval can be computed without apg, so it has no row at all
+    assert!(output.iter().all(|data| data.delivery_from != start() + Duration::minutes(30)));
+}
+
+#[test]
+fn division_by_zero_and_text_values_are_skipped() {
+    assert_eq!(eval("apg.x / (apg.y - 2)"), None);
+
+    let derived = derived("apg.x * 2").unwrap();
+    let mut text = rows(&[&[]]);
+    text[0].2.insert("x".to_string(), Value::Utf8("n/a".to_string()));
+    assert!(derived.compute(&HashMap::from([("apg".to_string(), text)])).is_empty());
+}
@@ -0,0 +1,104 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use chrono_tz::Pacific::Auckland;
+use chrono_tz::Tz;
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::Command;
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use scraping_service::config::DEFAULT_BASE_PATH;
+use scraping_service::fixtures::MOCK_TYPE;
+use scraping_service::partition::Granularity;
+use scraping_service::query::{read_batches, read_value_rows};
+use scraping_service::storage::Storage;
+
+const FOLDER: &str = "mock/imbalance";
+
+fn day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()
+}
+
+fn record(start: DateTime<Utc>) -> ScraperData {
+    ScraperData {
+        delivery_from: start,
+        delivery_to: start + Duration::minutes(15),
+        payload: ScraperPayload::Values([("price".to_string(), 50.0)].into_iter().collect()),
+    }
+}
+
+/// 02:00 UTC on June 2 is still June 1 in New York, but already June 2 in Vienna
+fn late_evening() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 2, 2, 0, 0).unwrap()
+}
+
+fn noon(date: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    tz.from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap()).single().unwrap().with_timezone(&Utc)
+}
+
+fn partition(base_path: &str, date: NaiveDate) -> PathBuf {
+    PathBuf::from(Granularity::Day.path(&format!("{}/{}", base_path, FOLDER), date))
+}
+
+#[tokio::test]
+async fn rows_land_on_the_local_market_day() {
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    let storage = Storage::new(base_path, None).with_partition_timezone(FOLDER, New_York);
+
+    storage.save_backfill("mock_imb", Some(FOLDER), &[record(noon(day(), New_York)), record(late_evening())], None).await.unwrap();
+    assert!(!partition(base_path, day() + Duration::days(1)).exists());
+    let rows = read_value_rows(&read_batches(&partition(base_path, day())).unwrap()).unwrap();
+    assert_eq!(rows.len(), 2);
+}
+
+#[tokio::test]
+async fn retention_expires_the_local_day() {
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    // Auckland is ten or more hours ahead of Vienna, so for half of the day its date is a day later
+    let storage = Storage::new(base_path, None).with_partition_timezone(FOLDER, Auckland);
+    let cutoff = (Utc::now() - Duration::days(3)).with_timezone(&Auckland).date_naive();
+    let expired = cutoff - Duration::days(1);
+
+    storage.save_backfill("mock_imb", Some(FOLDER), &[record(noon(expired, Auckland)), record(noon(cutoff, Auckland))], None).await.unwrap();
+    assert!(partition(base_path, expired).exists());
+
+    storage.cleanup_folder(FOLDER, 3, None).await.unwrap();
+    assert!(!partition(base_path, expired).exists());
+    assert!(partition(base_path, cutoff).exists());
+}
+
+#[tokio::test]
+async fn repartition_moves_rows_to_the_local_day() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().join(DEFAULT_BASE_PATH);
+    let base_path = data_dir.to_str().unwrap();
+
+    // Stored on Vienna days before the timezone was configured
+    let storage = Storage::new(base_path, None);
+    storage.save_backfill("mock_imb", Some(FOLDER), &[record(noon(day(), New_York)), record(late_evening())], None).await.unwrap();
+    assert!(partition(base_path, day() + Duration::days(1)).exists());
+
+    let config = json!({
+        "scrapers": [{
+            "name": "mock_imb",
+            "workers": 1,
+            "task_generator_delay_ms": 0,
+            "type": MOCK_TYPE,
+            "sub_data_folder": FOLDER,
+            "partition_timezone": "America/New_York"
+        }]
+    });
+    std::fs::write(dir.path().join("config.json"), config.to_string()).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_repartition"))
+        .arg("mock_imb")
+        .current_dir(dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert!(!partition(base_path, day() + Duration::days(1)).exists());
+    let rows = read_value_rows(&read_batches(&partition(base_path, day())).unwrap()).unwrap();
+    assert_eq!(rows.iter().map(|row| row.start).collect::<Vec<_>>(), vec![noon(day(), New_York), late_evening()]);
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use serde_json::json;
+use std::collections::BTreeMap;
+use ve_energy_scrapers::models::strategy_information_scraper_config::StrategyInformationScraperConfig;
+
+use scraping_service::fixtures::{self, MOCK_TYPE};
+use scraping_service::http_client::HttpClientConfig;
+use scraping_service::provenance::Provenance;
+use scraping_service::query::{Query, QueryResult, ValueRow};
+use scraping_service::raw_archive::{RawRecord, RawResponse};
+use scraping_service::scraper_factory::create_scraper;
+use scraping_service::storage::Storage;
+
+const FOLDER: &str = "mock/imbalance";
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap()
+}
+
+fn records(count: i64, value: f64) -> Vec<RawRecord> {
+    (0..count).map(|i| RawRecord {
+        delivery_from: start() + Duration::minutes(15 * i),
+        delivery_to: start() + Duration::minutes(15 * (i + 1)),
+        values: Some(BTreeMap::from([("price".to_string(), value + i as f64)])),
+        bids: None,
+    }).collect()
+}
+
+fn mock_config(values: serde_json::Value) -> StrategyInformationScraperConfig {
+    let mut config = json!({ "name": "mock_imb", "workers": 1, "task_generator_delay_ms": 0, "type": MOCK_TYPE });
+    config.as_object_mut().unwrap().extend(values.as_object().unwrap().clone());
+    serde_json::from_value(config).unwrap()
+}
+
+fn stored(base_path: &str) -> Vec<ValueRow> {
+    let day = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+    match Query::new(base_path).latest(FOLDER, day - Duration::days(1), day + Duration::days(1), None).unwrap() {
+        QueryResult::Values(rows) => rows,
+        QueryResult::Bids(_) => panic!("expected values"),
+    }
+}
+
+#[tokio::test]
+async fn inline_records_are_stored_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    let scraper = create_scraper(&mock_config(json!({ "records": records(4, 50.0) })), None).await.unwrap();
+    let storage = Storage::new(base_path, None);
+
+    let data = scraper.scrape_data(start(), start() + Duration::days(1)).await.unwrap();
+    assert_eq!(data.len(), 4);
+    assert_eq!(storage.save_if_new("mock_imb", Some(FOLDER), &data, None).await.unwrap(), 4);
+    // A second scrape of the same response is deduplicated
+    assert_eq!(storage.save_if_new("mock_imb", Some(FOLDER), &data, None).await.unwrap(), 0);
+
+    let rows = stored(base_path);
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[0].start, start());
+    assert_eq!(rows[3].values["price"].as_f64(), Some(53.0));
+}
+
+#[tokio::test]
+async fn replayed_fixture_keeps_revisions() {
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().join("data");
+    let base_path = base_path.to_str().unwrap();
+    let fixture_dir = dir.path().join("fixtures");
+
+    // Two recordings of the same window, the second one revising every value
+    for (name, value) in [("1_first.json", 50.0), ("2_revised.json", 60.0)] {
+        let records = records(2, value);
+        let response = RawResponse { provenance: Provenance::new("mock_imb", None, start(), start() + Duration::hours(1), Utc::now(), &[]), records };
+        fixtures::save(&fixture_dir.join(name), &response).unwrap();
+    }
+    let responses = fixtures::load(&fixture_dir).unwrap();
+    assert_eq!(responses.len(), 2);
+
+    let storage = Storage::new(base_path, None);
+    for response in &responses {
+        let scraper = create_scraper(&mock_config(json!({ "records": response.records })), None).await.unwrap();
+        let data = scraper.scrape_data(start(), start() + Duration::hours(1)).await.unwrap();
+        assert_eq!(storage.save_if_new("mock_imb", Some(FOLDER), &data, None).await.unwrap(), 2);
+    }
+
+    let rows = stored(base_path);
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].values["price"].as_f64(), Some(60.0));
+}
+
+#[tokio::test]
+async fn scrape_window_filters_fixture() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = dir.path().join("fixture.json");
+    let response = RawResponse { provenance: Provenance::new("mock_imb", None, start(), start() + Duration::days(1), Utc::now(), &[]), records: records(8, 0.0) };
+    fixtures::save(&fixture, &response).unwrap();
+
+    let scraper = create_scraper(&mock_config(json!({ "fixture": fixture })), None).await.unwrap();
+    let data = scraper.scrape_data(start() + Duration::minutes(30), start() + Duration::hours(1)).await.unwrap();
+    let starts: Vec<_> = data.iter().map(|d| d.delivery_from).collect();
+    assert_eq!(starts, vec![start() + Duration::minutes(30), start() + Duration::minutes(45)]);
+}
+
+#[tokio::test]
+async fn failing_mock_returns_error() {
+    let scraper = create_scraper(&mock_config(json!({ "fail": true })), None).await.unwrap();
+    assert!(scraper.scrape_data(start(), start() + Duration::hours(1)).await.is_err());
+}
+
+#[tokio::test]
+async fn invalid_http_settings_fail_on_creation() {
+    let http = HttpClientConfig { proxy_url: Some("ftp://proxy.internal".to_string()), ..Default::default() };
+    assert!(create_scraper(&mock_config(json!({})), Some(&http)).await.is_err());
+
+    let http = HttpClientConfig { timeout_ms: Some(30_000), ..Default::default() };
+    assert!(create_scraper(&mock_config(json!({})), Some(&http)).await.is_ok());
+}
@@ -0,0 +1,41 @@
+use chrono::{Duration, TimeZone, Utc};
+use serde_json::json;
+
+use scraping_service::config::ScraperConfig;
+use scraping_service::fixtures::MOCK_TYPE;
+
+fn scraper(values: serde_json::Value) -> ScraperConfig {
+    let mut config = json!({ "name": "mock_imb", "workers": 1, "task_generator_delay_ms": 0, "type": MOCK_TYPE });
+    config.as_object_mut().unwrap().extend(values.as_object().unwrap().clone());
+    serde_json::from_value(config).unwrap()
+}
+
+#[test]
+fn zero_revision_interval_is_rejected() {
+    assert!(scraper(json!({ "revision_window_days": 7, "revision_interval_hours": 0 })).validate().is_err());
+    assert!(scraper(json!({ "revision_window_days": 7, "revision_interval_hours": 6 })).validate().is_ok());
+}
+
+#[test]
+fn revision_interval_defaults_to_a_day() {
+    assert_eq!(scraper(json!({})).revision_interval(), None);
+    assert_eq!(scraper(json!({ "revision_window_days": 7 })).revision_interval(), Some(std::time::Duration::from_secs(24 * 60 * 60)));
+    assert_eq!(scraper(json!({ "revision_window_days": 7, "revision_interval_hours": 6 })).revision_interval(), Some(std::time::Duration::from_secs(6 * 60 * 60)));
+}
+
+#[test]
+fn revision_window_ends_where_the_lookback_starts() {
+    let now = Utc.with_ymd_and_hms(2025, 6, 10, 12, 0, 0).unwrap();
+    let (start, end) = (now - Duration::days(4), now - Duration::days(1));
+
+    assert!(scraper(json!({})).revision_windows(now).is_empty());
+    assert_eq!(scraper(json!({ "revision_window_days": 3 })).revision_windows(now), vec![(start, end)]);
+
+    // Split by the backfill window, the last request covers the rest
+    let windows = scraper(json!({ "revision_window_days": 3, "backfill_window_hours": 30 })).revision_windows(now);
+    assert_eq!(windows, vec![
+        (start, start + Duration::hours(30)),
+        (start + Duration::hours(30), start + Duration::hours(60)),
+        (start + Duration::hours(60), end),
+    ]);
+}
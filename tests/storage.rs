@@ -0,0 +1,99 @@
+mod common;
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use ve_energy_scrapers::models::scraper_data::{ScraperData, ScraperPayload};
+
+use scraping_service::partition::Granularity;
+use scraping_service::query::{read_batches, read_value_rows, Query, QueryResult, ValueRow};
+use scraping_service::storage::{BufferConfig, Storage};
+use scraping_service::uploader::Uploader;
+
+const FOLDER: &str = "mock/imbalance";
+
+fn day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()
+}
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap()
+}
+
+fn data(value: f64) -> Vec<ScraperData> {
+    (0..4).map(|i| ScraperData {
+        delivery_from: start() + Duration::minutes(15 * i),
+        delivery_to: start() + Duration::minutes(15 * (i + 1)),
+        payload: ScraperPayload::Values([("price".to_string(), value)].into_iter().collect()),
+    }).collect()
+}
+
+fn partition(base_path: &str) -> PathBuf {
+    PathBuf::from(Granularity::Day.path(&format!("{}/{}", base_path, FOLDER), day()))
+}
+
+/// Every stored version of the partition, not only the latest per interval
+fn versions(base_path: &str) -> Vec<ValueRow> {
+    read_value_rows(&read_batches(&partition(base_path)).unwrap()).unwrap()
+}
+
+fn assert_unique_versions(rows: &[ValueRow]) {
+    let keys: HashSet<_> = rows.iter().map(|row| (row.start, row.scraped_at)).collect();
+    assert_eq!(keys.len(), rows.len(), "an interval is stored twice with the same scraped_at");
+}
+
+#[tokio::test]
+async fn hydrated_partition_is_deduplicated() {
+    let (endpoint, objects) = common::fake_s3().await;
+
+    // The partition as uploaded by an earlier run on another disk
+    let uploaded = tempfile::tempdir().unwrap();
+    let uploaded_path = uploaded.path().to_str().unwrap();
+    Storage::new(uploaded_path, None).save_if_new("mock_imb", Some(FOLDER), &data(50.0), None).await.unwrap();
+    let key = partition(uploaded_path).strip_prefix(uploaded_path).unwrap().to_string_lossy().to_string();
+    objects.lock().unwrap().insert(key, std::fs::read(partition(uploaded_path)).unwrap());
+
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    let uploader = Uploader::new(common::BUCKET.to_string(), None, Some(endpoint), String::new()).await.unwrap()
+        .with_base_path(base_path);
+    let storage = Storage::new(base_path, None).with_hydration(Arc::new(uploader));
+
+    // The same values are already in S3, so nothing is written
+    assert_eq!(storage.save_if_new("mock_imb", Some(FOLDER), &data(50.0), None).await.unwrap(), 0);
+    assert_eq!(versions(base_path).len(), 4);
+
+    // A correction is added next to the uploaded history
+    assert_eq!(storage.save_if_new("mock_imb", Some(FOLDER), &data(60.0), None).await.unwrap(), 4);
+    let rows = versions(base_path);
+    assert_eq!(rows.len(), 8);
+    assert_unique_versions(&rows);
+}
+
+#[tokio::test]
+async fn buffered_versions_keep_their_scrape_time() {
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    let storage = Storage::new(base_path, None).with_buffer(BufferConfig { flush_interval_ms: 60_000, max_rows: 1_000 });
+
+    assert_eq!(storage.save_if_new("mock_imb", Some(FOLDER), &data(50.0), None).await.unwrap(), 4);
+    // An unchanged re-scrape isn't buffered again
+    assert_eq!(storage.save_if_new("mock_imb", Some(FOLDER), &data(50.0), None).await.unwrap(), 0);
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    // A correction is buffered as a new version instead of replacing the first one
+    assert_eq!(storage.save_if_new("mock_imb", Some(FOLDER), &data(60.0), None).await.unwrap(), 4);
+    assert!(!partition(base_path).exists());
+
+    storage.flush().await.unwrap();
+    let rows = versions(base_path);
+    assert_eq!(rows.len(), 8);
+    assert_unique_versions(&rows);
+    assert_eq!(rows.iter().map(|row| row.scraped_at).collect::<HashSet<_>>().len(), 2);
+
+    let latest = match Query::new(base_path).latest(FOLDER, day(), day(), None).unwrap() {
+        QueryResult::Values(rows) => rows,
+        QueryResult::Bids(_) => panic!("expected values"),
+    };
+    assert!(latest.iter().all(|row| row.values["price"].as_f64() == Some(60.0)));
+}
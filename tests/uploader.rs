@@ -0,0 +1,82 @@
+mod common;
+
+use bytes::Bytes;
+use chrono::{TimeZone, Utc};
+use http_body::Body;
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use scraping_service::rate_limit::ThrottledBody;
+use scraping_service::uploader::{UploadConfig, UploadWindow, Uploader};
+
+fn evenings() -> UploadConfig {
+    UploadConfig { upload_windows: vec![UploadWindow { start_hour: 18, end_hour: 8 }], ..Default::default() }
+}
+
+#[test]
+fn upload_windows_follow_the_configured_timezone() {
+    let at = |hour, minute| Utc.with_ymd_and_hms(2025, 6, 1, hour, minute, 0).unwrap();
+
+    // Vienna by default, two hours ahead of UTC in summer
+    let vienna = evenings();
+    assert!(vienna.in_upload_window_at(at(16, 30)));
+    assert!(!vienna.in_upload_window_at(at(15, 30)));
+    // The window wraps past midnight
+    assert!(vienna.in_upload_window_at(at(5, 30)));
+    assert!(!vienna.in_upload_window_at(at(6, 30)));
+
+    let london = UploadConfig { upload_timezone: Some("Europe/London".to_string()), ..evenings() };
+    assert!(!london.in_upload_window_at(at(16, 30)));
+    assert!(london.in_upload_window_at(at(17, 30)));
+
+    assert!(UploadConfig::default().in_upload_window_at(at(12, 0)));
+}
+
+#[test]
+fn invalid_upload_timezone_is_rejected() {
+    assert!(UploadConfig { upload_timezone: Some("Europe/Atlantis".to_string()), ..evenings() }.validate().is_err());
+    assert!(UploadConfig { upload_timezone: Some("Europe/Oslo".to_string()), ..evenings() }.validate().is_ok());
+}
+
+#[tokio::test]
+async fn throttled_body_never_exceeds_the_rate() {
+    // Chunks of a tenth of a second, 1000 bytes at 10 000 bytes per second
+    let mut body = ThrottledBody::new(Bytes::from(vec![0u8; 3_000]), 10_000);
+    let started = Instant::now();
+    let mut sent = Vec::new();
+    while let Some(frame) = poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+        let data = frame.unwrap().into_data().unwrap();
+        sent.push((started.elapsed(), data.len()));
+    }
+
+    assert_eq!(sent.iter().map(|(_, len)| len).sum::<usize>(), 3_000);
+    assert!(sent.iter().all(|(_, len)| *len <= 1_000));
+    // The first chunk goes out right away, every further one a tenth of a second later
+    for (i, (elapsed, _)) in sent.iter().enumerate().skip(1) {
+        assert!(*elapsed >= Duration::from_millis(100 * i as u64 - 10), "chunk {} sent after {:?}", i, elapsed);
+    }
+    assert!(body.is_end_stream());
+}
+
+#[tokio::test]
+async fn throttled_upload_is_sent_at_the_limit() {
+    let (endpoint, objects) = common::fake_s3().await;
+    let dir = tempfile::tempdir().unwrap();
+    let base_path = dir.path().to_str().unwrap();
+    let key = "mock/imbalance/year=2025/month=06/day=01/data.parquet";
+    let file = dir.path().join(key);
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(&file, vec![0u8; 30_000]).unwrap();
+
+    let options = UploadConfig { max_bytes_per_second: Some(20_000), ..Default::default() };
+    let uploader = Uploader::new(common::BUCKET.to_string(), None, Some(endpoint), String::new()).await.unwrap()
+        .with_base_path(base_path)
+        .with_options(options);
+
+    let started = Instant::now();
+    uploader.upload_file(file.to_str().unwrap()).await.unwrap();
+    // The first 2000 bytes are sent right away, the other 28 000 at 20 000 bytes per second
+    assert!(started.elapsed() >= Duration::from_millis(1_300), "uploaded in {:?}", started.elapsed());
+    assert!(objects.lock().unwrap().contains_key(key));
+}